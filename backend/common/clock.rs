@@ -0,0 +1,159 @@
+// Where services get the time from, instead of calling Utc::now() directly.
+//
+// In production it's the system clock. Simulations run the services on a scaled clock (an
+// epoch plus `speedup` times the wall time passed since an anchor, shared with the simulator
+// through CLOCK_START / CLOCK_SPEEDUP / CLOCK_WALL_ANCHOR) or, fully in-process, on a virtual
+// clock that only moves when the simulation advances it.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Is at `epoch` when the wall clock is at `anchor` and runs `speedup` times faster from there.
+/// Processes given the same three values agree on the time however far apart they started.
+#[derive(Debug, Clone)]
+pub struct ScaledClock {
+    epoch: DateTime<Utc>,
+    anchor: DateTime<Utc>,
+    speedup: f64,
+}
+
+impl ScaledClock {
+    pub fn new(epoch: DateTime<Utc>, anchor: DateTime<Utc>, speedup: f64) -> Self {
+        Self {
+            epoch,
+            anchor,
+            speedup: speedup.max(f64::MIN_POSITIVE),
+        }
+    }
+
+    /// Wall time at which the clock is at its epoch.
+    pub fn anchor(&self) -> DateTime<Utc> {
+        self.anchor
+    }
+
+    pub fn speedup(&self) -> f64 {
+        self.speedup
+    }
+
+    /// Scaled time passed since the epoch, zero until the anchor.
+    pub fn elapsed(&self) -> Duration {
+        (Utc::now() - self.anchor)
+            .to_std()
+            .unwrap_or_default()
+            .mul_f64(self.speedup)
+    }
+
+    /// Wall time it takes for `duration` of scaled time to pass.
+    pub fn to_wall(&self, duration: Duration) -> Duration {
+        duration.div_f64(self.speedup)
+    }
+}
+
+impl Clock for ScaledClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.epoch + chrono::Duration::from_std(self.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Only moves when told to, for discrete event simulations.
+#[derive(Debug)]
+pub struct VirtualClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+impl VirtualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: RwLock::new(start),
+        }
+    }
+
+    /// Moves the clock to `at`, never backwards.
+    pub fn advance_to(&self, at: DateTime<Utc>) {
+        let mut now = self.now.write().unwrap();
+        if at > *now {
+            *now = at;
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.read().unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub enum ClockConfigError {
+    InvalidStart(chrono::ParseError),
+    InvalidSpeedup(String),
+    InvalidWallAnchor(chrono::ParseError),
+}
+
+impl std::fmt::Display for ClockConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClockConfigError::InvalidStart(e) => {
+                write!(f, "CLOCK_START must be an RFC 3339 timestamp: {}", e)
+            }
+            ClockConfigError::InvalidSpeedup(value) => {
+                write!(f, "CLOCK_SPEEDUP must be a positive number, got {:?}", value)
+            }
+            ClockConfigError::InvalidWallAnchor(e) => {
+                write!(f, "CLOCK_WALL_ANCHOR must be an RFC 3339 timestamp: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClockConfigError {}
+
+/// CLOCK_WALL_ANCHOR (RFC 3339), the wall clock time at which a scaled clock is at CLOCK_START.
+pub fn wall_anchor_from_env() -> Result<Option<DateTime<Utc>>, ClockConfigError> {
+    let Ok(anchor) = std::env::var("CLOCK_WALL_ANCHOR") else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(&anchor)
+        .map(|anchor| Some(anchor.with_timezone(&Utc)))
+        .map_err(ClockConfigError::InvalidWallAnchor)
+}
+
+/// The system clock, or a scaled one when CLOCK_START (RFC 3339) and optionally
+/// CLOCK_SPEEDUP (default 1) and CLOCK_WALL_ANCHOR are set. Processes given the same anchor
+/// agree on the time exactly; without one the clock is anchored at process start, which is
+/// only good enough for a single process.
+pub fn clock_from_env() -> Result<SharedClock, ClockConfigError> {
+    let Ok(start) = std::env::var("CLOCK_START") else {
+        return Ok(Arc::new(SystemClock));
+    };
+    let epoch = DateTime::parse_from_rfc3339(&start)
+        .map_err(ClockConfigError::InvalidStart)?
+        .with_timezone(&Utc);
+    let speedup = match std::env::var("CLOCK_SPEEDUP") {
+        Ok(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|s| *s > 0.0)
+            .ok_or(ClockConfigError::InvalidSpeedup(value))?,
+        Err(_) => 1.0,
+    };
+    let anchor = wall_anchor_from_env()?.unwrap_or_else(Utc::now);
+    Ok(Arc::new(ScaledClock::new(epoch, anchor, speedup)))
+}
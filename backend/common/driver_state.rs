@@ -0,0 +1,289 @@
+// Live driver state: the drivers:locations GEO set, the per driver state hashes and the airport
+// queues. The services keep it in Redis; a simulation running them in one process keeps it in
+// memory instead, both behind `DriverStateStore`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use redis::geo::{Coord, RadiusOptions, RadiusOrder, RadiusSearchResult};
+use redis::{geo, AsyncCommands};
+use uuid::Uuid;
+
+use crate::geo::{haversine_m, LatLng};
+use crate::redis_key_helpers::{driver_state_namespace, zone_queue_namespace};
+use crate::redis_namespaces::DRIVER_LOCATION_NAMESPACE;
+
+/// A GEO set member found by `drivers_within`.
+#[derive(Debug, Clone)]
+pub struct NearbyDriver {
+    pub driver_id: Uuid,
+    pub position: LatLng,
+    /// from the search center
+    pub distance_m: f64,
+}
+
+#[async_trait]
+pub trait DriverStateStore: Send + Sync {
+    /// Located drivers within `radius_m` of `center`, closest first.
+    async fn drivers_within(&self, center: LatLng, radius_m: f64) -> Result<Vec<NearbyDriver>, Error>;
+    /// Every located driver.
+    async fn located_drivers(&self) -> Result<Vec<Uuid>, Error>;
+    /// Last reported position per driver, None for one that isn't located.
+    async fn positions(&self, driver_ids: &[Uuid]) -> Result<Vec<Option<LatLng>>, Error>;
+    async fn set_position(&self, driver_id: Uuid, position: LatLng) -> Result<(), Error>;
+    /// State hash per driver, empty for one without.
+    async fn states(&self, driver_ids: &[Uuid]) -> Result<Vec<HashMap<String, String>>, Error>;
+    /// Sets and removes fields of the driver's state hash at once and renews its TTL.
+    async fn update_state(
+        &self,
+        driver_id: Uuid,
+        set: &[(&str, String)],
+        remove: &[&str],
+        ttl_secs: i64,
+    ) -> Result<(), Error>;
+    /// Sets fields only if the driver has a state hash, returns whether it had one.
+    async fn update_existing_state(&self, driver_id: Uuid, set: &[(&str, String)]) -> Result<bool, Error>;
+    /// Members of an airport queue, the longest waiting first.
+    async fn zone_queue(&self, zone_id: &str) -> Result<Vec<String>, Error>;
+    async fn remove_from_zone_queue(&self, zone_id: &str, members: &[String]) -> Result<(), Error>;
+}
+
+pub struct RedisDriverStateStore {
+    // a multiplexed connection is cheap to clone and safe to share, every call uses its own clone
+    con: redis::aio::MultiplexedConnection,
+}
+
+impl RedisDriverStateStore {
+    pub fn new(con: redis::aio::MultiplexedConnection) -> Self {
+        Self { con }
+    }
+}
+
+#[async_trait]
+impl DriverStateStore for RedisDriverStateStore {
+    async fn drivers_within(&self, center: LatLng, radius_m: f64) -> Result<Vec<NearbyDriver>, Error> {
+        let opts = RadiusOptions::default()
+            .with_dist()
+            .with_coord()
+            .order(RadiusOrder::Asc);
+        let results: Vec<RadiusSearchResult> = self
+            .con
+            .clone()
+            .geo_radius(
+                DRIVER_LOCATION_NAMESPACE,
+                center.lng,
+                center.lat,
+                radius_m,
+                geo::Unit::Meters,
+                opts,
+            )
+            .await?;
+
+        Ok(results
+            .into_iter()
+            .filter_map(|result| {
+                let Ok(driver_id) = result.name.parse::<Uuid>() else {
+                    eprintln!("Skipping malformed member {} in {}", result.name, DRIVER_LOCATION_NAMESPACE);
+                    return None;
+                };
+                let position = result
+                    .coord
+                    .map(|c| LatLng::new(c.latitude, c.longitude))
+                    .unwrap_or(center);
+                Some(NearbyDriver {
+                    driver_id,
+                    position,
+                    distance_m: result.dist.unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    async fn located_drivers(&self) -> Result<Vec<Uuid>, Error> {
+        // GEO sets are sorted sets under the hood, so ZRANGE lists every member
+        let members: Vec<String> = self.con.clone().zrange(DRIVER_LOCATION_NAMESPACE, 0, -1).await?;
+        Ok(members.iter().filter_map(|m| Uuid::parse_str(m).ok()).collect())
+    }
+
+    async fn positions(&self, driver_ids: &[Uuid]) -> Result<Vec<Option<LatLng>>, Error> {
+        if driver_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let members: Vec<String> = driver_ids.iter().map(|id| id.to_string()).collect();
+        let positions: Vec<Option<Coord<f64>>> = self
+            .con
+            .clone()
+            .geo_pos(DRIVER_LOCATION_NAMESPACE, members)
+            .await?;
+        Ok(positions
+            .into_iter()
+            .map(|p| p.map(|c| LatLng::new(c.latitude, c.longitude)))
+            .collect())
+    }
+
+    async fn set_position(&self, driver_id: Uuid, position: LatLng) -> Result<(), Error> {
+        let _: () = self
+            .con
+            .clone()
+            .geo_add(
+                DRIVER_LOCATION_NAMESPACE,
+                (position.lng, position.lat, driver_id.to_string()),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn states(&self, driver_ids: &[Uuid]) -> Result<Vec<HashMap<String, String>>, Error> {
+        if driver_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        // one round trip for all of them
+        let mut pipe = redis::pipe();
+        for driver_id in driver_ids {
+            pipe.hgetall(driver_state_namespace(*driver_id));
+        }
+        Ok(pipe.query_async(&mut self.con.clone()).await?)
+    }
+
+    async fn update_state(
+        &self,
+        driver_id: Uuid,
+        set: &[(&str, String)],
+        remove: &[&str],
+        ttl_secs: i64,
+    ) -> Result<(), Error> {
+        let key = driver_state_namespace(driver_id);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if !set.is_empty() {
+            pipe.hset_multiple(&key, set).ignore();
+        }
+        if !remove.is_empty() {
+            pipe.hdel(&key, remove).ignore();
+        }
+        pipe.expire(&key, ttl_secs).ignore();
+        pipe.query_async::<()>(&mut self.con.clone()).await?;
+        Ok(())
+    }
+
+    async fn update_existing_state(&self, driver_id: Uuid, set: &[(&str, String)]) -> Result<bool, Error> {
+        let key = driver_state_namespace(driver_id);
+        let mut con = self.con.clone();
+        let exists: bool = con.exists(&key).await?;
+        if exists && !set.is_empty() {
+            let _: () = con.hset_multiple(&key, set).await?;
+        }
+        Ok(exists)
+    }
+
+    async fn zone_queue(&self, zone_id: &str) -> Result<Vec<String>, Error> {
+        // sorted by the time they entered the zone
+        Ok(self.con.clone().zrange(zone_queue_namespace(zone_id), 0, -1).await?)
+    }
+
+    async fn remove_from_zone_queue(&self, zone_id: &str, members: &[String]) -> Result<(), Error> {
+        if members.is_empty() {
+            return Ok(());
+        }
+        let _: () = self.con.clone().zrem(zone_queue_namespace(zone_id), members).await?;
+        Ok(())
+    }
+}
+
+/// Keeps everything in memory, for running the services in one process. Nothing expires: a
+/// simulated driver never goes quiet without going offline first.
+#[derive(Default)]
+pub struct InMemoryDriverStateStore {
+    // ordered so searches don't depend on hash order and the same run gives the same answers
+    positions: Mutex<BTreeMap<Uuid, LatLng>>,
+    states: Mutex<HashMap<Uuid, HashMap<String, String>>>,
+}
+
+impl InMemoryDriverStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DriverStateStore for InMemoryDriverStateStore {
+    async fn drivers_within(&self, center: LatLng, radius_m: f64) -> Result<Vec<NearbyDriver>, Error> {
+        let mut nearby: Vec<NearbyDriver> = self
+            .positions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(driver_id, position)| NearbyDriver {
+                driver_id: *driver_id,
+                position: *position,
+                distance_m: haversine_m(*position, center),
+            })
+            .filter(|driver| driver.distance_m <= radius_m)
+            .collect();
+        // stable, equally far drivers stay in id order
+        nearby.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        Ok(nearby)
+    }
+
+    async fn located_drivers(&self) -> Result<Vec<Uuid>, Error> {
+        Ok(self.positions.lock().unwrap().keys().copied().collect())
+    }
+
+    async fn positions(&self, driver_ids: &[Uuid]) -> Result<Vec<Option<LatLng>>, Error> {
+        let positions = self.positions.lock().unwrap();
+        Ok(driver_ids.iter().map(|id| positions.get(id).copied()).collect())
+    }
+
+    async fn set_position(&self, driver_id: Uuid, position: LatLng) -> Result<(), Error> {
+        self.positions.lock().unwrap().insert(driver_id, position);
+        Ok(())
+    }
+
+    async fn states(&self, driver_ids: &[Uuid]) -> Result<Vec<HashMap<String, String>>, Error> {
+        let states = self.states.lock().unwrap();
+        Ok(driver_ids
+            .iter()
+            .map(|id| states.get(id).cloned().unwrap_or_default())
+            .collect())
+    }
+
+    async fn update_state(
+        &self,
+        driver_id: Uuid,
+        set: &[(&str, String)],
+        remove: &[&str],
+        _ttl_secs: i64,
+    ) -> Result<(), Error> {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(driver_id).or_default();
+        for (field, value) in set {
+            state.insert(field.to_string(), value.clone());
+        }
+        for field in remove {
+            state.remove(*field);
+        }
+        Ok(())
+    }
+
+    async fn update_existing_state(&self, driver_id: Uuid, set: &[(&str, String)]) -> Result<bool, Error> {
+        let mut states = self.states.lock().unwrap();
+        let Some(state) = states.get_mut(&driver_id) else {
+            return Ok(false);
+        };
+        for (field, value) in set {
+            state.insert(field.to_string(), value.clone());
+        }
+        Ok(true)
+    }
+
+    // nothing queues drivers in memory, a simulation has no airport zones
+    async fn zone_queue(&self, _zone_id: &str) -> Result<Vec<String>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn remove_from_zone_queue(&self, _zone_id: &str, _members: &[String]) -> Result<(), Error> {
+        Ok(())
+    }
+}
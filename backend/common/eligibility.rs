@@ -0,0 +1,97 @@
+// Driver eligibility shared across the project. The driver service owns it (postgres) and
+// mirrors it into the driver state hash, the matcher reads it back from there.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::redis_namespaces::{
+    DRIVER_COOLDOWN_UNTIL_FIELD, DRIVER_LICENSE_EXPIRES_AT_FIELD, DRIVER_SUSPENDED_FIELD,
+    DRIVER_SUSPENDED_UNTIL_FIELD,
+};
+
+/// Why a driver can't be offered rides right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IneligibilityReason {
+    Suspended,
+    Cooldown,
+    LicenseExpired,
+}
+
+impl IneligibilityReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IneligibilityReason::Suspended => "suspended",
+            IneligibilityReason::Cooldown => "cooldown",
+            IneligibilityReason::LicenseExpired => "license_expired",
+        }
+    }
+}
+
+impl std::fmt::Display for IneligibilityReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DriverEligibility {
+    pub suspended: bool,
+    pub suspension_reason: Option<String>,
+    /// None while suspended means until reinstated
+    pub suspended_until: Option<DateTime<Utc>>,
+    /// set when the driver rejects too many of the rides offered to them
+    pub cooldown_until: Option<DateTime<Utc>>,
+    pub license_expires_at: Option<DateTime<Utc>>,
+}
+
+impl DriverEligibility {
+    /// None if the driver may be offered rides at `now`. A suspension wins over a cooldown,
+    /// a cooldown over an expired license, so the most actionable reason is reported.
+    pub fn check(&self, now: DateTime<Utc>) -> Option<IneligibilityReason> {
+        if self.suspended && self.suspended_until.is_none_or(|until| until > now) {
+            return Some(IneligibilityReason::Suspended);
+        }
+        if self.cooldown_until.is_some_and(|until| until > now) {
+            return Some(IneligibilityReason::Cooldown);
+        }
+        if self.license_expires_at.is_some_and(|expires| expires <= now) {
+            return Some(IneligibilityReason::LicenseExpired);
+        }
+        None
+    }
+
+    /// Fields to HSET into the driver state hash. Timestamps are unix seconds like
+    /// last_location_ts, an empty string means not set. The suspension reason stays in postgres.
+    pub fn to_redis_fields(&self) -> Vec<(&'static str, String)> {
+        let ts = |t: Option<DateTime<Utc>>| t.map(|t| t.timestamp().to_string()).unwrap_or_default();
+        vec![
+            (
+                DRIVER_SUSPENDED_FIELD,
+                if self.suspended { "1" } else { "0" }.to_string(),
+            ),
+            (DRIVER_SUSPENDED_UNTIL_FIELD, ts(self.suspended_until)),
+            (DRIVER_COOLDOWN_UNTIL_FIELD, ts(self.cooldown_until)),
+            (DRIVER_LICENSE_EXPIRES_AT_FIELD, ts(self.license_expires_at)),
+        ]
+    }
+
+    /// Reads eligibility back from a driver state hash (HGETALL result). Missing fields
+    /// count as not set, so a hash written before eligibility existed is eligible.
+    pub fn from_redis_hash(hash: &HashMap<String, String>) -> Self {
+        let ts = |field: &str| {
+            hash.get(field)
+                .and_then(|v| v.parse::<i64>().ok())
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        };
+        Self {
+            suspended: hash.get(DRIVER_SUSPENDED_FIELD).is_some_and(|v| v == "1"),
+            suspension_reason: None,
+            suspended_until: ts(DRIVER_SUSPENDED_UNTIL_FIELD),
+            cooldown_until: ts(DRIVER_COOLDOWN_UNTIL_FIELD),
+            license_expires_at: ts(DRIVER_LICENSE_EXPIRES_AT_FIELD),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Road network ETAs shared by the driver service (pickup estimates) and the matcher (scoring).
+//
+// A point is snapped to its closest graph node, the node to node part is routed with A* on
+// travel time (edge speeds scaled by the traffic model at departure time), and the legs between the points and their nodes are driven slowly in a straight
+// line. When the graph can't help (point far off the map, disconnected nodes) the ETA falls back
+// to a straight line estimate so callers always get a number.
+
+pub mod graph;
+pub mod routing;
+pub mod traffic;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::eta::graph::RoadGraph;
+use crate::eta::routing::shortest_path;
+use crate::eta::traffic::{TrafficModel, TrafficProfile};
+use crate::geo::{haversine_m, LatLng};
+
+// speed on the legs between a point and its snapped node (parking lots, driveways)
+const CONNECTOR_SPEED_KMH: f64 = 15.0;
+// further than this from any node and the point is considered off the map
+pub const MAX_SNAP_DISTANCE_M: f64 = 1_000.0;
+// straight line fallback, roads are never straight
+const FALLBACK_DETOUR_FACTOR: f64 = 1.4;
+const FALLBACK_SPEED_KMH: f64 = 25.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EtaSource {
+    /// routed over the road graph
+    Road,
+    /// straight line estimate, the graph had no route
+    StraightLine,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Eta {
+    pub duration_secs: f64,
+    pub distance_m: f64,
+    pub source: EtaSource,
+}
+
+impl Eta {
+    /// Whole minutes, rounded up so a 30 second trip is still "1 min away".
+    pub fn minutes(&self) -> u32 {
+        (self.duration_secs / 60.0).ceil().max(0.0) as u32
+    }
+}
+
+pub struct EtaEngine {
+    graph: RoadGraph,
+    traffic: TrafficModel,
+}
+
+impl EtaEngine {
+    pub fn new(graph: RoadGraph, traffic_profile: TrafficProfile) -> Self {
+        Self {
+            graph,
+            traffic: TrafficModel::new(traffic_profile),
+        }
+    }
+
+    pub fn graph(&self) -> &RoadGraph {
+        &self.graph
+    }
+
+    /// Incidents are added and cleared through here.
+    pub fn traffic(&self) -> &TrafficModel {
+        &self.traffic
+    }
+
+    /// Driving time from `from` to `to` when leaving at `at`. The whole route uses the traffic
+    /// of the departure hour, trips are short enough for that.
+    pub fn eta(&self, from: LatLng, to: LatLng, at: DateTime<Utc>) -> Eta {
+        self.route(from, to, at)
+            .unwrap_or_else(|| Self::straight_line(from, to))
+    }
+
+    fn route(&self, from: LatLng, to: LatLng, at: DateTime<Utc>) -> Option<Eta> {
+        let (start, start_snap_m) = self.graph.nearest_node(from, MAX_SNAP_DISTANCE_M)?;
+        let (goal, goal_snap_m) = self.graph.nearest_node(to, MAX_SNAP_DISTANCE_M)?;
+
+        let max_speed_mps =
+            kmh_to_mps(self.graph.max_speed_kmh() * self.traffic.profile().max_multiplier());
+        if max_speed_mps <= 0.0 {
+            return None; // no edges at all
+        }
+        let route = shortest_path(&self.graph, start, goal, max_speed_mps, |edge_id, edge| {
+            // a closed edge costs infinity, A* never relaxes through it
+            let factor = self.traffic.speed_factor(edge_id, edge, at);
+            edge.length_m / kmh_to_mps(edge.speed_kmh * factor)
+        })?;
+
+        let connector_m = start_snap_m + goal_snap_m;
+        Some(Eta {
+            duration_secs: route.duration_secs + connector_m / kmh_to_mps(CONNECTOR_SPEED_KMH),
+            distance_m: route.distance_m + connector_m,
+            source: EtaSource::Road,
+        })
+    }
+
+    fn straight_line(from: LatLng, to: LatLng) -> Eta {
+        let distance_m = haversine_m(from, to) * FALLBACK_DETOUR_FACTOR;
+        Eta {
+            duration_secs: distance_m / kmh_to_mps(FALLBACK_SPEED_KMH),
+            distance_m,
+            source: EtaSource::StraightLine,
+        }
+    }
+}
+
+fn kmh_to_mps(kmh: f64) -> f64 {
+    kmh / 3.6
+}
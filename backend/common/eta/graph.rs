@@ -0,0 +1,507 @@
+// Road graph the ETA engine routes on.
+//
+// Graphs are loaded from a small line based text format so they can be generated by a script,
+// exported from an OSM extract or written by `RoadGraph::write`:
+//
+//   # comments and blank lines are ignored
+//   node <id> <lat> <lng>
+//   edge <from_id> <to_id> <road_class> [speed_kmh] [oneway]
+//
+// Edge length is the distance between the two nodes, speed defaults to the road class speed
+// and edges are two way unless marked `oneway`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geo::{haversine_m, LatLng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoadClass {
+    Motorway,
+    Primary,
+    Secondary,
+    Residential,
+}
+
+impl RoadClass {
+    pub const ALL: [RoadClass; 4] = [
+        RoadClass::Motorway,
+        RoadClass::Primary,
+        RoadClass::Secondary,
+        RoadClass::Residential,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoadClass::Motorway => "motorway",
+            RoadClass::Primary => "primary",
+            RoadClass::Secondary => "secondary",
+            RoadClass::Residential => "residential",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "motorway" => Some(RoadClass::Motorway),
+            "primary" => Some(RoadClass::Primary),
+            "secondary" => Some(RoadClass::Secondary),
+            "residential" => Some(RoadClass::Residential),
+            _ => None,
+        }
+    }
+
+    /// Free flow speed used when an edge doesn't carry its own.
+    pub fn default_speed_kmh(&self) -> f64 {
+        match self {
+            RoadClass::Motorway => 90.0,
+            RoadClass::Primary => 50.0,
+            RoadClass::Secondary => 40.0,
+            RoadClass::Residential => 25.0,
+        }
+    }
+}
+
+impl std::fmt::Display for RoadClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+pub type NodeId = u32;
+pub type EdgeId = u32;
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub length_m: f64,
+    /// free flow speed
+    pub speed_kmh: f64,
+    pub road_class: RoadClass,
+}
+
+#[derive(Debug)]
+pub enum RoadGraphError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl std::fmt::Display for RoadGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoadGraphError::Io(e) => write!(f, "failed to read road graph: {}", e),
+            RoadGraphError::Parse { line, message } => {
+                write!(f, "road graph line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoadGraphError {}
+
+impl From<std::io::Error> for RoadGraphError {
+    fn from(e: std::io::Error) -> Self {
+        RoadGraphError::Io(e)
+    }
+}
+
+/// Parameters of a generated Manhattan style grid, handy when there is no real extract around.
+#[derive(Debug, Clone)]
+pub struct GridSpec {
+    pub center: LatLng,
+    pub rows: u32,
+    pub cols: u32,
+    pub spacing_m: f64,
+    /// every n-th street is a primary road, the rest residential
+    pub arterial_every: u32,
+}
+
+impl Default for GridSpec {
+    // roughly 12x12 km of downtown San Francisco
+    fn default() -> Self {
+        Self {
+            center: LatLng::new(37.7749, -122.4194),
+            rows: 60,
+            cols: 60,
+            spacing_m: 200.0,
+            arterial_every: 5,
+        }
+    }
+}
+
+/// Directed graph in compressed adjacency form: the outgoing edges of node n are
+/// `edges[first_edge[n]..first_edge[n + 1]]`.
+#[derive(Debug, Clone)]
+pub struct RoadGraph {
+    nodes: Vec<LatLng>,
+    first_edge: Vec<u32>,
+    edges: Vec<Edge>,
+    max_speed_kmh: f64,
+    node_index: NodeIndex,
+}
+
+impl RoadGraph {
+    /// Builds the graph from node positions and directed edges (from, to, class, speed).
+    pub fn from_parts(nodes: Vec<LatLng>, mut edges: Vec<Edge>) -> Self {
+        edges.sort_by_key(|e| e.from);
+
+        let mut first_edge = vec![0u32; nodes.len() + 1];
+        for edge in &edges {
+            first_edge[edge.from as usize + 1] += 1;
+        }
+        for n in 0..nodes.len() {
+            first_edge[n + 1] += first_edge[n];
+        }
+
+        let max_speed_kmh = edges.iter().map(|e| e.speed_kmh).fold(0.0, f64::max);
+        let node_index = NodeIndex::build(&nodes);
+
+        Self {
+            nodes,
+            first_edge,
+            edges,
+            max_speed_kmh,
+            node_index,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RoadGraphError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RoadGraphError> {
+        let mut ids: HashMap<&str, NodeId> = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line = i + 1;
+            let err = |message: String| RoadGraphError::Parse { line, message };
+            let raw = raw.trim();
+            if raw.is_empty() || raw.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = raw.split_whitespace().collect();
+            match fields.as_slice() {
+                ["node", id, lat, lng] => {
+                    let lat: f64 = lat.parse().map_err(|_| err(format!("bad lat {}", lat)))?;
+                    let lng: f64 = lng.parse().map_err(|_| err(format!("bad lng {}", lng)))?;
+                    if ids.insert(id, nodes.len() as NodeId).is_some() {
+                        return Err(err(format!("duplicate node {}", id)));
+                    }
+                    nodes.push(LatLng::new(lat, lng));
+                }
+                ["edge", from, to, class, rest @ ..] => {
+                    let from = *ids
+                        .get(from)
+                        .ok_or_else(|| err(format!("unknown node {}", from)))?;
+                    let to = *ids
+                        .get(to)
+                        .ok_or_else(|| err(format!("unknown node {}", to)))?;
+                    let road_class = RoadClass::parse(class)
+                        .ok_or_else(|| err(format!("unknown road class {}", class)))?;
+
+                    let mut speed_kmh = road_class.default_speed_kmh();
+                    let mut oneway = false;
+                    for field in rest {
+                        if *field == "oneway" {
+                            oneway = true;
+                        } else {
+                            speed_kmh = field
+                                .parse()
+                                .ok()
+                                .filter(|s: &f64| *s > 0.0)
+                                .ok_or_else(|| err(format!("bad speed {}", field)))?;
+                        }
+                    }
+
+                    let length_m = haversine_m(nodes[from as usize], nodes[to as usize]);
+                    edges.push(Edge {
+                        from,
+                        to,
+                        length_m,
+                        speed_kmh,
+                        road_class,
+                    });
+                    if !oneway {
+                        edges.push(Edge {
+                            from: to,
+                            to: from,
+                            length_m,
+                            speed_kmh,
+                            road_class,
+                        });
+                    }
+                }
+                _ => return Err(err(format!("unrecognized line: {}", raw))),
+            }
+        }
+
+        Ok(Self::from_parts(nodes, edges))
+    }
+
+    /// Writes the graph in the text format `parse` reads. Every directed edge is written
+    /// as a oneway edge, so a load gives back exactly this graph.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), RoadGraphError> {
+        use std::fmt::Write;
+
+        let mut out = String::from("# ubersimx road graph\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(out, "node {} {:.7} {:.7}", id, node.lat, node.lng);
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "edge {} {} {} {} oneway",
+                edge.from, edge.to, edge.road_class, edge.speed_kmh
+            );
+        }
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+
+    pub fn generate_grid(spec: &GridSpec) -> Self {
+        let meters_per_deg_lat = 111_320.0;
+        let meters_per_deg_lng = meters_per_deg_lat * spec.center.lat.to_radians().cos();
+        let d_lat = spec.spacing_m / meters_per_deg_lat;
+        let d_lng = spec.spacing_m / meters_per_deg_lng;
+        let south = spec.center.lat - d_lat * (spec.rows.saturating_sub(1)) as f64 / 2.0;
+        let west = spec.center.lng - d_lng * (spec.cols.saturating_sub(1)) as f64 / 2.0;
+
+        let id = |row: u32, col: u32| row * spec.cols + col;
+        let mut nodes = Vec::with_capacity((spec.rows * spec.cols) as usize);
+        for row in 0..spec.rows {
+            for col in 0..spec.cols {
+                nodes.push(LatLng::new(south + d_lat * row as f64, west + d_lng * col as f64));
+            }
+        }
+
+        let class_of = |street: u32| {
+            if spec.arterial_every > 0 && street.is_multiple_of(spec.arterial_every) {
+                RoadClass::Primary
+            } else {
+                RoadClass::Residential
+            }
+        };
+
+        let mut edges = Vec::new();
+        let mut connect = |a: NodeId, b: NodeId, road_class: RoadClass| {
+            let length_m = haversine_m(nodes[a as usize], nodes[b as usize]);
+            let speed_kmh = road_class.default_speed_kmh();
+            for (from, to) in [(a, b), (b, a)] {
+                edges.push(Edge {
+                    from,
+                    to,
+                    length_m,
+                    speed_kmh,
+                    road_class,
+                });
+            }
+        };
+        for row in 0..spec.rows {
+            for col in 0..spec.cols {
+                // east-west streets are classed by row, north-south avenues by column
+                if col + 1 < spec.cols {
+                    connect(id(row, col), id(row, col + 1), class_of(row));
+                }
+                if row + 1 < spec.rows {
+                    connect(id(row, col), id(row + 1, col), class_of(col));
+                }
+            }
+        }
+
+        Self::from_parts(nodes, edges)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn node(&self, id: NodeId) -> LatLng {
+        self.nodes[id as usize]
+    }
+
+    pub fn edge(&self, id: EdgeId) -> &Edge {
+        &self.edges[id as usize]
+    }
+
+    /// Ids of the edges leaving `node`.
+    pub fn out_edges(&self, node: NodeId) -> std::ops::Range<EdgeId> {
+        self.first_edge[node as usize]..self.first_edge[node as usize + 1]
+    }
+
+    /// Fastest free flow speed in the graph, bounds the A* heuristic.
+    pub fn max_speed_kmh(&self) -> f64 {
+        self.max_speed_kmh
+    }
+
+    /// Closest node to `p` within `max_distance_m` and its distance in meters, None if there
+    /// is none.
+    pub fn nearest_node(&self, p: LatLng, max_distance_m: f64) -> Option<(NodeId, f64)> {
+        self.node_index.nearest(&self.nodes, p, max_distance_m)
+    }
+}
+
+// Buckets nodes into a lat/lng grid so snapping a point doesn't scan every node.
+#[derive(Debug, Clone)]
+struct NodeIndex {
+    buckets: HashMap<(i32, i32), Vec<NodeId>>,
+    // bucket rows and columns the nodes span, None without nodes
+    extent: Option<BucketExtent>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BucketExtent {
+    min_row: i32,
+    max_row: i32,
+    min_col: i32,
+    max_col: i32,
+}
+
+impl BucketExtent {
+    // rings around (row, col) it takes to cover every bucket of the extent
+    fn rings_to_cover(&self, row: i32, col: i32) -> i32 {
+        (row - self.min_row)
+            .abs()
+            .max((row - self.max_row).abs())
+            .max((col - self.min_col).abs())
+            .max((col - self.max_col).abs())
+    }
+
+    // rings around (row, col) before the first bucket of the extent, 0 inside it
+    fn rings_to_reach(&self, row: i32, col: i32) -> i32 {
+        let rows = (self.min_row - row).max(row - self.max_row).max(0);
+        let cols = (self.min_col - col).max(col - self.max_col).max(0);
+        rows.max(cols)
+    }
+}
+
+// ~1.1km in latitude, small enough that a city grid has a handful of nodes per bucket
+const BUCKET_DEG: f64 = 0.01;
+const METERS_PER_DEG: f64 = 111_320.0;
+
+impl NodeIndex {
+    fn bucket(p: LatLng) -> (i32, i32) {
+        (
+            (p.lat / BUCKET_DEG).floor() as i32,
+            (p.lng / BUCKET_DEG).floor() as i32,
+        )
+    }
+
+    // rings of buckets around `p` that cover `distance_m`, buckets are narrowest in longitude
+    fn rings_within(p: LatLng, distance_m: f64) -> i32 {
+        let bucket_m = METERS_PER_DEG * BUCKET_DEG * p.lat.to_radians().cos().max(0.01);
+        // a float to int cast saturates, an infinite distance is i32::MAX rings
+        (distance_m / bucket_m).ceil() as i32
+    }
+
+    fn build(nodes: &[LatLng]) -> Self {
+        let mut buckets: HashMap<(i32, i32), Vec<NodeId>> = HashMap::new();
+        for (id, node) in nodes.iter().enumerate() {
+            buckets.entry(Self::bucket(*node)).or_default().push(id as NodeId);
+        }
+        let extent = buckets.keys().fold(None, |extent: Option<BucketExtent>, &(r, c)| {
+            Some(match extent {
+                None => BucketExtent {
+                    min_row: r,
+                    max_row: r,
+                    min_col: c,
+                    max_col: c,
+                },
+                Some(e) => BucketExtent {
+                    min_row: e.min_row.min(r),
+                    max_row: e.max_row.max(r),
+                    min_col: e.min_col.min(c),
+                    max_col: e.max_col.max(c),
+                },
+            })
+        });
+        Self { buckets, extent }
+    }
+
+    fn nearest(&self, nodes: &[LatLng], p: LatLng, max_distance_m: f64) -> Option<(NodeId, f64)> {
+        let extent = self.extent?;
+        let (row, col) = Self::bucket(p);
+        let max_ring = Self::rings_within(p, max_distance_m);
+        // far outside the graph, don't walk the rings in between
+        if extent.rings_to_reach(row, col) > max_ring {
+            return None;
+        }
+
+        let mut best: Option<(NodeId, f64)> = None;
+        // search rings of buckets outwards. Once something is found one more ring is
+        // checked, a node in the next ring can still be closer than one in a corner bucket.
+        let last_ring = max_ring.min(extent.rings_to_cover(row, col) + 1);
+        let mut stop_after = None;
+        for ring in 0..=last_ring {
+            for r in row - ring..=row + ring {
+                for c in col - ring..=col + ring {
+                    if (r - row).abs() != ring && (c - col).abs() != ring {
+                        continue; // inner buckets were done in earlier rings
+                    }
+                    let Some(ids) = self.buckets.get(&(r, c)) else {
+                        continue;
+                    };
+                    for &id in ids {
+                        let d = haversine_m(p, nodes[id as usize]);
+                        if d <= max_distance_m && best.is_none_or(|(_, best_d)| d < best_d) {
+                            best = Some((id, d));
+                        }
+                    }
+                }
+            }
+            if best.is_some() && stop_after.is_none() {
+                stop_after = Some(ring + 1);
+            }
+            if stop_after == Some(ring) {
+                break;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> RoadGraph {
+        RoadGraph::generate_grid(&GridSpec::default())
+    }
+
+    #[test]
+    fn nearest_node_snaps_a_point_on_the_map() {
+        let graph = grid();
+        let node = graph.node(0);
+        let p = LatLng::new(node.lat + 0.0001, node.lng);
+        let (id, d) = graph.nearest_node(p, 1_000.0).unwrap();
+        assert_eq!(graph.node(id), node);
+        assert!(d < 20.0);
+    }
+
+    #[test]
+    fn nearest_node_gives_up_on_a_far_away_point() {
+        let graph = grid();
+        // the other side of the planet and just outside the snap distance
+        assert!(graph.nearest_node(LatLng::new(-40.0, 170.0), 1_000.0).is_none());
+        let node = graph.node(0);
+        let p = LatLng::new(node.lat - 0.02, node.lng - 0.02);
+        assert!(graph.nearest_node(p, 1_000.0).is_none());
+        assert!(graph.nearest_node(p, f64::INFINITY).is_some());
+    }
+
+    #[test]
+    fn nearest_node_of_an_empty_graph() {
+        let graph = RoadGraph::from_parts(Vec::new(), Vec::new());
+        assert!(graph.nearest_node(LatLng::new(0.0, 0.0), f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+// A* shortest path on the road graph, minimizing travel time.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::eta::graph::{Edge, EdgeId, NodeId, RoadGraph};
+use crate::geo::haversine_m;
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub duration_secs: f64,
+    pub distance_m: f64,
+    /// nodes from start to goal, both included
+    pub nodes: Vec<NodeId>,
+}
+
+// min-heap entry ordered by estimated total cost
+#[derive(Debug, Clone, Copy)]
+struct QueueEntry {
+    estimate: f64,
+    node: NodeId,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, BinaryHeap is a max-heap
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Fastest route from `start` to `goal`. `edge_secs` gives the traversal time of an edge and
+/// must never go below `length / max_speed_mps`, otherwise the heuristic overestimates and the
+/// route found may not be the fastest. None if `goal` can't be reached.
+pub fn shortest_path(
+    graph: &RoadGraph,
+    start: NodeId,
+    goal: NodeId,
+    max_speed_mps: f64,
+    edge_secs: impl Fn(EdgeId, &Edge) -> f64,
+) -> Option<Route> {
+    let n = graph.node_count();
+    let goal_pos = graph.node(goal);
+    let heuristic = |node: NodeId| haversine_m(graph.node(node), goal_pos) / max_speed_mps;
+
+    let mut best_secs = vec![f64::INFINITY; n];
+    let mut best_dist = vec![0.0; n];
+    let mut came_from = vec![NodeId::MAX; n];
+    let mut queue = BinaryHeap::new();
+
+    best_secs[start as usize] = 0.0;
+    queue.push(QueueEntry {
+        estimate: heuristic(start),
+        node: start,
+    });
+
+    while let Some(QueueEntry { estimate, node }) = queue.pop() {
+        if node == goal {
+            let mut nodes = vec![goal];
+            let mut current = goal;
+            while current != start {
+                current = came_from[current as usize];
+                nodes.push(current);
+            }
+            nodes.reverse();
+            return Some(Route {
+                duration_secs: best_secs[goal as usize],
+                distance_m: best_dist[goal as usize],
+                nodes,
+            });
+        }
+
+        // stale entry, the node was reached faster since it was queued
+        if estimate > best_secs[node as usize] + heuristic(node) + 1e-9 {
+            continue;
+        }
+
+        for edge_id in graph.out_edges(node) {
+            let edge = graph.edge(edge_id);
+            let secs = best_secs[node as usize] + edge_secs(edge_id, edge);
+            if secs < best_secs[edge.to as usize] {
+                best_secs[edge.to as usize] = secs;
+                best_dist[edge.to as usize] = best_dist[node as usize] + edge.length_m;
+                came_from[edge.to as usize] = node;
+                queue.push(QueueEntry {
+                    estimate: secs + heuristic(edge.to),
+                    node: edge.to,
+                });
+            }
+        }
+    }
+
+    None
+}
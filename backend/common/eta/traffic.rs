@@ -0,0 +1,267 @@
+// Time of day traffic for the ETA engine.
+//
+// Two things slow an edge down: the profile, a speed multiplier per road class for every
+// hour of the week (rush hour, quiet nights), and incidents, which slow or close the edges
+// of an area for a while (crash, road works, a closed bridge).
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::eta::graph::{Edge, EdgeId, RoadClass, RoadGraph};
+use crate::geo::{haversine_m, LatLng};
+
+/// Multipliers above this would mean driving faster than free flow by a lot, and they
+/// bound the A* heuristic, so they are capped.
+pub const MAX_SPEED_MULTIPLIER: f64 = 1.5;
+
+#[derive(Debug)]
+pub enum TrafficProfileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for TrafficProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficProfileError::Io(e) => write!(f, "failed to read traffic profile: {}", e),
+            TrafficProfileError::Json(e) => write!(f, "failed to parse traffic profile: {}", e),
+            TrafficProfileError::Invalid(message) => write!(f, "invalid traffic profile: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TrafficProfileError {}
+
+/// Speed multipliers per road class and hour of the week, in local time.
+///
+/// JSON layout: `{"utc_offset_minutes": -480, "multipliers": {"primary": [[24 values] x 7], ...}}`
+/// with days Monday first. A class that is missing drives at free flow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficProfile {
+    /// local time of the simulated city, no DST
+    pub utc_offset_minutes: i32,
+    pub multipliers: HashMap<RoadClass, Vec<Vec<f64>>>,
+}
+
+impl Default for TrafficProfile {
+    /// Weekday rush hours 7-9 and 16-19, a busy midday, quiet nights and lighter weekends.
+    /// Big roads suffer the most in rush hour, side streets the least.
+    fn default() -> Self {
+        let rush_hour = |class: RoadClass| match class {
+            RoadClass::Motorway => 0.5,
+            RoadClass::Primary => 0.6,
+            RoadClass::Secondary => 0.7,
+            RoadClass::Residential => 0.85,
+        };
+        let daytime = |class: RoadClass| match class {
+            RoadClass::Motorway => 0.85,
+            RoadClass::Primary => 0.8,
+            RoadClass::Secondary => 0.85,
+            RoadClass::Residential => 0.95,
+        };
+
+        let multipliers = RoadClass::ALL
+            .iter()
+            .map(|&class| {
+                let days = (0..7)
+                    .map(|day| {
+                        let weekend = day >= 5;
+                        (0..24)
+                            .map(|hour| match hour {
+                                7..=9 | 16..=19 if !weekend => rush_hour(class),
+                                7..=20 => daytime(class),
+                                _ => 1.0,
+                            })
+                            .collect()
+                    })
+                    .collect();
+                (class, days)
+            })
+            .collect();
+
+        Self {
+            utc_offset_minutes: -8 * 60,
+            multipliers,
+        }
+    }
+}
+
+impl TrafficProfile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrafficProfileError> {
+        let text = std::fs::read_to_string(path).map_err(TrafficProfileError::Io)?;
+        let profile: TrafficProfile =
+            serde_json::from_str(&text).map_err(TrafficProfileError::Json)?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    pub fn validate(&self) -> Result<(), TrafficProfileError> {
+        if FixedOffset::east_opt(self.utc_offset_minutes * 60).is_none() {
+            return Err(TrafficProfileError::Invalid(format!(
+                "utc offset of {} minutes",
+                self.utc_offset_minutes
+            )));
+        }
+        for (class, days) in &self.multipliers {
+            if days.len() != 7 || !days.iter().all(|day| day.len() == 24) {
+                return Err(TrafficProfileError::Invalid(format!(
+                    "{} needs 7 days of 24 hours",
+                    class
+                )));
+            }
+            if let Some(m) = days
+                .iter()
+                .flatten()
+                .find(|m| !(**m > 0.0 && **m <= MAX_SPEED_MULTIPLIER))
+            {
+                return Err(TrafficProfileError::Invalid(format!(
+                    "{} multiplier {} outside (0, {}]",
+                    class, m, MAX_SPEED_MULTIPLIER
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn multiplier(&self, class: RoadClass, at: DateTime<Utc>) -> f64 {
+        let Some(days) = self.multipliers.get(&class) else {
+            return 1.0;
+        };
+        let offset = FixedOffset::east_opt(self.utc_offset_minutes * 60)
+            .unwrap_or(FixedOffset::east_opt(0).unwrap());
+        let local = at.with_timezone(&offset);
+        let day = local.weekday().num_days_from_monday() as usize;
+        days[day][local.hour() as usize]
+    }
+
+    /// Highest multiplier in the profile, at least 1 since unlisted classes drive at free flow.
+    pub fn max_multiplier(&self) -> f64 {
+        self.multipliers
+            .values()
+            .flatten()
+            .flatten()
+            .copied()
+            .fold(1.0, f64::max)
+    }
+}
+
+/// Slows (or closes, with a factor of 0) every edge with both ends inside the circle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: Uuid,
+    pub center: LatLng,
+    pub radius_m: f64,
+    /// multiplies the edge speed, 0 closes the road
+    pub speed_factor: f64,
+    pub starts_at: DateTime<Utc>,
+    /// None until cleared
+    pub ends_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+}
+
+impl Incident {
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.starts_at <= at && self.ends_at.is_none_or(|ends_at| at < ends_at)
+    }
+}
+
+struct ActiveIncident {
+    incident: Incident,
+    edges: HashSet<EdgeId>,
+}
+
+/// Profile plus the incidents currently known. Incidents come and go while the engine is
+/// shared, hence the lock.
+pub struct TrafficModel {
+    profile: TrafficProfile,
+    incidents: RwLock<HashMap<Uuid, ActiveIncident>>,
+}
+
+impl TrafficModel {
+    pub fn new(profile: TrafficProfile) -> Self {
+        Self {
+            profile,
+            incidents: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn profile(&self) -> &TrafficProfile {
+        &self.profile
+    }
+
+    /// Speed multiplier of the edge at `at`, 0 if it's closed.
+    pub fn speed_factor(&self, edge_id: EdgeId, edge: &Edge, at: DateTime<Utc>) -> f64 {
+        let incidents = self.incidents.read().unwrap();
+        let incident_factor = incidents
+            .values()
+            .filter(|active| active.incident.is_active(at) && active.edges.contains(&edge_id))
+            .map(|active| active.incident.speed_factor)
+            .fold(1.0, f64::min);
+        self.profile.multiplier(edge.road_class, at) * incident_factor
+    }
+
+    /// Adds or replaces (same id) an incident. Returns the number of edges it affects.
+    pub fn add_incident(&self, graph: &RoadGraph, incident: Incident, now: DateTime<Utc>) -> usize {
+        let inside = |p: LatLng| haversine_m(p, incident.center) <= incident.radius_m;
+        let edges: HashSet<EdgeId> = (0..graph.edge_count() as EdgeId)
+            .filter(|&id| {
+                let edge = graph.edge(id);
+                inside(graph.node(edge.from)) && inside(graph.node(edge.to))
+            })
+            .collect();
+        let affected = edges.len();
+
+        let mut incidents = self.incidents.write().unwrap();
+        // ended incidents are only dropped here, there is no background job for it
+        incidents.retain(|_, active| active.incident.ends_at.is_none_or(|ends_at| ends_at > now));
+        incidents.insert(incident.id, ActiveIncident { incident, edges });
+        affected
+    }
+
+    /// Returns false if the incident is unknown.
+    pub fn clear_incident(&self, id: Uuid) -> bool {
+        self.incidents.write().unwrap().remove(&id).is_some()
+    }
+
+    pub fn incidents(&self) -> Vec<Incident> {
+        self.incidents
+            .read()
+            .unwrap()
+            .values()
+            .map(|active| active.incident.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_profile_is_valid() {
+        assert!(TrafficProfile::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_days_that_are_not_24_hours() {
+        let mut profile = TrafficProfile::default();
+        // still 168 hours in total, one day too long and one too short
+        let days = profile.multipliers.get_mut(&RoadClass::Primary).unwrap();
+        let hour = days[0].pop().unwrap();
+        days[1].push(hour);
+        assert!(matches!(profile.validate(), Err(TrafficProfileError::Invalid(_))));
+    }
+
+    #[test]
+    fn validate_rejects_a_missing_day() {
+        let mut profile = TrafficProfile::default();
+        profile.multipliers.get_mut(&RoadClass::Residential).unwrap().pop();
+        assert!(matches!(profile.validate(), Err(TrafficProfileError::Invalid(_))));
+    }
+}
@@ -5,6 +5,10 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use crate::eta::traffic::Incident;
+use crate::geo::hex::HexCell;
+use crate::vehicle::RideRequirements;
+
 
 #[derive(Debug, Clone, Serialize)]
 pub struct DriverAvailabilityChangedEvent {
@@ -21,6 +25,9 @@ pub struct RideRequestedEvent {
     pub destination_lat: f64,
     pub destination_lng: f64,
     pub created_at: DateTime<Utc>,
+    // defaults to a plain economy ride for publishers that don't send it
+    #[serde(default)]
+    pub requirements: RideRequirements,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,6 +39,9 @@ pub struct DriverAssignedRideEvent {
     pub pickup_lng: f64,
     pub dropoff_lat: f64,
     pub dropoff_lng: f64,
+    // surge multiplier at the pickup when the ride was matched, None without surge
+    #[serde(default)]
+    pub surge_multiplier: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -57,4 +67,59 @@ pub struct DriverAcceptedRideEvent {
 pub struct DriverRejectedRideEvent {
     pub ride_id: Uuid,
     pub driver_id: Uuid,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RidePickedUpEvent {
+    pub ride_id: Uuid,
+    pub driver_id: Uuid,
+    pub picked_up_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RideCompletedEvent {
+    pub ride_id: Uuid,
+    pub driver_id: Uuid,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Every service with an ETA engine applies it, so pickup estimates and matching agree.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrafficIncidentReportedEvent {
+    pub incident: Incident,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrafficIncidentClearedEvent {
+    pub incident_id: Uuid,
+}
+
+/// New multiplier of a surge cell, published when it changes. 1.0 means the surge is over.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SurgeUpdatedEvent {
+    pub cell: HexCell,
+    pub multiplier: f64,
+    /// ride requests in the cell over the sliding window, matched or not: request volume,
+    /// not requests still waiting for a driver
+    #[serde(alias = "open_requests")]
+    pub recent_requests: u32,
+    pub available_drivers: u32,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Requests expected in a cell over one interval.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForecastInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub expected_requests: f64,
+}
+
+/// Demand forecast of a cell for the coming intervals, the first being the one in progress.
+/// Published by the matcher whenever an interval ends, for cells expecting any demand.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DemandForecastEvent {
+    pub cell: HexCell,
+    pub intervals: Vec<ForecastInterval>,
+    pub computed_at: DateTime<Utc>,
+}
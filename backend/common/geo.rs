@@ -0,0 +1,30 @@
+// Geographic helpers shared across the project.
+
+use serde::{Deserialize, Serialize};
+
+pub mod hex;
+
+/// Mean earth radius used by redis GEO commands too, so distances line up with GEORADIUS.
+pub const EARTH_RADIUS_M: f64 = 6_372_797.560856;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatLng {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl LatLng {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Self { lat, lng }
+    }
+}
+
+/// Great circle distance in meters.
+pub fn haversine_m(a: LatLng, b: LatLng) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (b.lng - a.lng).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
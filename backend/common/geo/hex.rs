@@ -0,0 +1,275 @@
+// Hexagonal cells, H3 style buckets for anything counted per area (surge, demand, zones).
+//
+// Cells are regular hexagons (pointy top, axial q/r coordinates) laid over the web mercator
+// plane, the same projection redis GEO works in. That keeps cell ids global and stable
+// without any per-city setup. The price is that cells shrink on the ground away from the
+// equator: edge lengths are mercator meters, a res 7 cell is about 400 m across an edge in
+// San Francisco instead of 500 m. Within one city that difference doesn't matter.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geo::{LatLng, EARTH_RADIUS_M};
+
+pub const MAX_RESOLUTION: u8 = 15;
+
+/// Resolution the services bucket by (driver cells in the state hash, surge), 500 m edges.
+pub const CELL_RESOLUTION: u8 = 7;
+
+// edge length of a res 0 cell, every resolution halves it
+const RES0_EDGE_M: f64 = 64_000.0;
+
+// web mercator can't go further, same bound as redis GEO
+const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_78;
+
+const SQRT_3: f64 = 1.732_050_807_568_877_2;
+
+// axial direction vectors, neighbor i of a cell is the cell + DIRECTIONS[i]
+const DIRECTIONS: [(i32, i32); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HexCell {
+    pub res: u8,
+    pub q: i32,
+    pub r: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHexCellError(String);
+
+impl std::fmt::Display for ParseHexCellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid hex cell id {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseHexCellError {}
+
+/// Edge length (= circumradius) of the cells of a resolution, in mercator meters.
+pub fn edge_length_m(res: u8) -> f64 {
+    RES0_EDGE_M / f64::powi(2.0, res.min(MAX_RESOLUTION) as i32)
+}
+
+fn to_mercator(point: LatLng) -> (f64, f64) {
+    let lat = point
+        .lat
+        .clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE)
+        .to_radians();
+    let x = EARTH_RADIUS_M * point.lng.to_radians();
+    let y = EARTH_RADIUS_M * (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln();
+    (x, y)
+}
+
+fn from_mercator(x: f64, y: f64) -> LatLng {
+    let lng = (x / EARTH_RADIUS_M).to_degrees();
+    let lat = (2.0 * (y / EARTH_RADIUS_M).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+    LatLng::new(lat, lng)
+}
+
+impl HexCell {
+    /// The cell containing `point` at resolution `res` (capped at `MAX_RESOLUTION`).
+    pub fn of(point: LatLng, res: u8) -> Self {
+        let res = res.min(MAX_RESOLUTION);
+        let size = edge_length_m(res);
+        let (x, y) = to_mercator(point);
+
+        // fractional axial coordinates, then rounded in cube space so the hexagon
+        // boundaries come out right
+        let q = (SQRT_3 / 3.0 * x - y / 3.0) / size;
+        let r = (2.0 / 3.0 * y) / size;
+        let s = -q - r;
+
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+
+        Self {
+            res,
+            q: rq as i32,
+            r: rr as i32,
+        }
+    }
+
+    pub fn center(&self) -> LatLng {
+        let (x, y) = self.center_mercator();
+        from_mercator(x, y)
+    }
+
+    fn center_mercator(&self) -> (f64, f64) {
+        let size = edge_length_m(self.res);
+        let x = size * SQRT_3 * (self.q as f64 + self.r as f64 / 2.0);
+        let y = size * 1.5 * self.r as f64;
+        (x, y)
+    }
+
+    /// Corners counter clockwise, starting with the one east of the center.
+    pub fn polygon(&self) -> [LatLng; 6] {
+        let size = edge_length_m(self.res);
+        let (cx, cy) = self.center_mercator();
+        std::array::from_fn(|i| {
+            // pointy top, corners at 30 + 60 * i degrees
+            let angle = (60.0 * i as f64 + 30.0).to_radians();
+            from_mercator(cx + size * angle.cos(), cy + size * angle.sin())
+        })
+    }
+
+    fn offset(&self, dq: i32, dr: i32) -> Self {
+        Self {
+            res: self.res,
+            q: self.q + dq,
+            r: self.r + dr,
+        }
+    }
+
+    pub fn neighbors(&self) -> [HexCell; 6] {
+        DIRECTIONS.map(|(dq, dr)| self.offset(dq, dr))
+    }
+
+    /// Number of cells to step through to get from one cell to the other, None across
+    /// resolutions.
+    pub fn grid_distance(&self, other: &HexCell) -> Option<u32> {
+        if self.res != other.res {
+            return None;
+        }
+        let dq = self.q - other.q;
+        let dr = self.r - other.r;
+        Some(((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as u32)
+    }
+
+    /// Cells exactly `k` steps away, 6k of them (just this cell for k = 0).
+    pub fn ring(&self, k: u32) -> Vec<HexCell> {
+        if k == 0 {
+            return vec![*self];
+        }
+        let k = k as i32;
+        let mut cells = Vec::with_capacity(6 * k as usize);
+        // start k steps in direction 4, then walk k steps along each side
+        let (sq, sr) = DIRECTIONS[4];
+        let mut cell = self.offset(sq * k, sr * k);
+        for (dq, dr) in DIRECTIONS {
+            for _ in 0..k {
+                cells.push(cell);
+                cell = cell.offset(dq, dr);
+            }
+        }
+        cells
+    }
+
+    /// Cells at most `k` steps away, this cell first then ring by ring.
+    pub fn disk(&self, k: u32) -> Vec<HexCell> {
+        (0..=k).flat_map(|i| self.ring(i)).collect()
+    }
+}
+
+/// `res:q:r`, e.g. `7:-3104:1538`. Stored like this in the driver state hash.
+impl std::fmt::Display for HexCell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.res, self.q, self.r)
+    }
+}
+
+impl FromStr for HexCell {
+    type Err = ParseHexCellError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseHexCellError(s.to_string());
+        let mut parts = s.split(':');
+        let (Some(res), Some(q), Some(r), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err());
+        };
+        let res: u8 = res.parse().map_err(|_| err())?;
+        if res > MAX_RESOLUTION {
+            return Err(err());
+        }
+        Ok(Self {
+            res,
+            q: q.parse().map_err(|_| err())?,
+            r: r.parse().map_err(|_| err())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // downtown San Francisco
+    const SF: LatLng = LatLng {
+        lat: 37.7749,
+        lng: -122.4194,
+    };
+
+    #[test]
+    fn center_is_in_its_own_cell() {
+        for res in [0, 5, CELL_RESOLUTION, 10, MAX_RESOLUTION] {
+            let cell = HexCell::of(SF, res);
+            assert_eq!(HexCell::of(cell.center(), res), cell);
+            for neighbor in cell.neighbors() {
+                assert_eq!(HexCell::of(neighbor.center(), res), neighbor);
+            }
+        }
+    }
+
+    #[test]
+    fn resolution_is_capped() {
+        assert_eq!(HexCell::of(SF, 40).res, MAX_RESOLUTION);
+    }
+
+    #[test]
+    fn ring_has_6k_distinct_cells_k_steps_away() {
+        let cell = HexCell::of(SF, CELL_RESOLUTION);
+        assert_eq!(cell.ring(0), vec![cell]);
+        for k in 1..=4 {
+            let mut ring = cell.ring(k);
+            assert_eq!(ring.len(), 6 * k as usize);
+            assert!(ring.iter().all(|c| cell.grid_distance(c) == Some(k)));
+            ring.sort();
+            ring.dedup();
+            assert_eq!(ring.len(), 6 * k as usize);
+        }
+        assert_eq!(cell.disk(2).len(), 1 + 6 + 12);
+    }
+
+    #[test]
+    fn grid_distance_is_symmetric() {
+        let cell = HexCell::of(SF, CELL_RESOLUTION);
+        let other = HexCell::of(LatLng::new(37.80, -122.27), CELL_RESOLUTION);
+
+        assert_eq!(cell.grid_distance(&cell), Some(0));
+        assert_eq!(cell.grid_distance(&other), other.grid_distance(&cell));
+        assert!(cell.grid_distance(&other).unwrap() > 0);
+        for neighbor in cell.neighbors() {
+            assert_eq!(cell.grid_distance(&neighbor), Some(1));
+            assert_eq!(neighbor.grid_distance(&cell), Some(1));
+        }
+        assert_eq!(cell.grid_distance(&HexCell::of(SF, 6)), None);
+    }
+
+    #[test]
+    fn display_and_from_str_round_trip() {
+        let cell = HexCell {
+            res: 7,
+            q: -3104,
+            r: 1538,
+        };
+        assert_eq!(cell.to_string(), "7:-3104:1538");
+        assert_eq!("7:-3104:1538".parse::<HexCell>(), Ok(cell));
+
+        let cell = HexCell::of(SF, CELL_RESOLUTION);
+        assert_eq!(cell.to_string().parse::<HexCell>(), Ok(cell));
+    }
+
+    #[test]
+    fn from_str_rejects_malformed_ids() {
+        for id in ["", "7:1", "7:1:2:3", "x:1:2", "7:1:y", "16:0:0", "-1:0:0"] {
+            assert!(id.parse::<HexCell>().is_err(), "{:?} parsed", id);
+        }
+    }
+}
@@ -1,5 +1,14 @@
+pub mod clock;
+pub mod driver_state;
+pub mod eligibility;
+pub mod eta;
 pub mod events_schema;
+pub mod geo;
 pub mod redis_key_helpers;
 pub mod redis_namespaces;
+pub mod repositioning;
+pub mod service_area;
 pub mod subjects;
+pub mod surge;
+pub mod vehicle;
 pub mod ws_schema;
\ No newline at end of file
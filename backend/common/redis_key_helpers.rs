@@ -6,3 +6,9 @@ use uuid::Uuid;
 pub fn driver_state_namespace(driver_id: Uuid) -> String {
 	format!("drivers:{}:state", driver_id)
 }
+
+/// Returns the Redis key of an airport queue, a sorted set of driver ids scored by the
+/// second they entered the zone.
+pub fn zone_queue_namespace(zone_id: &str) -> String {
+	format!("zones:{}:queue", zone_id)
+}
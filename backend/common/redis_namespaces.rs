@@ -10,4 +10,18 @@ pub const DRIVER_LAST_AVAILABILITY_UPDATE_FIELD: &str = "last_updated";
 pub const DRIVER_AVAILABILITY_FIELD: &str = "available";
 pub const DRIVER_AVAILABILITY_REASON_FIELD: &str = "reason";
 pub const DRIVER_IN_RIDE_FIELD: &str = "in_ride";
-pub const DRIVER_RIDE_ID_FIELD: &str = "ride_id";
\ No newline at end of file
+pub const DRIVER_RIDE_ID_FIELD: &str = "ride_id";
+pub const DRIVER_VEHICLE_CLASS_FIELD: &str = "vehicle_class";
+pub const DRIVER_SEATS_FIELD: &str = "seats";
+pub const DRIVER_PET_FRIENDLY_FIELD: &str = "pet_friendly";
+pub const DRIVER_WHEELCHAIR_ACCESSIBLE_FIELD: &str = "wheelchair_accessible";
+pub const DRIVER_SUSPENDED_FIELD: &str = "suspended";
+pub const DRIVER_SUSPENDED_UNTIL_FIELD: &str = "suspended_until";
+pub const DRIVER_COOLDOWN_UNTIL_FIELD: &str = "cooldown_until";
+pub const DRIVER_LICENSE_EXPIRES_AT_FIELD: &str = "license_expires_at";
+pub const DRIVER_PICKUP_LAT_FIELD: &str = "pickup_lat";
+pub const DRIVER_PICKUP_LNG_FIELD: &str = "pickup_lng";
+// hex cell (common::geo::hex, CELL_RESOLUTION) of the last reported location
+pub const DRIVER_CELL_FIELD: &str = "cell";
+// id of the airport queue zone the driver is in, "" outside any
+pub const DRIVER_ZONE_FIELD: &str = "zone";
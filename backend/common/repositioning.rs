@@ -0,0 +1,172 @@
+// Repositioning: where idle drivers should head so they are where the riders are about to be.
+//
+// Demand is the forecast the matcher publishes (DemandForecastEvent), summed over a short
+// lookahead per cell, supply the idle drivers in each cell. A cell expecting more requests than
+// it has idle drivers is short, one with more idle drivers than requests has spare ones. Spare
+// drivers are sent one at a time to the short cell nearby where they help most: up to one
+// request of the shortage, minus a penalty per cell of distance so nobody crosses the city for
+// a fraction of a ride. Cells and drivers go in a fixed order, the same input always gives the
+// same suggestions.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::events_schema::DemandForecastEvent;
+use crate::geo::hex::HexCell;
+
+/// Latest forecast per cell as seen in DemandForecastEvents. The matcher republishes every
+/// cell expecting demand once per interval, a forecast older than `max_age` belongs to a cell
+/// that dropped out and counts as nothing.
+pub struct DemandForecasts {
+    max_age: chrono::Duration,
+    cells: RwLock<HashMap<HexCell, DemandForecastEvent>>,
+}
+
+impl DemandForecasts {
+    pub fn new(max_age: chrono::Duration) -> Self {
+        Self {
+            max_age,
+            cells: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Out of order updates are ignored.
+    pub fn apply(&self, event: DemandForecastEvent) {
+        let mut cells = self.cells.write().unwrap();
+        if cells
+            .get(&event.cell)
+            .is_some_and(|current| current.computed_at > event.computed_at)
+        {
+            return;
+        }
+        cells.insert(event.cell, event);
+    }
+
+    /// Requests expected per cell between `from` and `from + window`, intervals partly in
+    /// it count in proportion.
+    pub fn expected(&self, from: DateTime<Utc>, window: chrono::Duration) -> HashMap<HexCell, f64> {
+        let to = from + window;
+        let mut cells = self.cells.write().unwrap();
+        cells.retain(|_, event| from - event.computed_at <= self.max_age);
+        cells
+            .iter()
+            .map(|(cell, event)| {
+                let expected = event
+                    .intervals
+                    .iter()
+                    .map(|interval| {
+                        let length = (interval.end - interval.start).num_milliseconds();
+                        let overlap = (interval.end.min(to) - interval.start.max(from)).num_milliseconds();
+                        if length <= 0 || overlap <= 0 {
+                            return 0.0;
+                        }
+                        interval.expected_requests * overlap as f64 / length as f64
+                    })
+                    .sum();
+                (*cell, expected)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepositioningPolicy {
+    /// how far ahead demand counts
+    pub lookahead: chrono::Duration,
+    /// farthest a driver is sent, in cells
+    pub max_cells: u32,
+    /// expected requests one cell of distance costs
+    pub distance_penalty: f64,
+    /// smallest gain worth a suggestion, in expected requests
+    pub min_gain: f64,
+}
+
+impl Default for RepositioningPolicy {
+    fn default() -> Self {
+        Self {
+            lookahead: chrono::Duration::minutes(30),
+            // about 3 km at CELL_RESOLUTION
+            max_cells: 4,
+            distance_penalty: 0.1,
+            min_gain: 0.3,
+        }
+    }
+}
+
+/// A suggestion for one driver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    pub driver_id: Uuid,
+    pub from: HexCell,
+    pub to: HexCell,
+    /// requests the target cell expects over the lookahead
+    pub expected_requests: f64,
+    /// idle drivers in the target cell, before any moves
+    pub idle_drivers: u32,
+}
+
+/// Which idle drivers should go where, given where they are and the demand expected per cell.
+pub fn plan_moves(
+    policy: &RepositioningPolicy,
+    idle: &[(Uuid, HexCell)],
+    demand: &HashMap<HexCell, f64>,
+) -> Vec<Move> {
+    let mut supply: BTreeMap<HexCell, Vec<Uuid>> = BTreeMap::new();
+    for (driver_id, cell) in idle {
+        supply.entry(*cell).or_default().push(*driver_id);
+    }
+    let expected = |cell: &HexCell| demand.get(cell).copied().unwrap_or(0.0);
+    let idle_in = |cell: &HexCell| supply.get(cell).map_or(0, |d| d.len());
+
+    let mut shortage: BTreeMap<HexCell, f64> = demand
+        .iter()
+        .map(|(cell, expected)| (*cell, expected - idle_in(cell) as f64))
+        .filter(|(_, short)| *short > 0.0)
+        .collect();
+
+    let mut moves = Vec::new();
+    for (cell, drivers) in &supply {
+        let mut drivers = drivers.clone();
+        drivers.sort();
+        let spare = (drivers.len() as f64 - expected(cell)).floor().max(0.0) as usize;
+        for driver_id in drivers.into_iter().take(spare) {
+            let best = shortage
+                .iter()
+                .filter_map(|(target, short)| {
+                    let distance = cell.grid_distance(target)?;
+                    if distance == 0 || distance > policy.max_cells {
+                        return None;
+                    }
+                    Some((*target, short.min(1.0) - policy.distance_penalty * distance as f64))
+                })
+                // the first of equally good cells, in cell order
+                .fold(None, |best: Option<(HexCell, f64)>, candidate| match best {
+                    Some(b) if b.1 >= candidate.1 => Some(b),
+                    _ => Some(candidate),
+                });
+            let Some((target, gain)) = best else {
+                break;
+            };
+            if gain < policy.min_gain {
+                break;
+            }
+            moves.push(Move {
+                driver_id,
+                from: *cell,
+                to: target,
+                expected_requests: expected(&target),
+                idle_drivers: idle_in(&target) as u32,
+            });
+            if let Some(short) = shortage.get_mut(&target) {
+                *short -= 1.0;
+                if *short <= 0.0 {
+                    shortage.remove(&target);
+                }
+            }
+        }
+    }
+    moves
+}
@@ -0,0 +1,525 @@
+// Service area: where rides can start and end and where drivers can work.
+//
+// Loaded from a GeoJSON FeatureCollection of Polygon / MultiPolygon features, each with a
+// `kind` property:
+// - `operating_area`: rides and drivers must be inside one of them. Without any, everything
+//   is inside.
+// - `no_pickup`: rides can't start here (stations, stadium forecourts), they can end here.
+// - `airport_queue`: drivers waiting here are queued first come first served for pickups
+//   inside the zone, see the matcher.
+// `name` and `id` properties are optional, the id defaults to the name or the feature index.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geo::{haversine_m, LatLng, EARTH_RADIUS_M};
+
+// how far past the boundary a suggested pickup is placed, so it isn't on the line
+const REDIRECT_MARGIN_M: f64 = 20.0;
+
+#[derive(Debug)]
+pub enum ServiceAreaError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ServiceAreaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceAreaError::Io(e) => write!(f, "failed to read service area: {}", e),
+            ServiceAreaError::Json(e) => write!(f, "failed to parse service area: {}", e),
+            ServiceAreaError::Invalid(message) => write!(f, "invalid service area: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ServiceAreaError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneKind {
+    OperatingArea,
+    AirportQueue,
+    NoPickup,
+}
+
+/// Why a point was refused, with a nearby point that would be accepted when there is one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum ServiceAreaViolation {
+    OutsideServiceArea {
+        suggested: Option<LatLng>,
+    },
+    NoPickupZone {
+        zone: String,
+        suggested: Option<LatLng>,
+    },
+}
+
+impl std::fmt::Display for ServiceAreaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceAreaViolation::OutsideServiceArea { .. } => {
+                write!(f, "location is outside the service area")
+            }
+            ServiceAreaViolation::NoPickupZone { zone, .. } => {
+                write!(f, "pickups are not allowed in {}", zone)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ServiceAreaViolation {}
+
+impl ServiceAreaViolation {
+    /// Error body for APIs: the serialized violation plus a human readable `message`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut body = serde_json::to_value(self).unwrap_or_default();
+        if let Some(object) = body.as_object_mut() {
+            object.insert("message".to_string(), self.to_string().into());
+        }
+        body
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Polygon {
+    pub exterior: Vec<LatLng>,
+    pub holes: Vec<Vec<LatLng>>,
+}
+
+impl Polygon {
+    pub fn contains(&self, point: LatLng) -> bool {
+        ring_contains(&self.exterior, point) && !self.holes.iter().any(|h| ring_contains(h, point))
+    }
+
+    fn rings(&self) -> impl Iterator<Item = &Vec<LatLng>> {
+        std::iter::once(&self.exterior).chain(self.holes.iter())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Zone {
+    pub id: String,
+    pub name: String,
+    pub kind: ZoneKind,
+    pub polygons: Vec<Polygon>,
+}
+
+impl Zone {
+    pub fn contains(&self, point: LatLng) -> bool {
+        self.polygons.iter().any(|p| p.contains(point))
+    }
+
+    // closest point on any of the zone's edges
+    fn nearest_boundary_point(&self, point: LatLng) -> Option<LatLng> {
+        self.polygons
+            .iter()
+            .flat_map(Polygon::rings)
+            .flat_map(|ring| ring.windows(2))
+            .map(|edge| nearest_on_segment(point, edge[0], edge[1]))
+            .min_by(|a, b| haversine_m(point, *a).total_cmp(&haversine_m(point, *b)))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ServiceArea {
+    zones: Vec<Zone>,
+}
+
+impl ServiceArea {
+    /// No operating area and no zones, everything is allowed.
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ServiceAreaError> {
+        let text = std::fs::read_to_string(path).map_err(ServiceAreaError::Io)?;
+        Self::from_geojson(&text)
+    }
+
+    pub fn from_geojson(text: &str) -> Result<Self, ServiceAreaError> {
+        let collection: FeatureCollection =
+            serde_json::from_str(text).map_err(ServiceAreaError::Json)?;
+
+        let mut zones = Vec::with_capacity(collection.features.len());
+        for (index, feature) in collection.features.into_iter().enumerate() {
+            let polygons = match feature.geometry {
+                Geometry::Polygon(rings) => vec![polygon_from_rings(index, rings)?],
+                Geometry::MultiPolygon(polygons) => polygons
+                    .into_iter()
+                    .map(|rings| polygon_from_rings(index, rings))
+                    .collect::<Result<_, _>>()?,
+            };
+            let name = feature
+                .properties
+                .name
+                .unwrap_or_else(|| format!("zone {}", index));
+            zones.push(Zone {
+                id: feature.properties.id.unwrap_or_else(|| name.clone()),
+                name,
+                kind: feature.properties.kind,
+                polygons,
+            });
+        }
+        Ok(Self { zones })
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    pub fn zone(&self, id: &str) -> Option<&Zone> {
+        self.zones.iter().find(|z| z.id == id)
+    }
+
+    fn zones_of(&self, kind: ZoneKind) -> impl Iterator<Item = &Zone> {
+        self.zones.iter().filter(move |z| z.kind == kind)
+    }
+
+    pub fn in_operating_area(&self, point: LatLng) -> bool {
+        let mut areas = self.zones_of(ZoneKind::OperatingArea).peekable();
+        areas.peek().is_none() || areas.any(|z| z.contains(point))
+    }
+
+    /// First zone of the kind containing the point.
+    pub fn zone_at(&self, point: LatLng, kind: ZoneKind) -> Option<&Zone> {
+        self.zones_of(kind).find(|z| z.contains(point))
+    }
+
+    pub fn queue_zone_at(&self, point: LatLng) -> Option<&Zone> {
+        self.zone_at(point, ZoneKind::AirportQueue)
+    }
+
+    fn pickup_allowed(&self, point: LatLng) -> bool {
+        self.in_operating_area(point) && self.zone_at(point, ZoneKind::NoPickup).is_none()
+    }
+
+    /// Rides start inside the operating area and outside no-pickup zones.
+    pub fn check_pickup(&self, point: LatLng) -> Result<(), ServiceAreaViolation> {
+        if !self.in_operating_area(point) {
+            return Err(ServiceAreaViolation::OutsideServiceArea {
+                suggested: self
+                    .suggest_operating_area_point(point)
+                    .filter(|p| self.pickup_allowed(*p)),
+            });
+        }
+        if let Some(zone) = self.zone_at(point, ZoneKind::NoPickup) {
+            return Err(ServiceAreaViolation::NoPickupZone {
+                zone: zone.name.clone(),
+                suggested: zone
+                    .nearest_boundary_point(point)
+                    .map(|boundary| step_past(point, boundary))
+                    .filter(|p| self.pickup_allowed(*p)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Rides end inside the operating area, no-pickup zones are fine.
+    pub fn check_dropoff(&self, point: LatLng) -> Result<(), ServiceAreaViolation> {
+        if self.in_operating_area(point) {
+            Ok(())
+        } else {
+            Err(ServiceAreaViolation::OutsideServiceArea {
+                suggested: self
+                    .suggest_operating_area_point(point)
+                    .filter(|p| self.in_operating_area(*p)),
+            })
+        }
+    }
+
+    // closest point just inside the operating area
+    fn suggest_operating_area_point(&self, point: LatLng) -> Option<LatLng> {
+        self.zones_of(ZoneKind::OperatingArea)
+            .filter_map(|z| z.nearest_boundary_point(point))
+            .min_by(|a, b| haversine_m(point, *a).total_cmp(&haversine_m(point, *b)))
+            .map(|boundary| step_past(point, boundary))
+    }
+}
+
+// ray casting in lat/lng, fine at city scale
+fn ring_contains(ring: &[LatLng], point: LatLng) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[j]);
+        if (a.lat > point.lat) != (b.lat > point.lat)
+            && point.lng < (b.lng - a.lng) * (point.lat - a.lat) / (b.lat - a.lat) + a.lng
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// local equirectangular projection around `origin`, in meters
+fn project(origin: LatLng, p: LatLng) -> (f64, f64) {
+    let x = (p.lng - origin.lng).to_radians() * EARTH_RADIUS_M * origin.lat.to_radians().cos();
+    let y = (p.lat - origin.lat).to_radians() * EARTH_RADIUS_M;
+    (x, y)
+}
+
+fn unproject(origin: LatLng, (x, y): (f64, f64)) -> LatLng {
+    LatLng::new(
+        origin.lat + (y / EARTH_RADIUS_M).to_degrees(),
+        origin.lng + (x / (EARTH_RADIUS_M * origin.lat.to_radians().cos())).to_degrees(),
+    )
+}
+
+fn nearest_on_segment(point: LatLng, a: LatLng, b: LatLng) -> LatLng {
+    let (ax, ay) = project(point, a);
+    let (bx, by) = project(point, b);
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        // the point is the origin of the projection
+        (-(ax * dx + ay * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    unproject(point, (ax + t * dx, ay + t * dy))
+}
+
+// continues from `point` through `boundary` for REDIRECT_MARGIN_M, to land on the other side
+fn step_past(point: LatLng, boundary: LatLng) -> LatLng {
+    let (x, y) = project(point, boundary);
+    let length = (x * x + y * y).sqrt();
+    if length == 0.0 {
+        return boundary;
+    }
+    let scale = (length + REDIRECT_MARGIN_M) / length;
+    unproject(point, (x * scale, y * scale))
+}
+
+fn polygon_from_rings(index: usize, rings: Vec<Vec<Vec<f64>>>) -> Result<Polygon, ServiceAreaError> {
+    let mut rings = rings.into_iter().map(|ring| {
+        // GeoJSON positions are [lng, lat] with an optional altitude
+        let ring: Vec<LatLng> = ring
+            .into_iter()
+            .filter(|position| position.len() >= 2)
+            .map(|position| LatLng::new(position[1], position[0]))
+            .collect();
+        if ring.len() < 4 || ring.first() != ring.last() {
+            return Err(ServiceAreaError::Invalid(format!(
+                "feature {} has a ring that isn't closed or has less than 3 corners",
+                index
+            )));
+        }
+        Ok(ring)
+    });
+    let exterior = rings
+        .next()
+        .ok_or_else(|| ServiceAreaError::Invalid(format!("feature {} has an empty polygon", index)))??;
+    Ok(Polygon {
+        exterior,
+        holes: rings.collect::<Result<_, _>>()?,
+    })
+}
+
+// the subset of GeoJSON we read
+
+#[derive(Deserialize)]
+struct FeatureCollection {
+    features: Vec<Feature>,
+}
+
+#[derive(Deserialize)]
+struct Feature {
+    properties: ZoneProperties,
+    geometry: Geometry,
+}
+
+#[derive(Deserialize)]
+struct ZoneProperties {
+    kind: ZoneKind,
+    id: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "coordinates")]
+enum Geometry {
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPolygon(Vec<Vec<Vec<Vec<f64>>>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // [lng, lat] corners of a closed box
+    fn square(south: f64, west: f64, north: f64, east: f64) -> serde_json::Value {
+        json!([[west, south], [east, south], [east, north], [west, north], [west, south]])
+    }
+
+    fn feature(kind: &str, name: &str, rings: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "type": "Feature",
+            "properties": { "kind": kind, "name": name },
+            "geometry": { "type": "Polygon", "coordinates": rings },
+        })
+    }
+
+    // an operating area with a hole, and a no-pickup zone whose west edge is the hole's east edge
+    fn area() -> ServiceArea {
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": [
+                feature(
+                    "operating_area",
+                    "city",
+                    vec![square(37.76, -122.44, 37.80, -122.39), square(37.78, -122.42, 37.79, -122.41)],
+                ),
+                feature("no_pickup", "station", vec![square(37.775, -122.41, 37.795, -122.40)]),
+            ],
+        });
+        ServiceArea::from_geojson(&collection.to_string()).unwrap()
+    }
+
+    #[test]
+    fn ring_contains_inside_and_outside() {
+        let ring = vec![
+            LatLng::new(0.0, 0.0),
+            LatLng::new(0.0, 1.0),
+            LatLng::new(1.0, 1.0),
+            LatLng::new(1.0, 0.0),
+            LatLng::new(0.0, 0.0),
+        ];
+        assert!(ring_contains(&ring, LatLng::new(0.5, 0.5)));
+        assert!(!ring_contains(&ring, LatLng::new(1.5, 0.5)));
+        assert!(!ring_contains(&ring, LatLng::new(0.5, -0.5)));
+    }
+
+    #[test]
+    fn operating_area_excludes_its_hole() {
+        let area = area();
+
+        assert!(area.in_operating_area(LatLng::new(37.77, -122.43)));
+        assert!(!area.in_operating_area(LatLng::new(37.75, -122.43)));
+        assert!(!area.in_operating_area(LatLng::new(37.785, -122.415)));
+        assert!(area.check_pickup(LatLng::new(37.77, -122.43)).is_ok());
+    }
+
+    #[test]
+    fn unrestricted_allows_everything() {
+        let area = ServiceArea::unrestricted();
+
+        assert!(area.in_operating_area(LatLng::new(0.0, 0.0)));
+        assert!(area.check_pickup(LatLng::new(0.0, 0.0)).is_ok());
+    }
+
+    #[test]
+    fn pickup_outside_suggests_a_point_just_inside() {
+        let area = area();
+        let point = LatLng::new(37.75, -122.43);
+
+        let Err(ServiceAreaViolation::OutsideServiceArea { suggested: Some(suggested) }) =
+            area.check_pickup(point)
+        else {
+            panic!("expected an outside violation with a suggestion");
+        };
+        assert!(area.in_operating_area(suggested));
+        assert!(suggested.lat > 37.76);
+        assert!(haversine_m(point, suggested) < 1_200.0);
+    }
+
+    #[test]
+    fn no_pickup_redirect_lands_outside_the_zone() {
+        let area = area();
+        // closest to the zone's south edge
+        let point = LatLng::new(37.776, -122.405);
+
+        let Err(ServiceAreaViolation::NoPickupZone { zone, suggested: Some(suggested) }) =
+            area.check_pickup(point)
+        else {
+            panic!("expected a no-pickup violation with a suggestion");
+        };
+        assert_eq!(zone, "station");
+        assert!(area.zone_at(suggested, ZoneKind::NoPickup).is_none());
+        assert!(area.check_pickup(suggested).is_ok());
+        // dropping off there is fine
+        assert!(area.check_dropoff(point).is_ok());
+    }
+
+    #[test]
+    fn no_pickup_redirect_into_the_hole_is_not_suggested() {
+        let area = area();
+        // closest to the zone's west edge, past which is the hole of the operating area
+        let point = LatLng::new(37.785, -122.4095);
+
+        assert_eq!(
+            area.check_pickup(point),
+            Err(ServiceAreaViolation::NoPickupZone {
+                zone: "station".to_string(),
+                suggested: None,
+            })
+        );
+    }
+
+    #[test]
+    fn step_past_goes_the_margin_beyond_the_boundary() {
+        let point = LatLng::new(37.78, -122.40);
+        let boundary = LatLng::new(37.781, -122.40);
+
+        let past = step_past(point, boundary);
+
+        assert!(past.lat > boundary.lat);
+        assert!((past.lng - point.lng).abs() < 1e-9);
+        assert!((haversine_m(boundary, past) - REDIRECT_MARGIN_M).abs() < 0.5);
+        // on the boundary already, nowhere to step
+        assert_eq!(step_past(boundary, boundary), boundary);
+    }
+
+    #[test]
+    fn polygon_from_rings_reads_lng_lat_and_holes() {
+        let polygon = polygon_from_rings(
+            0,
+            vec![
+                vec![vec![0.0, 0.0], vec![2.0, 0.0], vec![2.0, 1.0], vec![0.0, 0.0]],
+                vec![vec![0.5, 0.1], vec![1.0, 0.1], vec![1.0, 0.2], vec![0.5, 0.1]],
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(polygon.exterior[1], LatLng::new(0.0, 2.0));
+        assert_eq!(polygon.holes.len(), 1);
+    }
+
+    #[test]
+    fn unclosed_ring_is_rejected() {
+        let result = polygon_from_rings(
+            3,
+            vec![vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![1.0, 1.0], vec![0.0, 1.0]]],
+        );
+        assert!(matches!(result, Err(ServiceAreaError::Invalid(message)) if message.contains("feature 3")));
+
+        // a closed ring with only two corners
+        let result = polygon_from_rings(0, vec![vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0, 0.0]]]);
+        assert!(matches!(result, Err(ServiceAreaError::Invalid(_))));
+    }
+
+    #[test]
+    fn unclosed_ring_in_geojson_is_rejected() {
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": { "kind": "operating_area" },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[-122.44, 37.76], [-122.39, 37.76], [-122.39, 37.80], [-122.44, 37.80]]],
+                },
+            }],
+        });
+
+        assert!(matches!(
+            ServiceArea::from_geojson(&collection.to_string()),
+            Err(ServiceAreaError::Invalid(_))
+        ));
+    }
+}
@@ -4,4 +4,10 @@ pub const DRIVER_AVAILABILITY_SUBJECT: &str = "driver.availability.changed";
 pub const DRIVER_ASSIGNED_SUBJECT: &str = "driver.ride.assigned";
 pub const NO_DRIVERS_AVAILABLE_SUBJECT: &str = "rider.ride.no_drivers_available";
 pub const DRIVER_ACCEPTED_RIDE_SUBJECT: &str = "driver.ride.accepted";
-pub const DRIVER_REJECTED_RIDE_SUBJECT: &str = "driver.ride.rejected";
\ No newline at end of file
+pub const DRIVER_REJECTED_RIDE_SUBJECT: &str = "driver.ride.rejected";
+pub const TRAFFIC_INCIDENT_REPORTED_SUBJECT: &str = "traffic.incident.reported";
+pub const TRAFFIC_INCIDENT_CLEARED_SUBJECT: &str = "traffic.incident.cleared";
+pub const SURGE_UPDATED_SUBJECT: &str = "pricing.surge.updated";
+pub const RIDE_PICKED_UP_SUBJECT: &str = "driver.ride.picked_up";
+pub const RIDE_COMPLETED_SUBJECT: &str = "driver.ride.completed";
+pub const DEMAND_FORECAST_SUBJECT: &str = "forecast.demand.updated";
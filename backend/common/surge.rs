@@ -0,0 +1,57 @@
+// Surge pricing cells and the multipliers services keep for them.
+//
+// The matcher computes a multiplier per cell and publishes it (SurgeUpdatedEvent), the rider
+// service prices quotes with it and the driver service shows it on ride offers. Cells are the
+// shared hex cells at CELL_RESOLUTION, everyone maps a point to the same cell on their own.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+
+use crate::events_schema::SurgeUpdatedEvent;
+use crate::geo::hex::{HexCell, CELL_RESOLUTION};
+use crate::geo::LatLng;
+
+/// Latest multiplier per cell as seen in SurgeUpdatedEvents. Cells without one, or whose
+/// last update is older than `max_age` (the publisher went away), price at 1.0.
+pub struct SurgeMultipliers {
+    max_age: chrono::Duration,
+    cells: RwLock<HashMap<HexCell, (f64, DateTime<Utc>)>>,
+}
+
+impl SurgeMultipliers {
+    pub fn new(max_age: chrono::Duration) -> Self {
+        Self {
+            max_age,
+            cells: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Out of order updates are ignored.
+    pub fn apply(&self, event: &SurgeUpdatedEvent) {
+        let mut cells = self.cells.write().unwrap();
+        if cells
+            .get(&event.cell)
+            .is_some_and(|(_, computed_at)| *computed_at > event.computed_at)
+        {
+            return;
+        }
+        if event.multiplier <= 1.0 {
+            cells.remove(&event.cell);
+        } else {
+            cells.insert(event.cell, (event.multiplier, event.computed_at));
+        }
+    }
+
+    pub fn multiplier(&self, cell: HexCell, now: DateTime<Utc>) -> f64 {
+        match self.cells.read().unwrap().get(&cell) {
+            Some((multiplier, computed_at)) if now - *computed_at <= self.max_age => *multiplier,
+            _ => 1.0,
+        }
+    }
+
+    pub fn multiplier_at(&self, point: LatLng, now: DateTime<Utc>) -> f64 {
+        self.multiplier(HexCell::of(point, CELL_RESOLUTION), now)
+    }
+}
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use futures::Stream;
 use std::pin::Pin;
+pub mod memory;
 pub mod messagingclient;
 
 /// Application-level message type
@@ -0,0 +1,70 @@
+// In-process message bus with the same subject semantics as NATS (`.` separated tokens,
+// `*` matches one token, `>` the rest), for running services together without a server.
+// Delivery is synchronous: by the time publish returns, every matching subscriber has the
+// message queued, in publish order.
+
+use crate::{Message, Messaging};
+use async_trait::async_trait;
+use futures::Stream;
+use std::pin::Pin;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+#[derive(Default)]
+pub struct InMemoryBus {
+    subscribers: Mutex<Vec<(String, mpsc::UnboundedSender<Message>)>>,
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Whether `subject` matches the subscription `pattern`, NATS style.
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for token in pattern.split('.') {
+        if token == ">" {
+            return subject_tokens.next().is_some();
+        }
+        match subject_tokens.next() {
+            Some(s) if token == "*" || token == s => {}
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
+
+#[async_trait]
+impl Messaging for InMemoryBus {
+    async fn publish(&self, subject: String, data: Vec<u8>) -> anyhow::Result<()> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // dropped subscriptions are cleaned up as they are found
+        subscribers.retain(|(pattern, tx)| {
+            !subject_matches(pattern, &subject)
+                || tx
+                    .send(Message {
+                        subject: subject.clone(),
+                        data: data.clone(),
+                    })
+                    .is_ok()
+        });
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        subject: String,
+    ) -> anyhow::Result<Pin<Box<dyn Stream<Item = anyhow::Result<Message>> + Send>>> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push((subject, tx));
+        let stream = futures::stream::poll_fn(move |cx| rx.poll_recv(cx).map(|m| m.map(Ok)));
+        Ok(Box::pin(stream))
+    }
+
+    async fn request(&self, subject: String, _data: Vec<u8>) -> anyhow::Result<Message> {
+        // nothing in the backend answers requests yet
+        Err(anyhow::anyhow!("no responders for {}", subject))
+    }
+}
@@ -0,0 +1,179 @@
+// Vehicle related types shared across the project.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::redis_namespaces::{
+    DRIVER_PET_FRIENDLY_FIELD, DRIVER_SEATS_FIELD, DRIVER_VEHICLE_CLASS_FIELD,
+    DRIVER_WHEELCHAIR_ACCESSIBLE_FIELD,
+};
+
+/// Class of a vehicle, stored in postgres by the driver service and mirrored into
+/// the driver state hash in redis so the matcher can filter on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleClass {
+    Economy,
+    Comfort,
+    Xl,
+    Lux,
+}
+
+impl VehicleClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VehicleClass::Economy => "economy",
+            VehicleClass::Comfort => "comfort",
+            VehicleClass::Xl => "xl",
+            VehicleClass::Lux => "lux",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "economy" => Some(VehicleClass::Economy),
+            "comfort" => Some(VehicleClass::Comfort),
+            "xl" => Some(VehicleClass::Xl),
+            "lux" => Some(VehicleClass::Lux),
+            _ => None,
+        }
+    }
+
+    /// Whether a vehicle of this class may serve a ride of the given product.
+    /// A nicer car can take a cheaper product of the same size (free upgrade), but
+    /// size never goes down: an XL request is only ever served by an XL vehicle.
+    pub fn can_serve(&self, product: RideProduct) -> bool {
+        matches!(
+            (product, self),
+            (RideProduct::Economy, VehicleClass::Economy | VehicleClass::Comfort)
+                | (RideProduct::Comfort, VehicleClass::Comfort | VehicleClass::Lux)
+                | (RideProduct::Xl, VehicleClass::Xl)
+                | (RideProduct::Lux, VehicleClass::Lux)
+        )
+    }
+}
+
+impl std::fmt::Display for VehicleClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The product a rider orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RideProduct {
+    #[default]
+    Economy,
+    Comfort,
+    Xl,
+    Lux,
+}
+
+impl RideProduct {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RideProduct::Economy => "economy",
+            RideProduct::Comfort => "comfort",
+            RideProduct::Xl => "xl",
+            RideProduct::Lux => "lux",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "economy" => Some(RideProduct::Economy),
+            "comfort" => Some(RideProduct::Comfort),
+            "xl" => Some(RideProduct::Xl),
+            "lux" => Some(RideProduct::Lux),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RideProduct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a ride request needs from the vehicle. Part of RideRequestedEvent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RideRequirements {
+    pub product: RideProduct,
+    pub pet_friendly: bool,
+    pub wheelchair_accessible: bool,
+    /// passenger seats needed
+    pub seats: u8,
+}
+
+impl Default for RideRequirements {
+    fn default() -> Self {
+        Self {
+            product: RideProduct::Economy,
+            pet_friendly: false,
+            wheelchair_accessible: false,
+            seats: 1,
+        }
+    }
+}
+
+/// What a driver's active vehicle offers. Mirrored into the driver state hash in redis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriverCapabilities {
+    pub vehicle_class: VehicleClass,
+    /// passenger seats, the driver's seat not included
+    pub seats: u8,
+    pub pet_friendly: bool,
+    pub wheelchair_accessible: bool,
+}
+
+impl DriverCapabilities {
+    pub fn satisfies(&self, requirements: &RideRequirements) -> bool {
+        self.vehicle_class.can_serve(requirements.product)
+            && self.seats >= requirements.seats
+            && (self.pet_friendly || !requirements.pet_friendly)
+            && (self.wheelchair_accessible || !requirements.wheelchair_accessible)
+    }
+
+    /// Fields to HSET into the driver state hash. Booleans as "1"/"0" like the rest of the hash.
+    pub fn to_redis_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (DRIVER_VEHICLE_CLASS_FIELD, self.vehicle_class.as_str().to_string()),
+            (DRIVER_SEATS_FIELD, self.seats.to_string()),
+            (
+                DRIVER_PET_FRIENDLY_FIELD,
+                if self.pet_friendly { "1" } else { "0" }.to_string(),
+            ),
+            (
+                DRIVER_WHEELCHAIR_ACCESSIBLE_FIELD,
+                if self.wheelchair_accessible { "1" } else { "0" }.to_string(),
+            ),
+        ]
+    }
+
+    /// Reads capabilities back from a driver state hash (HGETALL result).
+    /// None if the driver has no active vehicle published.
+    pub fn from_redis_hash(hash: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            vehicle_class: VehicleClass::parse(hash.get(DRIVER_VEHICLE_CLASS_FIELD)?)?,
+            seats: hash.get(DRIVER_SEATS_FIELD)?.parse().ok()?,
+            pet_friendly: hash.get(DRIVER_PET_FRIENDLY_FIELD).is_some_and(|v| v == "1"),
+            wheelchair_accessible: hash
+                .get(DRIVER_WHEELCHAIR_ACCESSIBLE_FIELD)
+                .is_some_and(|v| v == "1"),
+        })
+    }
+
+    /// All hash fields owned by capabilities, for clearing them.
+    pub fn redis_field_names() -> [&'static str; 4] {
+        [
+            DRIVER_VEHICLE_CLASS_FIELD,
+            DRIVER_SEATS_FIELD,
+            DRIVER_PET_FRIENDLY_FIELD,
+            DRIVER_WHEELCHAIR_ACCESSIBLE_FIELD,
+        ]
+    }
+}
@@ -11,16 +11,19 @@ pub enum WSMsgType {
     RideOffer,
     HeartBeat,
     SystemMessage,
+    RepositionSuggestion,
 }
 
-impl ToString for WSMsgType {
-    fn to_string(&self) -> String {
-        match self {
-            WSMsgType::DriverLocationUpdate => "driver_location_update".to_string(),
-            WSMsgType::RideOffer => "ride_offer".to_string(),
-            WSMsgType::HeartBeat => "heart_beat".to_string(),
-            WSMsgType::SystemMessage => "system_message".to_string(),
-        }
+impl std::fmt::Display for WSMsgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            WSMsgType::DriverLocationUpdate => "driver_location_update",
+            WSMsgType::RideOffer => "ride_offer",
+            WSMsgType::HeartBeat => "heart_beat",
+            WSMsgType::SystemMessage => "system_message",
+            WSMsgType::RepositionSuggestion => "reposition_suggestion",
+        };
+        f.write_str(s)
     }
 }
 
@@ -78,6 +81,20 @@ pub struct RideOffer {
     pub surge: Option<f32>,
 }
 
+/// Server → Client: an idle driver is better off in another cell, riders are expected there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositionSuggestion {
+    /// hex cell id, see common::geo::hex
+    pub cell: String,
+    /// center of the cell
+    pub target: Coord,
+    /// ride requests the cell expects soon
+    pub expected_requests: f32,
+    /// idle drivers already there
+    pub idle_drivers: u32,
+    pub expires_in_sec: u16,
+}
+
 /// Server → Client: heartbeat.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerPong {
@@ -0,0 +1,28 @@
+CREATE TABLE vehicles (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    driver_id UUID NOT NULL
+        REFERENCES drivers(id) ON DELETE CASCADE,
+
+    make TEXT NOT NULL,
+    model TEXT NOT NULL,
+    plate_number TEXT NOT NULL UNIQUE,
+    year SMALLINT NOT NULL,
+
+    vehicle_class TEXT NOT NULL CHECK (
+        vehicle_class IN (
+            'economy',
+            'xl',
+            'lux'
+        )
+    ),
+
+    -- the vehicle the driver is currently driving, drivers.car_id mirrors it
+    is_active BOOLEAN NOT NULL DEFAULT FALSE,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX vehicles_driver_id_idx ON vehicles (driver_id);
+
+-- a driver can own several vehicles but only drive one at a time
+CREATE UNIQUE INDEX vehicles_one_active_per_driver ON vehicles (driver_id) WHERE is_active;
@@ -0,0 +1,14 @@
+ALTER TABLE vehicles DROP CONSTRAINT vehicles_vehicle_class_check;
+ALTER TABLE vehicles ADD CONSTRAINT vehicles_vehicle_class_check CHECK (
+    vehicle_class IN (
+        'economy',
+        'comfort',
+        'xl',
+        'lux'
+    )
+);
+
+-- passenger seats, the driver's seat not included
+ALTER TABLE vehicles ADD COLUMN seats SMALLINT NOT NULL DEFAULT 4 CHECK (seats > 0);
+ALTER TABLE vehicles ADD COLUMN pet_friendly BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE vehicles ADD COLUMN wheelchair_accessible BOOLEAN NOT NULL DEFAULT FALSE;
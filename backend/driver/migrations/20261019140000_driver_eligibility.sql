@@ -0,0 +1,31 @@
+-- driving license document, null until the driver uploads it
+ALTER TABLE drivers ADD COLUMN license_expires_at TIMESTAMPTZ;
+
+CREATE TABLE driver_eligibility (
+    driver_id UUID PRIMARY KEY
+        REFERENCES drivers(id) ON DELETE CASCADE,
+
+    suspended BOOLEAN NOT NULL DEFAULT FALSE,
+    suspension_reason TEXT,
+    suspended_at TIMESTAMPTZ,
+    -- null while suspended means until reinstated
+    suspended_until TIMESTAMPTZ,
+
+    -- set when the driver rejects too many offered rides
+    cooldown_until TIMESTAMPTZ,
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- every accept/reject of an offered ride, the cancellation rate is computed from these
+CREATE TABLE driver_ride_responses (
+    driver_id UUID NOT NULL
+        REFERENCES drivers(id) ON DELETE CASCADE,
+    ride_id UUID NOT NULL,
+    accepted BOOLEAN NOT NULL,
+    responded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (driver_id, ride_id)
+);
+
+CREATE INDEX driver_ride_responses_driver_time_idx ON driver_ride_responses (driver_id, responded_at);
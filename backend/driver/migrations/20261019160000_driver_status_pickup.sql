@@ -0,0 +1,2 @@
+-- set when the driver picks the passenger up, in_ride covers both driving to the pickup and the trip
+ALTER TABLE driver_status ADD COLUMN picked_up_at TIMESTAMPTZ NULL;
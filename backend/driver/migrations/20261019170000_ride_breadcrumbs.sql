@@ -0,0 +1,15 @@
+-- where the driver was during a ride, from accepting it to the dropoff, for trip traces
+CREATE TABLE ride_breadcrumbs (
+    id BIGSERIAL PRIMARY KEY,
+    ride_id UUID NOT NULL,
+    driver_id UUID NOT NULL
+        REFERENCES drivers(id) ON DELETE CASCADE,
+    -- a reported position, or where the passenger got on (pickup) or off (dropoff)
+    kind TEXT NOT NULL DEFAULT 'location'
+        CHECK (kind IN ('location', 'pickup', 'dropoff')),
+    lat DOUBLE PRECISION NOT NULL,
+    lng DOUBLE PRECISION NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX ride_breadcrumbs_ride_time_idx ON ride_breadcrumbs (ride_id, recorded_at, id);
@@ -0,0 +1,19 @@
+-- every location a driver reported, append only. Partitioned by day so retention drops whole
+-- partitions; the driver service creates the partitions ahead of time (LocationHistoryService)
+CREATE TABLE driver_location_history (
+    driver_id UUID NOT NULL,
+    lat DOUBLE PRECISION NOT NULL,
+    lng DOUBLE PRECISION NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL
+) PARTITION BY RANGE (recorded_at);
+
+-- anything outside the daily partitions, e.g. a simulation clock far in the past
+CREATE TABLE driver_location_history_default PARTITION OF driver_location_history DEFAULT;
+
+CREATE INDEX driver_location_history_driver_time_idx ON driver_location_history (driver_id, recorded_at);
+
+-- daily partitions already thinned out to one point per driver per downsampling interval
+CREATE TABLE driver_location_history_downsampled (
+    day DATE PRIMARY KEY,
+    downsampled_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
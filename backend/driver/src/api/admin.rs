@@ -0,0 +1,327 @@
+// Admin/ops endpoints. Nothing here is meant to be called by the driver app.
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use common::eligibility::{DriverEligibility, IneligibilityReason};
+use common::eta::traffic::Incident;
+use common::events_schema::{TrafficIncidentClearedEvent, TrafficIncidentReportedEvent};
+use common::geo::LatLng;
+use common::subjects::{TRAFFIC_INCIDENT_CLEARED_SUBJECT, TRAFFIC_INCIDENT_REPORTED_SUBJECT};
+use serde::{Deserialize, Serialize};
+use ubersimx_messaging::Messaging;
+use uuid::Uuid;
+
+use crate::api::driver::WsTokenResponse;
+use crate::api::router::AppState;
+use crate::infra::repository::driver_repository::DriverRepository;
+use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::infra::repository::vehicle_repository::VehicleRepository;
+use crate::service::location_update::validate_coordinates;
+use crate::service::state_reconciler::DriftReport;
+
+// Middleware on all admin routes, the caller has to send `Authorization: Bearer <ADMIN_TOKEN>`.
+pub async fn require_admin_token<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !constant_time_eq(bearer.as_bytes(), state.admin_token.as_bytes()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(next.run(request).await)
+}
+
+// so the comparison time doesn't give away how much of the token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Deserialize)]
+pub struct ReconcileParams {
+    /// reconcile a single driver instead of everyone
+    pub driver_id: Option<Uuid>,
+}
+
+// Compares driver_status in postgres with the redis state hashes and repairs redis.
+pub async fn reconcile_driver_state<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Query(params): Query<ReconcileParams>,
+) -> Result<Json<DriftReport>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let report = match params.driver_id {
+        Some(driver_id) => state
+            .state_reconciler
+            .reconcile_driver(driver_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?,
+        None => state
+            .state_reconciler
+            .reconcile_all()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+pub struct SuspendDriverRequest {
+    pub reason: String,
+    /// suspended until reinstated if not given
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct EligibilityResponse {
+    pub driver_id: Uuid,
+    pub eligible: bool,
+    /// why the driver can't be matched right now, if they can't
+    pub ineligible_reason: Option<IneligibilityReason>,
+    #[serde(flatten)]
+    pub eligibility: DriverEligibility,
+}
+
+impl EligibilityResponse {
+    fn new(driver_id: Uuid, eligibility: DriverEligibility, now: DateTime<Utc>) -> Self {
+        let ineligible_reason = eligibility.check(now);
+        Self {
+            driver_id,
+            eligible: ineligible_reason.is_none(),
+            ineligible_reason,
+            eligibility,
+        }
+    }
+}
+
+pub async fn get_driver_eligibility<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<Json<EligibilityResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let eligibility = state
+        .eligibility_service
+        .get(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(EligibilityResponse::new(driver_id, eligibility, state.clock.now())))
+}
+
+// Suspended drivers can't go online and are skipped by the matcher, a live driver is
+// affected right away through the redis state hash.
+pub async fn suspend_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Json(payload): Json<SuspendDriverRequest>,
+) -> Result<Json<EligibilityResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    if payload.reason.trim().is_empty() || payload.until.is_some_and(|until| until <= state.clock.now()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let eligibility = state
+        .eligibility_service
+        .suspend(driver_id, payload.reason.trim(), payload.until)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(EligibilityResponse::new(driver_id, eligibility, state.clock.now())))
+}
+
+// Issues a websocket token without the driver's current one, for drivers whose token expired
+// or got lost (and drivers created before tokens existed). Not for suspended drivers.
+pub async fn issue_ws_token<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<Json<WsTokenResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let eligibility = state
+        .eligibility_service
+        .get(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if eligibility.check(state.clock.now()) == Some(IneligibilityReason::Suspended) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let issued = state.ws_token_service.issue(driver_id);
+    Ok(Json(WsTokenResponse {
+        token: issued.token,
+        expires_at: issued.expires_at,
+    }))
+}
+
+// Lifts a suspension and any cancellation cooldown. An expired license stays expired.
+pub async fn reinstate_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<Json<EligibilityResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let eligibility = state
+        .eligibility_service
+        .reinstate(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(EligibilityResponse::new(driver_id, eligibility, state.clock.now())))
+}
+
+#[derive(Deserialize)]
+pub struct ReportIncidentRequest {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_m: f64,
+    /// 0 closes the roads, 0.5 halves their speed
+    pub speed_factor: f64,
+    /// now if not given
+    pub starts_at: Option<DateTime<Utc>>,
+    /// lasts until cleared if not given
+    pub ends_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReportIncidentResponse {
+    #[serde(flatten)]
+    pub incident: Incident,
+    /// road graph edges slowed down in this service
+    pub affected_edges: usize,
+}
+
+// Slows down or closes the roads of an area. Applied here right away and published so the
+// matcher's ETA engine applies it too.
+pub async fn report_traffic_incident<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Json(payload): Json<ReportIncidentRequest>,
+) -> Result<(StatusCode, Json<ReportIncidentResponse>), StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let starts_at = payload.starts_at.unwrap_or_else(|| state.clock.now());
+    if validate_coordinates(payload.latitude, payload.longitude).is_err()
+        || !(0.0..=1.0).contains(&payload.speed_factor)
+        || !payload.radius_m.is_finite()
+        || payload.radius_m <= 0.0
+        || payload.ends_at.is_some_and(|ends_at| ends_at <= starts_at)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let incident = Incident {
+        id: Uuid::new_v4(),
+        center: LatLng::new(payload.latitude, payload.longitude),
+        radius_m: payload.radius_m,
+        speed_factor: payload.speed_factor,
+        starts_at,
+        ends_at: payload.ends_at,
+        description: payload.description,
+    };
+    let affected_edges = state.eta_service.report_incident(incident.clone());
+
+    let event = TrafficIncidentReportedEvent {
+        incident: incident.clone(),
+    };
+    let payload = serde_json::to_vec(&event).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Err(e) = state
+        .messaging_client
+        .publish(TRAFFIC_INCIDENT_REPORTED_SUBJECT.to_string(), payload)
+        .await
+    {
+        eprintln!("Failed to publish {TRAFFIC_INCIDENT_REPORTED_SUBJECT} : {}", e);
+        // the matcher and rider service would never hear of it, so nobody applies it
+        state.eta_service.retract_incident(incident.id);
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ReportIncidentResponse {
+            incident,
+            affected_edges,
+        }),
+    ))
+}
+
+pub async fn list_traffic_incidents<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+) -> Json<Vec<Incident>>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    Json(state.eta_service.incidents())
+}
+
+pub async fn clear_traffic_incident<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(incident_id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    if !state.eta_service.withdraw_incident(incident_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let event = TrafficIncidentClearedEvent { incident_id };
+    let payload = serde_json::to_vec(&event).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Err(e) = state
+        .messaging_client
+        .publish(TRAFFIC_INCIDENT_CLEARED_SUBJECT.to_string(), payload)
+        .await
+    {
+        eprintln!("Failed to publish {TRAFFIC_INCIDENT_CLEARED_SUBJECT} : {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
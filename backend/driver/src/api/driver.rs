@@ -3,7 +3,6 @@ use common::redis_key_helpers::driver_state_namespace;
 use common::redis_namespaces::DRIVER_AVAILABILITY_FIELD;
 use common::redis_namespaces::DRIVER_AVAILABILITY_REASON_FIELD;
 use common::redis_namespaces::DRIVER_LAST_AVAILABILITY_UPDATE_FIELD;
-use common::redis_namespaces::DRIVER_LAST_LOCATION_UPDATE_FIELD;
 use common::redis_namespaces::DRIVER_LOCATION_NAMESPACE;
 use common::subjects::DRIVER_AVAILABILITY_SUBJECT;
 use redis::AsyncTypedCommands;
@@ -14,21 +13,27 @@ use ubersimx_messaging::Messaging;
 use uuid::Uuid;
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 
 use crate::api::router::AppState;
 use crate::infra::repository::driver_repository::DriverRepository;
-use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::infra::repository::driver_status_repository::{
+    DriverStatusRepository, StatusTransitionError,
+};
+use crate::infra::repository::vehicle_repository::VehicleRepository;
 use crate::models::AvailabilityReason;
 use crate::models::Driver;
+use crate::models::DriverListFilter;
 use crate::models::DriverRedisState;
 use crate::models::DriverStatus;
 use crate::models::RideStatus;
+use crate::service::location_update::{LocationUpdate, LocationUpdateError, DRIVER_STATE_TTL_SECS};
 use crate::service::ride_lifecycle::RideLifeCycle;
-use std::sync::Arc;
+use crate::service::ws_token::WsTokenService;
 
 #[derive(Deserialize)]
 pub struct CreateDriverRequest {
@@ -36,11 +41,65 @@ pub struct CreateDriverRequest {
     pub car_id: Option<Uuid>,
 }
 
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+const DEFAULT_HISTORY_WINDOW_MINS: i64 = 60;
+const DEFAULT_HISTORY_POINTS: i64 = 1_000;
+const MAX_HISTORY_POINTS: i64 = 10_000;
+
+#[derive(Deserialize)]
+pub struct UpdateDriverRequest {
+    pub name: Option<String>,
+    pub license_number: Option<String>,
+    pub license_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub rating: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct ListDriversParams {
+    pub name: Option<String>,
+    pub min_rating: Option<f32>,
+    pub has_car: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
 #[derive(Serialize)]
 pub struct DriverResponse {
     pub id: Uuid,
     pub name: String,
+    pub license_number: Option<String>,
+    pub rating: Option<f32>,
     pub car_id: Option<Uuid>,
+    pub license_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<Driver> for DriverResponse {
+    fn from(driver: Driver) -> Self {
+        Self {
+            id: driver.id,
+            name: driver.name,
+            license_number: driver.license_number,
+            rating: driver.rating,
+            car_id: driver.car_id,
+            license_expires_at: driver.license_expires_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WsTokenResponse {
+    pub token: String,
+    pub expires_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct CreateDriverResponse {
+    #[serde(flatten)]
+    pub driver: DriverResponse,
+    // token the client uses to open the driver websocket (GET /ws?token=...)
+    pub ws_token: WsTokenResponse,
 }
 
 #[derive(Deserialize)]
@@ -49,6 +108,33 @@ pub struct DriverLocationUpdateRequest {
     pub longitude: f64,
 }
 
+#[derive(Deserialize)]
+pub struct LocationHistoryParams {
+    /// defaults to an hour before `to`
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// defaults to now
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// one location per this many seconds, every location if not given
+    pub interval_secs: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct LocationHistoryPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize)]
+pub struct LocationHistoryResponse {
+    pub driver_id: Uuid,
+    pub from: chrono::DateTime<chrono::Utc>,
+    pub to: chrono::DateTime<chrono::Utc>,
+    pub interval_secs: Option<i64>,
+    pub locations: Vec<LocationHistoryPoint>,
+}
+
 #[derive(Deserialize)]
 pub struct DriverStatusUpdateRequest {
     pub driver_available: bool,
@@ -59,13 +145,14 @@ pub struct RideActionRequest {
     pub ride_id: Uuid,
 }
 
-pub async fn create_driver<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn create_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     Json(payload): Json<CreateDriverRequest>,
-) -> Result<Json<DriverResponse>, StatusCode>
+) -> Result<Json<CreateDriverResponse>, StatusCode>
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
     let repo = state.driver_repo.clone();
 
@@ -75,6 +162,7 @@ where
         car_id: payload.car_id,
         license_number: None,
         rating: None,
+        license_expires_at: None,
     };
 
     repo.create_driver(&driver)
@@ -103,97 +191,283 @@ where
         // should be sufficient to just print as long as the database creation was successful
         eprintln!("Failed to publish {DRIVER_AVAILABILITY_SUBJECT} : {}", e);
     }
-    Ok(Json(DriverResponse {
-        id: driver.id,
-        name: driver.name,
-        car_id: driver.car_id,
+
+    let issued = state.ws_token_service.issue(driver.id);
+
+    Ok(Json(CreateDriverResponse {
+        driver: DriverResponse::from(driver),
+        ws_token: WsTokenResponse {
+            token: issued.token,
+            expires_at: issued.expires_at,
+        },
     }))
 }
 
-pub async fn get_driver<R: DriverRepository>(
-    State(repo): State<Arc<R>>,
-    Path(id): Path<Uuid>,
-) -> Result<Json<DriverResponse>, StatusCode> {
-    match repo.get_driver(id).await {
-        Ok(Some(driver)) => Ok(Json(DriverResponse {
-            id: driver.id,
-            name: driver.name,
-            car_id: driver.car_id,
-        })),
+// The driver a request's bearer token (a ws token) was issued to, 401 without a valid one.
+pub(crate) fn bearer_driver_id(
+    ws_token_service: &WsTokenService,
+    headers: &HeaderMap,
+) -> Result<Uuid, StatusCode> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    ws_token_service
+        .verify(bearer)
+        .map_err(|_| StatusCode::UNAUTHORIZED)
+}
+
+// Issues a fresh websocket token. The caller has to present a still valid token
+// for the same driver as a bearer token, the first one is handed out by create_driver.
+pub async fn refresh_ws_token<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<WsTokenResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let token_driver_id = bearer_driver_id(&state.ws_token_service, &headers)?;
+
+    if token_driver_id != driver_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let issued = state.ws_token_service.issue(driver_id);
+    Ok(Json(WsTokenResponse {
+        token: issued.token,
+        expires_at: issued.expires_at,
+    }))
+}
+
+pub async fn get_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<Json<DriverResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    match state.driver_repo.get_driver(driver_id).await {
+        Ok(Some(driver)) => Ok(Json(DriverResponse::from(driver))),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
-pub async fn list_drivers<R: DriverRepository>(
-    State(repo): State<Arc<R>>,
-) -> Result<Json<Vec<DriverResponse>>, StatusCode> {
-    let drivers = repo
-        .list_drivers()
+pub async fn list_drivers<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Query(params): Query<ListDriversParams>,
+) -> Result<Json<Vec<DriverResponse>>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let filter = DriverListFilter {
+        name: params.name,
+        min_rating: params.min_rating,
+        has_car: params.has_car,
+        limit: params
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+        offset: params.offset.unwrap_or(0).max(0),
+    };
+
+    let drivers = state
+        .driver_repo
+        .list_drivers(&filter)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let resp: Vec<DriverResponse> = drivers
-        .into_iter()
-        .map(|d| DriverResponse {
-            id: d.id,
-            name: d.name,
-            car_id: d.car_id,
-        })
-        .collect();
+    let resp: Vec<DriverResponse> = drivers.into_iter().map(DriverResponse::from).collect();
     Ok(Json(resp))
 }
 
-pub async fn update_driver_location<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn update_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     Path(driver_id): Path<Uuid>,
-    Json(payload): Json<DriverLocationUpdateRequest>,
-) -> Result<StatusCode, StatusCode>
+    Json(payload): Json<UpdateDriverRequest>,
+) -> Result<Json<DriverResponse>, StatusCode>
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
-    // todo check if the driver exists before updating location
+    let mut driver = state
+        .driver_repo
+        .get_driver(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-    // Set driver location and availability using a Redis pipeline (atomic MULTI/EXEC).
-    // This issues the commands in one network round trip and executes them
-    // inside MULTI/EXEC so they are applied atomically on the server.
+    // only the fields present in the body are changed
+    if let Some(name) = payload.name {
+        driver.name = name;
+    }
+    if let Some(license_number) = payload.license_number {
+        driver.license_number = Some(license_number);
+    }
+    let license_expiry_changed = payload
+        .license_expires_at
+        .is_some_and(|expires_at| driver.license_expires_at != Some(expires_at));
+    if let Some(license_expires_at) = payload.license_expires_at {
+        driver.license_expires_at = Some(license_expires_at);
+    }
+    if let Some(rating) = payload.rating {
+        if !(0.0..=5.0).contains(&rating) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        driver.rating = Some(rating);
+    }
 
-    let timestamp = chrono::Utc::now().timestamp_millis();
+    let updated = state
+        .driver_repo
+        .update_driver(&driver)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !updated {
+        // deleted in between the read and the update
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // the matcher reads license expiry from the state hash of a live driver
+    if license_expiry_changed {
+        if let Err(e) = state.eligibility_service.refresh(driver_id).await {
+            eprintln!("Failed to refresh eligibility of driver {}: {:?}", driver_id, e);
+        }
+    }
+
+    Ok(Json(DriverResponse::from(driver)))
+}
+
+pub async fn delete_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let deleted = state
+        .driver_repo
+        .delete_driver(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
 
-    // Build pipeline
+    // drop the live state too so the matcher stops seeing the driver right away
     let mut pipe = redis::pipe();
     pipe.atomic()
-        .geo_add(
-            DRIVER_LOCATION_NAMESPACE,
-            (payload.longitude, payload.latitude, driver_id.to_string()),
-        )
+        .zrem(DRIVER_LOCATION_NAMESPACE, driver_id.to_string())
         .ignore()
-        .hset(
-            driver_state_namespace(driver_id),
-            DRIVER_LAST_LOCATION_UPDATE_FIELD,
-            timestamp,
-        )
-        .expire(driver_state_namespace(driver_id), 90);
-
+        .del(driver_state_namespace(driver_id))
+        .ignore();
     let mut con = state.redis_con.lock().await;
-    // Execute pipeline
-    pipe.query_async::<()>(&mut *con)
+    if let Err(e) = pipe.query_async::<()>(&mut *con).await {
+        // the sweeper removes it once the state hash expires anyway
+        eprintln!("Failed to remove redis state of deleted driver {}: {}", driver_id, e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn update_driver_location<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Json(payload): Json<DriverLocationUpdateRequest>,
+) -> Result<StatusCode, Response>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    // todo check if the driver exists before updating location
+    state
+        .location_update_service
+        .handle_location_update(driver_id, payload.latitude, payload.longitude)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| match e {
+            LocationUpdateError::InvalidCoordinates { .. } => StatusCode::BAD_REQUEST.into_response(),
+            LocationUpdateError::OutsideServiceArea(violation) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, Json(violation.to_json())).into_response()
+            }
+            LocationUpdateError::Redis(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        })?;
 
     Ok(StatusCode::OK)
 }
 
+// Where the driver was between `from` and `to`, oldest first.
+pub async fn get_driver_location_history<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Query(params): Query<LocationHistoryParams>,
+) -> Result<Json<LocationHistoryResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let to = params.to.unwrap_or_else(|| state.clock.now());
+    let from = params
+        .from
+        .unwrap_or(to - chrono::Duration::minutes(DEFAULT_HISTORY_WINDOW_MINS));
+    if from >= to || params.interval_secs.is_some_and(|s| s <= 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_POINTS)
+        .clamp(1, MAX_HISTORY_POINTS);
+
+    let locations = state
+        .location_history_service
+        .history(
+            driver_id,
+            from,
+            to,
+            params.interval_secs.map(chrono::Duration::seconds),
+            limit,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(LocationHistoryResponse {
+        driver_id,
+        from,
+        to,
+        interval_secs: params.interval_secs,
+        locations: locations
+            .into_iter()
+            .map(|l| LocationHistoryPoint {
+                latitude: l.latitude,
+                longitude: l.longitude,
+                recorded_at: l.recorded_at,
+            })
+            .collect(),
+    }))
+}
+
 // todo this needs cleanup as we got lots of nesting and repeated code
 // it will be moved into the service layer
-pub async fn update_driver_status<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn update_driver_status<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     Path(driver_id): Path<Uuid>,
     Json(driver_status_request): Json<DriverStatusUpdateRequest>,
 ) -> Result<StatusCode, StatusCode>
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
     // before updating here we need to check if the driver is in ride or not
     // becuase the client app might send availability updates while in ride.
@@ -204,6 +478,55 @@ where
 
     let key = driver_state_namespace(driver_id);
 
+    // a suspended, cooling down or unlicensed driver can't come online
+    let eligibility = if driver_status_request.driver_available {
+        let eligibility = state
+            .eligibility_service
+            .get(driver_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if eligibility
+            .as_ref()
+            .is_some_and(|e| e.check(state.clock.now()).is_some())
+        {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        eligibility
+    } else {
+        None
+    };
+
+    // the matcher filters on vehicle capabilities, so a driver coming online publishes those of their active vehicle
+    let capabilities = if driver_status_request.driver_available {
+        state
+            .vehicle_repo
+            .get_active_vehicle(driver_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|v| v.capabilities())
+    } else {
+        None
+    };
+
+    // with a ride assigned or on one the choice is only recorded, the driver stays busy in redis
+    // and the ride lifecycle applies it once the ride is over
+    let current_status = state
+        .driver_status_repo
+        .get_status(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if current_status
+        .as_ref()
+        .is_some_and(|s| matches!(s.ride_status, RideStatus::Assigned | RideStatus::InRide))
+    {
+        state
+            .driver_status_repo
+            .patch_status(driver_id, Some(driver_status_request.driver_available), None, None)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(StatusCode::OK);
+    }
+
     let mut con = state.redis_con.lock().await;
     let driver_state_map: std::collections::HashMap<String, String> = match con.hgetall(&key).await
     {
@@ -241,9 +564,15 @@ where
                 .hset(
                     &key,
                     DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
-                    chrono::Utc::now().timestamp(),
+                    state.clock.now().timestamp(),
                 )
-                .expire(&key, 90);
+                .expire(&key, DRIVER_STATE_TTL_SECS);
+            if let Some(capabilities) = &capabilities {
+                pipe.hset_multiple(&key, &capabilities.to_redis_fields());
+            }
+            if let Some(eligibility) = &eligibility {
+                pipe.hset_multiple(&key, &eligibility.to_redis_fields());
+            }
             pipe.query_async::<()>(&mut *con)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -260,7 +589,7 @@ where
                     driver_available: driver_status_request.driver_available,
                     ride_status: RideStatus::None,
                     current_trip_id: None,
-                    status_updated_at: chrono::Utc::now(),
+                    status_updated_at: state.clock.now(),
                 };
                 let _ = state
                     .driver_status_repo
@@ -283,7 +612,7 @@ where
         serde_json::from_str(&json).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let availability =
-        crate::models::compute_availability(chrono::Utc::now().timestamp(), &redis_driver_state);
+        crate::models::compute_availability(state.clock.now().timestamp(), &redis_driver_state);
 
     if !availability.available
         && driver_status_request.driver_available
@@ -321,7 +650,11 @@ where
     let mut con = state.redis_con.lock().await;
     let mut pipe = redis::pipe();
     pipe.atomic()
-        .hset(&key, DRIVER_AVAILABILITY_FIELD, true)
+        .hset(
+            &key,
+            DRIVER_AVAILABILITY_FIELD,
+            driver_status_request.driver_available,
+        )
         .hset(
             &key,
             DRIVER_AVAILABILITY_REASON_FIELD,
@@ -334,9 +667,15 @@ where
         .hset(
             &key,
             DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
-            chrono::Utc::now().timestamp(),
+            state.clock.now().timestamp(),
         )
-        .expire(&key, 90);
+        .expire(&key, DRIVER_STATE_TTL_SECS);
+    if let Some(capabilities) = &capabilities {
+        pipe.hset_multiple(&key, &capabilities.to_redis_fields());
+    }
+    if let Some(eligibility) = &eligibility {
+        pipe.hset_multiple(&key, &eligibility.to_redis_fields());
+    }
     pipe.query_async::<()>(&mut *con)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -361,7 +700,7 @@ where
                 driver_available: driver_status_request.driver_available,
                 ride_status: RideStatus::None,
                 current_trip_id: None,
-                status_updated_at: chrono::Utc::now(),
+                status_updated_at: state.clock.now(),
             };
             let result = state
                 .driver_status_repo
@@ -382,38 +721,84 @@ where
     }
 }
 
+// A driver acting on a ride they are not (or no longer) assigned to is a conflict, not a server error
+fn ride_action_error_status(e: anyhow::Error) -> StatusCode {
+    match e.downcast_ref::<StatusTransitionError>() {
+        Some(StatusTransitionError::Conflict { .. }) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 // Handler for when a driver accepts a ride
-pub async fn accept_ride_by_driver<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn accept_ride_by_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     Path(driver_id): Path<Uuid>,
     Json(payload): Json<RideActionRequest>,
 ) -> Result<StatusCode, StatusCode>
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
 
     state.ride_lifecycle_service
         .handle_driver_accept_ride_assignment(driver_id, payload.ride_id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(ride_action_error_status)?;
     Ok(StatusCode::OK)
 }
 
 // Handler for when a driver rejects a ride
-pub async fn reject_ride_by_driver<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn reject_ride_by_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     Path(driver_id): Path<Uuid>,
     Json(payload): Json<RideActionRequest>,
 ) -> Result<StatusCode, StatusCode>
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
 
     state.ride_lifecycle_service
         .handle_driver_reject_ride_assignment(driver_id, payload.ride_id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(ride_action_error_status)?;
+    Ok(StatusCode::OK)
+}
+
+// Handler for when the driver picked the passenger up
+pub async fn pickup_ride_by_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Json(payload): Json<RideActionRequest>,
+) -> Result<StatusCode, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    state.ride_lifecycle_service
+        .start_ride(driver_id, payload.ride_id)
+        .await
+        .map_err(ride_action_error_status)?;
+    Ok(StatusCode::OK)
+}
+
+// Handler for when the driver dropped the passenger off
+pub async fn complete_ride_by_driver<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Json(payload): Json<RideActionRequest>,
+) -> Result<StatusCode, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    state.ride_lifecycle_service
+        .complete_ride(driver_id, payload.ride_id)
+        .await
+        .map_err(ride_action_error_status)?;
     Ok(StatusCode::OK)
 }
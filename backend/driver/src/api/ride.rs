@@ -0,0 +1,58 @@
+// Ride endpoints, for looking back at what happened on a ride.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::api::driver::bearer_driver_id;
+use crate::api::router::AppState;
+use crate::infra::repository::driver_repository::DriverRepository;
+use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::infra::repository::vehicle_repository::VehicleRepository;
+use crate::service::ride_trace::TraceFormat;
+
+#[derive(Deserialize)]
+pub struct TraceParams {
+    #[serde(default)]
+    pub format: TraceFormat,
+}
+
+// The path the driver took on the ride with timestamps, pickup/dropoff markers and distances.
+// Only for the driver who drove it, with the same bearer token as the ws-token refresh.
+pub async fn get_ride_trace<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(ride_id): Path<Uuid>,
+    Query(params): Query<TraceParams>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let token_driver_id = bearer_driver_id(&state.ws_token_service, &headers)?;
+
+    let trace = state
+        .ride_trace_service
+        .trace(ride_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if trace.driver_id != token_driver_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(match params.format {
+        TraceFormat::Geojson => (
+            [(CONTENT_TYPE, "application/geo+json")],
+            trace.to_geojson().to_string(),
+        )
+            .into_response(),
+        TraceFormat::Gpx => ([(CONTENT_TYPE, "application/gpx+xml")], trace.to_gpx()).into_response(),
+    })
+}
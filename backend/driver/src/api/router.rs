@@ -1,10 +1,22 @@
 use crate::api::ws::ws_handler;
 use crate::infra::repository::driver_repository::DriverRepository;
 use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::infra::repository::vehicle_repository::VehicleRepository;
 use crate::infra::ws::hub::WsHub;
+use crate::service::eligibility::EligibilityService;
+use crate::service::eta_service::EtaService;
+use crate::service::location_history::LocationHistoryService;
 use crate::service::location_update::LocationUpdateService;
-use crate::{api::driver, service::ride_lifecycle::RideLifeCycleService};
-use axum::routing::get;
+use crate::service::ride_trace::RideTraceService;
+use crate::service::state_reconciler::StateReconcilerService;
+use crate::service::ws_token::WsTokenService;
+use crate::{
+    api::{admin, driver, ride, vehicle},
+    service::ride_lifecycle::RideLifeCycleService,
+};
+use axum::middleware;
+use axum::routing::{delete, get};
+use common::clock::SharedClock;
 use axum::{routing::post, Router};
 use ubersimx_messaging::messagingclient::MessagingClient;
 
@@ -16,49 +28,135 @@ use std::sync::Arc;
 // In contrast, the rider AppState does not use generics—I'm experimenting with both approaches to see
 // which fits best for our needs.
 #[derive(Clone)]
-pub struct AppState<D, C> {
+pub struct AppState<D, C, V> {
     pub driver_repo: Arc<D>,
     pub driver_status_repo: Arc<C>,
+    pub vehicle_repo: Arc<V>,
     pub messaging_client: Arc<MessagingClient>,
     pub redis_con: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
 
     // Usecase services - slowly start deleting direct repo access in handlers
     pub ride_lifecycle_service: Arc<RideLifeCycleService>,
     pub location_update_service: Arc<LocationUpdateService>,
-    pub ws_hub: Arc<WsHub>
+    pub location_history_service: Arc<LocationHistoryService>,
+    pub ws_hub: Arc<WsHub>,
+    pub ws_token_service: Arc<WsTokenService>,
+    pub admin_token: Arc<String>,
+    pub state_reconciler: Arc<StateReconcilerService>,
+    pub eligibility_service: Arc<EligibilityService>,
+    pub eta_service: Arc<EtaService>,
+    pub ride_trace_service: Arc<RideTraceService>,
+    pub clock: SharedClock,
 }
 
-pub fn create_router<D, C>(state: AppState<D, C>) -> Router
+pub fn create_router<D, C, V>(state: AppState<D, C, V>) -> Router
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
+    let admin_routes = Router::new()
+        .route(
+            "/api/v1/admin/reconcile",
+            post(admin::reconcile_driver_state::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/drivers/{driver_id}/eligibility",
+            get(admin::get_driver_eligibility::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/drivers/{driver_id}/suspend",
+            post(admin::suspend_driver::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/drivers/{driver_id}/ws-token",
+            post(admin::issue_ws_token::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/drivers/{driver_id}/reinstate",
+            post(admin::reinstate_driver::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/traffic/incidents",
+            post(admin::report_traffic_incident::<D, C, V>)
+                .get(admin::list_traffic_incidents::<D, C, V>),
+        )
+        .route(
+            "/api/v1/admin/traffic/incidents/{incident_id}",
+            delete(admin::clear_traffic_incident::<D, C, V>),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin::require_admin_token::<D, C, V>,
+        ));
+
     Router::new()
         // Driver routes
-        .route("/api/v1/drivers", post(driver::create_driver))
+        .route(
+            "/api/v1/drivers",
+            post(driver::create_driver::<D, C, V>).get(driver::list_drivers::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}",
+            get(driver::get_driver::<D, C, V>)
+                .patch(driver::update_driver::<D, C, V>)
+                .delete(driver::delete_driver::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}/ws-token",
+            post(driver::refresh_ws_token::<D, C, V>),
+        )
         .route(
             "/api/v1/drivers/{driver_id}/location",
-            post(driver::update_driver_location::<D, C>),
+            post(driver::update_driver_location::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}/locations/history",
+            get(driver::get_driver_location_history::<D, C, V>),
         )
         .route(
             "/api/v1/drivers/{driver_id}/status",
-            post(driver::update_driver_status::<D, C>),
+            post(driver::update_driver_status::<D, C, V>),
         )
         .route(
             "/api/v1/drivers/{driver_id}/ride/accept",
-            post(driver::accept_ride_by_driver::<D, C>),
+            post(driver::accept_ride_by_driver::<D, C, V>),
         )
         .route(
             "/api/v1/drivers/{driver_id}/ride/reject",
-            post(driver::reject_ride_by_driver::<D, C>),
-        )
-        .route("/ws", get(ws_handler::<D, C>))
-        // .route("/drivers", get(driver::list_drivers::<D>))
-        // .route("/drivers/:id", get(driver::get_driver::<D>))
-        // Car routes
-        // .route("/vehicles", post(crate::infra::repository::vehicle_repository::create_vehicle::<D, C>))
-        // .route("/vehicles", get(car::list_vehicles::<D, C>))
-        // .route("/vehicles/:id", get(car::get_car::<D, C>))
+            post(driver::reject_ride_by_driver::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}/ride/pickup",
+            post(driver::pickup_ride_by_driver::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}/ride/complete",
+            post(driver::complete_ride_by_driver::<D, C, V>),
+        )
+        // Vehicle routes
+        .route(
+            "/api/v1/drivers/{driver_id}/vehicles",
+            post(vehicle::create_vehicle::<D, C, V>).get(vehicle::list_driver_vehicles::<D, C, V>),
+        )
+        .route(
+            "/api/v1/drivers/{driver_id}/vehicles/{vehicle_id}/activate",
+            post(vehicle::activate_vehicle::<D, C, V>),
+        )
+        .route(
+            "/api/v1/vehicles/{vehicle_id}",
+            get(vehicle::get_vehicle::<D, C, V>)
+                .patch(vehicle::update_vehicle::<D, C, V>)
+                .delete(vehicle::delete_vehicle::<D, C, V>),
+        )
+        // Ride routes
+        .route(
+            "/api/v1/rides/{ride_id}/trace",
+            get(ride::get_ride_trace::<D, C, V>),
+        )
+        .route("/ws", get(ws_handler::<D, C, V>))
+        // Admin routes, all behind the admin token
+        .merge(admin_routes)
         // hook the state
         // there is .layer that allows to attach different bits of state separately, like DBpool, metrics, feature flag store etc
         .with_state(state)
@@ -0,0 +1,334 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use common::redis_key_helpers::driver_state_namespace;
+use common::vehicle::{DriverCapabilities, VehicleClass};
+use redis::AsyncTypedCommands;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::router::AppState;
+use crate::infra::repository::driver_repository::DriverRepository;
+use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::infra::repository::vehicle_repository::VehicleRepository;
+use crate::models::Vehicle;
+
+#[derive(Deserialize)]
+pub struct CreateVehicleRequest {
+    pub make: String,
+    pub model: String,
+    pub plate_number: String,
+    pub year: u16,
+    pub vehicle_class: VehicleClass,
+    #[serde(default = "default_seats")]
+    pub seats: u8,
+    #[serde(default)]
+    pub pet_friendly: bool,
+    #[serde(default)]
+    pub wheelchair_accessible: bool,
+}
+
+fn default_seats() -> u8 {
+    4
+}
+
+#[derive(Deserialize)]
+pub struct UpdateVehicleRequest {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub plate_number: Option<String>,
+    pub year: Option<u16>,
+    pub vehicle_class: Option<VehicleClass>,
+    pub seats: Option<u8>,
+    pub pet_friendly: Option<bool>,
+    pub wheelchair_accessible: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct VehicleResponse {
+    pub id: Uuid,
+    pub driver_id: Uuid,
+    pub make: String,
+    pub model: String,
+    pub plate_number: String,
+    pub year: u16,
+    pub vehicle_class: VehicleClass,
+    pub seats: u8,
+    pub pet_friendly: bool,
+    pub wheelchair_accessible: bool,
+    pub is_active: bool,
+}
+
+impl From<Vehicle> for VehicleResponse {
+    fn from(v: Vehicle) -> Self {
+        Self {
+            id: v.id,
+            driver_id: v.driver_id,
+            make: v.make,
+            model: v.model,
+            plate_number: v.plate_number,
+            year: v.year,
+            vehicle_class: v.vehicle_class,
+            seats: v.seats,
+            pet_friendly: v.pet_friendly,
+            wheelchair_accessible: v.wheelchair_accessible,
+            is_active: v.is_active,
+        }
+    }
+}
+
+pub async fn create_vehicle<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+    Json(payload): Json<CreateVehicleRequest>,
+) -> Result<(StatusCode, Json<VehicleResponse>), StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    // year is a SMALLINT column
+    if payload.seats == 0 || i16::try_from(payload.year).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    state
+        .driver_repo
+        .get_driver(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let vehicle = Vehicle {
+        id: Uuid::new_v4(),
+        make: payload.make,
+        model: payload.model,
+        plate_number: payload.plate_number,
+        year: payload.year,
+        driver_id,
+        vehicle_class: payload.vehicle_class,
+        seats: payload.seats,
+        pet_friendly: payload.pet_friendly,
+        wheelchair_accessible: payload.wheelchair_accessible,
+        is_active: false,
+    };
+
+    state
+        .vehicle_repo
+        .create_vehicle(&vehicle)
+        .await
+        .map_err(|e| conflict_or_internal(&e))?;
+
+    Ok((StatusCode::CREATED, Json(VehicleResponse::from(vehicle))))
+}
+
+pub async fn list_driver_vehicles<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(driver_id): Path<Uuid>,
+) -> Result<Json<Vec<VehicleResponse>>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let vehicles = state
+        .vehicle_repo
+        .list_vehicles_by_driver(driver_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(vehicles.into_iter().map(VehicleResponse::from).collect()))
+}
+
+pub async fn get_vehicle<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(vehicle_id): Path<Uuid>,
+) -> Result<Json<VehicleResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    match state.vehicle_repo.get_vehicle(vehicle_id).await {
+        Ok(Some(vehicle)) => Ok(Json(VehicleResponse::from(vehicle))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn update_vehicle<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(vehicle_id): Path<Uuid>,
+    Json(payload): Json<UpdateVehicleRequest>,
+) -> Result<Json<VehicleResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    if payload.seats == Some(0) || payload.year.is_some_and(|year| i16::try_from(year).is_err()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut vehicle = state
+        .vehicle_repo
+        .get_vehicle(vehicle_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if let Some(make) = payload.make {
+        vehicle.make = make;
+    }
+    if let Some(model) = payload.model {
+        vehicle.model = model;
+    }
+    if let Some(plate_number) = payload.plate_number {
+        vehicle.plate_number = plate_number;
+    }
+    if let Some(year) = payload.year {
+        vehicle.year = year;
+    }
+    let capabilities_before = vehicle.capabilities();
+    if let Some(vehicle_class) = payload.vehicle_class {
+        vehicle.vehicle_class = vehicle_class;
+    }
+    if let Some(seats) = payload.seats {
+        vehicle.seats = seats;
+    }
+    if let Some(pet_friendly) = payload.pet_friendly {
+        vehicle.pet_friendly = pet_friendly;
+    }
+    if let Some(wheelchair_accessible) = payload.wheelchair_accessible {
+        vehicle.wheelchair_accessible = wheelchair_accessible;
+    }
+
+    let updated = state
+        .vehicle_repo
+        .update_vehicle(&vehicle)
+        .await
+        .map_err(|e| conflict_or_internal(&e))?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    if vehicle.is_active && vehicle.capabilities() != capabilities_before {
+        publish_capabilities(&state, vehicle.driver_id, &vehicle.capabilities()).await;
+    }
+
+    Ok(Json(VehicleResponse::from(vehicle)))
+}
+
+pub async fn delete_vehicle<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path(vehicle_id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let vehicle = state
+        .vehicle_repo
+        .get_vehicle(vehicle_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let deleted = state
+        .vehicle_repo
+        .delete_vehicle(vehicle_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // a driver without an active vehicle can't be matched anymore
+    if vehicle.is_active {
+        let mut con = state.redis_con.lock().await;
+        if let Err(e) = con
+            .hdel(
+                driver_state_namespace(vehicle.driver_id),
+                &DriverCapabilities::redis_field_names(),
+            )
+            .await
+        {
+            eprintln!("Failed to clear capabilities of driver {}: {}", vehicle.driver_id, e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Assigns the vehicle as the one the driver is currently driving
+pub async fn activate_vehicle<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
+    Path((driver_id, vehicle_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<VehicleResponse>, StatusCode>
+where
+    D: DriverRepository + Send + Sync + Clone + 'static,
+    C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
+{
+    let activated = state
+        .vehicle_repo
+        .activate_vehicle(driver_id, vehicle_id)
+        .await
+        .map_err(|e| conflict_or_internal(&e))?;
+    if !activated {
+        // unknown vehicle or owned by another driver
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let vehicle = state
+        .vehicle_repo
+        .get_vehicle(vehicle_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    publish_capabilities(&state, driver_id, &vehicle.capabilities()).await;
+
+    Ok(Json(VehicleResponse::from(vehicle)))
+}
+
+// A unique violation is a duplicate plate number or a concurrent activation racing for the
+// one active vehicle per driver, anything else is on us.
+fn conflict_or_internal(e: &anyhow::Error) -> StatusCode {
+    let unique_violation = e
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .is_some_and(|e| e.is_unique_violation());
+    if unique_violation {
+        StatusCode::CONFLICT
+    } else {
+        eprintln!("Vehicle write failed: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+// Mirrors the active vehicle capabilities into the driver state hash, but only for a live driver.
+// An offline driver has no hash and gets them written when they come online (see update_driver_status).
+async fn publish_capabilities<D, C, V>(
+    state: &AppState<D, C, V>,
+    driver_id: Uuid,
+    capabilities: &DriverCapabilities,
+) {
+    let key = driver_state_namespace(driver_id);
+    let mut con = state.redis_con.lock().await;
+
+    match con.exists(&key).await {
+        Ok(true) => {
+            if let Err(e) = con
+                .hset_multiple(&key, &capabilities.to_redis_fields())
+                .await
+            {
+                eprintln!("Failed to set capabilities of driver {}: {}", driver_id, e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("Failed to read state of driver {}: {}", driver_id, e),
+    }
+}
@@ -1,49 +1,75 @@
 /// Handles WebSocket upgrade requests for driver connections.
-/// 
-/// Extracts the client ID from query parameters (or generates a new UUID if not provided),
-/// and upgrades the HTTP connection to a WebSocket. Once upgraded, the socket is passed
+///
+/// The client must pass a `token` query param issued by the driver service
+/// (see `service::ws_token`). The token is verified before the upgrade and the
+/// driver id it carries becomes the identity of the session, so a client can only
+/// ever act as the driver it was issued for. The driver must still exist and not be
+/// suspended or without a valid license. Once upgraded, the socket is passed
 /// to `ws_on_upgrade` along with the WebSocket hub and location update service for
 /// managing real-time driver location updates and connection lifecycle.
 use axum::{
     extract::{Query, State, WebSocketUpgrade},
-    response::Response,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
+use common::eligibility::IneligibilityReason;
 use std::collections::HashMap;
-use uuid::Uuid;
 
 use crate::{
     api::router::AppState,
     infra::{
         repository::{
             driver_repository::DriverRepository, driver_status_repository::DriverStatusRepository,
+            vehicle_repository::VehicleRepository,
         },
-        ws::{connections::ws_on_upgrade},
+        ws::connections::ws_on_upgrade,
     },
 };
 
-pub async fn ws_handler<D, C>(
-    State(state): State<AppState<D, C>>,
+pub async fn ws_handler<D, C, V>(
+    State(state): State<AppState<D, C, V>>,
     ws: WebSocketUpgrade,
     Query(params): Query<HashMap<String, String>>,
 ) -> Response
 where
     D: DriverRepository + Send + Sync + Clone + 'static,
     C: DriverStatusRepository + Send + Sync + Clone + 'static,
+    V: VehicleRepository + Send + Sync + Clone + 'static,
 {
-    let client_id = params
-        .get("client_id")
-        .and_then(|id| Uuid::parse_str(id).ok())
-        .unwrap_or_else(Uuid::new_v4);
+    let Some(token) = params.get("token") else {
+        return (StatusCode::UNAUTHORIZED, "missing token").into_response();
+    };
+
+    let driver_id = match state.ws_token_service.verify(token) {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, e.to_string()).into_response(),
+    };
+
+    // tokens can't be revoked, so a deleted or suspended driver is turned away here. A
+    // cooldown only pauses ride offers, those drivers may still connect.
+    match state.eligibility_service.get(driver_id).await {
+        Ok(Some(eligibility)) => match eligibility.check(state.clock.now()) {
+            Some(reason @ (IneligibilityReason::Suspended | IneligibilityReason::LicenseExpired)) => {
+                return (StatusCode::FORBIDDEN, reason.to_string()).into_response();
+            }
+            Some(IneligibilityReason::Cooldown) | None => {}
+        },
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "unknown driver").into_response(),
+        Err(e) => {
+            eprintln!("Failed to check driver {} on websocket upgrade: {:?}", driver_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
 
     let hub = state.ws_hub.clone();
     let location_update_service = state.location_update_service.clone();
-    
+
     ws.on_upgrade(move |socket| {
         ws_on_upgrade(
             socket,
             hub.clone(),
             location_update_service.clone(),
-            client_id,
+            driver_id,
         )
     })
 }
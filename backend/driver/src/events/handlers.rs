@@ -3,8 +3,13 @@
 
 use crate::events::schemas::DriverAssignedRideDto;
 use crate::service::ride_lifecycle::RideLifeCycle;
+use crate::service::eta_service::{EtaService, IncidentChange};
 use crate::service::ride_lifecycle::RideLifeCycleService;
-use common::events_schema::DriverAssignedRideEvent;
+use common::events_schema::{
+    DemandForecastEvent, DriverAssignedRideEvent, TrafficIncidentClearedEvent,
+    TrafficIncidentReportedEvent,
+};
+use common::repositioning::DemandForecasts;
 
 #[async_trait::async_trait]
 pub trait EventHandler<T> {
@@ -22,3 +27,34 @@ impl EventHandler<DriverAssignedRideEvent> for RideLifeCycleService {
         }
     }
 }
+
+#[async_trait::async_trait]
+impl EventHandler<TrafficIncidentReportedEvent> for EtaService {
+    async fn handle(&self, evt: TrafficIncidentReportedEvent) {
+        let incident_id = evt.incident.id;
+        if self.is_own_change(incident_id, IncidentChange::Reported) {
+            return;
+        }
+        let edges = self.apply_incident(evt.incident);
+        eprintln!("Traffic incident {} applied to {} edges", incident_id, edges);
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<TrafficIncidentClearedEvent> for EtaService {
+    async fn handle(&self, evt: TrafficIncidentClearedEvent) {
+        if self.is_own_change(evt.incident_id, IncidentChange::Cleared) {
+            return;
+        }
+        if !self.clear_incident(evt.incident_id) {
+            eprintln!("Cleared unknown traffic incident {}", evt.incident_id);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<DemandForecastEvent> for DemandForecasts {
+    async fn handle(&self, evt: DemandForecastEvent) {
+        self.apply(evt);
+    }
+}
@@ -1,16 +1,17 @@
 //  publishes outgoing events (MatchProposed, MatchConfirmed, etc)
 // technically this won't needed but I don't want hte services/business logic to depend on the messaging client directly
 use std::sync::Arc;
-use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
+use ubersimx_messaging::Messaging;
 
 /// Event producer publishes domain events back to NATS
 #[derive(Clone)]
 pub struct EventPublisher {
-    nc: Arc<MessagingClient>,
+    // NATS in the service, the in-memory bus in a simulation
+    nc: Arc<dyn Messaging>,
 }
 
 impl EventPublisher {
-    pub fn new(nc: Arc<MessagingClient>) -> Self {
+    pub fn new(nc: Arc<dyn Messaging>) -> Self {
         Self { nc }
     }
 
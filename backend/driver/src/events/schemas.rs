@@ -16,6 +16,7 @@ pub struct DriverAssignedRideDto {
     pub pickup_lng: f64,
     pub dropoff_lat: f64,
     pub dropoff_lng: f64,
+    pub surge_multiplier: Option<f64>,
 }
 
 /// Mapper from DriverAssignedRideEvent to DriverAssignedRideDto
@@ -29,6 +30,7 @@ impl From<&DriverAssignedRideEvent> for DriverAssignedRideDto {
             pickup_lng: event.pickup_lng,
             dropoff_lat: event.dropoff_lat,
             dropoff_lng: event.dropoff_lng,
+            surge_multiplier: event.surge_multiplier,
         }
     }
 }
@@ -6,12 +6,23 @@
 
 use std::sync::Arc;
 
-use common::{events_schema::DriverAssignedRideEvent, subjects::DRIVER_ASSIGNED_SUBJECT};
+use common::events_schema::{
+    DemandForecastEvent, DriverAssignedRideEvent, TrafficIncidentClearedEvent,
+    TrafficIncidentReportedEvent,
+};
+use common::repositioning::DemandForecasts;
+use common::subjects::{
+    DEMAND_FORECAST_SUBJECT, DRIVER_ASSIGNED_SUBJECT, TRAFFIC_INCIDENT_CLEARED_SUBJECT,
+    TRAFFIC_INCIDENT_REPORTED_SUBJECT,
+};
 use futures_util::StreamExt;
 use serde::de::DeserializeOwned;
 use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
 
-use crate::{events::handlers::EventHandler, service::ride_lifecycle::RideLifeCycleService};
+use crate::{
+    events::handlers::EventHandler,
+    service::{eta_service::EtaService, ride_lifecycle::RideLifeCycleService},
+};
 
 pub struct Subscribers {
     messaging_client: Arc<MessagingClient>,
@@ -42,10 +53,8 @@ impl Subscribers {
                 if let Ok(msg) = msg {
                     match serde_json::from_slice::<T>(&msg.data) {
                         Ok(evt) => handler.handle(evt).await,
-                        Err(e) => {
-                            eprintln!("Failed to parse event on {:?}: {:?}", &msg.data, e);
-                            todo!()
-                        }
+                        // skipped, one malformed event must not end the subscription
+                        Err(e) => eprintln!("Failed to parse event on {}: {:?}", msg.subject, e),
                     }
                 }
             }
@@ -57,4 +66,24 @@ impl Subscribers {
         self.subscribe::<DriverAssignedRideEvent, _>(DRIVER_ASSIGNED_SUBJECT, matcher.clone())
             .await;
     }
+
+    /// Traffic incidents feed the ETA engine, they can be reported by any service (or the simulator)
+    pub async fn register_traffic_consumers(&self, eta_service: Arc<EtaService>) {
+        self.subscribe::<TrafficIncidentReportedEvent, _>(
+            TRAFFIC_INCIDENT_REPORTED_SUBJECT,
+            eta_service.clone(),
+        )
+        .await;
+        self.subscribe::<TrafficIncidentClearedEvent, _>(
+            TRAFFIC_INCIDENT_CLEARED_SUBJECT,
+            eta_service.clone(),
+        )
+        .await;
+    }
+
+    /// The matcher's demand forecasts, for repositioning idle drivers
+    pub async fn register_forecast_consumers(&self, forecasts: Arc<DemandForecasts>) {
+        self.subscribe::<DemandForecastEvent, _>(DEMAND_FORECAST_SUBJECT, forecasts)
+            .await;
+    }
 }
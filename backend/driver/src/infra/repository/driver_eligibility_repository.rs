@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
+use common::eligibility::DriverEligibility;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait DriverEligibilityRepository {
+    /// None if the driver doesn't exist. A driver without an eligibility row is eligible.
+    async fn get_eligibility(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error>;
+    /// Returns false if the driver doesn't exist.
+    async fn suspend(
+        &self,
+        driver_id: Uuid,
+        reason: &str,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<bool, Error>;
+    /// Lifts the suspension and ends any cooldown now. Returns false if the driver doesn't exist.
+    async fn reinstate(&self, driver_id: Uuid) -> Result<bool, Error>;
+    async fn set_cooldown(&self, driver_id: Uuid, until: DateTime<Utc>) -> Result<(), Error>;
+    /// Records the driver's answer to an offered ride, a repeated answer for the same ride is ignored.
+    async fn record_ride_response(
+        &self,
+        driver_id: Uuid,
+        ride_id: Uuid,
+        accepted: bool,
+    ) -> Result<(), Error>;
+    /// (responses, rejections) of the driver since `since`.
+    async fn count_ride_responses(
+        &self,
+        driver_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<(i64, i64), Error>;
+}
+
+#[derive(Clone)]
+pub struct PgDriverEligibilityRepository {
+    pub pool: Arc<PgPool>,
+    // timestamps are bound from the service clock, NOW() would be wall time in a simulation
+    pub clock: SharedClock,
+}
+
+impl PgDriverEligibilityRepository {
+    pub fn new(pool: Arc<PgPool>, clock: SharedClock) -> Self {
+        Self { pool, clock }
+    }
+}
+
+type EligibilityRow = (
+    Option<bool>,
+    Option<String>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+);
+
+// DriverEligibility lives in common, so a plain fn instead of a From impl
+fn eligibility_from_row(row: EligibilityRow) -> DriverEligibility {
+    DriverEligibility {
+        suspended: row.0.unwrap_or(false),
+        suspension_reason: row.1,
+        suspended_until: row.2,
+        cooldown_until: row.3,
+        license_expires_at: row.4,
+    }
+}
+
+#[async_trait]
+impl DriverEligibilityRepository for PgDriverEligibilityRepository {
+    async fn get_eligibility(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error> {
+        // license expiry lives on the driver, the rest in driver_eligibility which may have no row yet
+        let row = sqlx::query_as::<_, EligibilityRow>(
+            "SELECT e.suspended, e.suspension_reason, e.suspended_until, e.cooldown_until, d.license_expires_at
+             FROM drivers d
+             LEFT JOIN driver_eligibility e ON e.driver_id = d.id
+             WHERE d.id = $1",
+        )
+        .bind(driver_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(row.map(eligibility_from_row))
+    }
+
+    async fn suspend(
+        &self,
+        driver_id: Uuid,
+        reason: &str,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<bool, Error> {
+        // selecting from drivers turns an unknown driver into 0 rows instead of a FK violation
+        let result = sqlx::query(
+            "INSERT INTO driver_eligibility (driver_id, suspended, suspension_reason, suspended_at, suspended_until)
+             SELECT id, TRUE, $2, $4, $3 FROM drivers WHERE id = $1
+             ON CONFLICT (driver_id) DO UPDATE SET
+                suspended = TRUE,
+                suspension_reason = EXCLUDED.suspension_reason,
+                suspended_at = EXCLUDED.suspended_at,
+                suspended_until = EXCLUDED.suspended_until,
+                updated_at = $4",
+        )
+        .bind(driver_id)
+        .bind(reason)
+        .bind(until)
+        .bind(self.clock.now())
+        .execute(self.pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn reinstate(&self, driver_id: Uuid) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "INSERT INTO driver_eligibility (driver_id)
+             SELECT id FROM drivers WHERE id = $1
+             ON CONFLICT (driver_id) DO UPDATE SET
+                suspended = FALSE,
+                suspension_reason = NULL,
+                suspended_at = NULL,
+                suspended_until = NULL,
+                cooldown_until = LEAST(driver_eligibility.cooldown_until, $2),
+                updated_at = $2",
+        )
+        .bind(driver_id)
+        .bind(self.clock.now())
+        .execute(self.pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn set_cooldown(&self, driver_id: Uuid, until: DateTime<Utc>) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO driver_eligibility (driver_id, cooldown_until, updated_at)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (driver_id) DO UPDATE SET
+                cooldown_until = EXCLUDED.cooldown_until,
+                updated_at = EXCLUDED.updated_at",
+        )
+        .bind(driver_id)
+        .bind(until)
+        .bind(self.clock.now())
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn record_ride_response(
+        &self,
+        driver_id: Uuid,
+        ride_id: Uuid,
+        accepted: bool,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO driver_ride_responses (driver_id, ride_id, accepted, responded_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (driver_id, ride_id) DO NOTHING",
+        )
+        .bind(driver_id)
+        .bind(ride_id)
+        .bind(accepted)
+        .bind(self.clock.now())
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn count_ride_responses(
+        &self,
+        driver_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<(i64, i64), Error> {
+        let counts = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COUNT(*) FILTER (WHERE NOT accepted)
+             FROM driver_ride_responses
+             WHERE driver_id = $1 AND responded_at >= $2",
+        )
+        .bind(driver_id)
+        .bind(since)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        Ok(counts)
+    }
+}
+
+// (accepted, responded_at) of a driver's answer to a ride
+type RideResponse = (bool, DateTime<Utc>);
+
+/// Eligibility in memory, for running the lifecycle in one process. Only drivers added with
+/// `add_driver` exist, like the drivers table the postgres one joins.
+pub struct InMemoryDriverEligibilityRepository {
+    eligibility: Mutex<HashMap<Uuid, DriverEligibility>>,
+    responses: Mutex<HashMap<(Uuid, Uuid), RideResponse>>,
+    pub clock: SharedClock,
+}
+
+impl InMemoryDriverEligibilityRepository {
+    pub fn new(clock: SharedClock) -> Self {
+        Self {
+            eligibility: Mutex::new(HashMap::new()),
+            responses: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    pub fn add_driver(&self, driver_id: Uuid, license_expires_at: Option<DateTime<Utc>>) {
+        self.eligibility.lock().unwrap().insert(
+            driver_id,
+            DriverEligibility {
+                license_expires_at,
+                ..Default::default()
+            },
+        );
+    }
+
+}
+
+#[async_trait]
+impl DriverEligibilityRepository for InMemoryDriverEligibilityRepository {
+    async fn get_eligibility(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error> {
+        Ok(self.eligibility.lock().unwrap().get(&driver_id).cloned())
+    }
+
+    async fn suspend(
+        &self,
+        driver_id: Uuid,
+        reason: &str,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<bool, Error> {
+        let mut eligibility = self.eligibility.lock().unwrap();
+        let Some(e) = eligibility.get_mut(&driver_id) else {
+            return Ok(false);
+        };
+        e.suspended = true;
+        e.suspension_reason = Some(reason.to_string());
+        e.suspended_until = until;
+        Ok(true)
+    }
+
+    async fn reinstate(&self, driver_id: Uuid) -> Result<bool, Error> {
+        let now = self.clock.now();
+        let mut eligibility = self.eligibility.lock().unwrap();
+        let Some(e) = eligibility.get_mut(&driver_id) else {
+            return Ok(false);
+        };
+        e.suspended = false;
+        e.suspension_reason = None;
+        e.suspended_until = None;
+        e.cooldown_until = e.cooldown_until.map(|until| until.min(now));
+        Ok(true)
+    }
+
+    async fn set_cooldown(&self, driver_id: Uuid, until: DateTime<Utc>) -> Result<(), Error> {
+        if let Some(e) = self.eligibility.lock().unwrap().get_mut(&driver_id) {
+            e.cooldown_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn record_ride_response(
+        &self,
+        driver_id: Uuid,
+        ride_id: Uuid,
+        accepted: bool,
+    ) -> Result<(), Error> {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry((driver_id, ride_id))
+            .or_insert((accepted, self.clock.now()));
+        Ok(())
+    }
+
+    async fn count_ride_responses(
+        &self,
+        driver_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<(i64, i64), Error> {
+        let responses = self.responses.lock().unwrap();
+        let recent = responses
+            .iter()
+            .filter(|((id, _), (_, responded_at))| *id == driver_id && *responded_at >= since);
+        let (mut total, mut rejected) = (0, 0);
+        for (_, (accepted, _)) in recent {
+            total += 1;
+            if !accepted {
+                rejected += 1;
+            }
+        }
+        Ok((total, rejected))
+    }
+}
@@ -2,18 +2,21 @@ use std::sync::Arc;
 
 use anyhow::Error;
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::models::Driver;
+use crate::models::{Driver, DriverListFilter};
 
 #[async_trait]
 pub trait DriverRepository {
     async fn create_driver(&self, driver: &Driver) -> Result<(), Error>;
     async fn get_driver(&self, id: Uuid) -> anyhow::Result<Option<Driver>>;
-    async fn list_drivers(&self) -> anyhow::Result<Vec<Driver>>;
-    async fn update_driver(&self, driver: &Driver) -> anyhow::Result<()>;
-    async fn delete_driver(&self, id: Uuid) -> Result<(), Error>;
+    async fn list_drivers(&self, filter: &DriverListFilter) -> anyhow::Result<Vec<Driver>>;
+    /// Returns false if the driver doesn't exist.
+    async fn update_driver(&self, driver: &Driver) -> anyhow::Result<bool>;
+    /// Returns false if the driver doesn't exist.
+    async fn delete_driver(&self, id: Uuid) -> Result<bool, Error>;
 }
 
 // Struct that holds the database pool
@@ -31,20 +34,42 @@ impl PgDriverRepository {
     }
 }
 
+type DriverRow = (
+    Uuid,
+    String,
+    Option<String>,
+    Option<f32>,
+    Option<Uuid>,
+    Option<DateTime<Utc>>,
+);
+
+impl From<DriverRow> for Driver {
+    fn from(row: DriverRow) -> Self {
+        Driver {
+            id: row.0,
+            name: row.1,
+            license_number: row.2,
+            rating: row.3,
+            car_id: row.4,
+            license_expires_at: row.5,
+        }
+    }
+}
+
 #[async_trait]
 impl DriverRepository for PgDriverRepository {
     async fn create_driver(&self, driver: &Driver) -> Result<(), Error> {
-        let id = Uuid::new_v4();
-
+        // the caller owns the id, it's returned to the client and used as the redis/ws identity
         sqlx::query(
-            "INSERT INTO drivers (id, name, license_number, rating, car_id)
-         VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO drivers (id, name, license_number, rating, car_id, license_expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
         )
-        .bind(id) // $1 → id
+        .bind(driver.id) // $1 → id
         .bind(&driver.name) // $2 → name
         .bind(driver.license_number.as_ref()) // $3 → license_number
         .bind(driver.rating) // $4 → rating
         .bind(driver.car_id) // $5 → car_id
+        .bind(driver.license_expires_at) // $6 → license_expires_at
         .execute(self.pool.as_ref())
         .await?;
 
@@ -52,28 +77,74 @@ impl DriverRepository for PgDriverRepository {
     }
 
     async fn get_driver(&self, id: Uuid) -> anyhow::Result<Option<Driver>> {
-        todo!()
+        let row = sqlx::query_as::<_, DriverRow>(
+            "SELECT id, name, license_number, rating, car_id, license_expires_at FROM drivers WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(row.map(Driver::from))
     }
 
-    async fn list_drivers(&self) -> anyhow::Result<Vec<Driver>> {
-        // let recs = sqlx::query_as!(
-        //     Driver,
-        //     r#"
-        //     SELECT id, name, rating
-        //     FROM drivers
-        //     "#
-        // )
-        // .fetch_all(self.pool.as_ref())
-        // .await?;
-        // Ok(recs)
-        todo!()
+    async fn list_drivers(&self, filter: &DriverListFilter) -> anyhow::Result<Vec<Driver>> {
+        let mut qb: QueryBuilder<Postgres> =
+            QueryBuilder::new("SELECT id, name, license_number, rating, car_id, license_expires_at FROM drivers WHERE TRUE");
+
+        if let Some(name) = &filter.name {
+            qb.push(" AND name ILIKE ")
+                .push_bind(format!("%{}%", name));
+        }
+        if let Some(min_rating) = filter.min_rating {
+            qb.push(" AND rating >= ").push_bind(min_rating);
+        }
+        if let Some(has_car) = filter.has_car {
+            qb.push(if has_car {
+                " AND car_id IS NOT NULL"
+            } else {
+                " AND car_id IS NULL"
+            });
+        }
+
+        // id as a tie breaker keeps pages stable when created_at collides
+        qb.push(" ORDER BY created_at DESC, id LIMIT ")
+            .push_bind(filter.limit)
+            .push(" OFFSET ")
+            .push_bind(filter.offset);
+
+        let rows = qb
+            .build_query_as::<DriverRow>()
+            .fetch_all(self.pool.as_ref())
+            .await?;
+
+        Ok(rows.into_iter().map(Driver::from).collect())
     }
 
-    async fn update_driver(&self, driver: &Driver) -> anyhow::Result<()> {
-        todo!()
+    async fn update_driver(&self, driver: &Driver) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "UPDATE drivers SET name = $2, license_number = $3, rating = $4, car_id = $5,
+             license_expires_at = $6
+             WHERE id = $1",
+        )
+        .bind(driver.id)
+        .bind(&driver.name)
+        .bind(driver.license_number.as_ref())
+        .bind(driver.rating)
+        .bind(driver.car_id)
+        .bind(driver.license_expires_at)
+        .execute(self.pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
     }
 
-    async fn delete_driver(&self, id: Uuid) -> Result<(), Error> {
-        todo!()
+    async fn delete_driver(&self, id: Uuid) -> Result<bool, Error> {
+        // driver_status rows go with it (ON DELETE CASCADE)
+        let result = sqlx::query("DELETE FROM drivers WHERE id = $1")
+            .bind(id)
+            .execute(self.pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() == 1)
     }
 }
@@ -1,16 +1,102 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::models::{DriverStatus, RideStatus};
 
+/// Ride lifecycle transitions guarded by a compare-and-set in postgres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusTransition {
+    /// none/completed -> assigned, only an idle and available driver can be assigned a ride
+    Assign,
+    /// assigned -> in_ride, only for the ride the driver was assigned
+    Accept,
+    /// assigned -> none, only for the ride the driver was assigned
+    Reject,
+    /// in_ride -> in_ride with the passenger on board, once per ride
+    Pickup,
+    /// in_ride -> completed after the pickup, the driver can be matched again if still available
+    Complete,
+}
+
+impl std::fmt::Display for StatusTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            StatusTransition::Assign => "assign",
+            StatusTransition::Accept => "accept",
+            StatusTransition::Reject => "reject",
+            StatusTransition::Pickup => "pick up",
+            StatusTransition::Complete => "complete",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug)]
+pub enum StatusTransitionError {
+    /// The driver was not in the state the transition requires, e.g. a reject for ride A
+    /// while the driver is already on ride B. `current` is None if the driver has no status row.
+    Conflict {
+        driver_id: Uuid,
+        ride_id: Uuid,
+        transition: StatusTransition,
+        current: Option<DriverStatus>,
+    },
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for StatusTransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusTransitionError::Conflict {
+                driver_id,
+                ride_id,
+                transition,
+                current,
+            } => match current {
+                Some(current) => write!(
+                    f,
+                    "cannot {} ride {} for driver {}: driver is {} on ride {:?}{}",
+                    transition,
+                    ride_id,
+                    driver_id,
+                    current.ride_status.to_string(),
+                    current.current_trip_id,
+                    if current.driver_available { "" } else { " and unavailable" }
+                ),
+                None => write!(
+                    f,
+                    "cannot {} ride {} for driver {}: driver has no status",
+                    transition, ride_id, driver_id
+                ),
+            },
+            StatusTransitionError::Database(e) => write!(f, "database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StatusTransitionError {}
+
+impl From<sqlx::Error> for StatusTransitionError {
+    fn from(e: sqlx::Error) -> Self {
+        StatusTransitionError::Database(e)
+    }
+}
+
 #[async_trait]
 pub trait DriverStatusRepository {
     async fn create_status(&self, status: &DriverStatus) -> Result<(), Error>;
     async fn delete_status(&self, driver_id: Uuid) -> Result<(), Error>;
+    async fn get_status(&self, driver_id: Uuid) -> Result<Option<DriverStatus>, Error>;
+    async fn list_statuses(&self) -> Result<Vec<DriverStatus>, Error>;
+    // drivers without a status row are left out
+    async fn get_statuses(&self, driver_ids: &[Uuid]) -> Result<Vec<DriverStatus>, Error>;
     async fn patch_status(
         &self,
         driver_id: Uuid,
@@ -18,16 +104,88 @@ pub trait DriverStatusRepository {
         ride_status: Option<RideStatus>,
         current_trip_id: Option<Uuid>,
     ) -> Result<(), Error>;
+    // Takes an available driver offline unless they have a ride assigned or are on one,
+    // returns whether the row changed. For drivers that vanished without toggling offline.
+    async fn mark_unavailable_if_idle(&self, driver_id: Uuid) -> Result<bool, Error>;
+
+    // Ride lifecycle transitions. Unlike patch_status these only apply if the driver is in the
+    // expected state and return StatusTransitionError::Conflict otherwise. They only move
+    // ride_status, driver_available stays what the driver chose: a driver who went offline
+    // during a ride is still offline once it's over.
+    async fn assign(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError>;
+    async fn accept(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError>;
+    async fn reject(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError>;
+    async fn pickup(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError>;
+    async fn complete(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError>;
+}
+
+type DriverStatusRow = (
+    Uuid,
+    bool,
+    String,
+    Option<Uuid>,
+    chrono::DateTime<chrono::Utc>,
+);
+
+impl From<DriverStatusRow> for DriverStatus {
+    fn from(row: DriverStatusRow) -> Self {
+        DriverStatus {
+            driver_id: row.0,
+            driver_available: row.1,
+            ride_status: RideStatus::from_str(&row.2),
+            current_trip_id: row.3,
+            status_updated_at: row.4,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct PgDriverStatusRepository {
     pub pool: Arc<PgPool>,
+    // status_updated_at comes from the service clock, NOW() would be wall time in a simulation
+    pub clock: SharedClock,
 }
 
 impl PgDriverStatusRepository {
-    pub fn new(pool: Arc<PgPool>) -> Self {
-        Self { pool }
+    pub fn new(pool: Arc<PgPool>, clock: SharedClock) -> Self {
+        Self { pool, clock }
+    }
+
+    /// Runs a guarded UPDATE, `query` binds $1 = driver_id, $2 = ride_id and $3 = now.
+    /// No affected row means the precondition didn't hold, the current status is loaded for the error.
+    async fn transition(
+        &self,
+        transition: StatusTransition,
+        query: &str,
+        driver_id: Uuid,
+        ride_id: Uuid,
+    ) -> Result<(), StatusTransitionError> {
+        let result = sqlx::query(query)
+            .bind(driver_id)
+            .bind(ride_id)
+            .bind(self.clock.now())
+            .execute(self.pool.as_ref())
+            .await?;
+
+        if result.rows_affected() == 1 {
+            return Ok(());
+        }
+
+        let current = sqlx::query_as::<_, DriverStatusRow>(
+            "SELECT driver_id, driver_available, ride_status, current_trip_id, status_updated_at
+             FROM driver_status WHERE driver_id = $1",
+        )
+        .bind(driver_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?
+        .map(DriverStatus::from);
+
+        Err(StatusTransitionError::Conflict {
+            driver_id,
+            ride_id,
+            transition,
+            current,
+        })
     }
 }
 
@@ -35,13 +193,14 @@ impl PgDriverStatusRepository {
 impl DriverStatusRepository for PgDriverStatusRepository {
     async fn create_status(&self, status: &DriverStatus) -> Result<(), Error> {
         sqlx::query(
-            "INSERT INTO driver_status (driver_id, driver_available, ride_status, current_trip_id)
-             VALUES ($1, $2, $3, $4)",
+            "INSERT INTO driver_status (driver_id, driver_available, ride_status, current_trip_id, status_updated_at)
+             VALUES ($1, $2, $3, $4, $5)",
         )
         .bind(status.driver_id)
         .bind(status.driver_available)
         .bind(status.ride_status.to_string())
         .bind(status.current_trip_id)
+        .bind(self.clock.now())
         .execute(self.pool.as_ref())
         .await?;
         Ok(())
@@ -55,6 +214,38 @@ impl DriverStatusRepository for PgDriverStatusRepository {
         Ok(())
     }
 
+    async fn get_status(&self, driver_id: Uuid) -> Result<Option<DriverStatus>, Error> {
+        let row = sqlx::query_as::<_, DriverStatusRow>(
+            "SELECT driver_id, driver_available, ride_status, current_trip_id, status_updated_at
+             FROM driver_status WHERE driver_id = $1",
+        )
+        .bind(driver_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        Ok(row.map(DriverStatus::from))
+    }
+
+    async fn list_statuses(&self) -> Result<Vec<DriverStatus>, Error> {
+        let rows = sqlx::query_as::<_, DriverStatusRow>(
+            "SELECT driver_id, driver_available, ride_status, current_trip_id, status_updated_at
+             FROM driver_status",
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows.into_iter().map(DriverStatus::from).collect())
+    }
+
+    async fn get_statuses(&self, driver_ids: &[Uuid]) -> Result<Vec<DriverStatus>, Error> {
+        let rows = sqlx::query_as::<_, DriverStatusRow>(
+            "SELECT driver_id, driver_available, ride_status, current_trip_id, status_updated_at
+             FROM driver_status WHERE driver_id = ANY($1)",
+        )
+        .bind(driver_ids)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows.into_iter().map(DriverStatus::from).collect())
+    }
+
     async fn patch_status(
         &self,
         driver_id: Uuid,
@@ -87,7 +278,8 @@ impl DriverStatusRepository for PgDriverStatusRepository {
         }
 
         // Always update status_updated_at
-        sets.push("status_updated_at = NOW()".to_string());
+        sets.push(format!("status_updated_at = ${}", param_index));
+        param_index += 1;
 
         if sets.len() == 1 {
             // Only status_updated_at would be updated, so nothing to patch
@@ -110,9 +302,463 @@ impl DriverStatusRepository for PgDriverStatusRepository {
         if let Some(val) = bind_current_trip_id {
             q = q.bind(val);
         }
+        q = q.bind(self.clock.now());
         q = q.bind(driver_id);
 
         q.execute(self.pool.as_ref()).await?;
         Ok(())
     }
+
+    async fn mark_unavailable_if_idle(&self, driver_id: Uuid) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "UPDATE driver_status SET driver_available = FALSE, status_updated_at = $2
+             WHERE driver_id = $1 AND driver_available AND ride_status NOT IN ('assigned', 'in_ride')",
+        )
+        .bind(driver_id)
+        .bind(self.clock.now())
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn assign(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Assign,
+            "UPDATE driver_status
+             SET ride_status = 'assigned', current_trip_id = $2, picked_up_at = NULL,
+                 status_updated_at = $3
+             WHERE driver_id = $1 AND driver_available AND ride_status IN ('none', 'completed')",
+            driver_id,
+            ride_id,
+        )
+        .await
+    }
+
+    async fn accept(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Accept,
+            "UPDATE driver_status
+             SET ride_status = 'in_ride', status_updated_at = $3
+             WHERE driver_id = $1 AND ride_status = 'assigned' AND current_trip_id = $2",
+            driver_id,
+            ride_id,
+        )
+        .await
+    }
+
+    async fn reject(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Reject,
+            "UPDATE driver_status
+             SET ride_status = 'none', current_trip_id = NULL, status_updated_at = $3
+             WHERE driver_id = $1 AND ride_status = 'assigned' AND current_trip_id = $2",
+            driver_id,
+            ride_id,
+        )
+        .await
+    }
+
+    async fn pickup(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Pickup,
+            "UPDATE driver_status
+             SET picked_up_at = $3, status_updated_at = $3
+             WHERE driver_id = $1 AND ride_status = 'in_ride' AND current_trip_id = $2
+               AND picked_up_at IS NULL",
+            driver_id,
+            ride_id,
+        )
+        .await
+    }
+
+    async fn complete(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Complete,
+            "UPDATE driver_status
+             SET ride_status = 'completed', current_trip_id = NULL, picked_up_at = NULL,
+                 status_updated_at = $3
+             WHERE driver_id = $1 AND ride_status = 'in_ride' AND current_trip_id = $2
+               AND picked_up_at IS NOT NULL",
+            driver_id,
+            ride_id,
+        )
+        .await
+    }
+}
+
+// the status and when the passenger got on, picked_up_at isn't part of DriverStatus
+type StatusEntry = (DriverStatus, Option<DateTime<Utc>>);
+
+/// Driver statuses in memory with the same guards as the postgres one, for running the
+/// lifecycle in one process.
+pub struct InMemoryDriverStatusRepository {
+    statuses: Mutex<HashMap<Uuid, StatusEntry>>,
+    pub clock: SharedClock,
+}
+
+impl InMemoryDriverStatusRepository {
+    pub fn new(clock: SharedClock) -> Self {
+        Self {
+            statuses: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    /// Applies `apply` if `allowed` holds for the driver's status, a Conflict otherwise.
+    fn transition(
+        &self,
+        transition: StatusTransition,
+        driver_id: Uuid,
+        ride_id: Uuid,
+        allowed: impl Fn(&DriverStatus, Option<DateTime<Utc>>) -> bool,
+        apply: impl FnOnce(&mut DriverStatus, &mut Option<DateTime<Utc>>, DateTime<Utc>),
+    ) -> Result<(), StatusTransitionError> {
+        let mut statuses = self.statuses.lock().unwrap();
+        let current = statuses.get_mut(&driver_id);
+        match current {
+            Some((status, picked_up_at)) if allowed(status, *picked_up_at) => {
+                let now = self.clock.now();
+                apply(status, picked_up_at, now);
+                status.status_updated_at = now;
+                Ok(())
+            }
+            current => Err(StatusTransitionError::Conflict {
+                driver_id,
+                ride_id,
+                transition,
+                current: current.map(|(status, _)| status.clone()),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl DriverStatusRepository for InMemoryDriverStatusRepository {
+    async fn create_status(&self, status: &DriverStatus) -> Result<(), Error> {
+        let mut statuses = self.statuses.lock().unwrap();
+        // the primary key in postgres
+        if statuses.contains_key(&status.driver_id) {
+            return Err(anyhow!("driver {} already has a status", status.driver_id));
+        }
+        let status = DriverStatus {
+            status_updated_at: self.clock.now(),
+            ..status.clone()
+        };
+        statuses.insert(status.driver_id, (status, None));
+        Ok(())
+    }
+
+    async fn delete_status(&self, driver_id: Uuid) -> Result<(), Error> {
+        self.statuses.lock().unwrap().remove(&driver_id);
+        Ok(())
+    }
+
+    async fn get_status(&self, driver_id: Uuid) -> Result<Option<DriverStatus>, Error> {
+        Ok(self
+            .statuses
+            .lock()
+            .unwrap()
+            .get(&driver_id)
+            .map(|(status, _)| status.clone()))
+    }
+
+    async fn list_statuses(&self) -> Result<Vec<DriverStatus>, Error> {
+        Ok(self
+            .statuses
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(status, _)| status.clone())
+            .collect())
+    }
+
+    async fn get_statuses(&self, driver_ids: &[Uuid]) -> Result<Vec<DriverStatus>, Error> {
+        let statuses = self.statuses.lock().unwrap();
+        Ok(driver_ids
+            .iter()
+            .filter_map(|id| statuses.get(id))
+            .map(|(status, _)| status.clone())
+            .collect())
+    }
+
+    async fn patch_status(
+        &self,
+        driver_id: Uuid,
+        driver_available: Option<bool>,
+        ride_status: Option<RideStatus>,
+        current_trip_id: Option<Uuid>,
+    ) -> Result<(), Error> {
+        if driver_available.is_none() && ride_status.is_none() && current_trip_id.is_none() {
+            return Ok(());
+        }
+        let mut statuses = self.statuses.lock().unwrap();
+        let Some((status, _)) = statuses.get_mut(&driver_id) else {
+            return Ok(());
+        };
+        if let Some(val) = driver_available {
+            status.driver_available = val;
+        }
+        if let Some(val) = ride_status {
+            status.ride_status = val;
+        }
+        if let Some(val) = current_trip_id {
+            status.current_trip_id = Some(val);
+        }
+        status.status_updated_at = self.clock.now();
+        Ok(())
+    }
+
+    async fn mark_unavailable_if_idle(&self, driver_id: Uuid) -> Result<bool, Error> {
+        let mut statuses = self.statuses.lock().unwrap();
+        match statuses.get_mut(&driver_id) {
+            Some((status, _))
+                if status.driver_available
+                    && !matches!(status.ride_status, RideStatus::Assigned | RideStatus::InRide) =>
+            {
+                status.driver_available = false;
+                status.status_updated_at = self.clock.now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn assign(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Assign,
+            driver_id,
+            ride_id,
+            |status, _| {
+                status.driver_available
+                    && matches!(status.ride_status, RideStatus::None | RideStatus::Completed)
+            },
+            |status, picked_up_at, _| {
+                status.ride_status = RideStatus::Assigned;
+                status.current_trip_id = Some(ride_id);
+                *picked_up_at = None;
+            },
+        )
+    }
+
+    async fn accept(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Accept,
+            driver_id,
+            ride_id,
+            |status, _| {
+                matches!(status.ride_status, RideStatus::Assigned)
+                    && status.current_trip_id == Some(ride_id)
+            },
+            |status, _, _| status.ride_status = RideStatus::InRide,
+        )
+    }
+
+    async fn reject(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Reject,
+            driver_id,
+            ride_id,
+            |status, _| {
+                matches!(status.ride_status, RideStatus::Assigned)
+                    && status.current_trip_id == Some(ride_id)
+            },
+            |status, _, _| {
+                status.ride_status = RideStatus::None;
+                status.current_trip_id = None;
+            },
+        )
+    }
+
+    async fn pickup(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Pickup,
+            driver_id,
+            ride_id,
+            |status, picked_up_at| {
+                matches!(status.ride_status, RideStatus::InRide)
+                    && status.current_trip_id == Some(ride_id)
+                    && picked_up_at.is_none()
+            },
+            |_, picked_up_at, now| *picked_up_at = Some(now),
+        )
+    }
+
+    async fn complete(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), StatusTransitionError> {
+        self.transition(
+            StatusTransition::Complete,
+            driver_id,
+            ride_id,
+            |status, picked_up_at| {
+                matches!(status.ride_status, RideStatus::InRide)
+                    && status.current_trip_id == Some(ride_id)
+                    && picked_up_at.is_some()
+            },
+            |status, picked_up_at, _| {
+                status.ride_status = RideStatus::Completed;
+                status.current_trip_id = None;
+                *picked_up_at = None;
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::clock::VirtualClock;
+
+    async fn repo_with_driver(available: bool) -> (InMemoryDriverStatusRepository, Uuid) {
+        let start: DateTime<Utc> = "2025-03-03T08:00:00Z".parse().unwrap();
+        let repo = InMemoryDriverStatusRepository::new(Arc::new(VirtualClock::new(start)));
+        let driver_id = Uuid::new_v4();
+        repo.create_status(&DriverStatus {
+            driver_id,
+            driver_available: available,
+            ride_status: RideStatus::None,
+            current_trip_id: None,
+            status_updated_at: start,
+        })
+        .await
+        .unwrap();
+        (repo, driver_id)
+    }
+
+    fn is_conflict(result: Result<(), StatusTransitionError>) -> bool {
+        matches!(result, Err(StatusTransitionError::Conflict { .. }))
+    }
+
+    async fn status(repo: &InMemoryDriverStatusRepository, driver_id: Uuid) -> DriverStatus {
+        repo.get_status(driver_id).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn assign_conflicts_for_an_unavailable_driver() {
+        let (repo, driver_id) = repo_with_driver(false).await;
+
+        assert!(is_conflict(repo.assign(driver_id, Uuid::new_v4()).await));
+        assert!(matches!(status(&repo, driver_id).await.ride_status, RideStatus::None));
+    }
+
+    #[tokio::test]
+    async fn assign_conflicts_for_a_busy_driver() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        assert!(is_conflict(repo.assign(driver_id, Uuid::new_v4()).await));
+
+        repo.accept(driver_id, ride_id).await.unwrap();
+        assert!(is_conflict(repo.assign(driver_id, Uuid::new_v4()).await));
+        assert_eq!(status(&repo, driver_id).await.current_trip_id, Some(ride_id));
+    }
+
+    #[tokio::test]
+    async fn assign_conflicts_without_a_status() {
+        let (repo, _) = repo_with_driver(true).await;
+
+        let result = repo.assign(Uuid::new_v4(), Uuid::new_v4()).await;
+        assert!(matches!(
+            result,
+            Err(StatusTransitionError::Conflict { current: None, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn accept_and_reject_conflict_for_another_ride() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        assert!(is_conflict(repo.accept(driver_id, Uuid::new_v4()).await));
+        assert!(is_conflict(repo.reject(driver_id, Uuid::new_v4()).await));
+
+        let status = status(&repo, driver_id).await;
+        assert!(matches!(status.ride_status, RideStatus::Assigned));
+        assert_eq!(status.current_trip_id, Some(ride_id));
+    }
+
+    #[tokio::test]
+    async fn reject_frees_the_driver_for_the_next_ride() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        repo.reject(driver_id, ride_id).await.unwrap();
+
+        // a late accept for the rejected ride doesn't bring it back
+        assert!(is_conflict(repo.accept(driver_id, ride_id).await));
+        repo.assign(driver_id, Uuid::new_v4()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pickup_only_happens_once() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+        repo.accept(driver_id, ride_id).await.unwrap();
+
+        repo.pickup(driver_id, ride_id).await.unwrap();
+
+        assert!(is_conflict(repo.pickup(driver_id, ride_id).await));
+    }
+
+    #[tokio::test]
+    async fn complete_conflicts_before_pickup() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        // neither before the accept nor before the passenger is on board
+        assert!(is_conflict(repo.complete(driver_id, ride_id).await));
+        repo.accept(driver_id, ride_id).await.unwrap();
+        assert!(is_conflict(repo.complete(driver_id, ride_id).await));
+
+        repo.pickup(driver_id, ride_id).await.unwrap();
+        repo.complete(driver_id, ride_id).await.unwrap();
+        let status = status(&repo, driver_id).await;
+        assert!(matches!(status.ride_status, RideStatus::Completed));
+        assert_eq!(status.current_trip_id, None);
+    }
+
+    #[tokio::test]
+    async fn a_driver_who_went_offline_during_a_ride_stays_offline() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+        repo.accept(driver_id, ride_id).await.unwrap();
+        repo.pickup(driver_id, ride_id).await.unwrap();
+
+        repo.patch_status(driver_id, Some(false), None, None)
+            .await
+            .unwrap();
+        repo.complete(driver_id, ride_id).await.unwrap();
+
+        assert!(!status(&repo, driver_id).await.driver_available);
+        assert!(is_conflict(repo.assign(driver_id, Uuid::new_v4()).await));
+    }
+
+    #[tokio::test]
+    async fn reject_keeps_the_driver_available() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        repo.reject(driver_id, ride_id).await.unwrap();
+
+        assert!(status(&repo, driver_id).await.driver_available);
+    }
+
+    #[tokio::test]
+    async fn mark_unavailable_if_idle_leaves_busy_drivers_alone() {
+        let (repo, driver_id) = repo_with_driver(true).await;
+        let ride_id = Uuid::new_v4();
+        repo.assign(driver_id, ride_id).await.unwrap();
+
+        assert!(!repo.mark_unavailable_if_idle(driver_id).await.unwrap());
+        assert!(status(&repo, driver_id).await.driver_available);
+
+        repo.reject(driver_id, ride_id).await.unwrap();
+        assert!(repo.mark_unavailable_if_idle(driver_id).await.unwrap());
+        assert!(!status(&repo, driver_id).await.driver_available);
+    }
 }
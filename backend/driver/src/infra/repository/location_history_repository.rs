@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::DriverLocation;
+
+const TABLE: &str = "driver_location_history";
+const DEFAULT_PARTITION: &str = "driver_location_history_default";
+// daily partitions are driver_location_history_pYYYYMMDD
+const PARTITION_PREFIX: &str = "driver_location_history_p";
+
+fn partition_name(day: NaiveDate) -> String {
+    format!("{}{}", PARTITION_PREFIX, day.format("%Y%m%d"))
+}
+
+fn partition_day(name: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(name.strip_prefix(PARTITION_PREFIX)?, "%Y%m%d").ok()
+}
+
+fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+#[async_trait]
+pub trait LocationHistoryRepository {
+    async fn insert_batch(&self, locations: &[DriverLocation]) -> Result<(), Error>;
+    /// The driver's locations in [from, to) in time order, at most `limit`. With `bucket_secs`
+    /// only the first location of every bucket of that many seconds.
+    async fn history(
+        &self,
+        driver_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket_secs: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<DriverLocation>, Error>;
+
+    /// Days that have a partition.
+    async fn partition_days(&self) -> Result<Vec<NaiveDate>, Error>;
+    /// Creates the partition of `day` unless it exists, moving that day's rows out of the
+    /// default partition.
+    async fn create_partition(&self, day: NaiveDate) -> Result<(), Error>;
+    async fn drop_partition(&self, day: NaiveDate) -> Result<(), Error>;
+    /// Deletes the default partition's rows recorded before `before`, returns how many.
+    async fn purge_default(&self, before: DateTime<Utc>) -> Result<u64, Error>;
+
+    /// Days whose partition was already downsampled.
+    async fn downsampled_days(&self) -> Result<Vec<NaiveDate>, Error>;
+    /// Keeps the first location per driver and bucket of `bucket_secs` in the partition of
+    /// `day`, returns how many were deleted.
+    async fn downsample(&self, day: NaiveDate, bucket_secs: i64) -> Result<u64, Error>;
+}
+
+#[derive(Clone)]
+pub struct PgLocationHistoryRepository {
+    pub pool: Arc<PgPool>,
+}
+
+impl PgLocationHistoryRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+type LocationRow = (Uuid, f64, f64, DateTime<Utc>);
+
+impl From<LocationRow> for DriverLocation {
+    fn from(row: LocationRow) -> Self {
+        DriverLocation {
+            driver_id: row.0,
+            latitude: row.1,
+            longitude: row.2,
+            recorded_at: row.3,
+        }
+    }
+}
+
+#[async_trait]
+impl LocationHistoryRepository for PgLocationHistoryRepository {
+    async fn insert_batch(&self, locations: &[DriverLocation]) -> Result<(), Error> {
+        if locations.is_empty() {
+            return Ok(());
+        }
+        // one statement for the whole batch, postgres routes the rows to their partitions
+        let driver_ids: Vec<Uuid> = locations.iter().map(|l| l.driver_id).collect();
+        let lats: Vec<f64> = locations.iter().map(|l| l.latitude).collect();
+        let lngs: Vec<f64> = locations.iter().map(|l| l.longitude).collect();
+        let times: Vec<DateTime<Utc>> = locations.iter().map(|l| l.recorded_at).collect();
+        sqlx::query(
+            "INSERT INTO driver_location_history (driver_id, lat, lng, recorded_at)
+             SELECT * FROM UNNEST($1::uuid[], $2::float8[], $3::float8[], $4::timestamptz[])",
+        )
+        .bind(driver_ids)
+        .bind(lats)
+        .bind(lngs)
+        .bind(times)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn history(
+        &self,
+        driver_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket_secs: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<DriverLocation>, Error> {
+        let rows = match bucket_secs {
+            Some(bucket_secs) => {
+                sqlx::query_as::<_, LocationRow>(
+                    "SELECT driver_id, lat, lng, recorded_at FROM (
+                        SELECT DISTINCT ON (floor(extract(epoch FROM recorded_at) / $4::float8))
+                            driver_id, lat, lng, recorded_at
+                        FROM driver_location_history
+                        WHERE driver_id = $1 AND recorded_at >= $2 AND recorded_at < $3
+                        ORDER BY floor(extract(epoch FROM recorded_at) / $4::float8), recorded_at
+                     ) buckets
+                     ORDER BY recorded_at
+                     LIMIT $5",
+                )
+                .bind(driver_id)
+                .bind(from)
+                .bind(to)
+                .bind(bucket_secs as f64)
+                .bind(limit)
+                .fetch_all(self.pool.as_ref())
+                .await?
+            }
+            None => {
+                sqlx::query_as::<_, LocationRow>(
+                    "SELECT driver_id, lat, lng, recorded_at
+                     FROM driver_location_history
+                     WHERE driver_id = $1 AND recorded_at >= $2 AND recorded_at < $3
+                     ORDER BY recorded_at
+                     LIMIT $4",
+                )
+                .bind(driver_id)
+                .bind(from)
+                .bind(to)
+                .bind(limit)
+                .fetch_all(self.pool.as_ref())
+                .await?
+            }
+        };
+        Ok(rows.into_iter().map(DriverLocation::from).collect())
+    }
+
+    async fn partition_days(&self) -> Result<Vec<NaiveDate>, Error> {
+        let names = sqlx::query_scalar::<_, String>(
+            "SELECT c.relname::text
+             FROM pg_inherits i
+             JOIN pg_class c ON c.oid = i.inhrelid
+             JOIN pg_class p ON p.oid = i.inhparent
+             WHERE p.relname = $1",
+        )
+        .bind(TABLE)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        let mut days: Vec<NaiveDate> = names.iter().filter_map(|n| partition_day(n)).collect();
+        days.sort();
+        Ok(days)
+    }
+
+    async fn create_partition(&self, day: NaiveDate) -> Result<(), Error> {
+        let name = partition_name(day);
+        let (from, to) = (day_start(day), day_start(day + chrono::Days::new(1)));
+
+        // DDL can't take bind parameters, the name and bounds are built from a date
+        let mut tx = self.pool.begin().await?;
+        let exists = sqlx::query_scalar::<_, bool>("SELECT to_regclass($1) IS NOT NULL")
+            .bind(&name)
+            .fetch_one(&mut *tx)
+            .await?;
+        if exists {
+            return Ok(());
+        }
+        sqlx::query(&format!(
+            "CREATE TABLE {} (LIKE {} INCLUDING DEFAULTS INCLUDING CONSTRAINTS)",
+            name, TABLE
+        ))
+        .execute(&mut *tx)
+        .await?;
+        // attaching fails while the default partition holds rows of the day
+        sqlx::query(&format!(
+            "WITH moved AS (
+                DELETE FROM {} WHERE recorded_at >= $1 AND recorded_at < $2 RETURNING *
+             )
+             INSERT INTO {} SELECT * FROM moved",
+            DEFAULT_PARTITION, name
+        ))
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "ALTER TABLE {} ATTACH PARTITION {} FOR VALUES FROM ('{}') TO ('{}')",
+            TABLE,
+            name,
+            from.to_rfc3339(),
+            to.to_rfc3339()
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn drop_partition(&self, day: NaiveDate) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", partition_name(day)))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM driver_location_history_downsampled WHERE day = $1")
+            .bind(day)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn purge_default(&self, before: DateTime<Utc>) -> Result<u64, Error> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {} WHERE recorded_at < $1",
+            DEFAULT_PARTITION
+        ))
+        .bind(before)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn downsampled_days(&self) -> Result<Vec<NaiveDate>, Error> {
+        let days = sqlx::query_scalar::<_, NaiveDate>(
+            "SELECT day FROM driver_location_history_downsampled ORDER BY day",
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(days)
+    }
+
+    async fn downsample(&self, day: NaiveDate, bucket_secs: i64) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(&format!(
+            "DELETE FROM {name} WHERE ctid IN (
+                SELECT ctid FROM (
+                    SELECT ctid, row_number() OVER (
+                        PARTITION BY driver_id, floor(extract(epoch FROM recorded_at) / $1::float8)
+                        ORDER BY recorded_at
+                    ) AS n
+                    FROM {name}
+                ) ranked
+                WHERE n > 1
+             )",
+            name = partition_name(day)
+        ))
+        .bind(bucket_secs as f64)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO driver_location_history_downsampled (day) VALUES ($1)
+             ON CONFLICT (day) DO NOTHING",
+        )
+        .bind(day)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::{BreadcrumbKind, RideBreadcrumb};
+
+#[async_trait]
+pub trait RideTraceRepository {
+    async fn append(&self, breadcrumb: &RideBreadcrumb) -> Result<(), Error>;
+    async fn append_batch(&self, breadcrumbs: &[RideBreadcrumb]) -> Result<(), Error>;
+    /// Every breadcrumb of the ride in the order they were recorded, empty for an unknown ride.
+    async fn breadcrumbs(&self, ride_id: Uuid) -> Result<Vec<RideBreadcrumb>, Error>;
+}
+
+#[derive(Clone)]
+pub struct PgRideTraceRepository {
+    pub pool: Arc<PgPool>,
+}
+
+impl PgRideTraceRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+type BreadcrumbRow = (Uuid, Uuid, String, f64, f64, DateTime<Utc>);
+
+impl From<BreadcrumbRow> for RideBreadcrumb {
+    fn from(row: BreadcrumbRow) -> Self {
+        RideBreadcrumb {
+            ride_id: row.0,
+            driver_id: row.1,
+            kind: BreadcrumbKind::from_str(&row.2),
+            latitude: row.3,
+            longitude: row.4,
+            recorded_at: row.5,
+        }
+    }
+}
+
+#[async_trait]
+impl RideTraceRepository for PgRideTraceRepository {
+    async fn append(&self, breadcrumb: &RideBreadcrumb) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO ride_breadcrumbs (ride_id, driver_id, kind, lat, lng, recorded_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(breadcrumb.ride_id)
+        .bind(breadcrumb.driver_id)
+        .bind(breadcrumb.kind.to_string())
+        .bind(breadcrumb.latitude)
+        .bind(breadcrumb.longitude)
+        .bind(breadcrumb.recorded_at)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn append_batch(&self, breadcrumbs: &[RideBreadcrumb]) -> Result<(), Error> {
+        if breadcrumbs.is_empty() {
+            return Ok(());
+        }
+        // one statement for the whole batch
+        let ride_ids: Vec<Uuid> = breadcrumbs.iter().map(|b| b.ride_id).collect();
+        let driver_ids: Vec<Uuid> = breadcrumbs.iter().map(|b| b.driver_id).collect();
+        let kinds: Vec<String> = breadcrumbs.iter().map(|b| b.kind.to_string()).collect();
+        let lats: Vec<f64> = breadcrumbs.iter().map(|b| b.latitude).collect();
+        let lngs: Vec<f64> = breadcrumbs.iter().map(|b| b.longitude).collect();
+        let times: Vec<DateTime<Utc>> = breadcrumbs.iter().map(|b| b.recorded_at).collect();
+        sqlx::query(
+            "INSERT INTO ride_breadcrumbs (ride_id, driver_id, kind, lat, lng, recorded_at)
+             SELECT * FROM UNNEST($1::uuid[], $2::uuid[], $3::text[], $4::float8[], $5::float8[], $6::timestamptz[])",
+        )
+        .bind(ride_ids)
+        .bind(driver_ids)
+        .bind(kinds)
+        .bind(lats)
+        .bind(lngs)
+        .bind(times)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn breadcrumbs(&self, ride_id: Uuid) -> Result<Vec<RideBreadcrumb>, Error> {
+        // id breaks ties between breadcrumbs recorded in the same instant
+        let rows = sqlx::query_as::<_, BreadcrumbRow>(
+            "SELECT ride_id, driver_id, kind, lat, lng, recorded_at
+             FROM ride_breadcrumbs
+             WHERE ride_id = $1
+             ORDER BY recorded_at, id",
+        )
+        .bind(ride_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        Ok(rows.into_iter().map(RideBreadcrumb::from).collect())
+    }
+}
+
+/// Breadcrumbs in memory, for running the lifecycle in one process.
+#[derive(Default)]
+pub struct InMemoryRideTraceRepository {
+    breadcrumbs: Mutex<HashMap<Uuid, Vec<RideBreadcrumb>>>,
+}
+
+impl InMemoryRideTraceRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RideTraceRepository for InMemoryRideTraceRepository {
+    async fn append(&self, breadcrumb: &RideBreadcrumb) -> Result<(), Error> {
+        self.breadcrumbs
+            .lock()
+            .unwrap()
+            .entry(breadcrumb.ride_id)
+            .or_default()
+            .push(breadcrumb.clone());
+        Ok(())
+    }
+
+    async fn append_batch(&self, breadcrumbs: &[RideBreadcrumb]) -> Result<(), Error> {
+        let mut rides = self.breadcrumbs.lock().unwrap();
+        for breadcrumb in breadcrumbs {
+            rides.entry(breadcrumb.ride_id).or_default().push(breadcrumb.clone());
+        }
+        Ok(())
+    }
+
+    async fn breadcrumbs(&self, ride_id: Uuid) -> Result<Vec<RideBreadcrumb>, Error> {
+        Ok(self
+            .breadcrumbs
+            .lock()
+            .unwrap()
+            .get(&ride_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
 use crate::models::Vehicle;
+use anyhow::Error;
+use async_trait::async_trait;
+use common::vehicle::VehicleClass;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+#[async_trait]
 pub trait VehicleRepository {
-    fn create_vehicle(&self, vehicle: Vehicle) -> Result<Vehicle, String>;
-    fn get_vehicle(&self, vehicle_id: Uuid) -> Option<Vehicle>;
-    fn update_vehicle(&self, vehicle: Vehicle) -> Result<Vehicle, String>;
-    fn delete_vehicle(&self, vehicle_id: Uuid) -> Result<(), String>;
-    fn list_vehicles_by_driver(&self, driver_id: Uuid) -> Vec<Vehicle>;
+    async fn create_vehicle(&self, vehicle: &Vehicle) -> Result<(), Error>;
+    async fn get_vehicle(&self, vehicle_id: Uuid) -> Result<Option<Vehicle>, Error>;
+    /// Updates the descriptive fields, ownership and activation are not touched. Returns false if not found.
+    async fn update_vehicle(&self, vehicle: &Vehicle) -> Result<bool, Error>;
+    /// Returns false if not found.
+    async fn delete_vehicle(&self, vehicle_id: Uuid) -> Result<bool, Error>;
+    async fn list_vehicles_by_driver(&self, driver_id: Uuid) -> Result<Vec<Vehicle>, Error>;
+    async fn get_active_vehicle(&self, driver_id: Uuid) -> Result<Option<Vehicle>, Error>;
+    /// Makes the vehicle the driver's only active vehicle. Returns false if the vehicle
+    /// doesn't exist or belongs to another driver.
+    async fn activate_vehicle(&self, driver_id: Uuid, vehicle_id: Uuid) -> Result<bool, Error>;
 }
 
 // Struct that holds the database pool
@@ -24,24 +34,170 @@ impl PgVehicleRepository {
     }
 }
 
+type VehicleRow = (
+    Uuid,
+    Uuid,
+    String,
+    String,
+    String,
+    i16,
+    String,
+    i16,
+    bool,
+    bool,
+    bool,
+);
+
+const VEHICLE_COLUMNS: &str =
+    "id, driver_id, make, model, plate_number, year, vehicle_class, seats, pet_friendly, wheelchair_accessible, is_active";
+
+impl TryFrom<VehicleRow> for Vehicle {
+    type Error = Error;
+
+    fn try_from(row: VehicleRow) -> Result<Self, Self::Error> {
+        Ok(Vehicle {
+            id: row.0,
+            driver_id: row.1,
+            make: row.2,
+            model: row.3,
+            plate_number: row.4,
+            year: u16::try_from(row.5)?,
+            vehicle_class: VehicleClass::parse(&row.6)
+                .ok_or_else(|| anyhow::anyhow!("unknown vehicle class {}", row.6))?,
+            seats: u8::try_from(row.7)?,
+            pet_friendly: row.8,
+            wheelchair_accessible: row.9,
+            is_active: row.10,
+        })
+    }
+}
+
+#[async_trait]
 impl VehicleRepository for PgVehicleRepository {
-    fn create_vehicle(&self, _vehicle: Vehicle) -> Result<Vehicle, String> {
-        todo!()
+    async fn create_vehicle(&self, vehicle: &Vehicle) -> Result<(), Error> {
+        // vehicles are created inactive, activate_vehicle is the only way to make one active
+        sqlx::query(
+            "INSERT INTO vehicles (id, driver_id, make, model, plate_number, year, vehicle_class, seats, pet_friendly, wheelchair_accessible)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(vehicle.id)
+        .bind(vehicle.driver_id)
+        .bind(&vehicle.make)
+        .bind(&vehicle.model)
+        .bind(&vehicle.plate_number)
+        .bind(i16::try_from(vehicle.year)?)
+        .bind(vehicle.vehicle_class.as_str())
+        .bind(i16::from(vehicle.seats))
+        .bind(vehicle.pet_friendly)
+        .bind(vehicle.wheelchair_accessible)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(())
     }
 
-    fn get_vehicle(&self, _vehicle_id: Uuid) -> Option<Vehicle> {
-        todo!()
+    async fn get_vehicle(&self, vehicle_id: Uuid) -> Result<Option<Vehicle>, Error> {
+        let row = sqlx::query_as::<_, VehicleRow>(&format!(
+            "SELECT {} FROM vehicles WHERE id = $1",
+            VEHICLE_COLUMNS
+        ))
+        .bind(vehicle_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        row.map(Vehicle::try_from).transpose()
     }
 
-    fn update_vehicle(&self, _vehicle: Vehicle) -> Result<Vehicle, String> {
-        todo!()
+    async fn update_vehicle(&self, vehicle: &Vehicle) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "UPDATE vehicles SET make = $2, model = $3, plate_number = $4, year = $5, vehicle_class = $6,
+             seats = $7, pet_friendly = $8, wheelchair_accessible = $9
+             WHERE id = $1",
+        )
+        .bind(vehicle.id)
+        .bind(&vehicle.make)
+        .bind(&vehicle.model)
+        .bind(&vehicle.plate_number)
+        .bind(i16::try_from(vehicle.year)?)
+        .bind(vehicle.vehicle_class.as_str())
+        .bind(i16::from(vehicle.seats))
+        .bind(vehicle.pet_friendly)
+        .bind(vehicle.wheelchair_accessible)
+        .execute(self.pool.as_ref())
+        .await?;
+        Ok(result.rows_affected() == 1)
     }
 
-    fn delete_vehicle(&self, _vehicle_id: Uuid) -> Result<(), String> {
-        todo!()
+    async fn delete_vehicle(&self, vehicle_id: Uuid) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // deleting the active vehicle leaves the driver without a car
+        sqlx::query("UPDATE drivers SET car_id = NULL WHERE car_id = $1")
+            .bind(vehicle_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let result = sqlx::query("DELETE FROM vehicles WHERE id = $1")
+            .bind(vehicle_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() == 1)
     }
 
-    fn list_vehicles_by_driver(&self, _driver_id: Uuid) -> Vec<Vehicle> {
-        todo!()
+    async fn list_vehicles_by_driver(&self, driver_id: Uuid) -> Result<Vec<Vehicle>, Error> {
+        let rows = sqlx::query_as::<_, VehicleRow>(&format!(
+            "SELECT {} FROM vehicles WHERE driver_id = $1 ORDER BY created_at",
+            VEHICLE_COLUMNS
+        ))
+        .bind(driver_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        rows.into_iter().map(Vehicle::try_from).collect()
+    }
+
+    async fn get_active_vehicle(&self, driver_id: Uuid) -> Result<Option<Vehicle>, Error> {
+        let row = sqlx::query_as::<_, VehicleRow>(&format!(
+            "SELECT {} FROM vehicles WHERE driver_id = $1 AND is_active",
+            VEHICLE_COLUMNS
+        ))
+        .bind(driver_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        row.map(Vehicle::try_from).transpose()
+    }
+
+    async fn activate_vehicle(&self, driver_id: Uuid, vehicle_id: Uuid) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // deactivate first, the partial unique index allows only one active vehicle per driver
+        sqlx::query("UPDATE vehicles SET is_active = FALSE WHERE driver_id = $1 AND is_active AND id <> $2")
+            .bind(driver_id)
+            .bind(vehicle_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let result =
+            sqlx::query("UPDATE vehicles SET is_active = TRUE WHERE id = $1 AND driver_id = $2")
+                .bind(vehicle_id)
+                .bind(driver_id)
+                .execute(&mut *tx)
+                .await?;
+
+        if result.rows_affected() != 1 {
+            // dropping the transaction rolls back the deactivation
+            return Ok(false);
+        }
+
+        sqlx::query("UPDATE drivers SET car_id = $2 WHERE id = $1")
+            .bind(driver_id)
+            .bind(vehicle_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
     }
 }
@@ -1,4 +1,4 @@
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use common::ws_schema::{DriverLocationV1, Envelope, WSMsgType};
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
@@ -9,29 +9,43 @@ use super::hub::{ClientTx, WsHub};
 use crate::service::location_update::{LocationUpdate, LocationUpdateService};
 // Disclaimer: this was written with the help of copilot
 
+// 4000-4999 are free for applications to use
+const SESSION_REPLACED_CLOSE_CODE: u16 = 4001;
+
 /// Handles a single WebSocket connection lifecycle:
-/// - registers client in the hub
+/// - registers the driver session in the hub (replacing any older session)
 /// - forwards server messages to the socket
 /// - reads client messages, processes them
 /// - unregisters on disconnect
+///
+/// `driver_id` comes from a verified token, never from the client payload.
 pub async fn ws_on_upgrade(
     socket: WebSocket,
     hub: Arc<WsHub>,
     location_update_service: Arc<LocationUpdateService>,
-    client_id: Uuid,
+    driver_id: Uuid,
 ) {
+    let session_id = Uuid::new_v4();
+
     // Channel from server components -> this connection
     let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
-    hub.register(client_id, ClientTx { tx }).await;
+    if let Some(previous) = hub.register(driver_id, ClientTx { session_id, tx }).await {
+        // newest session wins, e.g. the app reconnected before the old socket timed out
+        let _ = previous.tx.send(Message::Close(Some(CloseFrame {
+            code: SESSION_REPLACED_CLOSE_CODE,
+            reason: "session replaced by a newer connection".into(),
+        })));
+    }
 
     // Optional: greet the client
     let _ = hub
         .send_to(
-            &client_id,
+            &driver_id,
             Message::Text(
                 serde_json::json!({
                     "type": "ws.welcome",
-                    "client_id": client_id.to_string(),
+                    "driver_id": driver_id.to_string(),
+                    "session_id": session_id.to_string(),
                     "ts": chrono::Utc::now().timestamp_millis(),
                 })
                 .to_string()
@@ -44,48 +58,54 @@ pub async fn ws_on_upgrade(
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Task: forward server-pushed messages to the client
-    let forward_task = {
-        let client_id = client_id;
-        tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                // If the client side is closed, stop forwarding
-                if ws_tx.send(msg).await.is_err() {
-                    break;
-                }
+    let mut forward_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let is_close = matches!(msg, Message::Close(_));
+            // If the client side is closed, stop forwarding
+            if ws_tx.send(msg).await.is_err() || is_close {
+                break;
             }
-            // Optionally: log that forwarding ended for client_id
-            eprintln!("Forwarding task ended for client_id: {}", client_id);
-        })
-    };
+        }
+        // Optionally: log that forwarding ended for driver_id
+        eprintln!("Forwarding task ended for driver_id: {}", driver_id);
+    });
 
     // Task: read messages from the client and handle them
-    let read_task = {
+    let mut read_task = {
         let hub = hub.clone();
         tokio::spawn(async move {
             while let Some(Ok(msg)) = ws_rx.next().await {
+                // a replaced session must not keep acting on behalf of the driver
+                if !hub.is_current(&driver_id, &session_id).await {
+                    break;
+                }
+
                 match msg {
                     Message::Text(text) => {
                         // Example: route by "type" field in JSON
                         if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
-                            let _message: String =
-                                WSMsgType::DriverLocationUpdate.to_string();
+                            let location_update_type = WSMsgType::DriverLocationUpdate.to_string();
 
                             match v.get("type").and_then(|t| t.as_str()) {
-                                Some("driver_location_update") => {
+                                Some(t) if t == location_update_type => {
                                     if let Ok(env) = serde_json::from_value::<Envelope<DriverLocationV1>>(v.clone()) {
-                                        location_update_service
+                                        // the driver_id in the payload is ignored, the session is bound to the token
+                                        if let Err(e) = location_update_service
                                             .handle_location_update(
-                                                client_id,
+                                                driver_id,
                                                 env.data.latitude,
                                                 env.data.longitude,
                                             )
-                                            .await;
+                                            .await
+                                        {
+                                            eprintln!("Location update from driver {} failed: {}", driver_id, e);
+                                        }
                                     }
                                 }
                                 Some("client.ping") => {
                                     let _ = hub
                                         .send_to(
-                                            &client_id,
+                                            &driver_id,
                                             Message::Text("{\"type\":\"server.pong\"}".into()),
                                         )
                                         .await;
@@ -101,7 +121,7 @@ pub async fn ws_on_upgrade(
                     }
                     Message::Ping(data) => {
                         // Respond with Pong
-                        let _ = hub.send_to(&client_id, Message::Pong(data)).await;
+                        let _ = hub.send_to(&driver_id, Message::Pong(data)).await;
                     }
                     Message::Close(_) => break,
                     _ => {}
@@ -110,7 +130,11 @@ pub async fn ws_on_upgrade(
         })
     };
 
-    // Wait until either task finishes; then clean up
-    let _ = tokio::join!(forward_task, read_task);
-    hub.unregister(&client_id).await;
+    // Wait until either task finishes, stop the other one; then clean up.
+    // (joining both would hang since the hub keeps the sender alive until unregister)
+    tokio::select! {
+        _ = &mut forward_task => read_task.abort(),
+        _ = &mut read_task => forward_task.abort(),
+    }
+    hub.unregister(&driver_id, &session_id).await;
 }
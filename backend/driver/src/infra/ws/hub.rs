@@ -4,12 +4,16 @@ use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 // disclaimer: this was written with the help of copilot
 /// A handle to send messages to a connected client.
+/// `session_id` identifies the physical connection, so a stale connection can't
+/// unregister a newer session of the same driver.
 #[derive(Clone)]
 pub struct ClientTx {
+    pub session_id: Uuid,
     pub tx: mpsc::UnboundedSender<Message>,
 }
 
-/// Registry of connected clients by UUID.
+/// Registry of connected clients keyed by the authenticated driver id.
+/// A driver only ever has one live session, newer connections replace older ones.
 #[derive(Default, Clone)]
 pub struct WsHub {
     clients: Arc<RwLock<HashMap<Uuid, ClientTx>>>,
@@ -20,19 +24,35 @@ impl WsHub {
         Self::default()
     }
 
-    /// Register a client with its sending channel.
-    pub async fn register(&self, id: Uuid, tx: ClientTx) {
-        self.clients.write().await.insert(id, tx);
+    /// Register a driver session. If the driver already had a session it is replaced
+    /// and returned so the caller can close it.
+    pub async fn register(&self, driver_id: Uuid, tx: ClientTx) -> Option<ClientTx> {
+        self.clients.write().await.insert(driver_id, tx)
     }
 
-    /// Unregister a client, removing it from the hub.
-    pub async fn unregister(&self, id: &Uuid) {
-        self.clients.write().await.remove(id);
+    /// Unregister a driver session. This is a no-op if the session was already replaced.
+    pub async fn unregister(&self, driver_id: &Uuid, session_id: &Uuid) {
+        let mut clients = self.clients.write().await;
+        if clients
+            .get(driver_id)
+            .is_some_and(|c| c.session_id == *session_id)
+        {
+            clients.remove(driver_id);
+        }
+    }
+
+    /// Returns true if the given session is the active session for the driver.
+    pub async fn is_current(&self, driver_id: &Uuid, session_id: &Uuid) -> bool {
+        self.clients
+            .read()
+            .await
+            .get(driver_id)
+            .is_some_and(|c| c.session_id == *session_id)
     }
 
-    /// Send a message to a specific client. Returns true if the client exists.
-    pub async fn send_to(&self, id: &Uuid, msg: Message) -> bool {
-        if let Some(client) = self.clients.read().await.get(id) {
+    /// Send a message to a specific driver. Returns true if the driver is connected.
+    pub async fn send_to(&self, driver_id: &Uuid, msg: Message) -> bool {
+        if let Some(client) = self.clients.read().await.get(driver_id) {
             let _ = client.tx.send(msg);
             true
         } else {
@@ -52,4 +72,8 @@ impl WsHub {
     pub async fn len(&self) -> usize {
         self.clients.read().await.len()
     }
-}
\ No newline at end of file
+
+    pub async fn is_empty(&self) -> bool {
+        self.clients.read().await.is_empty()
+    }
+}
@@ -0,0 +1,48 @@
+// the driver service as a library, main wires it to Postgres, Redis and NATS, the simulator's
+// discrete mode runs the ride lifecycle in process
+pub mod models;
+pub mod infra {
+    pub mod repository {
+        pub mod driver_eligibility_repository;
+        pub mod driver_repository;
+        pub mod driver_status_repository;
+        pub mod location_history_repository;
+        pub mod ride_trace_repository;
+        pub mod vehicle_repository;
+    }
+
+    pub mod ws {
+        pub mod connections;
+        pub mod hub;
+    }
+}
+// repository modules moved to infra/repository
+
+pub mod api {
+    pub mod admin;
+    pub mod driver;
+    pub mod ride;
+    pub mod vehicle;
+    pub mod router;
+    pub mod ws;
+}
+
+pub mod service {
+    pub mod eligibility;
+    pub mod redis_cleanup;
+    pub mod repositioning;
+    pub mod ride_lifecycle;
+    pub mod ride_trace;
+    pub mod state_reconciler;
+    pub mod eta_service;
+    pub mod location_history;
+    pub mod location_update;
+    pub mod ws_token;
+}
+
+pub mod events {
+    pub mod handlers;
+    pub mod publisher;
+    pub mod schemas;
+    pub mod subscribers;
+}
@@ -1,54 +1,34 @@
 use anyhow::Result;
-use futures_util::stream::StreamExt;
 use sqlx::postgres::PgPoolOptions;
 use tokio::sync::Mutex;
-use std::{env, sync::Arc};
-use tokio::task;
-use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
-
-use crate::api::router::{create_router, AppState};
-use crate::infra::repository::driver_repository::PgDriverRepository;
-use crate::infra::repository::driver_status_repository::PgDriverStatusRepository;
-use crate::infra::repository::vehicle_repository::PgVehicleRepository;
-use crate::infra::ws::hub::WsHub;
-use crate::service::location_update::{self, LocationUpdateService};
-
-mod models;
-pub mod infra {
-    pub mod repository {
-        pub mod driver_repository;
-        pub mod driver_status_repository;
-        pub mod vehicle_repository;
-    }
-
-    pub mod ws {
-        pub mod connections;
-        pub mod hub;
-    }
-}
-// TODO: not supposed to use unwrap I know, but I was experimenting to get a skeleton mvp quick
-// repository modules moved to infra/repository
-
-pub mod api {
-    pub mod driver;
-    pub mod router;
-    pub mod ws;
-}
+use std::{env, sync::Arc, time::Duration};
+use ubersimx_messaging::messagingclient::MessagingClient;
 
-mod service {
-    pub mod redis_cleanup;
-    pub mod ride_lifecycle;
-    pub mod eta_service;
-    pub mod location_update;
-}
-
-pub mod events {
-    pub mod handlers;
-    pub mod publisher;
-    pub mod schemas;
-    pub mod subscribers;
-}
+use driver::api::router::{create_router, AppState};
+use driver::infra::repository::driver_eligibility_repository::PgDriverEligibilityRepository;
+use driver::infra::repository::driver_repository::PgDriverRepository;
+use driver::infra::repository::driver_status_repository::PgDriverStatusRepository;
+use driver::infra::repository::location_history_repository::PgLocationHistoryRepository;
+use driver::infra::repository::ride_trace_repository::PgRideTraceRepository;
+use driver::infra::repository::vehicle_repository::PgVehicleRepository;
+use driver::infra::ws::hub::WsHub;
+use driver::service::eligibility::{CooldownPolicy, EligibilityService};
+use driver::service::eta_service::EtaService;
+use common::driver_state::{DriverStateStore, RedisDriverStateStore};
+use common::eta::graph::{GridSpec, RoadGraph};
+use common::eta::traffic::TrafficProfile;
+use common::eta::EtaEngine;
+use common::repositioning::{DemandForecasts, RepositioningPolicy};
+use common::service_area::ServiceArea;
+use driver::service::location_history::{LocationHistoryPolicy, LocationHistoryService};
+use driver::service::location_update::LocationUpdateService;
+use driver::service::redis_cleanup::RedisCleanupService;
+use driver::service::repositioning::RepositioningService;
+use driver::service::ride_trace::RideTraceService;
+use driver::service::state_reconciler::StateReconcilerService;
+use driver::service::ws_token::WsTokenService;
 
+// TODO: not supposed to use unwrap I know, but I was experimenting to get a skeleton mvp quick
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::from_filename("settings.env").ok();
@@ -65,6 +45,64 @@ async fn main() -> Result<()> {
     let redis_url = env::var("REDIS_URL")
         .map_err(|e| anyhow::anyhow!("REDIS_URL must be set in .env: {}", e))?;
 
+    let ws_token_secret = env::var("WS_TOKEN_SECRET")
+        .map_err(|e| anyhow::anyhow!("WS_TOKEN_SECRET must be set in .env: {}", e))?;
+
+    // bearer token for the /api/v1/admin endpoints
+    let admin_token = env::var("ADMIN_TOKEN")
+        .map_err(|e| anyhow::anyhow!("ADMIN_TOKEN must be set in .env: {}", e))?;
+
+    let redis_cleanup_interval_secs = env::var("REDIS_CLEANUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+
+    let repositioning_interval_secs = env::var("REPOSITIONING_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(60);
+
+    // days of location history kept, and after how many days it's thinned out to a point a minute
+    let location_history_defaults = LocationHistoryPolicy::default();
+    let location_history_retention_days = env::var("LOCATION_HISTORY_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(location_history_defaults.retention_days);
+    let location_history_downsample_after_days = env::var("LOCATION_HISTORY_DOWNSAMPLE_AFTER_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(location_history_defaults.downsample_after_days);
+
+    let ws_token_ttl_secs = env::var("WS_TOKEN_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(24 * 60 * 60);
+
+    // system time unless CLOCK_START is set, simulations share a scaled clock with the simulator
+    let clock = common::clock::clock_from_env()?;
+
+    // road graph for ETAs, a generated grid unless a graph file is given
+    let road_graph = match env::var("ROAD_GRAPH_PATH") {
+        Ok(path) => RoadGraph::load(&path)?,
+        Err(_) => RoadGraph::generate_grid(&GridSpec::default()),
+    };
+    eprintln!(
+        "Road graph: {} nodes, {} edges",
+        road_graph.node_count(),
+        road_graph.edge_count()
+    );
+    let traffic_profile = match env::var("TRAFFIC_PROFILE_PATH") {
+        Ok(path) => TrafficProfile::load(&path)?,
+        Err(_) => TrafficProfile::default(),
+    };
+    let eta_engine = Arc::new(EtaEngine::new(road_graph, traffic_profile));
+
+    // GeoJSON operating area and zones, anywhere goes without one
+    let service_area = Arc::new(match env::var("SERVICE_AREA_PATH") {
+        Ok(path) => ServiceArea::load(&path)?,
+        Err(_) => ServiceArea::unrestricted(),
+    });
+
     // Create a connection pool
     let pool = Arc::new(
         PgPoolOptions::new()
@@ -75,7 +113,10 @@ async fn main() -> Result<()> {
 
     let driver_repo = Arc::new(PgDriverRepository::new(pool.clone()));
     let vehicle_repo = Arc::new(PgVehicleRepository::new(pool.clone()));
-    let driver_status_repo = Arc::new(PgDriverStatusRepository::new(pool.clone()));
+    let driver_status_repo = Arc::new(PgDriverStatusRepository::new(pool.clone(), clock.clone()));
+    let driver_eligibility_repo = Arc::new(PgDriverEligibilityRepository::new(pool.clone(), clock.clone()));
+    let ride_trace_repo = Arc::new(PgRideTraceRepository::new(pool.clone()));
+    let location_history_repo = Arc::new(PgLocationHistoryRepository::new(pool.clone()));
 
     // Connect to your messaging service
     let messaging_client = Arc::new(MessagingClient::connect(&messaging_url).await.unwrap());
@@ -83,43 +124,142 @@ async fn main() -> Result<()> {
     // setup Redis connection for live state management (e.g., driver locations) vs PostgreSQL for persistent storage
     let redis_client = redis::Client::open(redis_url)?;
     let con = redis_client.get_multiplexed_async_connection().await?;
+    let driver_state: Arc<dyn DriverStateStore> = Arc::new(RedisDriverStateStore::new(con.clone()));
 
     // setup the producer (for outgoing events)
-    let event_publisher = Arc::new(events::publisher::EventPublisher::new(
+    let event_publisher = Arc::new(driver::events::publisher::EventPublisher::new(
         messaging_client.clone(),
     ));
 
     // Create Usecases
-    let ride_lifecycle_service = Arc::new(service::ride_lifecycle::RideLifeCycleService {
+    let eligibility_service = Arc::new(EligibilityService {
+        eligibility_repo: driver_eligibility_repo,
+        driver_state: driver_state.clone(),
+        cooldown_policy: CooldownPolicy::default(),
+        clock: clock.clone(),
+    });
+
+    let eta_service = Arc::new(EtaService {
+        engine: eta_engine,
+        driver_state: driver_state.clone(),
+        own_changes: Default::default(),
+        clock: clock.clone(),
+    });
+
+    // breadcrumbs of the rides in progress, for trip traces
+    let ride_trace_service = Arc::new(RideTraceService {
+        ride_trace_repo: ride_trace_repo.clone(),
+        driver_state: driver_state.clone(),
+        clock: clock.clone(),
+    });
+
+    // every reported location, written to postgres in the background, with the breadcrumbs of
+    // the rides in progress
+    let location_history_service = LocationHistoryService::spawn(
+        location_history_repo,
+        ride_trace_repo,
+        LocationHistoryPolicy {
+            retention_days: location_history_retention_days,
+            downsample_after_days: location_history_downsample_after_days,
+            ..location_history_defaults
+        },
+        clock.clone(),
+    );
+
+    // Create the WebSocket hub and wrap it in Arc for sharing, ride offers are pushed through it
+    let ws_hub = Arc::new(WsHub::new());
+
+    let ride_lifecycle_service = Arc::new(driver::service::ride_lifecycle::RideLifeCycleService {
         driver_status_repo: driver_status_repo.clone(),
         producer: event_publisher.clone(),
-        redis_con: Arc::new(Mutex::new(con.clone())),
+        driver_state: driver_state.clone(),
+        eligibility_service: eligibility_service.clone(),
+        eta_service: eta_service.clone(),
+        ws_hub: ws_hub.clone(),
+        ride_trace_service: ride_trace_service.clone(),
+        clock: clock.clone(),
     });
 
     let location_update_service = Arc::new(
         LocationUpdateService {
             redis_con: Arc::new(Mutex::new(con.clone())),
+            service_area,
+            location_history_service: location_history_service.clone(),
+            clock: clock.clone(),
         },
     );
+    // postgres is the source of truth, bring the redis mirror in line before serving traffic
+    let state_reconciler = Arc::new(StateReconcilerService {
+        driver_status_repo: driver_status_repo.clone(),
+        redis_con: Arc::new(Mutex::new(con.clone())),
+    });
+    match state_reconciler.reconcile_all().await {
+        Ok(report) => eprintln!(
+            "Startup reconciliation: checked {} drivers, repaired {}, {} not in redis, {} changed meanwhile",
+            report.checked,
+            report.repaired.len(),
+            report.missing_in_redis,
+            report.skipped
+        ),
+        // not fatal, it can be re-run on demand via the admin endpoint
+        Err(e) => eprintln!("Startup reconciliation failed: {:?}", e),
+    }
+
+    // background job that drops drivers with expired state/stale locations from the GEO set
+    let redis_cleanup_service = Arc::new(RedisCleanupService {
+        driver_status_repo: driver_status_repo.clone(),
+        redis_con: Arc::new(Mutex::new(con.clone())),
+        clock: clock.clone(),
+    });
+    redis_cleanup_service.spawn(Duration::from_secs(redis_cleanup_interval_secs));
+
     // setup the consumers (incoming events)
-    let event_subscribers = events::subscribers::Subscribers::new(messaging_client.clone());
+    let event_subscribers = driver::events::subscribers::Subscribers::new(messaging_client.clone());
     event_subscribers
         .register_ride_evnets_consumers(ride_lifecycle_service.clone())
         .await;
+    event_subscribers
+        .register_traffic_consumers(eta_service.clone())
+        .await;
+
+    // the matcher republishes forecasts every 15 minutes, a cell missing for longer expects nothing
+    let forecasts = Arc::new(DemandForecasts::new(chrono::Duration::minutes(20)));
+    event_subscribers
+        .register_forecast_consumers(forecasts.clone())
+        .await;
+
+    // background job that suggests idle drivers where to wait for the next ride
+    let repositioning_service = Arc::new(RepositioningService {
+        driver_state: driver_state.clone(),
+        forecasts,
+        ws_hub: ws_hub.clone(),
+        policy: RepositioningPolicy::default(),
+        suggested: Default::default(),
+        clock: clock.clone(),
+    });
+    repositioning_service.spawn(Duration::from_secs(repositioning_interval_secs));
 
     // can also have factory function to create AppState that takes pool and creates repos inside
     // todo clean up this to take usecases instead of infra repos directly
-    // Create the WebSocket hub and wrap it in Arc for sharing
-    let ws_hub = Arc::new(WsHub::new());
+    let ws_token_service = Arc::new(WsTokenService::new(ws_token_secret, ws_token_ttl_secs, clock.clone()));
 
     let state = AppState {
         driver_repo,
         driver_status_repo,
+        vehicle_repo,
         messaging_client: messaging_client.clone(),
         redis_con: Arc::new(tokio::sync::Mutex::new(con)),
         ride_lifecycle_service: ride_lifecycle_service.clone(),
         location_update_service: location_update_service.clone(),
+        location_history_service,
         ws_hub: ws_hub.clone(),
+        ws_token_service,
+        admin_token: Arc::new(admin_token),
+        state_reconciler,
+        eligibility_service,
+        eta_service,
+        ride_trace_service,
+        clock,
     };
     let app = create_router(state);
 
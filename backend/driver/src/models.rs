@@ -1,7 +1,8 @@
-use serde::{Deserialize, Serialize};
+use common::vehicle::{DriverCapabilities, VehicleClass};
+use serde::Deserialize;
 use uuid::Uuid;
 
-const LOCATION_STALE_THRESHOLD_SECS: i64 = 60;
+pub(crate) const LOCATION_STALE_THRESHOLD_SECS: i64 = 60;
 #[derive(Debug, Clone)]
 pub enum RideStatus {
     None,
@@ -25,7 +26,7 @@ impl ToString for RideStatus {
 }
 
 impl RideStatus {
-    pub fn from_str(s: &str) -> Self {
+    pub(crate) fn from_str(s: &str) -> Self {
         match s {
             "assigned" => RideStatus::Assigned,
             "in_ride" => RideStatus::InRide,
@@ -88,7 +89,7 @@ impl ToString for AvailabilityReason {
 // todo this might need a heartbeat mechanism separate from location updates will see
 /// Compute availability from the given driver state
 /// Computes availability status based on DriverState.
-pub fn compute_availability(now: i64, s: &DriverRedisState) -> Availability {
+pub(crate) fn compute_availability(now: i64, s: &DriverRedisState) -> Availability {
     // Convert Redis-style fields to bools.
     let is_online = s.driver_online == "1";
     let is_in_ride = s.in_ride == "1";
@@ -135,6 +136,18 @@ pub struct Driver {
     pub license_number: Option<String>, // might not be known at time of creation
     pub rating: Option<f32>,            // not known at time of creation
     pub car_id: Option<Uuid>,           // might not be assigned at creation
+    pub license_expires_at: Option<chrono::DateTime<chrono::Utc>>, // drivers with an expired license are not matched
+}
+
+/// Filters and pagination for listing drivers.
+#[derive(Debug, Clone)]
+pub struct DriverListFilter {
+    /// case insensitive substring match on the name
+    pub name: Option<String>,
+    pub min_rating: Option<f32>,
+    pub has_car: Option<bool>,
+    pub limit: i64,
+    pub offset: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -145,14 +158,72 @@ pub struct Vehicle {
     pub plate_number: String,
     pub year: u16,
     pub driver_id: Uuid, // Foreign key to Driver
+    pub vehicle_class: VehicleClass,
+    pub seats: u8, // passenger seats, the driver's seat not included
+    pub pet_friendly: bool,
+    pub wheelchair_accessible: bool,
+    pub is_active: bool, // the vehicle the driver is currently driving, at most one per driver
+}
+
+impl Vehicle {
+    // what the matcher sees of this vehicle once it's the active one
+    pub fn capabilities(&self) -> DriverCapabilities {
+        DriverCapabilities {
+            vehicle_class: self.vehicle_class,
+            seats: self.seats,
+            pet_friendly: self.pet_friendly,
+            wheelchair_accessible: self.wheelchair_accessible,
+        }
+    }
+}
+
+/// What a ride breadcrumb is: a position the driver reported, or where the passenger got on
+/// or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreadcrumbKind {
+    Location,
+    Pickup,
+    Dropoff,
+}
+
+impl std::fmt::Display for BreadcrumbKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BreadcrumbKind::Location => "location",
+            BreadcrumbKind::Pickup => "pickup",
+            BreadcrumbKind::Dropoff => "dropoff",
+        };
+        f.write_str(s)
+    }
 }
 
+impl BreadcrumbKind {
+    pub(crate) fn from_str(s: &str) -> Self {
+        match s {
+            "pickup" => BreadcrumbKind::Pickup,
+            "dropoff" => BreadcrumbKind::Dropoff,
+            _ => BreadcrumbKind::Location,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RideBreadcrumb {
+    pub ride_id: Uuid,
+    pub driver_id: Uuid,
+    pub kind: BreadcrumbKind,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// One entry of a driver's location history.
 #[derive(Debug, Clone)]
 pub struct DriverLocation {
-    pub driver_id: i32,
+    pub driver_id: Uuid,
     pub latitude: f64,
     pub longitude: f64,
-    pub timestamp: chrono::NaiveDateTime,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
 }
 
 // optional later: Add models for DriverScedule (if you want planned shifts for later), DriverEarnings, DriverPrerferences(max distance, max time, etc.)
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
+use common::eligibility::DriverEligibility;
+use uuid::Uuid;
+
+use crate::infra::repository::driver_eligibility_repository::DriverEligibilityRepository;
+
+/// When rejecting offered rides puts a driver on cooldown. "Cancellation" here is a reject of
+/// an assigned ride, drivers can't cancel a ride once accepted.
+#[derive(Debug, Clone)]
+pub struct CooldownPolicy {
+    /// how far back responses are counted, never further than the end of the last cooldown
+    pub window: chrono::Duration,
+    /// below this many responses in the window the rate is too noisy to act on
+    pub min_responses: i64,
+    /// rejections / responses above this trigger the cooldown
+    pub max_rejection_rate: f64,
+    pub cooldown: chrono::Duration,
+}
+
+impl Default for CooldownPolicy {
+    fn default() -> Self {
+        Self {
+            window: chrono::Duration::hours(24),
+            min_responses: 5,
+            max_rejection_rate: 0.5,
+            cooldown: chrono::Duration::minutes(30),
+        }
+    }
+}
+
+pub struct EligibilityService {
+    pub eligibility_repo: Arc<dyn DriverEligibilityRepository + Send + Sync>,
+    pub driver_state: Arc<dyn DriverStateStore>,
+    pub cooldown_policy: CooldownPolicy,
+    pub clock: SharedClock,
+}
+
+impl EligibilityService {
+    /// None if the driver doesn't exist.
+    pub async fn get(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error> {
+        self.eligibility_repo.get_eligibility(driver_id).await
+    }
+
+    /// `until` None suspends until reinstated. None if the driver doesn't exist.
+    pub async fn suspend(
+        &self,
+        driver_id: Uuid,
+        reason: &str,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Option<DriverEligibility>, Error> {
+        if !self.eligibility_repo.suspend(driver_id, reason, until).await? {
+            return Ok(None);
+        }
+        self.refresh(driver_id).await
+    }
+
+    /// Lifts the suspension and any cooldown. None if the driver doesn't exist.
+    pub async fn reinstate(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error> {
+        if !self.eligibility_repo.reinstate(driver_id).await? {
+            return Ok(None);
+        }
+        self.refresh(driver_id).await
+    }
+
+    /// Re-reads eligibility from postgres and mirrors it into redis if the driver is live.
+    pub async fn refresh(&self, driver_id: Uuid) -> Result<Option<DriverEligibility>, Error> {
+        let eligibility = self.eligibility_repo.get_eligibility(driver_id).await?;
+        if let Some(eligibility) = &eligibility {
+            self.mirror(driver_id, eligibility).await?;
+        }
+        Ok(eligibility)
+    }
+
+    /// Records the driver's answer to an offered ride and puts them on cooldown if
+    /// their rejection rate over the policy window got too high.
+    pub async fn record_ride_response(
+        &self,
+        driver_id: Uuid,
+        ride_id: Uuid,
+        accepted: bool,
+    ) -> Result<(), Error> {
+        self.eligibility_repo
+            .record_ride_response(driver_id, ride_id, accepted)
+            .await?;
+
+        if accepted {
+            return Ok(());
+        }
+
+        let policy = &self.cooldown_policy;
+        let now = self.clock.now();
+        // responses that already earned a cooldown don't count towards the next one
+        let mut since = now - policy.window;
+        let last_cooldown = self
+            .eligibility_repo
+            .get_eligibility(driver_id)
+            .await?
+            .and_then(|e| e.cooldown_until);
+        match last_cooldown {
+            Some(until) if until > now => return Ok(()),
+            Some(until) => since = since.max(until),
+            None => {}
+        }
+        let (responses, rejections) = self
+            .eligibility_repo
+            .count_ride_responses(driver_id, since)
+            .await?;
+
+        if responses < policy.min_responses
+            || (rejections as f64 / responses as f64) <= policy.max_rejection_rate
+        {
+            return Ok(());
+        }
+
+        eprintln!(
+            "Driver {} rejected {} of {} rides, cooling down for {} minutes",
+            driver_id,
+            rejections,
+            responses,
+            policy.cooldown.num_minutes()
+        );
+        self.eligibility_repo
+            .set_cooldown(driver_id, now + policy.cooldown)
+            .await?;
+        self.refresh(driver_id).await?;
+        Ok(())
+    }
+
+    // Only for a live driver, an offline driver has no hash and gets the fields written when
+    // they come online (see update_driver_status).
+    async fn mirror(&self, driver_id: Uuid, eligibility: &DriverEligibility) -> Result<(), Error> {
+        self.driver_state
+            .update_existing_state(driver_id, &eligibility.to_redis_fields())
+            .await?;
+        Ok(())
+    }
+}
@@ -1,16 +1,105 @@
-use rand::Rng;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 
-/// Dummy ETA service that returns a random ETA between 5 and 10 minutes.
-pub struct EtaService;
+use anyhow::Error;
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
+use common::eta::traffic::Incident;
+use common::eta::{Eta, EtaEngine};
+use common::geo::LatLng;
+use common::redis_namespaces::{DRIVER_PICKUP_LAT_FIELD, DRIVER_PICKUP_LNG_FIELD};
+use uuid::Uuid;
 
-pub trait EtaCalculator {
-    /// Returns ETA in minutes.
-    fn calculate_eta_minutes(&self) -> u32;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IncidentChange {
+    Reported,
+    Cleared,
 }
 
-impl EtaCalculator for EtaService {
-    fn calculate_eta_minutes(&self) -> u32 {
-        let mut rng = rand::rng();
-        rng.random_range(5..=10)
+/// Pickup ETAs over the road network, see `common::eta`.
+pub struct EtaService {
+    pub engine: Arc<EtaEngine>,
+    pub driver_state: Arc<dyn DriverStateStore>,
+    // incident changes made here and published, the driver service is subscribed to its own
+    // events and skips them when they come back
+    pub own_changes: Mutex<HashSet<(Uuid, IncidentChange)>>,
+    pub clock: SharedClock,
+}
+
+impl EtaService {
+    /// ETA from the driver's last known position to the pickup point of the ride they are
+    /// assigned (stored in the state hash on assignment). None if either is unknown.
+    pub async fn pickup_eta(&self, driver_id: Uuid) -> Result<Option<Eta>, Error> {
+        let position = self.driver_state.positions(&[driver_id]).await?;
+        let state = self.driver_state.states(&[driver_id]).await?;
+        let field = |name: &str| {
+            state
+                .first()
+                .and_then(|state| state.get(name))
+                .and_then(|value| value.parse::<f64>().ok())
+        };
+
+        let (Some(Some(position)), Some(pickup_lat), Some(pickup_lng)) = (
+            position.into_iter().next(),
+            field(DRIVER_PICKUP_LAT_FIELD),
+            field(DRIVER_PICKUP_LNG_FIELD),
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.engine.eta(
+            position,
+            LatLng::new(pickup_lat, pickup_lng),
+            self.clock.now(),
+        )))
+    }
+
+    /// Adds or replaces an incident, returns how many edges it slows down.
+    pub fn apply_incident(&self, incident: Incident) -> usize {
+        self.engine
+            .traffic()
+            .add_incident(self.engine.graph(), incident, self.clock.now())
+    }
+
+    pub fn clear_incident(&self, incident_id: Uuid) -> bool {
+        self.engine.traffic().clear_incident(incident_id)
+    }
+
+    /// An incident reported here, applied right away and its event skipped.
+    pub fn report_incident(&self, incident: Incident) -> usize {
+        self.own_changes
+            .lock()
+            .unwrap()
+            .insert((incident.id, IncidentChange::Reported));
+        self.apply_incident(incident)
+    }
+
+    /// Undoes `report_incident` when its event couldn't be published, so this service doesn't
+    /// route around an incident nobody else knows about.
+    pub fn retract_incident(&self, incident_id: Uuid) {
+        self.clear_incident(incident_id);
+        self.is_own_change(incident_id, IncidentChange::Reported);
+    }
+
+    /// An incident cleared here, cleared right away and its event skipped. False if the
+    /// incident is unknown.
+    pub fn withdraw_incident(&self, incident_id: Uuid) -> bool {
+        if !self.clear_incident(incident_id) {
+            return false;
+        }
+        self.own_changes
+            .lock()
+            .unwrap()
+            .insert((incident_id, IncidentChange::Cleared));
+        true
+    }
+
+    /// True once for every change made by `report_incident` or `withdraw_incident`.
+    pub fn is_own_change(&self, incident_id: Uuid, change: IncidentChange) -> bool {
+        self.own_changes.lock().unwrap().remove(&(incident_id, change))
+    }
+
+    pub fn incidents(&self) -> Vec<Incident> {
+        self.engine.traffic().incidents()
     }
 }
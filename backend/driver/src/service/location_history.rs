@@ -0,0 +1,246 @@
+// Location history: every location a driver reports, kept in Postgres for looking back at
+// where drivers were (Redis only has the latest position).
+//
+// Location updates only queue the location, a background writer inserts the queue in batches
+// so the update path never waits on Postgres. Locations of a driver on an accepted ride go into
+// the same batches as breadcrumbs of the ride's trace. When the queue is full (Postgres down or
+// too slow) locations are dropped rather than slowing the drivers down. The table is partitioned
+// by day; a maintenance job creates the partitions ahead of time, thins out days older than
+// `downsample_after` to one location per driver and `downsample_interval`, and drops the days
+// older than `retention`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::infra::repository::location_history_repository::LocationHistoryRepository;
+use crate::infra::repository::ride_trace_repository::RideTraceRepository;
+use crate::models::{BreadcrumbKind, DriverLocation, RideBreadcrumb};
+
+#[derive(Debug, Clone)]
+pub struct LocationHistoryPolicy {
+    /// most locations per insert
+    pub batch_size: usize,
+    /// longest a location waits in the queue before it's written
+    pub flush_interval: Duration,
+    /// locations waiting to be written, beyond this they are dropped
+    pub queue_capacity: usize,
+    /// days kept, counting today
+    pub retention_days: u64,
+    /// days older than this are downsampled
+    pub downsample_after_days: u64,
+    pub downsample_interval: chrono::Duration,
+    /// days of partitions created ahead of today
+    pub partitions_ahead: u64,
+    /// how often partitions, downsampling and retention are taken care of
+    pub maintenance_interval: Duration,
+}
+
+impl Default for LocationHistoryPolicy {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            flush_interval: Duration::from_secs(1),
+            queue_capacity: 10_000,
+            retention_days: 30,
+            downsample_after_days: 7,
+            downsample_interval: chrono::Duration::minutes(1),
+            partitions_ahead: 2,
+            maintenance_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// What one maintenance round did.
+#[derive(Debug, Default)]
+pub struct MaintenanceReport {
+    pub created: usize,
+    pub downsampled: usize,
+    pub thinned_out: u64,
+    pub dropped: usize,
+    pub purged: u64,
+}
+
+// a queued location and the ride it's a breadcrumb of, if any
+type QueuedLocation = (DriverLocation, Option<Uuid>);
+
+pub struct LocationHistoryService {
+    pub location_history_repo: Arc<dyn LocationHistoryRepository + Send + Sync>,
+    pub ride_trace_repo: Arc<dyn RideTraceRepository + Send + Sync>,
+    pub policy: LocationHistoryPolicy,
+    queue: mpsc::Sender<QueuedLocation>,
+    // locations dropped because the queue was full, since the last warning
+    dropped: AtomicU64,
+    pub clock: SharedClock,
+}
+
+impl LocationHistoryService {
+    /// Creates the service and spawns its writer and maintenance job.
+    pub fn spawn(
+        location_history_repo: Arc<dyn LocationHistoryRepository + Send + Sync>,
+        ride_trace_repo: Arc<dyn RideTraceRepository + Send + Sync>,
+        policy: LocationHistoryPolicy,
+        clock: SharedClock,
+    ) -> Arc<Self> {
+        let (queue, rx) = mpsc::channel(policy.queue_capacity.max(1));
+        let service = Arc::new(Self {
+            location_history_repo,
+            ride_trace_repo,
+            policy,
+            queue,
+            dropped: AtomicU64::new(0),
+            clock,
+        });
+        tokio::spawn(service.clone().write(rx));
+        service.clone().spawn_maintenance();
+        service
+    }
+
+    /// Queues a reported location, also as a breadcrumb of `ride_id` if the driver is on one.
+    /// Never waits.
+    pub fn record(&self, driver_id: Uuid, latitude: f64, longitude: f64, ride_id: Option<Uuid>) {
+        let location = DriverLocation {
+            driver_id,
+            latitude,
+            longitude,
+            recorded_at: self.clock.now(),
+        };
+        if self.queue.try_send((location, ride_id)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The driver's locations in [from, to), at most `limit`. With `interval` only the first
+    /// location of every interval.
+    pub async fn history(
+        &self,
+        driver_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: Option<chrono::Duration>,
+        limit: i64,
+    ) -> Result<Vec<DriverLocation>, Error> {
+        let bucket_secs = interval.map(|i| i.num_seconds()).filter(|s| *s > 0);
+        self.location_history_repo
+            .history(driver_id, from, to, bucket_secs, limit)
+            .await
+    }
+
+    // the writer: a batch goes out when it's full or the flush interval is up
+    async fn write(self: Arc<Self>, mut rx: mpsc::Receiver<QueuedLocation>) {
+        let mut batch = Vec::with_capacity(self.policy.batch_size);
+        let mut ticker = tokio::time::interval(self.policy.flush_interval);
+        loop {
+            tokio::select! {
+                location = rx.recv() => {
+                    let Some(location) = location else {
+                        break;
+                    };
+                    batch.push(location);
+                    if batch.len() < self.policy.batch_size {
+                        continue;
+                    }
+                }
+                _ = ticker.tick() => {
+                    let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        eprintln!("Location history queue full, dropped {} locations", dropped);
+                    }
+                }
+            }
+            self.flush(&mut batch).await;
+        }
+        self.flush(&mut batch).await;
+    }
+
+    async fn flush(&self, batch: &mut Vec<QueuedLocation>) {
+        if batch.is_empty() {
+            return;
+        }
+        let breadcrumbs: Vec<RideBreadcrumb> = batch
+            .iter()
+            .filter_map(|(location, ride_id)| {
+                Some(RideBreadcrumb {
+                    ride_id: (*ride_id)?,
+                    driver_id: location.driver_id,
+                    kind: BreadcrumbKind::Location,
+                    latitude: location.latitude,
+                    longitude: location.longitude,
+                    recorded_at: location.recorded_at,
+                })
+            })
+            .collect();
+        let locations: Vec<DriverLocation> = batch.drain(..).map(|(location, _)| location).collect();
+
+        // not retried, a failing database would only make the queue back up
+        if let Err(e) = self.location_history_repo.insert_batch(&locations).await {
+            eprintln!("Failed to write {} locations to the history: {:?}", locations.len(), e);
+        }
+        if let Err(e) = self.ride_trace_repo.append_batch(&breadcrumbs).await {
+            eprintln!("Failed to write {} ride breadcrumbs: {:?}", breadcrumbs.len(), e);
+        }
+    }
+
+    /// Creates the coming partitions, downsamples and drops the old ones.
+    pub async fn maintain(&self) -> Result<MaintenanceReport, Error> {
+        let today = self.clock.now().date_naive();
+        let mut report = MaintenanceReport::default();
+        let existing = self.location_history_repo.partition_days().await?;
+
+        for ahead in 0..=self.policy.partitions_ahead {
+            let day = today + chrono::Days::new(ahead);
+            if !existing.contains(&day) {
+                self.location_history_repo.create_partition(day).await?;
+                report.created += 1;
+            }
+        }
+
+        let keep_from = today - chrono::Days::new(self.policy.retention_days.saturating_sub(1));
+        for day in existing.iter().filter(|d| **d < keep_from) {
+            self.location_history_repo.drop_partition(*day).await?;
+            report.dropped += 1;
+        }
+        report.purged = self
+            .location_history_repo
+            .purge_default(keep_from.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+            .await?;
+
+        let downsample_before = today - chrono::Days::new(self.policy.downsample_after_days);
+        let downsampled = self.location_history_repo.downsampled_days().await?;
+        let bucket_secs = self.policy.downsample_interval.num_seconds().max(1);
+        for day in existing
+            .iter()
+            .filter(|d| **d >= keep_from && **d < downsample_before && !downsampled.contains(d))
+        {
+            report.thinned_out += self.location_history_repo.downsample(*day, bucket_secs).await?;
+            report.downsampled += 1;
+        }
+        Ok(report)
+    }
+
+    fn spawn_maintenance(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.policy.maintenance_interval);
+            loop {
+                ticker.tick().await;
+                match self.maintain().await {
+                    Ok(report) => {
+                        if report.created + report.downsampled + report.dropped > 0 || report.purged > 0 {
+                            eprintln!(
+                                "Location history: created {} partitions, downsampled {} days ({} locations), dropped {} days, purged {} locations",
+                                report.created, report.downsampled, report.thinned_out, report.dropped, report.purged
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("Location history maintenance failed: {:?}", e),
+                }
+            }
+        });
+    }
+}
@@ -1,24 +1,166 @@
 use std::sync::Arc;
 
+use common::clock::SharedClock;
+use common::geo::hex::{HexCell, CELL_RESOLUTION};
+use common::geo::LatLng;
+use common::redis_key_helpers::{driver_state_namespace, zone_queue_namespace};
+use common::redis_namespaces::{
+    DRIVER_CELL_FIELD, DRIVER_IN_RIDE_FIELD, DRIVER_LAST_LOCATION_UPDATE_FIELD,
+    DRIVER_LOCATION_NAMESPACE, DRIVER_RIDE_ID_FIELD, DRIVER_ZONE_FIELD,
+};
+use common::service_area::{ServiceArea, ServiceAreaViolation};
+use redis::AsyncCommands;
 use uuid::Uuid;
 
-pub trait LocationUpdate {
-    async fn handle_location_update(&self, driver_id: Uuid, latitude: f64, longitude: f64);
+use crate::service::location_history::LocationHistoryService;
+
+// Redis GEO can only index latitudes within the web mercator range
+const MAX_GEO_LATITUDE: f64 = 85.051_128_78;
+const MAX_GEO_LONGITUDE: f64 = 180.0;
+
+// the state hash expires if the driver stops sending locations, this is how the
+// system notices a driver that went away without toggling offline
+pub const DRIVER_STATE_TTL_SECS: i64 = 90;
+
+#[derive(Debug)]
+pub enum LocationUpdateError {
+    InvalidCoordinates { latitude: f64, longitude: f64 },
+    OutsideServiceArea(ServiceAreaViolation),
+    Redis(redis::RedisError),
+}
+
+impl std::fmt::Display for LocationUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocationUpdateError::InvalidCoordinates {
+                latitude,
+                longitude,
+            } => write!(f, "invalid coordinates lat={}, lng={}", latitude, longitude),
+            LocationUpdateError::OutsideServiceArea(violation) => write!(f, "{}", violation),
+            LocationUpdateError::Redis(e) => write!(f, "failed to store location: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LocationUpdateError {}
+
+pub(crate) trait LocationUpdate {
+    async fn handle_location_update(
+        &self,
+        driver_id: Uuid,
+        latitude: f64,
+        longitude: f64,
+    ) -> Result<(), LocationUpdateError>;
 }
 
 /// Service responsible for handling driver location updates.
+/// Both the REST location endpoint and the websocket `driver_location_update` messages go through here,
+/// so the matcher sees the same `drivers:locations` GEO set regardless of how the driver reports.
+///
+/// Locations outside the operating area are refused, the driver then goes stale like one that
+/// stopped reporting. Entering or leaving an airport queue zone moves the driver in or out of
+/// that zone's queue. Positions of a driver on an accepted ride are kept as breadcrumbs of the
+/// ride for its trace, and every accepted location goes into the location history.
 pub struct LocationUpdateService {
     pub redis_con: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
+    pub service_area: Arc<ServiceArea>,
+    pub location_history_service: Arc<LocationHistoryService>,
+    pub clock: SharedClock,
+}
+
+pub(crate) fn validate_coordinates(latitude: f64, longitude: f64) -> Result<(), LocationUpdateError> {
+    let valid = latitude.is_finite()
+        && longitude.is_finite()
+        && latitude.abs() <= MAX_GEO_LATITUDE
+        && longitude.abs() <= MAX_GEO_LONGITUDE;
+
+    if valid {
+        Ok(())
+    } else {
+        Err(LocationUpdateError::InvalidCoordinates {
+            latitude,
+            longitude,
+        })
+    }
 }
 
 impl LocationUpdate for LocationUpdateService {
     /// Handle a location update for a driver.
-    async fn handle_location_update(&self, driver_id: Uuid, latitude: f64, longitude: f64) {
-        // TODO validate inputs
-        // TODO: Implement logic to update location in Redis, DB, or publish event
-        println!(
-            "Location update: driver_id={}, lat={}, lng={}",
-            driver_id, latitude, longitude
-        );
+    async fn handle_location_update(
+        &self,
+        driver_id: Uuid,
+        latitude: f64,
+        longitude: f64,
+    ) -> Result<(), LocationUpdateError> {
+        validate_coordinates(latitude, longitude)?;
+        let point = LatLng::new(latitude, longitude);
+        if !self.service_area.in_operating_area(point) {
+            return Err(LocationUpdateError::OutsideServiceArea(
+                ServiceAreaViolation::OutsideServiceArea { suggested: None },
+            ));
+        }
+
+        // Set driver location and heartbeat using a Redis pipeline (atomic MULTI/EXEC).
+        // This issues the commands in one network round trip and executes them
+        // inside MULTI/EXEC so they are applied atomically on the server.
+        // seconds, same unit as the other timestamps in the state hash (see compute_availability)
+        let timestamp = self.clock.now().timestamp();
+        let key = driver_state_namespace(driver_id);
+        // per cell counts (surge) read this instead of asking the GEO set for every position
+        let cell = HexCell::of(point, CELL_RESOLUTION);
+        let zone = self
+            .service_area
+            .queue_zone_at(point)
+            .map(|z| z.id.clone())
+            .unwrap_or_default();
+
+        let mut con = self.redis_con.lock().await;
+        let (previous_zone, in_ride, ride_id): (Option<String>, Option<String>, Option<String>) = con
+            .hget(&key, &[DRIVER_ZONE_FIELD, DRIVER_IN_RIDE_FIELD, DRIVER_RIDE_ID_FIELD])
+            .await
+            .map_err(LocationUpdateError::Redis)?;
+        let previous_zone = previous_zone.unwrap_or_default();
+        // in_ride is set from the accept to the dropoff
+        let ride_id = ride_id
+            .filter(|_| in_ride.as_deref() == Some("1"))
+            .and_then(|id| Uuid::parse_str(&id).ok());
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .geo_add(
+                DRIVER_LOCATION_NAMESPACE,
+                (longitude, latitude, driver_id.to_string()),
+            )
+            .ignore()
+            .hset(&key, DRIVER_LAST_LOCATION_UPDATE_FIELD, timestamp)
+            .ignore()
+            .hset(&key, DRIVER_CELL_FIELD, cell.to_string())
+            .ignore()
+            .hset(&key, DRIVER_ZONE_FIELD, &zone)
+            .ignore()
+            .expire(&key, DRIVER_STATE_TTL_SECS)
+            .ignore();
+        // the queue position is the time of entering, updates from inside the zone keep it
+        if zone != previous_zone {
+            if !previous_zone.is_empty() {
+                pipe.zrem(zone_queue_namespace(&previous_zone), driver_id.to_string())
+                    .ignore();
+            }
+            if !zone.is_empty() {
+                pipe.zadd(zone_queue_namespace(&zone), driver_id.to_string(), timestamp)
+                    .ignore();
+            }
+        }
+
+        pipe.query_async::<()>(&mut *con)
+            .await
+            .map_err(LocationUpdateError::Redis)?;
+        drop(con);
+
+        // the ride's breadcrumb goes with it
+        self.location_history_service
+            .record(driver_id, latitude, longitude, ride_id);
+
+        Ok(())
     }
 }
@@ -1 +1,194 @@
+// Background sweeper that keeps the drivers:locations GEO set honest.
+// A driver that stops sending locations (app killed, network lost) never toggles offline,
+// its state hash just expires. Without this job the matcher would keep finding that driver
+// in geo_radius forever, because GEO members don't expire on their own.
 
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+use common::clock::SharedClock;
+use common::redis_key_helpers::driver_state_namespace;
+use common::redis_namespaces::{
+    DRIVER_IN_RIDE_FIELD, DRIVER_LAST_LOCATION_UPDATE_FIELD, DRIVER_LOCATION_NAMESPACE,
+};
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::models::LOCATION_STALE_THRESHOLD_SECS;
+
+// members per ZSCAN page, the connection lock is only held for one page at a time
+const SWEEP_PAGE_SIZE: usize = 500;
+
+// Re-checks every candidate and removes it in one atomic step, so a location that arrives
+// after the first read keeps the driver. KEYS[1] is the GEO set, KEYS[i] the state hash of
+// the member in ARGV[i + 3]; ARGV[1..4] are now, the stale threshold and the heartbeat and
+// in_ride field names.
+const REAP_SCRIPT: &str = r"
+local now = tonumber(ARGV[1])
+local threshold = tonumber(ARGV[2])
+local reaped = {}
+for i = 2, #KEYS do
+    local state = redis.call('HMGET', KEYS[i], ARGV[3], ARGV[4])
+    local ts = tonumber(state[1])
+    if state[2] ~= '1' and (ts == nil or now - ts > threshold) then
+        if redis.call('ZREM', KEYS[1], ARGV[i + 3]) == 1 then
+            table.insert(reaped, ARGV[i + 3])
+        end
+    end
+end
+return reaped
+";
+
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    /// members found in the GEO set
+    pub scanned: usize,
+    /// members removed because their state expired or their location was stale
+    pub reaped: usize,
+    /// reaped drivers that could not be marked unavailable in postgres
+    pub status_update_failures: usize,
+}
+
+// A reaped driver is also taken offline in postgres, otherwise the next reconcile or the
+// driver's next status toggle would treat them as available. That's a compare-and-set:
+// a driver with a ride assigned or on one keeps their status.
+pub struct RedisCleanupService {
+    pub driver_status_repo: Arc<dyn DriverStatusRepository + Send + Sync>,
+    pub redis_con: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
+    pub clock: SharedClock,
+}
+
+impl RedisCleanupService {
+    /// Run a single sweep over drivers:locations.
+    pub async fn sweep(&self) -> Result<CleanupReport, Error> {
+        let mut report = CleanupReport::default();
+        let now = self.clock.now().timestamp();
+
+        // GEO sets are sorted sets under the hood, ZSCAN pages through them without blocking
+        // redis on a big set. A member can show up twice, the script only counts real removals.
+        let mut cursor: u64 = 0;
+        let mut reaped_drivers = Vec::new();
+        loop {
+            let mut con = self.redis_con.lock().await;
+            // the page alternates member and score
+            let (next, page): (u64, Vec<String>) = redis::cmd("ZSCAN")
+                .arg(DRIVER_LOCATION_NAMESPACE)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(SWEEP_PAGE_SIZE)
+                .query_async(&mut *con)
+                .await?;
+            let members: Vec<String> = page.into_iter().step_by(2).collect();
+            report.scanned += members.len();
+            reaped_drivers.extend(self.sweep_page(&mut con, &members, now, &mut report).await?);
+            drop(con);
+
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        for driver_id in reaped_drivers {
+            if let Err(e) = self
+                .driver_status_repo
+                .mark_unavailable_if_idle(driver_id)
+                .await
+            {
+                eprintln!("Failed to mark reaped driver {} unavailable: {:?}", driver_id, e);
+                report.status_update_failures += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Reaps the stale members of one page, returns the reaped driver ids.
+    async fn sweep_page(
+        &self,
+        con: &mut redis::aio::MultiplexedConnection,
+        members: &[String],
+        now: i64,
+        report: &mut CleanupReport,
+    ) -> Result<Vec<Uuid>, Error> {
+        if members.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // a member that isn't a driver id can never get a heartbeat, it goes right away
+        let (drivers, junk): (Vec<(&String, Option<Uuid>)>, Vec<_>) = members
+            .iter()
+            .map(|m| (m, Uuid::parse_str(m).ok()))
+            .partition(|(_, id)| id.is_some());
+        if !junk.is_empty() {
+            let junk: Vec<&String> = junk.into_iter().map(|(member, _)| member).collect();
+            let removed: usize = con.zrem(DRIVER_LOCATION_NAMESPACE, &junk).await?;
+            report.reaped += removed;
+        }
+
+        // fetch all heartbeats in one round trip, a missing hash comes back as nil. Drivers on
+        // a ride are left alone, their location keeps the ride going.
+        let mut pipe = redis::pipe();
+        for (_, driver_id) in &drivers {
+            if let Some(driver_id) = driver_id {
+                pipe.hget(
+                    driver_state_namespace(*driver_id),
+                    &[DRIVER_LAST_LOCATION_UPDATE_FIELD, DRIVER_IN_RIDE_FIELD],
+                );
+            }
+        }
+        let states: Vec<(Option<i64>, Option<String>)> = pipe.query_async(&mut *con).await?;
+
+        let candidates: Vec<(&String, Uuid)> = drivers
+            .into_iter()
+            .zip(states)
+            .filter(|(_, (ts, in_ride))| {
+                in_ride.as_deref() != Some("1")
+                    && ts.is_none_or(|ts| now - ts > LOCATION_STALE_THRESHOLD_SECS)
+            })
+            .filter_map(|((member, id), _)| Some((member, id?)))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // the read above is only a filter, the script decides
+        let script = redis::Script::new(REAP_SCRIPT);
+        let mut invocation = script.key(DRIVER_LOCATION_NAMESPACE);
+        invocation
+            .arg(now)
+            .arg(LOCATION_STALE_THRESHOLD_SECS)
+            .arg(DRIVER_LAST_LOCATION_UPDATE_FIELD)
+            .arg(DRIVER_IN_RIDE_FIELD);
+        for (member, driver_id) in &candidates {
+            invocation.key(driver_state_namespace(*driver_id)).arg(*member);
+        }
+        let reaped: Vec<String> = invocation.invoke_async(&mut *con).await?;
+        report.reaped += reaped.len();
+
+        Ok(reaped
+            .iter()
+            .filter_map(|member| Uuid::parse_str(member).ok())
+            .collect())
+    }
+
+    /// Spawn the sweeper on a fixed interval.
+    pub fn spawn(self: Arc<Self>, every: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                match self.sweep().await {
+                    Ok(report) if report.reaped > 0 => eprintln!(
+                        "Redis cleanup reaped {} of {} drivers ({} status updates failed)",
+                        report.reaped, report.scanned, report.status_update_failures
+                    ),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Redis cleanup sweep failed: {:?}", e),
+                }
+            }
+        })
+    }
+}
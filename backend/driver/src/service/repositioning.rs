@@ -0,0 +1,116 @@
+// Background job suggesting idle drivers a better cell to wait in, pushed over the websocket.
+// Supply is the available drivers in drivers:locations with the cell of their last location,
+// demand the matcher's forecast; the plan itself is common::repositioning.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Error;
+use axum::extract::ws::Message;
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
+use common::geo::hex::HexCell;
+use common::redis_namespaces::{DRIVER_AVAILABILITY_FIELD, DRIVER_CELL_FIELD};
+use common::repositioning::{plan_moves, DemandForecasts, RepositioningPolicy};
+use common::ws_schema::{Coord, Envelope, RepositionSuggestion, WSMsgType};
+use uuid::Uuid;
+
+use crate::infra::ws::hub::WsHub;
+
+// a driver isn't sent somewhere else while on the way to the last suggestion
+const SUGGESTION_EXPIRES_IN_SECS: u16 = 120;
+
+pub struct RepositioningService {
+    pub driver_state: Arc<dyn DriverStateStore>,
+    pub forecasts: Arc<DemandForecasts>,
+    pub ws_hub: Arc<WsHub>,
+    pub policy: RepositioningPolicy,
+    // when each driver was last sent a suggestion
+    pub suggested: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+    pub clock: SharedClock,
+}
+
+impl RepositioningService {
+    /// Plans one round of moves and pushes them to the connected drivers, returns how many
+    /// suggestions went out.
+    pub async fn recommend(&self) -> Result<usize, Error> {
+        let now = self.clock.now();
+        let demand = self.forecasts.expected(now, self.policy.lookahead);
+        if demand.is_empty() {
+            return Ok(0);
+        }
+        let idle = self.idle_drivers().await?;
+        let moves = plan_moves(&self.policy, &idle, &demand);
+
+        let expires_in = chrono::Duration::seconds(SUGGESTION_EXPIRES_IN_SECS as i64);
+        let mut sent = 0;
+        for mv in moves {
+            {
+                let mut suggested = self.suggested.lock().unwrap();
+                suggested.retain(|_, at| now - *at < expires_in);
+                if suggested.contains_key(&mv.driver_id) {
+                    continue;
+                }
+            }
+            let center = mv.to.center();
+            let suggestion = Envelope::new(
+                WSMsgType::RepositionSuggestion,
+                1,
+                now.timestamp_millis(),
+                RepositionSuggestion {
+                    cell: mv.to.to_string(),
+                    target: Coord {
+                        lat: center.lat,
+                        lng: center.lng,
+                    },
+                    expected_requests: mv.expected_requests as f32,
+                    idle_drivers: mv.idle_drivers,
+                    expires_in_sec: SUGGESTION_EXPIRES_IN_SECS,
+                },
+            );
+            let text = serde_json::to_string(&suggestion)?;
+            // a driver who didn't get it can be sent one next round
+            if self.ws_hub.send_to(&mv.driver_id, Message::Text(text.into())).await {
+                self.suggested.lock().unwrap().insert(mv.driver_id, now);
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    // available drivers and the cell they were last seen in
+    async fn idle_drivers(&self) -> Result<Vec<(Uuid, HexCell)>, Error> {
+        let drivers = self.driver_state.located_drivers().await?;
+        if drivers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let states = self.driver_state.states(&drivers).await?;
+
+        Ok(drivers
+            .into_iter()
+            .zip(states)
+            .filter_map(|(driver_id, state)| {
+                let cell = state.get(DRIVER_CELL_FIELD)?.parse::<HexCell>().ok()?;
+                (state.get(DRIVER_AVAILABILITY_FIELD).map(String::as_str) == Some("1"))
+                    .then_some((driver_id, cell))
+            })
+            .collect())
+    }
+
+    /// Spawn the recommender on a fixed interval.
+    pub fn spawn(self: Arc<Self>, every: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                match self.recommend().await {
+                    Ok(0) => {}
+                    Ok(sent) => eprintln!("Sent {} repositioning suggestions", sent),
+                    Err(e) => eprintln!("Repositioning round failed: {:?}", e),
+                }
+            }
+        })
+    }
+}
@@ -2,18 +2,24 @@ use std::sync::Arc;
 
 use crate::events::publisher::EventPublisher;
 use crate::events::schemas::DriverAssignedRideDto;
-use crate::infra::repository::driver_status_repository::DriverStatusRepository;
-use crate::models::AvailabilityReason;
-use crate::models::RideStatus;
-use crate::service::eta_service::EtaCalculator;
+use crate::infra::ws::hub::WsHub;
+use crate::infra::repository::driver_status_repository::{
+    DriverStatusRepository, StatusTransitionError,
+};
+use crate::models::{AvailabilityReason, BreadcrumbKind};
+use crate::service::eligibility::EligibilityService;
 use crate::service::eta_service::EtaService;
-use anyhow::anyhow;
+use crate::service::location_update::DRIVER_STATE_TTL_SECS;
+use crate::service::ride_trace::RideTraceService;
 use anyhow::Error;
 use async_trait::async_trait;
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
 use common::events_schema::DriverAcceptedRideEvent;
 use common::events_schema::DriverRejectedRideEvent;
-use common::redis_key_helpers::driver_state_namespace;
+use common::events_schema::{RideCompletedEvent, RidePickedUpEvent};
 use common::redis_namespaces::DRIVER_IN_RIDE_FIELD;
+use common::redis_namespaces::{DRIVER_PICKUP_LAT_FIELD, DRIVER_PICKUP_LNG_FIELD};
 use common::redis_namespaces::DRIVER_RIDE_ID_FIELD;
 use common::redis_namespaces::{
     DRIVER_AVAILABILITY_FIELD, DRIVER_AVAILABILITY_REASON_FIELD,
@@ -21,12 +27,14 @@ use common::redis_namespaces::{
 };
 use common::subjects::DRIVER_ACCEPTED_RIDE_SUBJECT;
 use common::subjects::DRIVER_REJECTED_RIDE_SUBJECT;
+use common::subjects::{RIDE_COMPLETED_SUBJECT, RIDE_PICKED_UP_SUBJECT};
+use common::ws_schema::{Coord, Envelope, RideOffer, WSMsgType};
 use uuid::Uuid;
 
 #[async_trait]
 pub trait RideLifeCycle: Send + Sync {
-    async fn start_ride(&self) -> Result<(), Error>;
-    async fn complete_ride(&self) -> Result<(), Error>;
+    async fn start_ride(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), Error>;
+    async fn complete_ride(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), Error>;
     async fn handle_driver_assigned(&self, event: DriverAssignedRideDto) -> Result<(), Error>;
     async fn handle_driver_accept_ride_assignment(
         &self,
@@ -42,59 +50,187 @@ pub trait RideLifeCycle: Send + Sync {
 }
 pub struct RideLifeCycleService {
     pub driver_status_repo: Arc<dyn DriverStatusRepository + Send + Sync>,
-    pub(crate) producer: Arc<EventPublisher>,
-    pub redis_con: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
+    pub producer: Arc<EventPublisher>,
+    pub driver_state: Arc<dyn DriverStateStore>,
+    pub eligibility_service: Arc<EligibilityService>,
+    pub eta_service: Arc<EtaService>,
+    pub ws_hub: Arc<WsHub>,
+    pub ride_trace_service: Arc<RideTraceService>,
+    pub clock: SharedClock,
 }
 
+impl RideLifeCycleService {
+    // the driver is done with the ride or turned it down. They can be matched again unless
+    // they went offline in the meantime, postgres keeps that choice through the ride.
+    async fn release_driver(&self, driver_id: Uuid) -> Result<(), Error> {
+        let available = self
+            .driver_status_repo
+            .get_status(driver_id)
+            .await?
+            .is_some_and(|status| status.driver_available);
+        let reason = if available {
+            AvailabilityReason::Available
+        } else {
+            AvailabilityReason::OfflineToggle
+        };
+
+        self.driver_state
+            .update_state(
+                driver_id,
+                &[
+                    (DRIVER_AVAILABILITY_FIELD, if available { "1" } else { "0" }.to_string()),
+                    (DRIVER_AVAILABILITY_REASON_FIELD, reason.to_string()),
+                    (DRIVER_IN_RIDE_FIELD, "0".to_string()),
+                    (DRIVER_RIDE_ID_FIELD, "".to_string()),
+                    (
+                        DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
+                        self.clock.now().timestamp().to_string(),
+                    ),
+                ],
+                &[DRIVER_PICKUP_LAT_FIELD, DRIVER_PICKUP_LNG_FIELD],
+                DRIVER_STATE_TTL_SECS,
+            )
+            .await
+    }
+}
+
+// shown to the driver, nothing withdraws an unanswered offer server side yet
+const RIDE_OFFER_EXPIRES_IN_SECS: u16 = 15;
+
 #[async_trait]
 impl RideLifeCycle for RideLifeCycleService {
-    async fn start_ride(&self) -> Result<(), Error> {
-        // Logic to start a ride
+    async fn start_ride(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), Error> {
+        // the passenger is on board, only once and only for the accepted ride
+        self.driver_status_repo.pickup(driver_id, ride_id).await?;
+
+        // the pickup point is no longer needed for ETAs
+        self.driver_state
+            .update_state(
+                driver_id,
+                &[],
+                &[DRIVER_PICKUP_LAT_FIELD, DRIVER_PICKUP_LNG_FIELD],
+                DRIVER_STATE_TTL_SECS,
+            )
+            .await?;
+
+        // the trace is a nice to have, not worth failing the pickup over
+        if let Err(e) = self.ride_trace_service.mark(driver_id, ride_id, BreadcrumbKind::Pickup).await {
+            eprintln!("Failed to mark the pickup of ride {} in its trace: {:?}", ride_id, e);
+        }
+
+        let event = RidePickedUpEvent {
+            ride_id,
+            driver_id,
+            picked_up_at: self.clock.now(),
+        };
+        self.producer
+            .publish(RIDE_PICKED_UP_SUBJECT, serde_json::to_vec(&event)?)
+            .await?;
+
         Ok(())
     }
 
-    async fn complete_ride(&self) -> Result<(), Error> {
-        // Logic to complete a ride
+    async fn complete_ride(&self, driver_id: Uuid, ride_id: Uuid) -> Result<(), Error> {
+        // done, the matcher can assign the next ride right away unless the driver went offline
+        self.driver_status_repo.complete(driver_id, ride_id).await?;
+
+        self.release_driver(driver_id).await?;
+
+        if let Err(e) = self.ride_trace_service.mark(driver_id, ride_id, BreadcrumbKind::Dropoff).await {
+            eprintln!("Failed to mark the dropoff of ride {} in its trace: {:?}", ride_id, e);
+        }
+
+        let event = RideCompletedEvent {
+            ride_id,
+            driver_id,
+            completed_at: self.clock.now(),
+        };
+        self.producer
+            .publish(RIDE_COMPLETED_SUBJECT, serde_json::to_vec(&event)?)
+            .await?;
+
         Ok(())
     }
 
     async fn handle_driver_assigned(&self, event: DriverAssignedRideDto) -> Result<(), Error> {
         // Logic to handle driver assigned event
-        println!("{:?}", event);
+        eprintln!("Driver {} assigned to ride {}", event.driver_id, event.ride_id);
 
         // update driver status to assigned in the driver status repository and redis so matcher don't match this driver for other rides
 
-        self.driver_status_repo
-            .patch_status(
+        // only an idle, available driver can be assigned, a conflict means the matcher raced another
+        // assignment or the driver going offline. The ride still needs a driver, so it's turned
+        // down on the driver's behalf like a reject would, nothing changes for the driver though.
+        if let Err(e) = self
+            .driver_status_repo
+            .assign(event.driver_id, event.ride_id)
+            .await
+        {
+            if matches!(e, StatusTransitionError::Conflict { .. }) {
+                let reject_event = DriverRejectedRideEvent {
+                    driver_id: event.driver_id,
+                    ride_id: event.ride_id,
+                };
+                self.producer
+                    .publish(DRIVER_REJECTED_RIDE_SUBJECT, serde_json::to_vec(&reject_event)?)
+                    .await?;
+            }
+            return Err(e.into());
+        }
+
+        self.driver_state
+            .update_state(
                 event.driver_id,
-                Some(false),
-                Some(RideStatus::Assigned),
-                Some(event.ride_id),
+                &[
+                    (DRIVER_AVAILABILITY_FIELD, "0".to_string()),
+                    (
+                        DRIVER_AVAILABILITY_REASON_FIELD,
+                        AvailabilityReason::RideAssigned.to_string(),
+                    ),
+                    (DRIVER_IN_RIDE_FIELD, "0".to_string()),
+                    (DRIVER_RIDE_ID_FIELD, event.ride_id.to_string()),
+                    // kept for the pickup ETA once the driver accepts
+                    (DRIVER_PICKUP_LAT_FIELD, event.pickup_lat.to_string()),
+                    (DRIVER_PICKUP_LNG_FIELD, event.pickup_lng.to_string()),
+                    (
+                        DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
+                        self.clock.now().timestamp().to_string(),
+                    ),
+                ],
+                &[],
+                DRIVER_STATE_TTL_SECS,
             )
             .await?;
 
-        let key = driver_state_namespace(event.driver_id);
-
-        let mut con = self.redis_con.lock().await;
-        let mut pipe = redis::pipe();
-        pipe.atomic()
-            .hset(&key, DRIVER_AVAILABILITY_FIELD, false)
-            .hset(
-                &key,
-                DRIVER_AVAILABILITY_REASON_FIELD,
-                AvailabilityReason::RideAssigned.to_string(),
-            )
-            .hset(
-                &key,
-                DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
-                chrono::Utc::now().timestamp(),
+        // push the offer to the driver app, it answers via the accept/reject endpoints
+        let offer = Envelope::new(
+            WSMsgType::RideOffer,
+            2,
+            self.clock.now().timestamp_millis(),
+            RideOffer {
+                ride_id: event.ride_id,
+                expires_in_sec: RIDE_OFFER_EXPIRES_IN_SECS,
+                pickup: Coord {
+                    lat: event.pickup_lat,
+                    lng: event.pickup_lng,
+                },
+                dropoff: Coord {
+                    lat: event.dropoff_lat,
+                    lng: event.dropoff_lng,
+                },
+                surge: event.surge_multiplier.map(|m| m as f32),
+            },
+        );
+        let sent = self
+            .ws_hub
+            .send_to(
+                &event.driver_id,
+                axum::extract::ws::Message::Text(serde_json::to_string(&offer)?.into()),
             )
-            .expire(&key, 90);
-        pipe.query_async::<()>(&mut *con)
-            .await
-            .map_err(|_| anyhow!("Failed to update driver status in Redis"))?;
-
-        // need to push notifications to the client/simulator to accept/reject ride
+            .await;
+        if !sent {
+            eprintln!("Driver {} is not connected, ride offer {} not pushed", event.driver_id, event.ride_id);
+        }
 
         // reactions to driver accept/reject will be handled in separate methods
         Ok(())
@@ -106,47 +242,60 @@ impl RideLifeCycle for RideLifeCycleService {
         ride_id: Uuid,
     ) -> Result<(), Error> {
 
-        // - Update driver status repo and redis
-        self.driver_status_repo
-            .patch_status(
+        // - Update driver status repo and redis, only if the driver is still assigned to this ride
+        self.driver_status_repo.accept(driver_id, ride_id).await?;
+
+        // feeds the cancellation rate, not worth failing the accept over
+        if let Err(e) = self
+            .eligibility_service
+            .record_ride_response(driver_id, ride_id, true)
+            .await
+        {
+            eprintln!("Failed to record accept of ride {} by driver {}: {:?}", ride_id, driver_id, e);
+        }
+
+        self.driver_state
+            .update_state(
                 driver_id,
-                Some(false),
-                Some(RideStatus::InRide),
-                Some(ride_id),
+                &[
+                    (DRIVER_AVAILABILITY_FIELD, "0".to_string()),
+                    (
+                        DRIVER_AVAILABILITY_REASON_FIELD,
+                        AvailabilityReason::InRide.to_string(),
+                    ),
+                    (DRIVER_IN_RIDE_FIELD, "1".to_string()),
+                    (DRIVER_RIDE_ID_FIELD, ride_id.to_string()),
+                    (
+                        DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
+                        self.clock.now().timestamp().to_string(),
+                    ),
+                ],
+                &[],
+                DRIVER_STATE_TTL_SECS,
             )
             .await?;
 
-        let key = driver_state_namespace(driver_id);
-
-        let mut con = self.redis_con.lock().await;
-        let mut pipe = redis::pipe();
-        pipe.atomic()
-            .hset(&key, DRIVER_AVAILABILITY_FIELD, false)
-            .hset(
-                &key,
-                DRIVER_AVAILABILITY_REASON_FIELD,
-                AvailabilityReason::InRide.to_string(),
-            )
-            .hset(&key, DRIVER_IN_RIDE_FIELD, true)
-            .hset(&key, DRIVER_RIDE_ID_FIELD, ride_id.to_string())
-            .hset(
-                &key,
-                DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
-                chrono::Utc::now().timestamp(),
-            )
-            .expire(&key, 90);
-        pipe.query_async::<()>(&mut *con)
-            .await
-            .map_err(|_| anyhow!("Failed to update driver status in Redis"))?;
 
 
+        // 0 means unknown, the driver position or the pickup point went missing from redis
+        let estimated_pickup_time_minutes = match self.eta_service.pickup_eta(driver_id).await {
+            Ok(Some(eta)) => eta.minutes(),
+            Ok(None) => {
+                eprintln!("No pickup ETA for driver {} on ride {}", driver_id, ride_id);
+                0
+            }
+            Err(e) => {
+                eprintln!("Failed to compute pickup ETA for driver {}: {:?}", driver_id, e);
+                0
+            }
+        };
 
         // - Notify matcher to look for another driver
         let accepted_event = DriverAcceptedRideEvent {
             driver_id,
             ride_id,
-            accepted_at: chrono::Utc::now(),
-            estimated_pickup_time_minutes: EtaService{}.calculate_eta_minutes(),
+            accepted_at: self.clock.now(),
+            estimated_pickup_time_minutes,
         };
 
         let payload = serde_json::to_vec(&accepted_event)?;
@@ -164,33 +313,19 @@ impl RideLifeCycle for RideLifeCycleService {
         driver_id: Uuid,
         ride_id: Uuid,
     ) -> Result<(), Error> {
-        // - Update driver status repo and redis
-        self.driver_status_repo
-            .patch_status(driver_id, Some(true), Some(RideStatus::None), None)
-            .await?;
-
-        let key = driver_state_namespace(driver_id);
+        // - Update driver status repo and redis, a stale reject for another ride must not free the driver
+        self.driver_status_repo.reject(driver_id, ride_id).await?;
 
-        let mut con = self.redis_con.lock().await;
-        let mut pipe = redis::pipe();
-        pipe.atomic()
-            .hset(&key, DRIVER_AVAILABILITY_FIELD, true)
-            .hset(
-                &key,
-                DRIVER_AVAILABILITY_REASON_FIELD,
-                AvailabilityReason::Available.to_string(),
-            )
-            .hset(&key, DRIVER_IN_RIDE_FIELD, false)
-            .hset(&key, DRIVER_RIDE_ID_FIELD, "".to_string())
-            .hset(
-                &key,
-                DRIVER_LAST_AVAILABILITY_UPDATE_FIELD,
-                chrono::Utc::now().timestamp(),
-            )
-            .expire(&key, 90);
-        pipe.query_async::<()>(&mut *con)
+        // may put the driver on cooldown, the matcher then skips them until it runs out
+        if let Err(e) = self
+            .eligibility_service
+            .record_ride_response(driver_id, ride_id, false)
             .await
-            .map_err(|_| anyhow!("Failed to update driver status in Redis"))?;
+        {
+            eprintln!("Failed to record reject of ride {} by driver {}: {:?}", ride_id, driver_id, e);
+        }
+
+        self.release_driver(driver_id).await?;
 
         // - Notify matcher to look for another driver
 
@@ -0,0 +1,219 @@
+// Trip traces: what the driver actually drove on a ride, from accepting it to the dropoff.
+//
+// Positions reported while the driver has an accepted ride (REST or websocket, both go through
+// LocationUpdateService) are stored as breadcrumbs of that ride. They are queued with the
+// location history and written in its batches, so a location update never waits on Postgres
+// for them either. The pickup and the dropoff are markers at the driver's last known position
+// when they were reported. A trace is the breadcrumbs in order, exported as GeoJSON or GPX.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use anyhow::Error;
+use chrono::{DateTime, SecondsFormat, Utc};
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
+use common::geo::{haversine_m, LatLng};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::infra::repository::ride_trace_repository::RideTraceRepository;
+use crate::models::{BreadcrumbKind, RideBreadcrumb};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFormat {
+    #[default]
+    Geojson,
+    Gpx,
+}
+
+pub struct RideTraceService {
+    pub ride_trace_repo: Arc<dyn RideTraceRepository + Send + Sync>,
+    pub driver_state: Arc<dyn DriverStateStore>,
+    pub clock: SharedClock,
+}
+
+impl RideTraceService {
+    /// Marks the pickup or the dropoff at the driver's last known position, nothing is marked
+    /// if the driver has no position.
+    pub async fn mark(&self, driver_id: Uuid, ride_id: Uuid, kind: BreadcrumbKind) -> Result<(), Error> {
+        let positions = self.driver_state.positions(&[driver_id]).await?;
+        let Some(Some(position)) = positions.into_iter().next() else {
+            return Ok(());
+        };
+        self.ride_trace_repo
+            .append(&RideBreadcrumb {
+                ride_id,
+                driver_id,
+                kind,
+                latitude: position.lat,
+                longitude: position.lng,
+                recorded_at: self.clock.now(),
+            })
+            .await
+    }
+
+    /// None if nothing was recorded for the ride.
+    pub async fn trace(&self, ride_id: Uuid) -> Result<Option<RideTrace>, Error> {
+        let breadcrumbs = self.ride_trace_repo.breadcrumbs(ride_id).await?;
+        Ok(RideTrace::new(ride_id, breadcrumbs))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RideTrace {
+    pub ride_id: Uuid,
+    pub driver_id: Uuid,
+    /// every breadcrumb in order, markers included
+    pub points: Vec<RideBreadcrumb>,
+    pub to_pickup_m: f64,
+    pub with_rider_m: f64,
+}
+
+impl RideTrace {
+    fn new(ride_id: Uuid, points: Vec<RideBreadcrumb>) -> Option<Self> {
+        let driver_id = points.first()?.driver_id;
+        // everything before the pickup marker is the way to the passenger
+        let mut picked_up = false;
+        let (mut to_pickup_m, mut with_rider_m) = (0.0, 0.0);
+        for pair in points.windows(2) {
+            let meters = haversine_m(pair[0].point(), pair[1].point());
+            picked_up |= pair[0].kind == BreadcrumbKind::Pickup;
+            if picked_up {
+                with_rider_m += meters;
+            } else {
+                to_pickup_m += meters;
+            }
+        }
+        Some(Self {
+            ride_id,
+            driver_id,
+            points,
+            to_pickup_m,
+            with_rider_m,
+        })
+    }
+
+    pub fn distance_m(&self) -> f64 {
+        self.to_pickup_m + self.with_rider_m
+    }
+
+    fn marker(&self, kind: BreadcrumbKind) -> Option<&RideBreadcrumb> {
+        self.points.iter().find(|p| p.kind == kind)
+    }
+
+    fn started_at(&self) -> DateTime<Utc> {
+        self.points.first().map(|p| p.recorded_at).unwrap_or_default()
+    }
+
+    fn ended_at(&self) -> DateTime<Utc> {
+        self.points.last().map(|p| p.recorded_at).unwrap_or_default()
+    }
+
+    /// A FeatureCollection with the path as a LineString (timestamps per position in
+    /// `coordTimes`) and the pickup and dropoff as Points.
+    pub fn to_geojson(&self) -> Value {
+        let mut coordinates: Vec<Value> = self
+            .points
+            .iter()
+            .map(|p| json!([p.longitude, p.latitude]))
+            .collect();
+        let mut times: Vec<String> = self.points.iter().map(|p| timestamp(p.recorded_at)).collect();
+        // a LineString needs two positions
+        if coordinates.len() == 1 {
+            coordinates.push(coordinates[0].clone());
+            times.push(times[0].clone());
+        }
+
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": coordinates },
+            "properties": {
+                "ride_id": self.ride_id,
+                "driver_id": self.driver_id,
+                "started_at": timestamp(self.started_at()),
+                "ended_at": timestamp(self.ended_at()),
+                "distance_m": self.distance_m().round(),
+                "to_pickup_m": self.to_pickup_m.round(),
+                "with_rider_m": self.with_rider_m.round(),
+                "coordTimes": times,
+            },
+        })];
+        for kind in [BreadcrumbKind::Pickup, BreadcrumbKind::Dropoff] {
+            if let Some(marker) = self.marker(kind) {
+                features.push(json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [marker.longitude, marker.latitude] },
+                    "properties": { "marker": kind.to_string(), "at": timestamp(marker.recorded_at) },
+                }));
+            }
+        }
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+
+    /// GPX 1.1: the pickup and dropoff as waypoints, the path as a track with the distances in
+    /// its description.
+    pub fn to_gpx(&self) -> String {
+        let mut gpx = String::new();
+        // writing into a String can't fail
+        let _ = writeln!(gpx, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            gpx,
+            r#"<gpx version="1.1" creator="ubersimx driver service" xmlns="http://www.topografix.com/GPX/1/1">"#
+        );
+        let _ = writeln!(
+            gpx,
+            "  <metadata><name>ride {}</name><time>{}</time></metadata>",
+            self.ride_id,
+            timestamp(self.started_at())
+        );
+        for kind in [BreadcrumbKind::Pickup, BreadcrumbKind::Dropoff] {
+            if let Some(marker) = self.marker(kind) {
+                let _ = writeln!(
+                    gpx,
+                    r#"  <wpt lat="{}" lon="{}"><time>{}</time><name>{}</name></wpt>"#,
+                    marker.latitude,
+                    marker.longitude,
+                    timestamp(marker.recorded_at),
+                    kind
+                );
+            }
+        }
+        let _ = writeln!(gpx, "  <trk>");
+        let _ = writeln!(gpx, "    <name>ride {}</name>", self.ride_id);
+        let _ = writeln!(
+            gpx,
+            "    <desc>driver {}, {:.0} m: {:.0} m to the pickup, {:.0} m with the rider</desc>",
+            self.driver_id,
+            self.distance_m(),
+            self.to_pickup_m,
+            self.with_rider_m
+        );
+        let _ = writeln!(gpx, "    <trkseg>");
+        for point in &self.points {
+            let _ = writeln!(
+                gpx,
+                r#"      <trkpt lat="{}" lon="{}"><time>{}</time></trkpt>"#,
+                point.latitude,
+                point.longitude,
+                timestamp(point.recorded_at)
+            );
+        }
+        let _ = writeln!(gpx, "    </trkseg>");
+        let _ = writeln!(gpx, "  </trk>");
+        let _ = writeln!(gpx, "</gpx>");
+        gpx
+    }
+}
+
+impl RideBreadcrumb {
+    fn point(&self) -> LatLng {
+        LatLng::new(self.latitude, self.longitude)
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
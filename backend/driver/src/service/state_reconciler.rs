@@ -0,0 +1,201 @@
+// Postgres is the source of truth for driver status (see README), redis only mirrors it for the matcher.
+// The two are written one after the other without a transaction spanning both, so a failure in between
+// (e.g. postgres updated, redis write failed in handle_driver_accept_ride_assignment) leaves them drifted.
+// The reconciler compares both stores and rewrites the redis mirror from postgres.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Error;
+use common::redis_key_helpers::driver_state_namespace;
+use common::redis_namespaces::{
+    DRIVER_AVAILABILITY_FIELD, DRIVER_AVAILABILITY_REASON_FIELD, DRIVER_IN_RIDE_FIELD,
+    DRIVER_LAST_AVAILABILITY_UPDATE_FIELD, DRIVER_RIDE_ID_FIELD,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::infra::repository::driver_status_repository::DriverStatusRepository;
+use crate::models::{AvailabilityReason, DriverStatus, RideStatus};
+use crate::service::location_update::DRIVER_STATE_TTL_SECS;
+
+// keeps a single pipeline reasonably sized
+const RECONCILE_BATCH_SIZE: usize = 500;
+
+// Writes the postgres fields into the state hash, unless redis moved on in the meantime.
+// A missing hash is left missing (the driver went offline or expired), and a hash whose
+// last availability update is newer than the postgres row was written by a lifecycle
+// step that happened after our read, so it's the fresher of the two.
+// KEYS[1] is the state hash; ARGV[1..3] are the postgres status_updated_at (unix seconds),
+// the ttl and the last availability update field name, the rest are field/value pairs.
+// Returns 1 if the hash was repaired, 0 if it was left alone.
+const REPAIR_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+local last_update = tonumber(redis.call('HGET', KEYS[1], ARGV[3]))
+if last_update ~= nil and last_update > tonumber(ARGV[1]) then
+    return 0
+end
+for i = 4, #ARGV, 2 do
+    redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 1])
+end
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 1
+";
+
+#[derive(Debug, Serialize)]
+pub struct FieldDrift {
+    pub field: &'static str,
+    pub redis: Option<String>,
+    pub postgres: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DriverDrift {
+    pub driver_id: Uuid,
+    pub fields: Vec<FieldDrift>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DriftReport {
+    /// drivers with a status row in postgres
+    pub checked: usize,
+    /// drivers without a redis state hash, i.e. offline or expired. Nothing to repair there.
+    pub missing_in_redis: usize,
+    /// drivers whose redis state was rewritten from postgres
+    pub repaired: Vec<DriverDrift>,
+    /// drifted drivers left alone because redis changed after the postgres read
+    pub skipped: usize,
+}
+
+/// Redis fields and values the state hash should hold for a given postgres status.
+/// Booleans are written as "1"/"0", same as redis-rs does for the bool hsets elsewhere.
+fn expected_redis_fields(status: &DriverStatus) -> Vec<(&'static str, String)> {
+    let in_ride = matches!(status.ride_status, RideStatus::InRide);
+    // driver_available is the driver's own choice, a driver with a ride isn't matchable either way
+    let busy = matches!(status.ride_status, RideStatus::InRide | RideStatus::Assigned);
+    let available = status.driver_available && !busy;
+    let reason = match status.ride_status {
+        RideStatus::InRide => AvailabilityReason::InRide,
+        RideStatus::Assigned => AvailabilityReason::RideAssigned,
+        _ if !status.driver_available => AvailabilityReason::OfflineToggle,
+        _ => AvailabilityReason::Available,
+    };
+
+    vec![
+        (
+            DRIVER_AVAILABILITY_FIELD,
+            if available { "1" } else { "0" }.to_string(),
+        ),
+        (DRIVER_AVAILABILITY_REASON_FIELD, reason.to_string()),
+        (DRIVER_IN_RIDE_FIELD, if in_ride { "1" } else { "0" }.to_string()),
+        (
+            DRIVER_RIDE_ID_FIELD,
+            status
+                .current_trip_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        ),
+    ]
+}
+
+pub struct StateReconcilerService {
+    pub driver_status_repo: Arc<dyn DriverStatusRepository + Send + Sync>,
+    pub redis_con: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
+}
+
+impl StateReconcilerService {
+    /// Reconcile every driver that has a status row.
+    pub async fn reconcile_all(&self) -> Result<DriftReport, Error> {
+        let driver_ids: Vec<Uuid> = self
+            .driver_status_repo
+            .list_statuses()
+            .await?
+            .into_iter()
+            .map(|status| status.driver_id)
+            .collect();
+
+        let mut report = DriftReport::default();
+        for batch in driver_ids.chunks(RECONCILE_BATCH_SIZE) {
+            // re-read right before comparing, a full pass can take a while and a status read
+            // at the start would be stale by the time the later batches get to redis
+            let statuses = self.driver_status_repo.get_statuses(batch).await?;
+            self.reconcile_batch(&statuses, &mut report).await?;
+        }
+        Ok(report)
+    }
+
+    /// Reconcile a single driver, returns None if the driver has no status row.
+    pub async fn reconcile_driver(&self, driver_id: Uuid) -> Result<Option<DriftReport>, Error> {
+        let Some(status) = self.driver_status_repo.get_status(driver_id).await? else {
+            return Ok(None);
+        };
+
+        let mut report = DriftReport::default();
+        self.reconcile_batch(std::slice::from_ref(&status), &mut report)
+            .await?;
+        Ok(Some(report))
+    }
+
+    async fn reconcile_batch(
+        &self,
+        statuses: &[DriverStatus],
+        report: &mut DriftReport,
+    ) -> Result<(), Error> {
+        let mut con = self.redis_con.lock().await;
+
+        let mut pipe = redis::pipe();
+        for status in statuses {
+            pipe.hgetall(driver_state_namespace(status.driver_id));
+        }
+        let states: Vec<HashMap<String, String>> = pipe.query_async(&mut *con).await?;
+
+        let script = redis::Script::new(REPAIR_SCRIPT);
+
+        for (status, state) in statuses.iter().zip(states) {
+            report.checked += 1;
+
+            // no hash means the driver is not live, recreating it here would make an
+            // offline driver look online to the matcher
+            if state.is_empty() {
+                report.missing_in_redis += 1;
+                continue;
+            }
+
+            let mut fields = Vec::new();
+            let mut invocation = script.key(driver_state_namespace(status.driver_id));
+            invocation
+                .arg(status.status_updated_at.timestamp())
+                .arg(DRIVER_STATE_TTL_SECS)
+                .arg(DRIVER_LAST_AVAILABILITY_UPDATE_FIELD);
+            for (field, expected) in expected_redis_fields(status) {
+                let actual = state.get(field);
+                if actual != Some(&expected) {
+                    invocation.arg(field).arg(&expected);
+                    fields.push(FieldDrift {
+                        field,
+                        redis: actual.cloned(),
+                        postgres: expected,
+                    });
+                }
+            }
+            if fields.is_empty() {
+                continue;
+            }
+
+            // the read above only finds the drift, the script re-checks before writing
+            let repaired: i32 = invocation.invoke_async(&mut *con).await?;
+            if repaired == 1 {
+                report.repaired.push(DriverDrift {
+                    driver_id: status.driver_id,
+                    fields,
+                });
+            } else {
+                report.skipped += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
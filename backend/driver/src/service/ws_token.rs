@@ -0,0 +1,206 @@
+// Signed tokens that bind a websocket session to a driver identity.
+// Format: base64url("{driver_id}:{expires_at}") + "." + base64url(hmac_sha256(payload))
+// Nothing fancy like JWT on purpose, the driver service is both the issuer and the only verifier.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use common::clock::SharedClock;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsTokenError {
+    Malformed,
+    BadSignature,
+    Expired,
+}
+
+impl std::fmt::Display for WsTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WsTokenError::Malformed => f.write_str("malformed token"),
+            WsTokenError::BadSignature => f.write_str("invalid token signature"),
+            WsTokenError::Expired => f.write_str("token expired"),
+        }
+    }
+}
+
+impl std::error::Error for WsTokenError {}
+
+/// A token handed to a driver client, `expires_at` is unix seconds.
+pub struct IssuedToken {
+    pub token: String,
+    pub expires_at: i64,
+}
+
+/// Issues and verifies websocket tokens for drivers.
+pub struct WsTokenService {
+    secret: Vec<u8>,
+    ttl_secs: i64,
+    clock: SharedClock,
+}
+
+impl WsTokenService {
+    pub fn new(secret: impl Into<Vec<u8>>, ttl_secs: i64, clock: SharedClock) -> Self {
+        Self {
+            secret: secret.into(),
+            ttl_secs,
+            clock,
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        // hmac accepts keys of any length so this can't fail
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length")
+    }
+
+    /// Issue a token for the given driver valid for the configured ttl.
+    pub fn issue(&self, driver_id: Uuid) -> IssuedToken {
+        let expires_at = self.clock.now().timestamp() + self.ttl_secs;
+        let payload = format!("{}:{}", driver_id, expires_at);
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = mac.finalize().into_bytes();
+
+        IssuedToken {
+            token: format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(payload.as_bytes()),
+                URL_SAFE_NO_PAD.encode(signature)
+            ),
+            expires_at,
+        }
+    }
+
+    /// Verify a token and return the driver id it was issued for.
+    pub fn verify(&self, token: &str) -> Result<Uuid, WsTokenError> {
+        let (payload_b64, signature_b64) =
+            token.split_once('.').ok_or(WsTokenError::Malformed)?;
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload_b64)
+            .map_err(|_| WsTokenError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature_b64)
+            .map_err(|_| WsTokenError::Malformed)?;
+
+        // verify_slice does a constant time comparison
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature)
+            .map_err(|_| WsTokenError::BadSignature)?;
+
+        let payload = String::from_utf8(payload).map_err(|_| WsTokenError::Malformed)?;
+        let (driver_id, expires_at) = payload.split_once(':').ok_or(WsTokenError::Malformed)?;
+        let driver_id = Uuid::parse_str(driver_id).map_err(|_| WsTokenError::Malformed)?;
+        let expires_at: i64 = expires_at.parse().map_err(|_| WsTokenError::Malformed)?;
+
+        if expires_at < self.clock.now().timestamp() {
+            return Err(WsTokenError::Expired);
+        }
+
+        Ok(driver_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+    use common::clock::{Clock, VirtualClock};
+    use std::sync::Arc;
+
+    fn token_service(secret: &str) -> (WsTokenService, Arc<VirtualClock>) {
+        let start: DateTime<Utc> = "2025-03-03T08:00:00Z".parse().unwrap();
+        let clock = Arc::new(VirtualClock::new(start));
+        (WsTokenService::new(secret, 300, clock.clone()), clock)
+    }
+
+    #[test]
+    fn verify_returns_the_driver_the_token_was_issued_to() {
+        let (service, _) = token_service("secret");
+        let driver_id = Uuid::new_v4();
+
+        let issued = service.issue(driver_id);
+
+        assert_eq!(service.verify(&issued.token), Ok(driver_id));
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let (service, _) = token_service("secret");
+        let issued = service.issue(Uuid::new_v4());
+        let (payload, signature) = issued.token.split_once('.').unwrap();
+
+        let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        signature[0] ^= 1;
+        let tampered = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature));
+
+        assert_eq!(service.verify(&tampered), Err(WsTokenError::BadSignature));
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let (service, _) = token_service("secret");
+        let (other, _) = token_service("another secret");
+        let issued = other.issue(Uuid::new_v4());
+
+        assert_eq!(service.verify(&issued.token), Err(WsTokenError::BadSignature));
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let (service, clock) = token_service("secret");
+        let issued = service.issue(Uuid::new_v4());
+
+        clock.advance_to(clock.now() + Duration::seconds(299));
+        assert!(service.verify(&issued.token).is_ok());
+
+        clock.advance_to(clock.now() + Duration::seconds(2));
+        assert_eq!(service.verify(&issued.token), Err(WsTokenError::Expired));
+    }
+
+    #[test]
+    fn token_for_another_driver_does_not_verify_as_this_driver() {
+        let (service, _) = token_service("secret");
+        let driver_id = Uuid::new_v4();
+        let other_driver_id = Uuid::new_v4();
+        let issued = service.issue(other_driver_id);
+
+        // swapping the driver id in the payload breaks the signature
+        let (_, signature) = issued.token.split_once('.').unwrap();
+        let payload = format!("{}:{}", driver_id, issued.expires_at);
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), signature);
+
+        assert_ne!(service.verify(&issued.token), Ok(driver_id));
+        assert_eq!(service.verify(&forged), Err(WsTokenError::BadSignature));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let (service, _) = token_service("secret");
+
+        assert_eq!(service.verify("no-dot"), Err(WsTokenError::Malformed));
+        assert_eq!(service.verify("not base64!.c2ln"), Err(WsTokenError::Malformed));
+        assert_eq!(service.verify("cGF5bG9hZA.not base64!"), Err(WsTokenError::Malformed));
+    }
+
+    #[test]
+    fn signed_payload_without_a_driver_id_is_malformed() {
+        let (service, _) = token_service("secret");
+        let payload = "not-a-uuid:1741000000";
+        let mut mac = service.mac();
+        mac.update(payload.as_bytes());
+        let token = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        );
+
+        assert_eq!(service.verify(&token), Err(WsTokenError::Malformed));
+    }
+}
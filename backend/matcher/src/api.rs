@@ -1 +1,2 @@
-mod router;
+// admin/debug API
+pub mod router;
@@ -1 +1,78 @@
-// optional for now. Will be used for admin/debug
+// admin/debug endpoints of the matcher, for now the demand forecast
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{routing::get, Json, Router};
+use common::events_schema::ForecastInterval;
+use common::geo::hex::HexCell;
+use serde::{Deserialize, Serialize};
+
+use crate::matcher::forecast::DemandForecaster;
+
+pub struct AppState {
+    pub forecaster: Arc<DemandForecaster>,
+}
+
+#[derive(Deserialize)]
+struct ForecastParams {
+    // defaults to the horizon of the published forecasts
+    intervals: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CellForecast {
+    cell: HexCell,
+    interval_mins: i64,
+    intervals: Vec<ForecastInterval>,
+}
+
+#[derive(Serialize)]
+struct CityForecast {
+    interval_mins: i64,
+    // cell id -> intervals, cells expecting (next to) nothing are left out
+    cells: BTreeMap<String, Vec<ForecastInterval>>,
+}
+
+fn intervals(state: &AppState, params: &ForecastParams) -> usize {
+    params.intervals.unwrap_or(state.forecaster.policy().horizon)
+}
+
+async fn get_forecast(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ForecastParams>,
+) -> Json<CityForecast> {
+    let cells = state
+        .forecaster
+        .forecasts(intervals(&state, &params))
+        .into_iter()
+        .map(|(cell, intervals)| (cell.to_string(), intervals))
+        .collect();
+    Json(CityForecast {
+        interval_mins: state.forecaster.policy().interval.num_minutes(),
+        cells,
+    })
+}
+
+// cell ids as in the driver state hash, "7:-15634:5876"
+async fn get_cell_forecast(
+    State(state): State<Arc<AppState>>,
+    Path(cell): Path<String>,
+    Query(params): Query<ForecastParams>,
+) -> Result<Json<CellForecast>, StatusCode> {
+    let cell: HexCell = cell.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(Json(CellForecast {
+        cell,
+        interval_mins: state.forecaster.policy().interval.num_minutes(),
+        intervals: state.forecaster.forecast(cell, intervals(&state, &params)),
+    }))
+}
+
+pub fn create_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/api/v1/forecast", get(get_forecast))
+        .route("/api/v1/forecast/cells/{cell}", get(get_cell_forecast))
+        .with_state(state)
+}
@@ -1,4 +1,4 @@
-pub(crate) mod consumers;
-mod handler;
-pub(crate) mod producers;
+pub mod consumers;
+pub mod handler;
+pub mod producers;
 pub(crate) mod schema;
@@ -3,12 +3,18 @@
 
 use std::sync::Arc;
 
-use common::{events_schema::RideRequestedEvent, subjects::RIDE_REQUESTED_SUBJECT};
+use common::events_schema::{
+    RideRequestedEvent, TrafficIncidentClearedEvent, TrafficIncidentReportedEvent,
+};
+use common::subjects::{
+    RIDE_REQUESTED_SUBJECT, TRAFFIC_INCIDENT_CLEARED_SUBJECT, TRAFFIC_INCIDENT_REPORTED_SUBJECT,
+};
 use futures_util::StreamExt;
 use serde::de::DeserializeOwned;
 use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
 
-use crate::{events::handler::EventHandler, matcher::service::MatcherService};
+use crate::events::handler::EventHandler;
+use crate::matcher::{forecast::DemandForecaster, service::MatcherService};
 
 pub struct Consumers {
     messaging_client: Arc<MessagingClient>,
@@ -39,7 +45,9 @@ impl Consumers {
                 if let Ok(msg) = msg {
                     match serde_json::from_slice::<T>(&msg.data) {
                         Ok(evt) => handler.handle(evt).await,
-                        Err(_) => todo!(), //tracing::error!("Failed to parse event on {}: {:?}", subject, e),
+                        // a malformed event (a replay, another version of a service) is skipped,
+                        // not worth ending the subscription over
+                        Err(e) => eprintln!("Failed to parse event on {}: {:?}", msg.subject, e),
                     }
                 }
             }
@@ -50,6 +58,24 @@ impl Consumers {
     pub async fn register_all(&self, matcher: Arc<MatcherService>) {
         self.subscribe::<RideRequestedEvent, _>(RIDE_REQUESTED_SUBJECT, matcher.clone())
             .await;
+        // incidents change ETAs and so which driver gets the ride
+        self.subscribe::<TrafficIncidentReportedEvent, _>(
+            TRAFFIC_INCIDENT_REPORTED_SUBJECT,
+            matcher.clone(),
+        )
+        .await;
+        self.subscribe::<TrafficIncidentClearedEvent, _>(
+            TRAFFIC_INCIDENT_CLEARED_SUBJECT,
+            matcher.clone(),
+        )
+        .await;
+    }
+
+    /// The forecaster counts ride requests on a subscription of its own, so a slow match
+    /// doesn't hold up the counting
+    pub async fn register_forecaster(&self, forecaster: Arc<DemandForecaster>) {
+        self.subscribe::<RideRequestedEvent, _>(RIDE_REQUESTED_SUBJECT, forecaster)
+            .await;
     }
 }
 
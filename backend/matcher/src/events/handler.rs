@@ -1,8 +1,11 @@
 // gets called from consumer then delegates to matcher service
 // gets called from matcher then produces to producer
 
-use common::events_schema::RideRequestedEvent;
+use common::events_schema::{
+    RideRequestedEvent, TrafficIncidentClearedEvent, TrafficIncidentReportedEvent,
+};
 
+use crate::matcher::forecast::DemandForecaster;
 use crate::matcher::service::MatcherService;
 
 // previously this was a concrete struct with methods for each event type before traits
@@ -32,3 +35,28 @@ impl EventHandler<RideRequestedEvent> for MatcherService {
         }
     }
 }
+
+#[async_trait::async_trait]
+impl EventHandler<TrafficIncidentReportedEvent> for MatcherService {
+    async fn handle(&self, evt: TrafficIncidentReportedEvent) {
+        let incident_id = evt.incident.id;
+        let edges = self.apply_incident(evt.incident);
+        eprintln!("Traffic incident {} applied to {} edges", incident_id, edges);
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<TrafficIncidentClearedEvent> for MatcherService {
+    async fn handle(&self, evt: TrafficIncidentClearedEvent) {
+        if !self.clear_incident(evt.incident_id) {
+            eprintln!("Cleared unknown traffic incident {}", evt.incident_id);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<RideRequestedEvent> for DemandForecaster {
+    async fn handle(&self, evt: RideRequestedEvent) {
+        self.record_request(&evt);
+    }
+}
@@ -0,0 +1,5 @@
+// the matcher as a library, main wires it to NATS and Redis, the simulator's discrete mode runs
+// it in process
+pub mod api;
+pub mod events;
+pub mod matcher;
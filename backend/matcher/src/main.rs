@@ -1,10 +1,13 @@
-mod api;
-mod events;
-mod matcher;
-
 use std::{env, sync::Arc};
 
 use anyhow::Ok;
+use common::eta::graph::{GridSpec, RoadGraph};
+use common::eta::traffic::TrafficProfile;
+use common::driver_state::RedisDriverStateStore;
+use common::eta::EtaEngine;
+use common::service_area::ServiceArea;
+use matcher::matcher::{forecast, service, surge};
+use matcher::{api, events};
 use ubersimx_messaging::messagingclient::MessagingClient;
 
 #[tokio::main]
@@ -17,6 +20,9 @@ async fn main() -> Result<(), anyhow::Error> {
     let redis_url = env::var("REDIS_URL")
         .map_err(|e| anyhow::anyhow!("REDIS_URL must be set in .env: {}", e))?;
 
+    let server_address =
+        env::var("SERVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3002".to_string());
+
     // Connect to the messaging service
     // todo properly configure the URL via env var or config file and handle the error
     let messaging_client = Arc::new(MessagingClient::connect(&messaging_url).await.unwrap());
@@ -30,16 +36,73 @@ async fn main() -> Result<(), anyhow::Error> {
     // setup Redis connection for state management
     let client = redis::Client::open(redis_url)?;
     let con = client.get_multiplexed_async_connection().await?;
+    let driver_state = Arc::new(RedisDriverStateStore::new(con));
+
+    // system time unless CLOCK_START is set, simulations share a scaled clock with the simulator
+    let clock = common::clock::clock_from_env()?;
+
+    // road graph and traffic for scoring candidates by ETA, a generated grid and the default
+    // profile unless files are given
+    let road_graph = match env::var("ROAD_GRAPH_PATH") {
+        Result::Ok(path) => RoadGraph::load(&path)?,
+        Err(_) => RoadGraph::generate_grid(&GridSpec::default()),
+    };
+    let traffic_profile = match env::var("TRAFFIC_PROFILE_PATH") {
+        Result::Ok(path) => TrafficProfile::load(&path)?,
+        Err(_) => TrafficProfile::default(),
+    };
+    let eta_engine = Arc::new(EtaEngine::new(road_graph, traffic_profile));
+
+    // GeoJSON operating area and zones, for the airport queues
+    let service_area = Arc::new(match env::var("SERVICE_AREA_PATH") {
+        Result::Ok(path) => ServiceArea::load(&path)?,
+        Err(_) => ServiceArea::unrestricted(),
+    });
+
+    // surge multipliers per cell, recomputed in the background
+    let surge_engine = Arc::new(surge::SurgeEngine::new(
+        producer.clone(),
+        driver_state.clone(),
+        surge::SurgePolicy::default(),
+        clock.clone(),
+    ));
+    surge_engine.clone().spawn();
+
+    // demand forecast per cell, trained on past requests when there is a file of them
+    let forecaster = Arc::new(forecast::DemandForecaster::new(
+        producer.clone(),
+        forecast::ForecastPolicy::default(),
+        clock.clone(),
+    ));
+    if let Result::Ok(path) = env::var("FORECAST_HISTORY_PATH") {
+        let requests = forecaster.load_history(&path)?;
+        println!("Demand forecast trained on {} past requests", requests);
+    }
+    forecaster.clone().spawn();
 
     // setup the matcher service (business logic)
-    let matcher_service = Arc::new(matcher::service::MatcherService::new(
+    let matcher_service = Arc::new(service::MatcherService::new(
         producer.clone(),
-        con.clone(),
+        driver_state,
+        eta_engine,
+        surge_engine,
+        service_area,
+        clock,
     ));
 
     // setup the consumers (incoming events)
     let consumers = events::consumers::Consumers::new(messaging_client.clone());
     consumers.register_all(matcher_service.clone()).await;
+    consumers.register_forecaster(forecaster.clone()).await;
+
+    let app = api::router::create_router(Arc::new(api::router::AppState { forecaster }));
+    let listener = tokio::net::TcpListener::bind(&server_address).await?;
+    println!("Server running on {}", server_address);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("Axum server error: {:?}", e);
+        }
+    });
 
     // Wait here so the service keeps running until interrupted (e.g., with Ctrl+C).
     // Using `tokio::signal::ctrl_c().await` allows graceful shutdown on user interrupt,
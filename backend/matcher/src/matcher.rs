@@ -1,2 +1,4 @@
 mod domain;
+pub mod forecast;
 pub mod service;
+pub mod surge;
@@ -1,14 +1,67 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
+use common::eligibility::DriverEligibility;
 use common::events_schema::RideRequestedEvent;
+use common::redis_namespaces::{
+    DRIVER_AVAILABILITY_FIELD, DRIVER_IN_RIDE_FIELD, DRIVER_ZONE_FIELD,
+};
+use common::vehicle::{DriverCapabilities, RideRequirements};
 use uuid::Uuid;
 
 /// Represents the state of a driver in the system.
 #[derive(Debug, Clone)]
 pub struct DriverState {
-    pub driver_id: String,
+    pub driver_id: Uuid,
     pub lat: f64,
     pub lon: f64,
+    /// distance to the pickup point in meters, as returned by the geo search
+    pub distance_m: f64,
     pub available: bool,
+    pub in_ride: bool,
+    /// None when the driver has no active vehicle published
+    pub capabilities: Option<DriverCapabilities>,
+    pub eligibility: DriverEligibility,
+    /// airport queue zone the driver is waiting in
+    pub zone: Option<String>,
+}
+
+impl DriverState {
+    /// Builds the candidate from a geo search hit and the driver state hash (HGETALL result).
+    pub fn from_redis_hash(
+        driver_id: Uuid,
+        lat: f64,
+        lon: f64,
+        distance_m: f64,
+        hash: &HashMap<String, String>,
+    ) -> Self {
+        Self {
+            driver_id,
+            lat,
+            lon,
+            distance_m,
+            available: hash.get(DRIVER_AVAILABILITY_FIELD).is_some_and(|v| v == "1"),
+            in_ride: hash.get(DRIVER_IN_RIDE_FIELD).is_some_and(|v| v == "1"),
+            capabilities: DriverCapabilities::from_redis_hash(hash),
+            eligibility: DriverEligibility::from_redis_hash(hash),
+            zone: hash
+                .get(DRIVER_ZONE_FIELD)
+                .filter(|zone| !zone.is_empty())
+                .cloned(),
+        }
+    }
+
+    /// Whether the driver can be offered the ride at `now`. A driver without a published vehicle
+    /// is never matched, we can't tell what they drive.
+    pub fn can_take(&self, requirements: &RideRequirements, now: DateTime<Utc>) -> bool {
+        self.available
+            && !self.in_ride
+            && self.eligibility.check(now).is_none()
+            && self
+                .capabilities
+                .as_ref()
+                .is_some_and(|c| c.satisfies(requirements))
+    }
 }
 
 /// Represents a ride record in the matcher domain.
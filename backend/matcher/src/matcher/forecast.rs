@@ -0,0 +1,277 @@
+// Demand forecast: ride requests expected per cell over the coming intervals, for surge and
+// for sending idle drivers where the riders are about to be.
+//
+// Every cell has its own additive exponential smoothing model with a weekly season: a level
+// (requests per interval) plus an offset per weekday and hour. When an interval is over, the
+// level and the offset of its weekday and hour are both nudged towards the interval's request
+// count, cells without requests count a 0. The expected requests of an interval are its
+// level plus offset. Weekdays and hours are UTC, which is the same weekly cycle shifted in any
+// timezone (DST moves it by an hour twice a year, the offsets catch up within weeks).
+//
+// Counts come from the RideRequestedEvents the matcher receives and, so a restart doesn't
+// start from nothing, from a JSON lines file of past ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use common::clock::SharedClock;
+use common::events_schema::{DemandForecastEvent, ForecastInterval, RideRequestedEvent};
+use common::geo::hex::{HexCell, CELL_RESOLUTION};
+use common::geo::LatLng;
+use common::subjects::DEMAND_FORECAST_SUBJECT;
+
+use crate::events::producers::EventProducer;
+
+// weekday and hour
+const SEASON_SLOTS: usize = 7 * 24;
+
+#[derive(Debug, Clone)]
+pub struct ForecastPolicy {
+    pub interval: chrono::Duration,
+    /// intervals ahead in the published forecasts
+    pub horizon: usize,
+    /// most intervals ahead the API hands out
+    pub max_horizon: usize,
+    /// weight of an interval's count in the level
+    pub level_smoothing: f64,
+    /// weight of an interval's count in the offset of its weekday and hour
+    pub season_smoothing: f64,
+    /// cells expecting fewer requests than this over the horizon aren't published
+    pub min_expected: f64,
+    /// how often the end of the interval is checked for
+    pub tick: Duration,
+}
+
+impl Default for ForecastPolicy {
+    fn default() -> Self {
+        Self {
+            interval: chrono::Duration::minutes(15),
+            horizon: 8,
+            max_horizon: 4 * 24 * 7,
+            level_smoothing: 0.1,
+            season_smoothing: 0.2,
+            min_expected: 0.1,
+            tick: Duration::from_secs(5),
+        }
+    }
+}
+
+fn season_slot(at: DateTime<Utc>) -> usize {
+    at.weekday().num_days_from_monday() as usize * 24 + at.hour() as usize
+}
+
+#[derive(Debug, Clone)]
+struct CellModel {
+    level: f64,
+    season: Vec<f64>,
+}
+
+impl CellModel {
+    fn new() -> Self {
+        Self {
+            level: 0.0,
+            season: vec![0.0; SEASON_SLOTS],
+        }
+    }
+
+    fn observe(&mut self, slot: usize, count: f64, policy: &ForecastPolicy) {
+        let level = self.level
+            + policy.level_smoothing * (count - self.season[slot] - self.level);
+        self.season[slot] += policy.season_smoothing * (count - level - self.season[slot]);
+        self.level = level;
+    }
+
+    fn expected(&self, slot: usize) -> f64 {
+        (self.level + self.season[slot]).max(0.0)
+    }
+
+    // nothing seen in a long while, every offset and the level decayed to nothing
+    fn is_empty(&self) -> bool {
+        self.level.abs() < 1e-3 && self.season.iter().all(|s| s.abs() < 1e-3)
+    }
+}
+
+#[derive(Default)]
+struct ForecastState {
+    // start of the interval requests are counted into, None before the first one
+    current: Option<DateTime<Utc>>,
+    counts: HashMap<HexCell, u32>,
+    models: HashMap<HexCell, CellModel>,
+    // interval the last forecasts were published for
+    published: Option<DateTime<Utc>>,
+}
+
+pub struct DemandForecaster {
+    producer: Arc<EventProducer>,
+    policy: ForecastPolicy,
+    state: Mutex<ForecastState>,
+    clock: SharedClock,
+}
+
+impl DemandForecaster {
+    pub fn new(producer: Arc<EventProducer>, policy: ForecastPolicy, clock: SharedClock) -> Self {
+        Self {
+            producer,
+            policy,
+            state: Mutex::new(ForecastState::default()),
+            clock,
+        }
+    }
+
+    pub fn policy(&self) -> &ForecastPolicy {
+        &self.policy
+    }
+
+    fn interval_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = self.policy.interval.num_seconds().max(1);
+        DateTime::from_timestamp(at.timestamp().div_euclid(secs) * secs, 0).unwrap_or(at)
+    }
+
+    /// Counts a live request into the interval in progress. Its created_at isn't trusted, a
+    /// skewed or bogus one would close intervals ahead of time and count the rest of them short.
+    pub fn record_request(&self, event: &RideRequestedEvent) {
+        self.record_at(event, self.clock.now());
+    }
+
+    // counts the request into the interval of `at`, late ones count into the current interval,
+    // the ones before it are already folded into the models
+    fn record_at(&self, event: &RideRequestedEvent, at: DateTime<Utc>) {
+        let cell = HexCell::of(
+            LatLng::new(event.origin_lat, event.origin_lng),
+            CELL_RESOLUTION,
+        );
+        let start = self.interval_start(at);
+        let mut state = self.state.lock().unwrap();
+        self.close_until(&mut state, start);
+        *state.counts.entry(cell).or_insert(0) += 1;
+    }
+
+    // folds every interval before `start` into the models
+    fn close_until(&self, state: &mut ForecastState, start: DateTime<Utc>) {
+        let Some(mut current) = state.current else {
+            state.current = Some(start);
+            return;
+        };
+        while current < start {
+            let slot = season_slot(current);
+            let counts = std::mem::take(&mut state.counts);
+            for (cell, _) in counts.iter() {
+                state.models.entry(*cell).or_insert_with(CellModel::new);
+            }
+            state.models.retain(|cell, model| {
+                let count = counts.get(cell).copied().unwrap_or(0);
+                model.observe(slot, count as f64, &self.policy);
+                count > 0 || !model.is_empty()
+            });
+            current += self.policy.interval;
+        }
+        state.current = Some(current);
+    }
+
+    /// Trains on a JSON lines file of RideRequestedEvents, in any order. Returns how many
+    /// were used, lines that don't parse are skipped.
+    pub fn load_history(&self, path: impl AsRef<Path>) -> Result<usize, anyhow::Error> {
+        let content = std::fs::read_to_string(path)?;
+        let mut events: Vec<RideRequestedEvent> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        events.sort_by_key(|e| e.created_at);
+        // history is replayed in the order it happened, here the timestamps are all there is
+        for event in &events {
+            self.record_at(event, event.created_at);
+        }
+        Ok(events.len())
+    }
+
+    /// Expected requests in `cell` for `intervals` intervals (at most the policy's
+    /// max_horizon), starting with the one in progress.
+    pub fn forecast(&self, cell: HexCell, intervals: usize) -> Vec<ForecastInterval> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        self.close_until(&mut state, self.interval_start(now));
+        self.intervals(&state, state.models.get(&cell), intervals)
+    }
+
+    /// Forecasts of every cell expecting at least the policy's min_expected requests over
+    /// the intervals asked for, by cell.
+    pub fn forecasts(&self, intervals: usize) -> BTreeMap<HexCell, Vec<ForecastInterval>> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        self.close_until(&mut state, self.interval_start(now));
+        state
+            .models
+            .iter()
+            .map(|(cell, model)| (*cell, self.intervals(&state, Some(model), intervals)))
+            .filter(|(_, intervals)| {
+                intervals.iter().map(|i| i.expected_requests).sum::<f64>() >= self.policy.min_expected
+            })
+            .collect()
+    }
+
+    fn intervals(
+        &self,
+        state: &ForecastState,
+        model: Option<&CellModel>,
+        intervals: usize,
+    ) -> Vec<ForecastInterval> {
+        let Some(current) = state.current else {
+            return Vec::new();
+        };
+        (0..intervals.min(self.policy.max_horizon) as i32)
+            .map(|i| {
+                let start = current + self.policy.interval * i;
+                let expected = model.map(|m| m.expected(season_slot(start))).unwrap_or(0.0);
+                ForecastInterval {
+                    start,
+                    end: start + self.policy.interval,
+                    expected_requests: (expected * 100.0).round() / 100.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Publishes the forecasts once per interval, returns how many cells were published.
+    pub async fn publish(&self) -> Result<usize, anyhow::Error> {
+        let now = self.clock.now();
+        let current = self.interval_start(now);
+        {
+            let mut state = self.state.lock().unwrap();
+            self.close_until(&mut state, current);
+            if state.published == Some(current) {
+                return Ok(0);
+            }
+            state.published = Some(current);
+        }
+
+        let forecasts = self.forecasts(self.policy.horizon);
+        let published = forecasts.len();
+        for (cell, intervals) in forecasts {
+            let event = DemandForecastEvent {
+                cell,
+                intervals,
+                computed_at: now,
+            };
+            let payload = serde_json::to_vec(&event)?;
+            self.producer.publish(DEMAND_FORECAST_SUBJECT, payload).await?;
+        }
+        Ok(published)
+    }
+
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.policy.tick);
+            loop {
+                ticker.tick().await;
+                match self.publish().await {
+                    Ok(0) => {}
+                    Ok(cells) => eprintln!("Demand forecast published for {} cells", cells),
+                    Err(e) => eprintln!("Demand forecast publish failed: {:?}", e),
+                }
+            }
+        });
+    }
+}
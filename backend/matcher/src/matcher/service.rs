@@ -2,33 +2,54 @@
 // todo add more classes to this module, such as state, scoring, where more logic can go
 // todo: for now we are using in memory caches, but we can swap out with redis or similar later
 
+use common::clock::SharedClock;
+use common::driver_state::{DriverStateStore, NearbyDriver};
+use common::eta::traffic::Incident;
+use common::eta::{Eta, EtaEngine};
 use common::events_schema::{DriverAssignedRideEvent, NoDriversAvailableEvent, RideRequestedEvent};
-use common::redis_namespaces::DRIVER_LOCATION_NAMESPACE;
+use common::geo::{haversine_m, LatLng};
+use common::service_area::ServiceArea;
 use common::subjects::{DRIVER_ASSIGNED_SUBJECT, NO_DRIVERS_AVAILABLE_SUBJECT};
-use redis::geo::{RadiusOptions, RadiusOrder, RadiusSearchResult};
-use redis::{geo, AsyncCommands};
 use uuid::Uuid;
 use std::sync::Arc;
 use tokio::time::Instant;
 
 use crate::events::producers::EventProducer;
+use crate::matcher::domain::DriverState;
+use crate::matcher::surge::SurgeEngine;
+
+// ETAs are only computed for the closest eligible drivers, routing everyone in the radius
+// costs more than it can win
+const MAX_SCORED_CANDIDATES: usize = 10;
+const SEARCH_RADIUS_M: f64 = 2_000.0;
 
 /// Core Matcher service
 pub struct MatcherService {
-    // The MultiplexedConnection is already designed to be shared safely across tasks and threads (it implements Clone, Send, and Sync).
-    // but we wanted to wrap it in mutex for internal mutability when needed.
-    redis_client: Arc<tokio::sync::Mutex<redis::aio::MultiplexedConnection>>,
+    // driver positions and state hashes, Redis in the service, in memory in a simulation
+    driver_state: Arc<dyn DriverStateStore>,
     producer: Arc<EventProducer>, // used to publish MatchProposed etc.
+    eta_engine: Arc<EtaEngine>,
+    surge_engine: Arc<SurgeEngine>,
+    service_area: Arc<ServiceArea>,
+    clock: SharedClock,
 }
 
 impl MatcherService {
     pub fn new(
         producer: Arc<EventProducer>,
-        redis_client: redis::aio::MultiplexedConnection,
+        driver_state: Arc<dyn DriverStateStore>,
+        eta_engine: Arc<EtaEngine>,
+        surge_engine: Arc<SurgeEngine>,
+        service_area: Arc<ServiceArea>,
+        clock: SharedClock,
     ) -> Self {
         Self {
-            redis_client: Arc::new(tokio::sync::Mutex::new(redis_client)),
+            driver_state,
             producer,
+            eta_engine,
+            surge_engine,
+            service_area,
+            clock,
         }
     }
 
@@ -36,45 +57,78 @@ impl MatcherService {
         &self,
         event: RideRequestedEvent,
     ) -> Result<(), anyhow::Error> {
+        // every request is demand, matched or not
+        self.surge_engine.record_request(&event);
+
         // get all available drivers within the range
         // todo production level if the selected driver don't accept we have to try the next best driver etc. or if no drivers available
         // we have to increase searched radus etc.
 
-        let mut redis_con = self.redis_client.lock().await;
-        let opts = RadiusOptions::default().with_dist().order(RadiusOrder::Asc);
-        let start = Instant::now();
-
-        let redis_search_results: Vec<RadiusSearchResult> = redis_con
-            .geo_radius(
-                DRIVER_LOCATION_NAMESPACE,
-                event.origin_lng,
-                event.origin_lat,
-                2.0,
-                geo::Unit::Kilometers,
-                opts,
-            )
-            .await?;
-        let duration = start.elapsed();
-        eprintln!("geo_radius took {:?}", duration);
-
-        drop(redis_con); // release lock early
-
-        let closest_driver = redis_search_results.first();
+        let pickup = LatLng::new(event.origin_lat, event.origin_lng);
+        let now = self.clock.now();
+
+        // zone rule: pickups inside an airport queue zone go to the drivers waiting there, first
+        // come first served, ETA doesn't matter. Only if nobody in the queue can take the ride
+        // the usual search runs.
+        let queued = match self.service_area.queue_zone_at(pickup) {
+            Some(zone) => self.first_in_queue(&zone.id, &event, now).await?,
+            None => None,
+        };
+
+        let (best_driver, candidates) = match queued {
+            Some(driver) => {
+                eprintln!(
+                    "Driver {} is first in the {} queue for ride {}",
+                    driver.driver_id,
+                    driver.zone.as_deref().unwrap_or_default(),
+                    event.ride_id
+                );
+                let eta = self
+                    .eta_engine
+                    .eta(LatLng::new(driver.lat, driver.lon), pickup, now);
+                (Some((driver.clone(), eta)), vec![driver])
+            }
+            None => {
+                let start = Instant::now();
+
+                let nearby = self.driver_state.drivers_within(pickup, SEARCH_RADIUS_M).await?;
+                let duration = start.elapsed();
+                eprintln!("geo_radius took {:?}", duration);
+
+                // the geo set only knows positions, availability and vehicle capabilities live in the state hashes
+                let candidates = self.load_candidates(nearby).await?;
+
+                let best_driver = self
+                    .pick_fastest(&event, &candidates, now)
+                    .map(|(driver, eta)| (driver.clone(), eta));
+                (best_driver, candidates)
+            }
+        };
         // send event to that one driver (MatchProposedEvent)
-        if let Some(driver) = closest_driver {
+        if let Some((driver, eta)) = best_driver {
             eprintln!(
-                "Closest driver to ride {} is driver {} at distance {:?} meters",
-                event.ride_id, driver.name, driver.dist
+                "Fastest driver to ride {} ({}) is driver {}, {:.0}s away ({:?}) at distance {} meters",
+                event.ride_id,
+                event.requirements.product,
+                driver.driver_id,
+                eta.duration_secs,
+                eta.source,
+                driver.distance_m
             );
 
             let driver_assigned_event = DriverAssignedRideEvent {
                 ride_id: event.ride_id,
-                driver_id: driver.name.parse::<Uuid>()?,
+                driver_id: driver.driver_id,
                 pickup_lat: event.origin_lat,
                 pickup_lng: event.origin_lng,
                 assigned_at: event.created_at,
                 dropoff_lat: event.destination_lat,
                 dropoff_lng: event.destination_lng,
+                surge_multiplier: Some(
+                    self.surge_engine
+                        .multiplier_at(LatLng::new(event.origin_lat, event.origin_lng)),
+                )
+                .filter(|multiplier| *multiplier > 1.0),
             };
 
             let payload = match serde_json::to_vec(&driver_assigned_event) {
@@ -99,7 +153,14 @@ impl MatcherService {
                 ride_id: event.ride_id,
                 rider_id: event.rider_id,
                 requested_at: event.created_at,
-                reason: Some("No available drivers in vicinity".to_string()),
+                reason: Some(if candidates.is_empty() {
+                    "No available drivers in vicinity".to_string()
+                } else {
+                    format!(
+                        "No available {} drivers in vicinity matching the ride requirements",
+                        event.requirements.product
+                    )
+                }),
             };
             let payload = match serde_json::to_vec(&no_driver_available_event) {
                 Ok(p) => p,
@@ -124,4 +185,123 @@ impl MatcherService {
 
         Ok(())
     }
+
+    // Fetches the state hash of every geo search hit in one round trip. Hits without a
+    // state hash (expired, driver went quiet) are dropped.
+    async fn load_candidates(
+        &self,
+        nearby: Vec<NearbyDriver>,
+    ) -> Result<Vec<DriverState>, anyhow::Error> {
+        if nearby.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let driver_ids: Vec<Uuid> = nearby.iter().map(|hit| hit.driver_id).collect();
+        let hashes = self.driver_state.states(&driver_ids).await?;
+
+        Ok(nearby
+            .into_iter()
+            .zip(hashes)
+            .filter(|(_, hash)| !hash.is_empty())
+            .map(|(hit, hash)| {
+                DriverState::from_redis_hash(
+                    hit.driver_id,
+                    hit.position.lat,
+                    hit.position.lng,
+                    hit.distance_m,
+                    &hash,
+                )
+            })
+            .collect())
+    }
+
+    // The longest waiting driver of the zone's queue that can take the ride. Members whose state
+    // expired or who are no longer in the zone are dropped from the queue on the way.
+    async fn first_in_queue(
+        &self,
+        zone_id: &str,
+        event: &RideRequestedEvent,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<DriverState>, anyhow::Error> {
+        // sorted by the time they entered the zone
+        let members = self.driver_state.zone_queue(zone_id).await?;
+        let (drivers, mut stale): (Vec<_>, Vec<_>) = members
+            .iter()
+            .map(|member| (member, member.parse::<Uuid>().ok()))
+            .partition(|(_, driver_id)| driver_id.is_some());
+        if drivers.is_empty() {
+            return Ok(None);
+        }
+
+        let driver_ids: Vec<Uuid> = drivers.iter().filter_map(|(_, driver_id)| *driver_id).collect();
+        let positions = self.driver_state.positions(&driver_ids).await?;
+        let hashes = self.driver_state.states(&driver_ids).await?;
+
+        let pickup = LatLng::new(event.origin_lat, event.origin_lng);
+        let mut first = None;
+        for (((member, driver_id), position), hash) in drivers.into_iter().zip(positions).zip(hashes) {
+            let (Some(driver_id), Some(position)) = (driver_id, position) else {
+                stale.push((member, None));
+                continue;
+            };
+            if hash.is_empty() {
+                stale.push((member, None));
+                continue;
+            }
+            let driver = DriverState::from_redis_hash(
+                driver_id,
+                position.lat,
+                position.lng,
+                haversine_m(position, pickup),
+                &hash,
+            );
+            if driver.zone.as_deref() != Some(zone_id) {
+                stale.push((member, None));
+                continue;
+            }
+            if driver.can_take(&event.requirements, now) {
+                first = Some(driver);
+                break;
+            }
+        }
+
+        if !stale.is_empty() {
+            let stale: Vec<String> = stale.into_iter().map(|(member, _)| member.clone()).collect();
+            self.driver_state.remove_from_zone_queue(zone_id, &stale).await?;
+        }
+        Ok(first)
+    }
+
+    // Among the closest eligible candidates, the one with the shortest road ETA to the pickup.
+    // Straight line distance lies across rivers and one way streets, the ETA doesn't.
+    fn pick_fastest<'a>(
+        &self,
+        event: &RideRequestedEvent,
+        candidates: &'a [DriverState],
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<(&'a DriverState, Eta)> {
+        let pickup = LatLng::new(event.origin_lat, event.origin_lng);
+        candidates
+            .iter()
+            .filter(|driver| driver.can_take(&event.requirements, now))
+            .take(MAX_SCORED_CANDIDATES)
+            .map(|driver| {
+                let eta = self
+                    .eta_engine
+                    .eta(LatLng::new(driver.lat, driver.lon), pickup, now);
+                (driver, eta)
+            })
+            .min_by(|(_, a), (_, b)| a.duration_secs.total_cmp(&b.duration_secs))
+    }
+
+    /// Adds or replaces a traffic incident in the ETA engine, returns how many edges it slows down.
+    pub fn apply_incident(&self, incident: Incident) -> usize {
+        self.eta_engine
+            .traffic()
+            .add_incident(self.eta_engine.graph(), incident, self.clock.now())
+    }
+
+    pub fn clear_incident(&self, incident_id: Uuid) -> bool {
+        self.eta_engine.traffic().clear_incident(incident_id)
+    }
 }
@@ -0,0 +1,210 @@
+// Surge engine: demand versus supply per cell, turned into a price multiplier.
+//
+// Demand is the ride requests the matcher saw in a cell over a sliding window, matched or
+// not. Matching happens as a request comes in, so counting only the unmatched ones would
+// leave next to nothing; the request volume is what outruns the drivers. Supply is the
+// available drivers currently in the cell (drivers:locations members and the cell and
+// availability kept in their state hashes).
+// The raw multiplier follows the ratio, the published one is smoothed so prices don't jump
+// around with every request, and capped.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::clock::SharedClock;
+use common::driver_state::DriverStateStore;
+use common::events_schema::{RideRequestedEvent, SurgeUpdatedEvent};
+use common::geo::hex::{HexCell, CELL_RESOLUTION};
+use common::geo::LatLng;
+use common::redis_namespaces::{DRIVER_AVAILABILITY_FIELD, DRIVER_CELL_FIELD};
+use common::subjects::SURGE_UPDATED_SUBJECT;
+use common::surge::SurgeMultipliers;
+
+use crate::events::producers::EventProducer;
+
+#[derive(Debug, Clone)]
+pub struct SurgePolicy {
+    /// how far back requests count as demand
+    pub window: chrono::Duration,
+    /// how often multipliers are recomputed
+    pub interval: Duration,
+    /// multiplier gained per request above one per available driver
+    pub sensitivity: f64,
+    pub max_multiplier: f64,
+    /// weight of the new raw multiplier, 1 disables smoothing
+    pub smoothing: f64,
+    /// multipliers closer than this to 1.0 are no surge
+    pub min_surge: f64,
+}
+
+impl Default for SurgePolicy {
+    fn default() -> Self {
+        Self {
+            window: chrono::Duration::minutes(5),
+            interval: Duration::from_secs(15),
+            sensitivity: 0.5,
+            max_multiplier: 3.0,
+            smoothing: 0.3,
+            min_surge: 0.05,
+        }
+    }
+}
+
+impl SurgePolicy {
+    fn raw_multiplier(&self, recent_requests: u32, available_drivers: u32) -> f64 {
+        let ratio = recent_requests as f64 / available_drivers.max(1) as f64;
+        (1.0 + self.sensitivity * (ratio - 1.0)).clamp(1.0, self.max_multiplier)
+    }
+}
+
+pub struct SurgeEngine {
+    driver_state: Arc<dyn DriverStateStore>,
+    producer: Arc<EventProducer>,
+    policy: SurgePolicy,
+    requests: Mutex<VecDeque<(DateTime<Utc>, HexCell)>>,
+    // smoothed multiplier per cell, only cells above 1.0
+    current: Mutex<HashMap<HexCell, f64>>,
+    // what was published, offers read the multiplier from here
+    published: SurgeMultipliers,
+    clock: SharedClock,
+}
+
+impl SurgeEngine {
+    pub fn new(
+        producer: Arc<EventProducer>,
+        driver_state: Arc<dyn DriverStateStore>,
+        policy: SurgePolicy,
+        clock: SharedClock,
+    ) -> Self {
+        // a multiplier that wasn't refreshed for a few rounds is not trusted anymore
+        let max_age = chrono::Duration::from_std(policy.interval * 4)
+            .unwrap_or(chrono::Duration::minutes(1));
+        Self {
+            driver_state,
+            producer,
+            policy,
+            requests: Mutex::new(VecDeque::new()),
+            current: Mutex::new(HashMap::new()),
+            published: SurgeMultipliers::new(max_age),
+            clock,
+        }
+    }
+
+    pub fn record_request(&self, event: &RideRequestedEvent) {
+        let cell = HexCell::of(
+            LatLng::new(event.origin_lat, event.origin_lng),
+            CELL_RESOLUTION,
+        );
+        self.requests.lock().unwrap().push_back((self.clock.now(), cell));
+    }
+
+    pub fn multiplier_at(&self, point: LatLng) -> f64 {
+        self.published.multiplier_at(point, self.clock.now())
+    }
+
+    /// Recomputes every cell with demand or an ongoing surge and publishes the surging ones,
+    /// plus a 1.0 for cells whose surge just ended. Returns how many cells changed.
+    pub async fn recompute(&self) -> Result<usize, anyhow::Error> {
+        let now = self.clock.now();
+        let demand = self.demand(now);
+        let supply = self.supply().await?;
+
+        let mut events = Vec::new();
+        let mut changed = 0;
+        {
+            let mut current = self.current.lock().unwrap();
+            let cells: HashSet<HexCell> = demand.keys().chain(current.keys()).copied().collect();
+            for cell in cells {
+                let recent_requests = demand.get(&cell).copied().unwrap_or(0);
+                let available_drivers = supply.get(&cell).copied().unwrap_or(0);
+                let raw = self.policy.raw_multiplier(recent_requests, available_drivers);
+
+                let previous = current.get(&cell).copied().unwrap_or(1.0);
+                let mut multiplier = previous + self.policy.smoothing * (raw - previous);
+                // smoothing only ever approaches 1.0, close enough is no surge
+                if multiplier - 1.0 < self.policy.min_surge {
+                    multiplier = 1.0;
+                }
+                let multiplier = (multiplier * 100.0).round() / 100.0;
+
+                if multiplier > 1.0 {
+                    current.insert(cell, multiplier);
+                } else {
+                    current.remove(&cell);
+                }
+                if multiplier != previous {
+                    changed += 1;
+                }
+                // ongoing surges are republished every round so consumers don't age them out
+                if multiplier > 1.0 || previous > 1.0 {
+                    events.push(SurgeUpdatedEvent {
+                        cell,
+                        multiplier,
+                        recent_requests,
+                        available_drivers,
+                        computed_at: now,
+                    });
+                }
+            }
+        }
+
+        // in cell order, so the same state always publishes the same sequence
+        events.sort_by_key(|event| event.cell);
+        for event in events {
+            self.published.apply(&event);
+            let payload = serde_json::to_vec(&event)?;
+            self.producer.publish(SURGE_UPDATED_SUBJECT, payload).await?;
+        }
+        Ok(changed)
+    }
+
+    // requests per cell in the window, older ones are dropped
+    fn demand(&self, now: DateTime<Utc>) -> HashMap<HexCell, u32> {
+        let mut requests = self.requests.lock().unwrap();
+        let cutoff = now - self.policy.window;
+        while requests.front().is_some_and(|(at, _)| *at < cutoff) {
+            requests.pop_front();
+        }
+        let mut demand = HashMap::new();
+        for (_, cell) in requests.iter() {
+            *demand.entry(*cell).or_insert(0) += 1;
+        }
+        demand
+    }
+
+    // available drivers per cell
+    async fn supply(&self) -> Result<HashMap<HexCell, u32>, anyhow::Error> {
+        let drivers = self.driver_state.located_drivers().await?;
+        if drivers.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        // the driver service keeps the cell of the last location in the state hash
+        let states = self.driver_state.states(&drivers).await?;
+
+        let mut supply = HashMap::new();
+        for state in states {
+            let cell = state.get(DRIVER_CELL_FIELD).and_then(|c| c.parse::<HexCell>().ok());
+            if let (Some("1"), Some(cell)) = (state.get(DRIVER_AVAILABILITY_FIELD).map(String::as_str), cell) {
+                *supply.entry(cell).or_insert(0) += 1;
+            }
+        }
+        Ok(supply)
+    }
+
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.policy.interval);
+            loop {
+                ticker.tick().await;
+                match self.recompute().await {
+                    Ok(0) => {}
+                    Ok(changed) => eprintln!("Surge changed in {} cells", changed),
+                    Err(e) => eprintln!("Surge recompute failed: {:?}", e),
+                }
+            }
+        });
+    }
+}
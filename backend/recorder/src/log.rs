@@ -0,0 +1,94 @@
+// The event log: one JSON object per line, in the order the events were received.
+//
+//   {"t":1760954400123,"s":"rider.ride.requested","d":{...}}
+//
+// `t` is when the recorder received the event (unix millis, on the recorder's clock), `s` the
+// subject and `d` the payload. Every event in the backend is JSON so the payload is stored as
+// is, anything that isn't is kept as a string with `"r":true`. Lines that don't parse are
+// skipped on reading, a recording cut off mid-line still replays.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recorded {
+    #[serde(rename = "t")]
+    pub at_ms: i64,
+    #[serde(rename = "s")]
+    pub subject: String,
+    #[serde(rename = "d")]
+    pub data: serde_json::Value,
+    /// the payload wasn't JSON, `data` is its text
+    #[serde(rename = "r", default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+}
+
+impl Recorded {
+    pub fn new(at: DateTime<Utc>, subject: String, payload: &[u8]) -> Self {
+        let (data, raw) = match serde_json::from_slice(payload) {
+            Ok(data) => (data, false),
+            Err(_) => (
+                serde_json::Value::String(String::from_utf8_lossy(payload).into_owned()),
+                true,
+            ),
+        };
+        Self {
+            at_ms: at.timestamp_millis(),
+            subject,
+            data,
+            raw,
+        }
+    }
+
+    pub fn at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.at_ms).unwrap_or_default()
+    }
+
+    /// The payload to publish again.
+    pub fn payload(&self) -> Result<Vec<u8>, serde_json::Error> {
+        match (&self.data, self.raw) {
+            (serde_json::Value::String(text), true) => Ok(text.clone().into_bytes()),
+            (data, _) => serde_json::to_vec(data),
+        }
+    }
+}
+
+pub struct LogWriter {
+    out: BufWriter<File>,
+}
+
+impl LogWriter {
+    /// Appends to `path`, so a recording can be resumed into the same file.
+    pub fn append(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, event: &Recorded) -> Result<(), anyhow::Error> {
+        serde_json::to_writer(&mut self.out, event)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the events of a log one at a time, skipping lines that don't parse.
+pub fn read_log(
+    path: impl AsRef<Path>,
+) -> Result<impl Iterator<Item = Recorded>, anyhow::Error> {
+    let file = File::open(path)?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok()))
+}
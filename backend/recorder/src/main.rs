@@ -0,0 +1,122 @@
+// Records the events on the bus into a compact log and replays them into another environment,
+// for reproducing production traffic, see README.md.
+
+mod log;
+mod record;
+mod replay;
+
+use std::collections::BTreeMap;
+use std::env;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use ubersimx_messaging::messagingclient::MessagingClient;
+
+use log::{read_log, LogWriter};
+use record::Recorder;
+use replay::{Filter, Replayer};
+
+const USAGE: &str = "usage: recorder record <log file> [--subject <pattern>]...\n       \
+    recorder replay <log file> [--speed <x> | --fast] [--subject <pattern>]... [--from <time>] [--until <time>]";
+
+// values of every `--name <value>` in the arguments
+fn option_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+    args.windows(2)
+        .filter(|pair| pair[0] == name)
+        .map(|pair| pair[1].as_str())
+        .collect()
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    option_values(args, name).pop()
+}
+
+fn parse_time(args: &[String], name: &str) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    option_value(args, name)
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| anyhow!("{} must be an RFC 3339 timestamp: {}", name, e))
+        })
+        .transpose()
+}
+
+fn print_counts(counts: &BTreeMap<String, usize>) {
+    for (subject, count) in counts {
+        println!("  {:<36} {}", subject, count);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    dotenvy::from_filename("settings.env").ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (Some(command), Some(path)) = (args.first(), args.get(1)) else {
+        return Err(anyhow!(USAGE));
+    };
+    let mut subjects: Vec<String> = option_values(&args, "--subject")
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    if subjects.is_empty() {
+        subjects.push(">".to_string());
+    }
+
+    match command.as_str() {
+        "record" => {
+            let messaging_url = env::var("MESSAGING_URL")
+                .map_err(|e| anyhow!("MESSAGING_URL must be set in .env: {}", e))?;
+            let messaging_client = MessagingClient::connect(&messaging_url).await?;
+            let recorder = Recorder {
+                subjects,
+                clock: common::clock::clock_from_env()?,
+            };
+            let mut log = LogWriter::append(path)?;
+            let mut recorded = BTreeMap::new();
+            println!("Recording {} into {}, ctrl-c to stop", recorder.subjects.join(", "), path);
+            tokio::select! {
+                result = recorder.run(&messaging_client, &mut log, &mut recorded) => result?,
+                _ = tokio::signal::ctrl_c() => log.flush()?,
+            }
+            println!("Recorded {} events", recorded.values().sum::<usize>());
+            print_counts(&recorded);
+        }
+        "replay" => {
+            // a separate variable so a replay can't go to production by accident
+            let messaging_url = env::var("REPLAY_MESSAGING_URL")
+                .map_err(|e| anyhow!("REPLAY_MESSAGING_URL must be set in .env: {}", e))?;
+            let speed = if args.iter().any(|a| a == "--fast") {
+                None
+            } else {
+                let speed = option_value(&args, "--speed").unwrap_or("1");
+                Some(
+                    speed
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|s| *s > 0.0)
+                        .ok_or_else(|| anyhow!("--speed must be a positive number, got {:?}", speed))?,
+                )
+            };
+            let replayer = Replayer {
+                filter: Filter {
+                    subjects,
+                    from: parse_time(&args, "--from")?,
+                    until: parse_time(&args, "--until")?,
+                },
+                speed,
+            };
+            let messaging_client = MessagingClient::connect(&messaging_url).await?;
+            let events = read_log(path)?;
+            let mut published = BTreeMap::new();
+            tokio::select! {
+                result = replayer.run(&messaging_client, events, &mut published) => result?,
+                _ = tokio::signal::ctrl_c() => println!("Replay interrupted"),
+            }
+            println!("Published {} events", published.values().sum::<usize>());
+            print_counts(&published);
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// Recorder: everything published on the bus, or the subjects asked for, into the event log.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use common::clock::SharedClock;
+use futures_util::StreamExt;
+use ubersimx_messaging::memory::subject_matches;
+use ubersimx_messaging::Messaging;
+
+use crate::log::{LogWriter, Recorded};
+
+// how much a crash or a kill can lose
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Recorder {
+    /// subject patterns to keep, NATS style
+    pub subjects: Vec<String>,
+    pub clock: SharedClock,
+}
+
+impl Recorder {
+    /// Records until the subscription ends, counting the events per subject into `recorded`.
+    pub async fn run(
+        &self,
+        messaging: &dyn Messaging,
+        log: &mut LogWriter,
+        recorded: &mut BTreeMap<String, usize>,
+    ) -> Result<(), anyhow::Error> {
+        // one wildcard subscription filtered here, overlapping patterns don't record twice
+        let mut events = messaging.subscribe(">".to_string()).await?;
+        let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                message = events.next() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    let message = message?;
+                    // request replies and the server's own subjects aren't events
+                    if message.subject.starts_with('_')
+                        || !self.subjects.iter().any(|p| subject_matches(p, &message.subject))
+                    {
+                        continue;
+                    }
+                    log.write(&Recorded::new(self.clock.now(), message.subject.clone(), &message.data))?;
+                    *recorded.entry(message.subject).or_default() += 1;
+                }
+                _ = flush.tick() => log.flush()?,
+            }
+        }
+        log.flush()
+    }
+}
@@ -0,0 +1,63 @@
+// Replayer: the events of a log published again, with the gaps between them as recorded or
+// scaled, for reproducing a stretch of traffic against a test environment.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+use ubersimx_messaging::memory::subject_matches;
+use ubersimx_messaging::Messaging;
+
+use crate::log::Recorded;
+
+/// Which recorded events are replayed.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// subject patterns, NATS style
+    pub subjects: Vec<String>,
+    /// recorded at or after
+    pub from: Option<DateTime<Utc>>,
+    /// recorded before
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Filter {
+    pub fn matches(&self, event: &Recorded) -> bool {
+        let at = event.at();
+        self.subjects.iter().any(|p| subject_matches(p, &event.subject))
+            && self.from.is_none_or(|from| at >= from)
+            && self.until.is_none_or(|until| at < until)
+    }
+}
+
+pub struct Replayer {
+    pub filter: Filter,
+    /// how many times faster than recorded, None for as fast as possible
+    pub speed: Option<f64>,
+}
+
+impl Replayer {
+    /// Publishes the events passing the filter, counting them per subject into `published`.
+    /// The first one goes out right away, the others as far after it as they were recorded
+    /// (divided by the speed).
+    pub async fn run(
+        &self,
+        messaging: &dyn Messaging,
+        events: impl Iterator<Item = Recorded>,
+        published: &mut BTreeMap<String, usize>,
+    ) -> Result<(), anyhow::Error> {
+        let mut first: Option<(DateTime<Utc>, Instant)> = None;
+        for event in events.filter(|e| self.filter.matches(e)) {
+            let at = event.at();
+            let (first_at, started) = *first.get_or_insert((at, Instant::now()));
+            if let Some(speed) = self.speed {
+                // events recorded out of order go out right away
+                let offset = (at - first_at).to_std().unwrap_or_default();
+                tokio::time::sleep_until(started + offset.div_f64(speed)).await;
+            }
+            messaging.publish(event.subject.clone(), event.payload()?).await?;
+            *published.entry(event.subject).or_default() += 1;
+        }
+        Ok(())
+    }
+}
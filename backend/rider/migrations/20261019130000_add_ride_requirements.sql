@@ -0,0 +1,5 @@
+-- what the ride needs from the vehicle, the matcher filters drivers on these
+ALTER TABLE rides ADD COLUMN product TEXT NOT NULL DEFAULT 'economy';
+ALTER TABLE rides ADD COLUMN seats SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE rides ADD COLUMN pet_friendly BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE rides ADD COLUMN wheelchair_accessible BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS fare_quotes (
+    id                      UUID PRIMARY KEY,
+    rider_id                UUID NOT NULL,
+    product                 TEXT NOT NULL,
+
+    origin_lat              DOUBLE PRECISION NOT NULL,
+    origin_lng              DOUBLE PRECISION NOT NULL,
+    destination_lat         DOUBLE PRECISION NOT NULL,
+    destination_lng         DOUBLE PRECISION NOT NULL,
+
+    distance_m              DOUBLE PRECISION NOT NULL,
+    duration_secs           DOUBLE PRECISION NOT NULL,
+
+    -- fare breakdown, all in cents
+    base_fare               BIGINT NOT NULL,
+    distance_fare           BIGINT NOT NULL,
+    time_fare               BIGINT NOT NULL,
+    surge_multiplier        DOUBLE PRECISION NOT NULL,
+    minimum_fare_adjustment BIGINT NOT NULL,
+    booking_fee             BIGINT NOT NULL,
+    total                   BIGINT NOT NULL,
+    currency                TEXT NOT NULL,
+
+    created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at              TIMESTAMPTZ NOT NULL,
+    ride_id                 UUID                             -- set once a ride was requested with it, a quote is single use
+);
+
+-- the fare the rider agreed to, null for rides requested without a quote
+ALTER TABLE rides ADD COLUMN quote_id UUID;
+ALTER TABLE rides ADD COLUMN fare_cents BIGINT;
+ALTER TABLE rides ADD COLUMN currency TEXT;
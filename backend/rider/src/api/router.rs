@@ -1,10 +1,18 @@
-use crate::models::{CreateRideRequest, CreateRiderRequest, Rider};
+use crate::models::{CreateRideRequest, CreateRiderRequest, FareQuote, QuotedFare, Rider};
+use crate::pricing::PricingEngine;
+use crate::repository::fare_quotes_repository::FareQuotesRepository;
 use crate::repository::riders_repository::RidersRepository;
 use crate::repository::rides_repository::RidesRepository;
+use axum::response::{IntoResponse, Response};
 use axum::{routing::post, Json, Router};
-use chrono::Utc;
+use common::clock::SharedClock;
+use common::eta::EtaEngine;
 use common::events_schema::RideRequestedEvent;
+use common::geo::{haversine_m, LatLng};
+use common::service_area::ServiceArea;
 use common::subjects::RIDE_REQUESTED_SUBJECT;
+use common::surge::SurgeMultipliers;
+use common::vehicle::{RideProduct, RideRequirements};
 use serde::Deserialize;
 use std::sync::Arc;
 use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
@@ -13,7 +21,20 @@ use uuid::Uuid;
 pub struct AppState {
     pub riders_repo: Arc<RidersRepository>,
     pub rides_repo: Arc<RidesRepository>,
+    pub fare_quotes_repo: Arc<FareQuotesRepository>,
     pub messaging_client: Arc<MessagingClient>,
+    pub pricing_engine: Arc<PricingEngine>,
+    pub eta_engine: Arc<EtaEngine>,
+    pub surge: Arc<SurgeMultipliers>,
+    pub service_area: Arc<ServiceArea>,
+    pub clock: SharedClock,
+}
+
+// how far pickup or dropoff may move from the quoted ones before the quote no longer applies
+const QUOTE_MAX_DRIFT_M: f64 = 200.0;
+
+fn valid_coordinates(p: LatLng) -> bool {
+    p.lat.is_finite() && p.lng.is_finite() && p.lat.abs() <= 90.0 && p.lng.abs() <= 180.0
 }
 
 #[derive(Deserialize)]
@@ -40,14 +61,170 @@ struct RequestRide {
     origin_lng: f64,
     destination_lat: f64,
     destination_lng: f64,
+    // all optional, a bare request is a one seat economy ride
+    #[serde(default)]
+    product: RideProduct,
+    #[serde(default)]
+    pet_friendly: bool,
+    #[serde(default)]
+    wheelchair_accessible: bool,
+    seats: Option<u8>,
+    // from POST /rides/estimate, the ride is charged the quoted fare if the quote is still valid
+    quote_id: Option<Uuid>,
+}
+
+// 422 with the violation (and a suggested point nearby when there is one) if the trip can't
+// start or end where asked
+fn check_service_area(
+    service_area: &ServiceArea,
+    origin: LatLng,
+    destination: LatLng,
+) -> Result<(), (axum::http::StatusCode, Json<serde_json::Value>)> {
+    let reject = |field: &str, violation: common::service_area::ServiceAreaViolation| {
+        let mut body = violation.to_json();
+        body["field"] = field.into();
+        (axum::http::StatusCode::UNPROCESSABLE_ENTITY, Json(body))
+    };
+    service_area
+        .check_pickup(origin)
+        .map_err(|v| reject("origin", v))?;
+    service_area
+        .check_dropoff(destination)
+        .map_err(|v| reject("destination", v))
+}
+
+#[derive(Deserialize)]
+struct EstimateRide {
+    rider_id: Uuid,
+    origin_lat: f64,
+    origin_lng: f64,
+    destination_lat: f64,
+    destination_lng: f64,
+    #[serde(default)]
+    product: RideProduct,
+}
+
+async fn estimate_ride(
+    state: axum::extract::State<Arc<AppState>>,
+    Json(payload): Json<EstimateRide>,
+) -> Result<Json<FareQuote>, Response> {
+    let origin = LatLng::new(payload.origin_lat, payload.origin_lng);
+    let destination = LatLng::new(payload.destination_lat, payload.destination_lng);
+    if !valid_coordinates(origin) || !valid_coordinates(destination) {
+        return Err(axum::http::StatusCode::BAD_REQUEST.into_response());
+    }
+    check_service_area(&state.service_area, origin, destination)
+        .map_err(IntoResponse::into_response)?;
+
+    let now = state.clock.now();
+    let trip = state.eta_engine.eta(origin, destination, now);
+    // surge where the ride starts, that's where drivers are short
+    let surge_multiplier = state.surge.multiplier_at(origin, now);
+    let fare = state
+        .pricing_engine
+        .price(
+            payload.product,
+            trip.distance_m,
+            trip.duration_secs,
+            surge_multiplier,
+        )
+        .ok_or(axum::http::StatusCode::UNPROCESSABLE_ENTITY.into_response())?;
+
+    let quote = FareQuote {
+        id: Uuid::new_v4(),
+        rider_id: payload.rider_id,
+        product: payload.product,
+        origin_lat: origin.lat,
+        origin_lng: origin.lng,
+        destination_lat: destination.lat,
+        destination_lng: destination.lng,
+        distance_m: trip.distance_m,
+        duration_secs: trip.duration_secs,
+        fare,
+        currency: state.pricing_engine.currency().to_string(),
+        created_at: now,
+        expires_at: now + state.pricing_engine.quote_ttl(),
+        ride_id: None,
+    };
+
+    match state.fare_quotes_repo.create_quote(&quote).await {
+        Ok(_) => Ok(Json(quote)),
+        Err(_) => Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+// 404 unknown quote, 409 quote for another rider/product/trip, 410 expired
+fn check_quote(
+    quote: Option<FareQuote>,
+    ride: &RideRequestedEvent,
+) -> Result<FareQuote, axum::http::StatusCode> {
+    let quote = quote.ok_or(axum::http::StatusCode::NOT_FOUND)?;
+
+    let drift = |quoted_lat, quoted_lng, lat, lng| {
+        haversine_m(LatLng::new(quoted_lat, quoted_lng), LatLng::new(lat, lng))
+    };
+    if quote.rider_id != ride.rider_id
+        || quote.product != ride.requirements.product
+        || drift(quote.origin_lat, quote.origin_lng, ride.origin_lat, ride.origin_lng)
+            > QUOTE_MAX_DRIFT_M
+        || drift(
+            quote.destination_lat,
+            quote.destination_lng,
+            ride.destination_lat,
+            ride.destination_lng,
+        ) > QUOTE_MAX_DRIFT_M
+    {
+        return Err(axum::http::StatusCode::CONFLICT);
+    }
+    if quote.expires_at <= ride.created_at {
+        return Err(axum::http::StatusCode::GONE);
+    }
+    Ok(quote)
+}
+
+// check_quote, then 409 if the quote was already used
+async fn claim_quote(
+    state: &AppState,
+    quote_id: Uuid,
+    ride: &RideRequestedEvent,
+) -> Result<QuotedFare, axum::http::StatusCode> {
+    let quote = match state.fare_quotes_repo.get_quote_by_id(quote_id).await {
+        Ok(quote) => check_quote(quote, ride)?,
+        Err(_) => return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    match state.fare_quotes_repo.claim_quote(quote_id, ride.ride_id).await {
+        Ok(true) => Ok(QuotedFare {
+            quote_id,
+            total: quote.fare.total,
+            currency: quote.currency,
+        }),
+        // used by another ride in the meantime (or expired right now)
+        Ok(false) => Err(axum::http::StatusCode::CONFLICT),
+        Err(_) => Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR),
+    }
 }
 
 async fn request_ride(
     state: axum::extract::State<Arc<AppState>>,
     Json(payload): Json<RequestRide>,
-) -> Result<(), axum::http::StatusCode> {
+) -> Result<(), Response> {
     // todo: validate rider exists and isn't currently in a ride. I will worry about that later.
 
+    let origin = LatLng::new(payload.origin_lat, payload.origin_lng);
+    let destination = LatLng::new(payload.destination_lat, payload.destination_lng);
+    if payload.seats == Some(0) || !valid_coordinates(origin) || !valid_coordinates(destination) {
+        return Err(axum::http::StatusCode::BAD_REQUEST.into_response());
+    }
+    check_service_area(&state.service_area, origin, destination)
+        .map_err(IntoResponse::into_response)?;
+    let requirements = RideRequirements {
+        product: payload.product,
+        pet_friendly: payload.pet_friendly,
+        wheelchair_accessible: payload.wheelchair_accessible,
+        seats: payload.seats.unwrap_or(1),
+    };
+
     let ride_request_event = RideRequestedEvent {
         ride_id: Uuid::new_v4(),
         rider_id: payload.rider_id,
@@ -55,7 +232,16 @@ async fn request_ride(
         origin_lng: payload.origin_lng,
         destination_lat: payload.destination_lat,
         destination_lng: payload.destination_lng,
-        created_at: Utc::now(),
+        created_at: state.clock.now(),
+        requirements,
+    };
+    let quoted_fare = match payload.quote_id {
+        Some(quote_id) => Some(
+            claim_quote(&state, quote_id, &ride_request_event)
+                .await
+                .map_err(IntoResponse::into_response)?,
+        ),
+        None => None,
     };
     let ride_request = CreateRideRequest {
         ride_id: ride_request_event.ride_id,
@@ -65,6 +251,8 @@ async fn request_ride(
         destination_lat: ride_request_event.destination_lat,
         destination_lng: ride_request_event.destination_lng,
         created_at: ride_request_event.created_at,
+        requirements: ride_request_event.requirements.clone(),
+        quoted_fare,
     };
 
     // You should send the event after calling the repository, for these important reasons:
@@ -77,20 +265,30 @@ async fn request_ride(
         Ok(_) => {
             // 2. Then, send event (with the actual ride data including generated ID)
             let ride_request_data = serde_json::to_vec(&ride_request_event).unwrap_or_default();
-            if let Err(_) = state
+            if state
                 .messaging_client
                 .publish(RIDE_REQUESTED_SUBJECT.to_string(), ride_request_data)
                 .await
+                .is_err()
             {
                 // todo: proper clean up, like delete the db transaction or retry logic could be implemented here
                 eprintln!("Failed to send ride requested event");
-                return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+                return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response());
             }
 
             Ok(())
         }
 
-        Err(_) => Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR),
+        Err(_) => {
+            // give the quote back so the rider can retry with it
+            if let Some(quote_id) = payload.quote_id {
+                let _ = state
+                    .fare_quotes_repo
+                    .release_quote(quote_id, ride_request_event.ride_id)
+                    .await;
+            }
+            Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
     }
 }
 
@@ -113,5 +311,94 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/riders", post(create_rider))
         .route("/riders/{id}", axum::routing::get(get_rider))
         .route("/rides", post(request_ride))
+        .route("/rides/estimate", post(estimate_ride))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::{PricingConfig, PricingEngine};
+    use axum::http::StatusCode;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn now() -> DateTime<Utc> {
+        "2025-03-03T08:00:00Z".parse().unwrap()
+    }
+
+    fn quote(rider_id: Uuid) -> FareQuote {
+        let engine = PricingEngine::new(PricingConfig::default());
+        FareQuote {
+            id: Uuid::new_v4(),
+            rider_id,
+            product: RideProduct::Economy,
+            origin_lat: 37.7749,
+            origin_lng: -122.4194,
+            destination_lat: 37.8044,
+            destination_lng: -122.2712,
+            distance_m: 15_000.0,
+            duration_secs: 1_200.0,
+            fare: engine.price(RideProduct::Economy, 15_000.0, 1_200.0, 1.0).unwrap(),
+            currency: engine.currency().to_string(),
+            created_at: now(),
+            expires_at: now() + engine.quote_ttl(),
+            ride_id: None,
+        }
+    }
+
+    // the ride the quote was made for, a minute later
+    fn ride(quote: &FareQuote) -> RideRequestedEvent {
+        RideRequestedEvent {
+            ride_id: Uuid::new_v4(),
+            rider_id: quote.rider_id,
+            origin_lat: quote.origin_lat,
+            origin_lng: quote.origin_lng,
+            destination_lat: quote.destination_lat,
+            destination_lng: quote.destination_lng,
+            created_at: now() + Duration::minutes(1),
+            requirements: RideRequirements::default(),
+        }
+    }
+
+    #[test]
+    fn matching_quote_is_accepted() {
+        let quote = quote(Uuid::new_v4());
+        let ride = ride(&quote);
+
+        assert_eq!(check_quote(Some(quote.clone()), &ride).unwrap().id, quote.id);
+    }
+
+    #[test]
+    fn unknown_quote_is_not_found() {
+        let ride = ride(&quote(Uuid::new_v4()));
+
+        assert_eq!(check_quote(None, &ride).err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn expired_quote_is_gone() {
+        let quote = quote(Uuid::new_v4());
+        let mut ride = ride(&quote);
+
+        ride.created_at = quote.expires_at;
+        assert_eq!(check_quote(Some(quote), &ride).err(), Some(StatusCode::GONE));
+    }
+
+    #[test]
+    fn quote_for_another_ride_conflicts() {
+        let quote = quote(Uuid::new_v4());
+        let conflicts = |change: fn(&mut RideRequestedEvent)| {
+            let mut ride = ride(&quote);
+            change(&mut ride);
+            check_quote(Some(quote.clone()), &ride).err() == Some(StatusCode::CONFLICT)
+        };
+
+        assert!(conflicts(|ride| ride.rider_id = Uuid::new_v4()));
+        assert!(conflicts(|ride| ride.requirements.product = RideProduct::Lux));
+        // about 330 m north of the quoted pickup, then dropoff
+        assert!(conflicts(|ride| ride.origin_lat += 0.003));
+        assert!(conflicts(|ride| ride.destination_lat += 0.003));
+        // about 110 m is within the drift allowed
+        assert!(!conflicts(|ride| ride.origin_lat += 0.001));
+    }
+}
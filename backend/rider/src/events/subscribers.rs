@@ -0,0 +1,82 @@
+// "subscribers": NATS subscriptions → stream incoming events
+// the rider service listens for surge and traffic incidents, the rest of its events are outgoing
+
+use std::sync::Arc;
+
+use common::clock::SharedClock;
+use common::eta::EtaEngine;
+use common::events_schema::{
+    SurgeUpdatedEvent, TrafficIncidentClearedEvent, TrafficIncidentReportedEvent,
+};
+use common::subjects::{
+    SURGE_UPDATED_SUBJECT, TRAFFIC_INCIDENT_CLEARED_SUBJECT, TRAFFIC_INCIDENT_REPORTED_SUBJECT,
+};
+use common::surge::SurgeMultipliers;
+use futures_util::StreamExt;
+use ubersimx_messaging::{messagingclient::MessagingClient, Messaging};
+
+/// Keeps `surge` up to date with the multipliers the matcher publishes.
+pub async fn subscribe_surge_updates(
+    messaging_client: Arc<MessagingClient>,
+    surge: Arc<SurgeMultipliers>,
+) -> anyhow::Result<()> {
+    let mut stream = messaging_client
+        .subscribe(SURGE_UPDATED_SUBJECT.to_string())
+        .await?;
+
+    tokio::spawn(async move {
+        while let Some(msg) = stream.next().await {
+            let Ok(msg) = msg else { continue };
+            match serde_json::from_slice::<SurgeUpdatedEvent>(&msg.data) {
+                Ok(event) => surge.apply(&event),
+                Err(e) => eprintln!("Failed to parse surge update: {:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Applies the traffic incidents reported to the driver service, so quoted trip durations
+/// see the same closures as the matcher's pickup ETAs.
+pub async fn subscribe_traffic_incidents(
+    messaging_client: Arc<MessagingClient>,
+    eta_engine: Arc<EtaEngine>,
+    clock: SharedClock,
+) -> anyhow::Result<()> {
+    let mut reported = messaging_client
+        .subscribe(TRAFFIC_INCIDENT_REPORTED_SUBJECT.to_string())
+        .await?;
+    let mut cleared = messaging_client
+        .subscribe(TRAFFIC_INCIDENT_CLEARED_SUBJECT.to_string())
+        .await?;
+
+    let engine = eta_engine.clone();
+    tokio::spawn(async move {
+        while let Some(msg) = reported.next().await {
+            let Ok(msg) = msg else { continue };
+            match serde_json::from_slice::<TrafficIncidentReportedEvent>(&msg.data) {
+                Ok(event) => {
+                    engine
+                        .traffic()
+                        .add_incident(engine.graph(), event.incident, clock.now());
+                }
+                Err(e) => eprintln!("Failed to parse traffic incident: {:?}", e),
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(msg) = cleared.next().await {
+            let Ok(msg) = msg else { continue };
+            match serde_json::from_slice::<TrafficIncidentClearedEvent>(&msg.data) {
+                Ok(event) => {
+                    eta_engine.traffic().clear_incident(event.incident_id);
+                }
+                Err(e) => eprintln!("Failed to parse cleared traffic incident: {:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
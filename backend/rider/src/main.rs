@@ -2,14 +2,27 @@ pub mod api {
     pub(crate) mod router;
 }
 
+pub mod events {
+    pub mod subscribers;
+}
+
 pub mod models;
+pub mod pricing;
 
 pub mod repository {
+    pub mod fare_quotes_repository;
     pub mod riders_repository;
     pub mod rides_repository;
 }
 
 use api::router::{create_router, AppState};
+use common::eta::graph::{GridSpec, RoadGraph};
+use common::eta::traffic::TrafficProfile;
+use common::eta::EtaEngine;
+use common::service_area::ServiceArea;
+use common::surge::SurgeMultipliers;
+use pricing::{PricingConfig, PricingEngine};
+use repository::fare_quotes_repository::FareQuotesRepository;
 use repository::riders_repository::RidersRepository;
 use repository::rides_repository::RidesRepository;
 use sqlx::postgres::PgPoolOptions;
@@ -37,16 +50,59 @@ async fn main() -> Result<()> {
         .connect(&database_url)
         .await?;
 
+    // system time unless CLOCK_START is set, simulations share a scaled clock with the simulator
+    let clock = common::clock::clock_from_env()?;
+
     let riders_repo = Arc::new(RidersRepository::new(pool.clone()));
-    let rides_repo = Arc::new(RidesRepository::new(pool.clone()));
+    let rides_repo = Arc::new(RidesRepository::new(pool.clone(), clock.clone()));
+    let fare_quotes_repo = Arc::new(FareQuotesRepository::new(pool.clone(), clock.clone()));
+
+    // trip distance and duration for fare quotes, same road graph and traffic as the other services
+    let road_graph = match env::var("ROAD_GRAPH_PATH") {
+        Ok(path) => RoadGraph::load(&path)?,
+        Err(_) => RoadGraph::generate_grid(&GridSpec::default()),
+    };
+    let traffic_profile = match env::var("TRAFFIC_PROFILE_PATH") {
+        Ok(path) => TrafficProfile::load(&path)?,
+        Err(_) => TrafficProfile::default(),
+    };
+    let eta_engine = Arc::new(EtaEngine::new(road_graph, traffic_profile));
+
+    // GeoJSON operating area and zones, anywhere goes without one
+    let service_area = Arc::new(match env::var("SERVICE_AREA_PATH") {
+        Ok(path) => ServiceArea::load(&path)?,
+        Err(_) => ServiceArea::unrestricted(),
+    });
+
+    let mut pricing_config = PricingConfig::default();
+    if let Some(ttl) = env::var("FARE_QUOTE_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+    {
+        pricing_config.quote_ttl = chrono::Duration::seconds(ttl);
+    }
+    let pricing_engine = Arc::new(PricingEngine::new(pricing_config));
 
     // Connect to your messaging service
     let client = Arc::new(MessagingClient::connect(&messaging_url).await.unwrap());
 
+    // surge multipliers published by the matcher, it republishes ongoing surges every few
+    // seconds so anything older than a minute is stale
+    let surge = Arc::new(SurgeMultipliers::new(chrono::Duration::minutes(1)));
+    events::subscribers::subscribe_surge_updates(client.clone(), surge.clone()).await?;
+    events::subscribers::subscribe_traffic_incidents(client.clone(), eta_engine.clone(), clock.clone())
+        .await?;
+
     let state = Arc::new(AppState {
         riders_repo,
         rides_repo,
+        fare_quotes_repo,
         messaging_client: client,
+        pricing_engine,
+        eta_engine,
+        surge,
+        service_area,
+        clock,
     });
 
     let app = create_router(state);
@@ -10,9 +10,12 @@
 // 6. Reusability - Models can be shared between multiple repositories or services
 
 use chrono::{DateTime, Utc};
+use common::vehicle::{RideProduct, RideRequirements};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::pricing::FareBreakdown;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Rider {
     pub id: Uuid,
@@ -51,4 +54,35 @@ pub struct CreateRideRequest {
     pub destination_lat: f64,
     pub destination_lng: f64,
     pub created_at: DateTime<Utc>,
-}
\ No newline at end of file
+    pub requirements: RideRequirements,
+
+    /// set when the ride was requested with a quote, the fare the rider agreed to
+    pub quoted_fare: Option<QuotedFare>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuotedFare {
+    pub quote_id: Uuid,
+    pub total: i64,
+    pub currency: String,
+}
+
+/// A priced trip, valid until `expires_at` and for one ride only.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FareQuote {
+    pub id: Uuid,
+    pub rider_id: Uuid,
+    pub product: RideProduct,
+    pub origin_lat: f64,
+    pub origin_lng: f64,
+    pub destination_lat: f64,
+    pub destination_lng: f64,
+    pub distance_m: f64,
+    pub duration_secs: f64,
+    pub fare: FareBreakdown,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// the ride that used the quote
+    pub ride_id: Option<Uuid>,
+}
@@ -0,0 +1,180 @@
+// Pricing - turns a trip (product, distance, duration, surge) into a fare.
+//
+// Money is kept in integer cents end to end, only the per km / per minute rates get
+// multiplied with fractional values and the result is rounded once per component.
+
+use std::collections::HashMap;
+
+use common::vehicle::RideProduct;
+use serde::{Deserialize, Serialize};
+
+/// Rates of one product, all in cents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateCard {
+    pub base_fare: i64,
+    pub per_km: i64,
+    pub per_minute: i64,
+    /// the fare before the booking fee never goes below this
+    pub minimum_fare: i64,
+    /// flat, not affected by surge
+    pub booking_fee: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PricingConfig {
+    pub currency: String,
+    pub rate_cards: HashMap<RideProduct, RateCard>,
+    /// how long a quote can be used to request a ride
+    pub quote_ttl: chrono::Duration,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        let card = |base_fare, per_km, per_minute, minimum_fare, booking_fee| RateCard {
+            base_fare,
+            per_km,
+            per_minute,
+            minimum_fare,
+            booking_fee,
+        };
+        Self {
+            currency: "USD".to_string(),
+            rate_cards: HashMap::from([
+                (RideProduct::Economy, card(250, 110, 30, 700, 275)),
+                (RideProduct::Comfort, card(350, 150, 40, 950, 275)),
+                (RideProduct::Xl, card(450, 200, 50, 1200, 275)),
+                (RideProduct::Lux, card(800, 325, 80, 2000, 275)),
+            ]),
+            quote_ttl: chrono::Duration::minutes(2),
+        }
+    }
+}
+
+/// What the rider pays and why, in cents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FareBreakdown {
+    pub base_fare: i64,
+    pub distance_fare: i64,
+    pub time_fare: i64,
+    /// applied to base + distance + time, 1.0 without surge
+    pub surge_multiplier: f64,
+    /// added when the surged fare was below the product minimum
+    pub minimum_fare_adjustment: i64,
+    pub booking_fee: i64,
+    pub total: i64,
+}
+
+pub struct PricingEngine {
+    config: PricingConfig,
+}
+
+impl PricingEngine {
+    pub fn new(config: PricingConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.config.currency
+    }
+
+    pub fn quote_ttl(&self) -> chrono::Duration {
+        self.config.quote_ttl
+    }
+
+    /// None if there is no rate card for the product.
+    pub fn price(
+        &self,
+        product: RideProduct,
+        distance_m: f64,
+        duration_secs: f64,
+        surge_multiplier: f64,
+    ) -> Option<FareBreakdown> {
+        let card = self.config.rate_cards.get(&product)?;
+        // surge never discounts
+        let surge_multiplier = surge_multiplier.max(1.0);
+
+        let distance_fare = (card.per_km as f64 * distance_m / 1000.0).round() as i64;
+        let time_fare = (card.per_minute as f64 * duration_secs / 60.0).round() as i64;
+        let surged =
+            ((card.base_fare + distance_fare + time_fare) as f64 * surge_multiplier).round() as i64;
+        let minimum_fare_adjustment = (card.minimum_fare - surged).max(0);
+
+        Some(FareBreakdown {
+            base_fare: card.base_fare,
+            distance_fare,
+            time_fare,
+            surge_multiplier,
+            minimum_fare_adjustment,
+            booking_fee: card.booking_fee,
+            total: surged + minimum_fare_adjustment + card.booking_fee,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // economy only: 2.50 base, 1.10/km, 0.30/min, 7.00 minimum, 2.75 booking fee
+    fn engine() -> PricingEngine {
+        let mut config = PricingConfig::default();
+        config.rate_cards.retain(|product, _| *product == RideProduct::Economy);
+        PricingEngine::new(config)
+    }
+
+    #[test]
+    fn fare_adds_up_the_components() {
+        let fare = engine().price(RideProduct::Economy, 10_000.0, 900.0, 1.0).unwrap();
+
+        assert_eq!(fare.base_fare, 250);
+        assert_eq!(fare.distance_fare, 1100);
+        assert_eq!(fare.time_fare, 450);
+        assert_eq!(fare.minimum_fare_adjustment, 0);
+        assert_eq!(fare.total, 250 + 1100 + 450 + 275);
+    }
+
+    #[test]
+    fn short_trip_is_raised_to_the_minimum_fare() {
+        let fare = engine().price(RideProduct::Economy, 1_000.0, 120.0, 1.0).unwrap();
+
+        // 250 + 110 + 60 = 420, 280 short of the minimum
+        assert_eq!(fare.minimum_fare_adjustment, 280);
+        assert_eq!(fare.total, 700 + 275);
+    }
+
+    #[test]
+    fn surge_applies_before_the_minimum_and_not_to_the_booking_fee() {
+        let fare = engine().price(RideProduct::Economy, 10_000.0, 900.0, 1.5).unwrap();
+        assert_eq!(fare.surge_multiplier, 1.5);
+        assert_eq!(fare.total, 2700 + 275);
+
+        // 420 * 1.5 = 630 is still below the minimum
+        let fare = engine().price(RideProduct::Economy, 1_000.0, 120.0, 1.5).unwrap();
+        assert_eq!(fare.minimum_fare_adjustment, 70);
+        assert_eq!(fare.total, 700 + 275);
+    }
+
+    #[test]
+    fn surge_never_discounts() {
+        let fare = engine().price(RideProduct::Economy, 10_000.0, 900.0, 0.5).unwrap();
+
+        assert_eq!(fare.surge_multiplier, 1.0);
+        assert_eq!(fare.total, 1800 + 275);
+    }
+
+    #[test]
+    fn components_are_rounded_to_cents() {
+        // 1.10 * 1.2345 km = 135.795, 0.30 * 1.5 min = 45, surged (250 + 136 + 45) * 1.7 = 732.7
+        let fare = engine().price(RideProduct::Economy, 1_234.5, 90.0, 1.7).unwrap();
+
+        assert_eq!(fare.distance_fare, 136);
+        assert_eq!(fare.time_fare, 45);
+        assert_eq!(fare.minimum_fare_adjustment, 0);
+        assert_eq!(fare.total, 733 + 275);
+    }
+
+    #[test]
+    fn product_without_a_rate_card_has_no_price() {
+        assert!(engine().price(RideProduct::Lux, 10_000.0, 900.0, 1.0).is_none());
+    }
+}
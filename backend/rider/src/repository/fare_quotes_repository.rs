@@ -0,0 +1,138 @@
+use crate::models::FareQuote;
+use crate::pricing::FareBreakdown;
+use common::clock::SharedClock;
+use common::vehicle::RideProduct;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+const FARE_QUOTE_COLUMNS: &str = r#"
+    id, rider_id, product, origin_lat, origin_lng, destination_lat, destination_lng,
+    distance_m, duration_secs,
+    base_fare, distance_fare, time_fare, surge_multiplier, minimum_fare_adjustment, booking_fee, total,
+    currency, created_at, expires_at, ride_id
+"#;
+
+pub struct FareQuotesRepository {
+    pool: PgPool,
+    // quotes expire on the service clock, which isn't the database's in a simulation
+    clock: SharedClock,
+}
+
+impl FareQuotesRepository {
+    pub fn new(pool: PgPool, clock: SharedClock) -> Self {
+        Self { pool, clock }
+    }
+
+    pub async fn create_quote(&self, quote: &FareQuote) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO fare_quotes (id, rider_id, product, origin_lat, origin_lng, destination_lat, destination_lng,
+                                     distance_m, duration_secs,
+                                     base_fare, distance_fare, time_fare, surge_multiplier, minimum_fare_adjustment,
+                                     booking_fee, total, currency, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
+            "#,
+        )
+        .bind(quote.id)
+        .bind(quote.rider_id)
+        .bind(quote.product.as_str())
+        .bind(quote.origin_lat)
+        .bind(quote.origin_lng)
+        .bind(quote.destination_lat)
+        .bind(quote.destination_lng)
+        .bind(quote.distance_m)
+        .bind(quote.duration_secs)
+        .bind(quote.fare.base_fare)
+        .bind(quote.fare.distance_fare)
+        .bind(quote.fare.time_fare)
+        .bind(quote.fare.surge_multiplier)
+        .bind(quote.fare.minimum_fare_adjustment)
+        .bind(quote.fare.booking_fee)
+        .bind(quote.fare.total)
+        .bind(&quote.currency)
+        .bind(quote.created_at)
+        .bind(quote.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_quote_by_id(&self, quote_id: Uuid) -> Result<Option<FareQuote>, sqlx::Error> {
+        // too many columns for a tuple row
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM fare_quotes WHERE id = $1",
+            FARE_QUOTE_COLUMNS
+        ))
+        .bind(quote_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| fare_quote_from_row(&row)).transpose()
+    }
+
+    /// Marks the quote as used by the ride. Only one ride can claim a quote and only before it
+    /// expires, returns false otherwise.
+    pub async fn claim_quote(&self, quote_id: Uuid, ride_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE fare_quotes
+            SET ride_id = $2
+            WHERE id = $1 AND ride_id IS NULL AND expires_at > $3
+            "#,
+        )
+        .bind(quote_id)
+        .bind(ride_id)
+        .bind(self.clock.now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Undoes `claim_quote` when the ride couldn't be created after all.
+    pub async fn release_quote(&self, quote_id: Uuid, ride_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE fare_quotes SET ride_id = NULL WHERE id = $1 AND ride_id = $2")
+            .bind(quote_id)
+            .bind(ride_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn fare_quote_from_row(row: &PgRow) -> Result<FareQuote, sqlx::Error> {
+    let product: String = row.try_get("product")?;
+    // products are only written by us, an unknown one is a bad migration and must not be
+    // priced as something else
+    let product = RideProduct::parse(&product).ok_or_else(|| sqlx::Error::ColumnDecode {
+        index: "product".to_string(),
+        source: format!("unknown ride product {:?}", product).into(),
+    })?;
+    Ok(FareQuote {
+        id: row.try_get("id")?,
+        rider_id: row.try_get("rider_id")?,
+        product,
+        origin_lat: row.try_get("origin_lat")?,
+        origin_lng: row.try_get("origin_lng")?,
+        destination_lat: row.try_get("destination_lat")?,
+        destination_lng: row.try_get("destination_lng")?,
+        distance_m: row.try_get("distance_m")?,
+        duration_secs: row.try_get("duration_secs")?,
+        fare: FareBreakdown {
+            base_fare: row.try_get("base_fare")?,
+            distance_fare: row.try_get("distance_fare")?,
+            time_fare: row.try_get("time_fare")?,
+            surge_multiplier: row.try_get("surge_multiplier")?,
+            minimum_fare_adjustment: row.try_get("minimum_fare_adjustment")?,
+            booking_fee: row.try_get("booking_fee")?,
+            total: row.try_get("total")?,
+        },
+        currency: row.try_get("currency")?,
+        created_at: row.try_get("created_at")?,
+        expires_at: row.try_get("expires_at")?,
+        ride_id: row.try_get("ride_id")?,
+    })
+}
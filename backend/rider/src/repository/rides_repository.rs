@@ -1,22 +1,25 @@
 use crate::models::{CreateRideRequest, Ride};
+use common::clock::SharedClock;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 pub struct RidesRepository {
     // SQLx PgPool is already Arc-like internally - PgPool itself is a connection pool that's designed to be cloned cheaply and shared across threads. It's essentially a smart pointer to the underlying pool.
     pool: PgPool,
+    clock: SharedClock,
 }
 
 impl RidesRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, clock: SharedClock) -> Self {
+        Self { pool, clock }
     }
 
     pub async fn create_ride(&self, request: CreateRideRequest) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            INSERT INTO rides (id, rider_id, origin_lat, origin_lng, destination_lat, destination_lng, status, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, 'requested', $7, $7)
+            INSERT INTO rides (id, rider_id, origin_lat, origin_lng, destination_lat, destination_lng, status, created_at, updated_at,
+                               product, seats, pet_friendly, wheelchair_accessible, quote_id, fare_cents, currency)
+            VALUES ($1, $2, $3, $4, $5, $6, 'requested', $7, $7, $8, $9, $10, $11, $12, $13, $14)
             "#
         )
         .bind(request.ride_id)
@@ -26,6 +29,13 @@ impl RidesRepository {
         .bind(request.destination_lat)
         .bind(request.destination_lng)
         .bind(request.created_at)
+        .bind(request.requirements.product.as_str())
+        .bind(request.requirements.seats as i16)
+        .bind(request.requirements.pet_friendly)
+        .bind(request.requirements.wheelchair_accessible)
+        .bind(request.quoted_fare.as_ref().map(|fare| fare.quote_id))
+        .bind(request.quoted_fare.as_ref().map(|fare| fare.total))
+        .bind(request.quoted_fare.map(|fare| fare.currency))
         .execute(&self.pool)
         .await?;
 
@@ -140,12 +150,13 @@ impl RidesRepository {
         sqlx::query(
             r#"
             UPDATE rides 
-            SET status = $2, updated_at = NOW()
+            SET status = $2, updated_at = $3
             WHERE id = $1
             "#,
         )
         .bind(ride_id)
         .bind(status)
+        .bind(self.clock.now())
         .execute(&self.pool)
         .await?;
 
@@ -0,0 +1,34 @@
+pub mod driver;
+pub mod rider;
+
+use std::time::Duration;
+
+use rand::Rng;
+use uuid::Uuid;
+
+/// Exponentially distributed duration with the given mean, the gaps of a Poisson process.
+pub fn exponential(rng: &mut impl Rng, mean: Duration) -> Duration {
+    let u: f64 = rng.random();
+    mean.mul_f64(-(1.0 - u).ln())
+}
+
+/// Picks one of the items with probability proportional to its weight.
+pub fn pick_weighted<T: Copy>(rng: &mut impl Rng, items: &[(T, f64)]) -> Option<T> {
+    let total: f64 = items.iter().map(|(_, w)| w.max(0.0)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut target = rng.random::<f64>() * total;
+    for (item, weight) in items {
+        target -= weight.max(0.0);
+        if target < 0.0 {
+            return Some(*item);
+        }
+    }
+    items.last().map(|(item, _)| *item)
+}
+
+/// Uuid drawn from the seeded generator, so ids are the same on every run.
+pub fn random_uuid(rng: &mut impl Rng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.random()).into_uuid()
+}
@@ -0,0 +1,327 @@
+// A simulated driver: signs up with a vehicle, goes online for one shift and cruises around
+// the city, answering the ride offers the driver service pushes over the websocket. Accepted
+// rides are driven to the pickup and then to the dropoff. Now and then the driver takes a
+// break and is offline for a while. Repositioning suggestions are followed with the scenario's
+// probability, the driver then heads to the suggested cell instead of a random intersection.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::geo::LatLng;
+use common::vehicle::VehicleClass;
+use common::ws_schema::{DriverLocationV1, Envelope, RepositionSuggestion, RideOffer, WSMsgType};
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+use super::{exponential, pick_weighted};
+use crate::api::DriverApi;
+use crate::clock::SimClock;
+use crate::map::{CityMap, Path};
+use crate::report::{Leg, SharedKpis};
+use crate::scenario::{DriverConfig, Scenario};
+
+type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+#[derive(Debug, Clone)]
+pub struct DriverBehavior {
+    /// chance an offer is accepted
+    pub acceptance_probability: f64,
+    /// simulated time to look at an offer before answering
+    pub response_delay: Duration,
+    /// mean time online between breaks
+    pub mean_time_between_breaks: Duration,
+    pub mean_break_length: Duration,
+    /// how often the app reports the position
+    pub location_interval: Duration,
+    /// chance a repositioning suggestion is followed
+    pub follow_repositioning: f64,
+}
+
+impl From<&DriverConfig> for DriverBehavior {
+    fn from(config: &DriverConfig) -> Self {
+        let minutes = |m: f64| Duration::from_secs_f64(m * 60.0);
+        Self {
+            acceptance_probability: config.acceptance_probability,
+            response_delay: Duration::from_secs_f64(config.response_delay_secs),
+            mean_time_between_breaks: minutes(config.mean_time_between_breaks_mins),
+            mean_break_length: minutes(config.mean_break_length_mins),
+            location_interval: Duration::from_secs_f64(config.location_interval_secs),
+            follow_repositioning: config.follow_repositioning,
+        }
+    }
+}
+
+/// Vehicle and shift of a driver, drawn the same way in both modes.
+pub struct DriverPlan {
+    pub vehicle_class: VehicleClass,
+    /// simulated time since the start of the run the driver goes online and offline
+    pub shift_start: Duration,
+    pub shift_end: Duration,
+}
+
+impl DriverPlan {
+    /// None if the vehicle mix is empty.
+    pub fn draw(
+        scenario: &Scenario,
+        vehicle_mix: &[(VehicleClass, f64)],
+        rng: &mut ChaCha8Rng,
+    ) -> Option<Self> {
+        let vehicle_class = pick_weighted(rng, vehicle_mix)?;
+        let window = Duration::from_secs_f64(scenario.drivers.shift_start_window_mins * 60.0);
+        let hours = scenario.drivers.shift_hours;
+        let shift_start = window.mul_f64(rng.random());
+        let shift_length = Duration::from_secs_f64(rng.random_range(hours.min..=hours.max) * 3600.0);
+        Some(Self {
+            vehicle_class,
+            shift_start,
+            shift_end: (shift_start + shift_length).min(scenario.duration()),
+        })
+    }
+}
+
+enum Phase {
+    Cruising,
+    ToPickup(RideOffer),
+    ToDropoff(RideOffer),
+    OnBreak { until: Duration },
+}
+
+pub struct DriverAgent {
+    pub index: usize,
+    pub plan: DriverPlan,
+    pub behavior: DriverBehavior,
+    pub api: DriverApi,
+    pub clock: SimClock,
+    pub map: Arc<CityMap>,
+    pub rng: ChaCha8Rng,
+    pub kpis: SharedKpis,
+}
+
+impl DriverAgent {
+    pub async fn run(mut self) -> Result<(), anyhow::Error> {
+        let created = self
+            .api
+            .create_driver(&format!("sim driver {}", self.index))
+            .await?;
+        let driver_id = created.id;
+        let plate_number = format!("SIM-{}", &driver_id.simple().to_string()[..8]);
+        self.api
+            .add_active_vehicle(driver_id, self.plan.vehicle_class, &plate_number)
+            .await?;
+
+        let (ws, _) = tokio_tungstenite::connect_async(self.api.ws_url(&created.ws_token.token)).await?;
+        let (mut ws_tx, ws_rx) = ws.split();
+        let (offers_tx, mut offers) = mpsc::unbounded_channel();
+        let (suggestions_tx, mut suggestions) = mpsc::unbounded_channel();
+        tokio::spawn(read_pushes(ws_rx, offers_tx, suggestions_tx));
+
+        let mut path = Path::parked(self.map.random_point(&mut self.rng));
+        self.clock
+            .sleep(self.plan.shift_start.saturating_sub(self.clock.elapsed()))
+            .await;
+        self.report_location(&mut ws_tx, driver_id, path.position()).await;
+        self.api.set_available(driver_id, true).await?;
+        self.kpis.lock().unwrap().driver_online(driver_id, self.clock.now());
+
+        let tick = self.behavior.location_interval;
+        let mut phase = Phase::Cruising;
+        // cruising towards a suggested cell rather than a random intersection
+        let mut repositioning = false;
+        // a ride taken before the end of the shift is driven to the end, the driver service has
+        // no way to drop a ride once accepted
+        while self.clock.elapsed() < self.plan.shift_end
+            || matches!(phase, Phase::ToPickup(_) | Phase::ToDropoff(_))
+        {
+            self.clock.sleep(tick).await;
+            let meters = path.advance(tick.as_secs_f64());
+            let leg = match phase {
+                Phase::ToPickup(_) => Leg::ToPickup,
+                Phase::ToDropoff(_) => Leg::WithRider,
+                Phase::Cruising if repositioning => Leg::Repositioning,
+                Phase::Cruising | Phase::OnBreak { .. } => Leg::Cruising,
+            };
+            self.kpis.lock().unwrap().driven(driver_id, meters, leg);
+            let position = path.position();
+
+            phase = match phase {
+                Phase::OnBreak { until } if self.clock.elapsed() >= until => {
+                    self.report_location(&mut ws_tx, driver_id, position).await;
+                    match self.api.set_available(driver_id, true).await {
+                        Ok(()) => self.kpis.lock().unwrap().driver_online(driver_id, self.clock.now()),
+                        Err(e) => eprintln!("Driver {} failed to come back online: {:?}", driver_id, e),
+                    }
+                    Phase::Cruising
+                }
+                // offline drivers don't report where they are
+                Phase::OnBreak { until } => Phase::OnBreak { until },
+                Phase::Cruising => {
+                    self.report_location(&mut ws_tx, driver_id, position).await;
+                    repositioning &= !path.is_done();
+                    // only the latest suggestion matters
+                    let mut suggestion = None;
+                    while let Ok(latest) = suggestions.try_recv() {
+                        suggestion = Some(latest);
+                    }
+                    if let Ok(offer) = offers.try_recv() {
+                        if self.answer_offer(driver_id, &offer).await {
+                            path = self.map.route(position, to_lat_lng(&offer.pickup), self.clock.now());
+                            Phase::ToPickup(offer)
+                        } else {
+                            Phase::Cruising
+                        }
+                    } else if self.starts_break(tick) {
+                        if let Err(e) = self.api.set_available(driver_id, false).await {
+                            eprintln!("Driver {} failed to go offline: {:?}", driver_id, e);
+                        }
+                        self.kpis.lock().unwrap().driver_offline(driver_id, self.clock.now());
+                        path = Path::parked(position);
+                        let length = exponential(&mut self.rng, self.behavior.mean_break_length);
+                        Phase::OnBreak {
+                            until: self.clock.elapsed() + length,
+                        }
+                    } else {
+                        if let Some(suggestion) = suggestion.filter(|_| self.follows_suggestion()) {
+                            path = self.map.route(position, to_lat_lng(&suggestion.target), self.clock.now());
+                            repositioning = true;
+                            self.kpis.lock().unwrap().repositioned(driver_id);
+                        } else if path.is_done() {
+                            let destination = self.map.random_point(&mut self.rng);
+                            path = self.map.route(position, destination, self.clock.now());
+                        }
+                        Phase::Cruising
+                    }
+                }
+                Phase::ToPickup(offer) => {
+                    self.report_location(&mut ws_tx, driver_id, position).await;
+                    if !path.is_done() {
+                        Phase::ToPickup(offer)
+                    } else if let Err(e) = self.api.ride_action(driver_id, "pickup", offer.ride_id).await {
+                        eprintln!("Driver {} failed to pick up ride {}: {:?}", driver_id, offer.ride_id, e);
+                        Phase::Cruising
+                    } else {
+                        path = self.map.route(position, to_lat_lng(&offer.dropoff), self.clock.now());
+                        Phase::ToDropoff(offer)
+                    }
+                }
+                Phase::ToDropoff(offer) => {
+                    self.report_location(&mut ws_tx, driver_id, position).await;
+                    if !path.is_done() {
+                        Phase::ToDropoff(offer)
+                    } else {
+                        if let Err(e) = self.api.ride_action(driver_id, "complete", offer.ride_id).await {
+                            eprintln!("Driver {} failed to complete ride {}: {:?}", driver_id, offer.ride_id, e);
+                        }
+                        Phase::Cruising
+                    }
+                }
+            };
+
+            // offers and suggestions only go to available drivers, anything that arrives while
+            // busy is stale
+            if !matches!(phase, Phase::Cruising) {
+                repositioning = false;
+                while offers.try_recv().is_ok() {}
+                while suggestions.try_recv().is_ok() {}
+            }
+        }
+
+        // end of the shift
+        self.kpis.lock().unwrap().driver_offline(driver_id, self.clock.now());
+        self.api.set_available(driver_id, false).await?;
+        let _ = ws_tx.close().await;
+        Ok(())
+    }
+
+    // accepts or rejects, true if the ride is ours
+    async fn answer_offer(&mut self, driver_id: Uuid, offer: &RideOffer) -> bool {
+        self.clock.sleep(self.behavior.response_delay).await;
+        let accept = self.rng.random_bool(self.behavior.acceptance_probability.clamp(0.0, 1.0));
+        let action = if accept { "accept" } else { "reject" };
+        match self.api.ride_action(driver_id, action, offer.ride_id).await {
+            Ok(()) => accept,
+            Err(e) => {
+                // e.g. a conflict, the ride went to someone else in the meantime
+                eprintln!("Driver {} failed to {} ride {}: {:?}", driver_id, action, offer.ride_id, e);
+                false
+            }
+        }
+    }
+
+    fn follows_suggestion(&mut self) -> bool {
+        self.rng.random_bool(self.behavior.follow_repositioning.clamp(0.0, 1.0))
+    }
+
+    fn starts_break(&mut self, tick: Duration) -> bool {
+        let mean = self.behavior.mean_time_between_breaks.as_secs_f64();
+        mean > 0.0 && self.rng.random_bool((tick.as_secs_f64() / mean).min(1.0))
+    }
+
+    // over the websocket like the app, falls back to REST if the socket is gone
+    async fn report_location(&self, ws_tx: &mut WsSink, driver_id: Uuid, position: LatLng) {
+        let update = Envelope::new(
+            WSMsgType::DriverLocationUpdate,
+            1,
+            self.clock.now().timestamp_millis(),
+            DriverLocationV1 {
+                latitude: position.lat,
+                longitude: position.lng,
+                driver_id,
+            },
+        );
+        let Ok(text) = serde_json::to_string(&update) else {
+            return;
+        };
+        if ws_tx.send(Message::Text(text.into())).await.is_ok() {
+            return;
+        }
+        if let Err(e) = self.api.update_location(driver_id, position).await {
+            eprintln!("Driver {} failed to report location: {:?}", driver_id, e);
+        }
+    }
+}
+
+// ride offers and repositioning suggestions pushed by the driver service
+async fn read_pushes(
+    mut ws_rx: futures_util::stream::SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    offers: mpsc::UnboundedSender<RideOffer>,
+    suggestions: mpsc::UnboundedSender<RepositionSuggestion>,
+) {
+    let offer_type = WSMsgType::RideOffer.to_string();
+    let suggestion_type = WSMsgType::RepositionSuggestion.to_string();
+    while let Some(Ok(msg)) = ws_rx.next().await {
+        let Message::Text(text) = msg else {
+            continue;
+        };
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&text) else {
+            continue;
+        };
+        let message_type = value.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+        let sent = if message_type == offer_type {
+            match serde_json::from_value::<Envelope<RideOffer>>(value) {
+                Ok(envelope) => offers.send(envelope.data).is_ok(),
+                Err(_) => true,
+            }
+        } else if message_type == suggestion_type {
+            match serde_json::from_value::<Envelope<RepositionSuggestion>>(value) {
+                Ok(envelope) => suggestions.send(envelope.data).is_ok(),
+                Err(_) => true,
+            }
+        } else {
+            true
+        };
+        if !sent {
+            break;
+        }
+    }
+}
+
+fn to_lat_lng(coord: &common::ws_schema::Coord) -> LatLng {
+    LatLng::new(coord.lat, coord.lng)
+}
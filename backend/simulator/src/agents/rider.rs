@@ -0,0 +1,56 @@
+// Simulated riders: the accounts of the rider pool, requesting the rides the demand
+// generator comes up with through the rider API.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::api::RiderApi;
+use crate::clock::SimClock;
+use crate::demand::DemandGenerator;
+use crate::map::CityMap;
+
+/// Signs up the accounts requests are made from.
+pub async fn create_riders(api: &RiderApi, count: usize) -> Result<Vec<Uuid>, anyhow::Error> {
+    let mut riders = Vec::with_capacity(count);
+    for index in 0..count {
+        riders.push(api.create_rider(&format!("sim rider {}", index)).await?);
+    }
+    Ok(riders)
+}
+
+pub struct DemandAgent {
+    pub demand: DemandGenerator,
+    pub riders: Arc<Vec<Uuid>>,
+    pub api: RiderApi,
+    pub clock: SimClock,
+    pub map: Arc<CityMap>,
+}
+
+impl DemandAgent {
+    /// Requests rides until `duration` of simulated time has passed since the start of the run.
+    pub async fn run(mut self, duration: Duration) -> Result<(), anyhow::Error> {
+        let mut at = self.clock.elapsed();
+
+        while let Some(request) = self.demand.next_request(at, duration, &self.map) {
+            at = request.at;
+            self.clock.sleep(at.saturating_sub(self.clock.elapsed())).await;
+
+            let rider_id = self.riders[request.rider];
+            if let Err(e) = self
+                .api
+                .request_ride(rider_id, request.origin, request.destination, request.product)
+                .await
+            {
+                eprintln!(
+                    "Rider {} failed to request a ride ({}): {:?}",
+                    rider_id,
+                    self.demand.source_name(request.source),
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+// Thin clients for the public driver and rider APIs, the simulator uses nothing else.
+
+use anyhow::anyhow;
+use common::geo::LatLng;
+use common::vehicle::{RideProduct, VehicleClass};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreatedDriver {
+    pub id: Uuid,
+    pub ws_token: WsToken,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsToken {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedVehicle {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedRider {
+    id: Uuid,
+}
+
+#[derive(Clone)]
+pub struct DriverApi {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl DriverApi {
+    pub fn new(http: reqwest::Client, base_url: &str) -> Self {
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// ws:// url of the driver websocket for a token from `create_driver`.
+    pub fn ws_url(&self, token: &str) -> String {
+        let base = self
+            .base_url
+            .replacen("https://", "wss://", 1)
+            .replacen("http://", "ws://", 1);
+        format!("{}/ws?token={}", base, token)
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) -> Result<reqwest::Response, anyhow::Error> {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("POST {} returned {}", path, response.status()));
+        }
+        Ok(response)
+    }
+
+    pub async fn create_driver(&self, name: &str) -> Result<CreatedDriver, anyhow::Error> {
+        let response = self.post("/api/v1/drivers", json!({ "name": name })).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Registers a vehicle of the class and makes it the driver's active one.
+    pub async fn add_active_vehicle(
+        &self,
+        driver_id: Uuid,
+        vehicle_class: VehicleClass,
+        plate_number: &str,
+    ) -> Result<Uuid, anyhow::Error> {
+        let seats = vehicle_seats(vehicle_class);
+        let vehicle: CreatedVehicle = self
+            .post(
+                &format!("/api/v1/drivers/{}/vehicles", driver_id),
+                json!({
+                    "make": "Sim",
+                    "model": vehicle_class.as_str(),
+                    "plate_number": plate_number,
+                    "year": 2024,
+                    "vehicle_class": vehicle_class,
+                    "seats": seats,
+                }),
+            )
+            .await?
+            .json()
+            .await?;
+        self.post(
+            &format!("/api/v1/drivers/{}/vehicles/{}/activate", driver_id, vehicle.id),
+            json!({}),
+        )
+        .await?;
+        Ok(vehicle.id)
+    }
+
+    pub async fn update_location(&self, driver_id: Uuid, position: LatLng) -> Result<(), anyhow::Error> {
+        self.post(
+            &format!("/api/v1/drivers/{}/location", driver_id),
+            json!({ "latitude": position.lat, "longitude": position.lng }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_available(&self, driver_id: Uuid, available: bool) -> Result<(), anyhow::Error> {
+        self.post(
+            &format!("/api/v1/drivers/{}/status", driver_id),
+            json!({ "driver_available": available }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// One of accept, reject, pickup and complete.
+    pub async fn ride_action(&self, driver_id: Uuid, action: &str, ride_id: Uuid) -> Result<(), anyhow::Error> {
+        self.post(
+            &format!("/api/v1/drivers/{}/ride/{}", driver_id, action),
+            json!({ "ride_id": ride_id }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RiderApi {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl RiderApi {
+    pub fn new(http: reqwest::Client, base_url: &str) -> Self {
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) -> Result<reqwest::Response, anyhow::Error> {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("POST {} returned {}", path, response.status()));
+        }
+        Ok(response)
+    }
+
+    pub async fn create_rider(&self, name: &str) -> Result<Uuid, anyhow::Error> {
+        let rider: CreatedRider = self.post("/riders", json!({ "name": name })).await?.json().await?;
+        Ok(rider.id)
+    }
+
+    pub async fn request_ride(
+        &self,
+        rider_id: Uuid,
+        origin: LatLng,
+        destination: LatLng,
+        product: RideProduct,
+    ) -> Result<(), anyhow::Error> {
+        self.post(
+            "/rides",
+            json!({
+                "rider_id": rider_id,
+                "origin_lat": origin.lat,
+                "origin_lng": origin.lng,
+                "destination_lat": destination.lat,
+                "destination_lng": destination.lng,
+                "product": product,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Passenger seats of the simulated vehicles.
+pub fn vehicle_seats(vehicle_class: VehicleClass) -> u8 {
+    match vehicle_class {
+        VehicleClass::Xl => 6,
+        _ => 4,
+    }
+}
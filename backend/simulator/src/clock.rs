@@ -0,0 +1,48 @@
+// Simulated time: starts at a chosen instant and runs `speedup` times faster than the wall
+// clock. Agents only ever look at this clock, so a run at 10x covers ten simulated minutes of
+// driving and requesting in one real minute. It's the same scaled clock the services run on
+// when started with CLOCK_START / CLOCK_SPEEDUP / CLOCK_WALL_ANCHOR, see common::clock.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::clock::{Clock, ScaledClock};
+
+#[derive(Debug, Clone)]
+pub struct SimClock {
+    inner: ScaledClock,
+}
+
+impl SimClock {
+    pub fn new(epoch: DateTime<Utc>, anchor: DateTime<Utc>, speedup: f64) -> Self {
+        Self {
+            inner: ScaledClock::new(epoch, anchor, speedup),
+        }
+    }
+
+    /// Wall time at which the simulation starts.
+    pub fn anchor(&self) -> DateTime<Utc> {
+        self.inner.anchor()
+    }
+
+    /// Sleeps until the anchor, for a CLOCK_WALL_ANCHOR in the future.
+    pub async fn wait_for_start(&self) {
+        if let Ok(wait) = (self.inner.anchor() - Utc::now()).to_std() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Simulated time passed since the start.
+    pub fn elapsed(&self) -> Duration {
+        self.inner.elapsed()
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.inner.now()
+    }
+
+    /// Sleeps for `duration` of simulated time.
+    pub async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(self.inner.to_wall(duration)).await;
+    }
+}
@@ -0,0 +1,482 @@
+// Synthetic ride demand, for the simulated riders and on its own as a load source.
+//
+// Every source of demand in the scenario (a zone's hourly curve, a commute peak, an event
+// spike) is a non-homogeneous Poisson process spread evenly over the hex cells
+// (CELL_RESOLUTION) of the area its rides start in. All of them together are sampled by
+// thinning: candidates come at the highest total rate possible within a short window and are
+// kept with probability rate / bound, so rates can change at any time, not only on the hour.
+// Which source a kept request belongs to is drawn by the sources' rates at that instant.
+// Where a ride goes follows the scenario's origin-destination matrix.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::events_schema::RideRequestedEvent;
+use common::geo::hex::{edge_length_m, HexCell, CELL_RESOLUTION};
+use common::geo::{haversine_m, LatLng};
+use common::vehicle::{RideProduct, RideRequirements};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use uuid::Uuid;
+
+use crate::agents::{exponential, pick_weighted};
+use crate::map::CityMap;
+use crate::scenario::{sorted_weights, Bounds, Scenario, SpikeDirection};
+
+// the bound on the rate is worked out per window, shorter windows reject fewer candidates
+// around peaks. Windows never cross a local hour since the zone curves jump there.
+const WINDOW: Duration = Duration::from_secs(15 * 60);
+
+// shorter trips are walked
+const MIN_TRIP_M: f64 = 1_000.0;
+
+// gives up on a destination far enough away after this many draws, for tiny maps
+const MAX_DESTINATION_DRAWS: usize = 20;
+
+// draws for a point of a cell that is also inside the area, edge cells can be mostly outside
+const MAX_POINT_DRAWS: usize = 20;
+
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+// rates are integrated in steps this long for the expected requests per cell
+const EXPECTATION_STEP: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+enum Extent {
+    Bounds(Bounds),
+    Circle { center: LatLng, radius_m: f64 },
+}
+
+impl Extent {
+    fn contains(&self, point: LatLng) -> bool {
+        match self {
+            Extent::Bounds(bounds) => bounds.contains(point),
+            Extent::Circle { center, radius_m } => haversine_m(*center, point) <= *radius_m,
+        }
+    }
+
+    fn center(&self) -> LatLng {
+        match self {
+            Extent::Bounds(bounds) => bounds.center(),
+            Extent::Circle { center, .. } => *center,
+        }
+    }
+
+    fn bounding_box(&self) -> Bounds {
+        match self {
+            Extent::Bounds(bounds) => *bounds,
+            Extent::Circle { center, radius_m } => {
+                let lat = radius_m / METERS_PER_DEGREE;
+                let lng = lat / center.lat.to_radians().cos();
+                Bounds {
+                    south: center.lat - lat,
+                    west: center.lng - lng,
+                    north: center.lat + lat,
+                    east: center.lng + lng,
+                }
+            }
+        }
+    }
+}
+
+/// Where rides start or end: the hex cells covering a zone or a venue.
+struct Area {
+    extent: Extent,
+    cells: Vec<HexCell>,
+}
+
+impl Area {
+    fn new(extent: Extent) -> Self {
+        // every cell the extent touches, found by probing points a third of a cell apart
+        let bounds = extent.bounding_box();
+        let center = extent.center();
+        let step_lat = edge_length_m(CELL_RESOLUTION) * center.lat.to_radians().cos()
+            / 3.0
+            / METERS_PER_DEGREE;
+        let step_lng = step_lat / center.lat.to_radians().cos();
+
+        let mut cells = BTreeSet::from([HexCell::of(center, CELL_RESOLUTION)]);
+        let mut lat = bounds.south;
+        while lat <= bounds.north {
+            let mut lng = bounds.west;
+            while lng <= bounds.east {
+                let point = LatLng::new(lat, lng);
+                if extent.contains(point) {
+                    cells.insert(HexCell::of(point, CELL_RESOLUTION));
+                }
+                lng += step_lng;
+            }
+            lat += step_lat;
+        }
+        Self {
+            extent,
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    // a random cell, then a random point of it inside the area
+    fn random_point(&self, rng: &mut impl Rng) -> LatLng {
+        let cell = self.cells[rng.random_range(0..self.cells.len())];
+        let corners = cell.polygon();
+        let (south, north) = corners
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(s, n), c| (s.min(c.lat), n.max(c.lat)));
+        let (west, east) = corners
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(w, e), c| (w.min(c.lng), e.max(c.lng)));
+        for _ in 0..MAX_POINT_DRAWS {
+            let point = LatLng::new(rng.random_range(south..=north), rng.random_range(west..=east));
+            if HexCell::of(point, CELL_RESOLUTION) == cell && self.extent.contains(point) {
+                return point;
+            }
+        }
+        self.extent.center()
+    }
+}
+
+enum Place {
+    Area(usize),
+    /// one of the zones' areas, by weight
+    Zones(Vec<(usize, f64)>),
+    /// a random intersection of the map
+    Anywhere,
+}
+
+enum Rate {
+    /// the zone's requests per hour curve
+    Hourly(usize),
+    /// normal bell around a local time of day
+    Bell {
+        peak_hour: f64,
+        width_hours: f64,
+        peak: f64,
+        weekdays_only: bool,
+    },
+    Window {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        rate: f64,
+    },
+}
+
+// hours between two times of day, around midnight if that is shorter
+fn hours_apart(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(24.0);
+    d.min(24.0 - d)
+}
+
+impl Rate {
+    fn at(&self, scenario: &Scenario, at: DateTime<Utc>) -> f64 {
+        match self {
+            Rate::Hourly(zone) => scenario.demand[*zone].rate_per_hour(scenario.local_hour(at)),
+            Rate::Bell {
+                peak_hour,
+                width_hours,
+                peak,
+                weekdays_only,
+            } => {
+                if *weekdays_only && !scenario.is_local_weekday(at) {
+                    return 0.0;
+                }
+                let d = hours_apart(scenario.local_time_of_day(at), *peak_hour);
+                peak * (-d * d / (2.0 * width_hours * width_hours)).exp()
+            }
+            Rate::Window { start, end, rate } => {
+                if (*start..*end).contains(&at) {
+                    *rate
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    // the highest rate between `from` and `to`, which are within one local hour
+    fn max(&self, scenario: &Scenario, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        match self {
+            Rate::Hourly(_) => self.at(scenario, from),
+            Rate::Bell {
+                peak_hour,
+                width_hours,
+                peak,
+                weekdays_only,
+            } => {
+                if *weekdays_only && !scenario.is_local_weekday(from) {
+                    return 0.0;
+                }
+                let t0 = scenario.local_time_of_day(from);
+                let t1 = t0 + (to - from).num_milliseconds() as f64 / 3.6e6;
+                let d = if (peak_hour - t0).rem_euclid(24.0) <= t1 - t0 {
+                    0.0
+                } else {
+                    hours_apart(t0, *peak_hour).min(hours_apart(t1, *peak_hour))
+                };
+                peak * (-d * d / (2.0 * width_hours * width_hours)).exp()
+            }
+            Rate::Window { start, end, rate } => {
+                if from < *end && to > *start {
+                    *rate
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+struct Source {
+    name: String,
+    rate: Rate,
+    origin: Place,
+    destination: Place,
+    product_mix: Vec<(RideProduct, f64)>,
+}
+
+/// A ride somebody asks for.
+#[derive(Debug, Clone)]
+pub struct GeneratedRequest {
+    /// since the start of the run
+    pub at: Duration,
+    /// index of the source, see DemandGenerator::source_name
+    pub source: usize,
+    /// index into the rider pool
+    pub rider: usize,
+    pub origin: LatLng,
+    pub destination: LatLng,
+    pub product: RideProduct,
+}
+
+impl GeneratedRequest {
+    /// The event the rider service would publish for the request.
+    pub fn event(&self, ride_id: Uuid, rider_id: Uuid, created_at: DateTime<Utc>) -> RideRequestedEvent {
+        RideRequestedEvent {
+            ride_id,
+            rider_id,
+            origin_lat: self.origin.lat,
+            origin_lng: self.origin.lng,
+            destination_lat: self.destination.lat,
+            destination_lng: self.destination.lng,
+            created_at,
+            requirements: RideRequirements {
+                product: self.product,
+                ..RideRequirements::default()
+            },
+        }
+    }
+}
+
+/// All requests of a scenario, in order. Shared by the live riders, the discrete event mode
+/// and the load mode, so all of them draw the same requests for a seed.
+pub struct DemandGenerator {
+    scenario: Arc<Scenario>,
+    areas: Vec<Area>,
+    sources: Vec<Source>,
+    rng: ChaCha8Rng,
+}
+
+impl DemandGenerator {
+    pub fn new(scenario: Arc<Scenario>, rng: ChaCha8Rng) -> Self {
+        // the zones' areas come first, indexed like scenario.demand, then the venues
+        let mut areas: Vec<Area> = scenario
+            .demand
+            .iter()
+            .map(|zone| Area::new(Extent::Bounds(zone.bounds)))
+            .collect();
+        let zone_index: HashMap<&str, usize> = scenario
+            .demand
+            .iter()
+            .enumerate()
+            .map(|(i, zone)| (zone.zone.as_str(), i))
+            .collect();
+        // validation made sure every zone name is known
+        let evenly = |zones: &[String]| -> Place {
+            if zones.is_empty() {
+                return Place::Anywhere;
+            }
+            Place::Zones(zones.iter().map(|z| (zone_index[z.as_str()], 1.0)).collect())
+        };
+
+        let mut sources = Vec::new();
+        for (i, zone) in scenario.demand.iter().enumerate() {
+            let destination = match scenario.od.get(&zone.zone) {
+                Some(row) => {
+                    let mut weights: Vec<(usize, f64)> =
+                        row.iter().map(|(z, w)| (zone_index[z.as_str()], *w)).collect();
+                    // the row is a hash map, the order must not depend on it
+                    weights.sort_by_key(|(z, _)| *z);
+                    Place::Zones(weights)
+                }
+                None => Place::Anywhere,
+            };
+            sources.push(Source {
+                name: zone.zone.clone(),
+                rate: Rate::Hourly(i),
+                origin: Place::Area(i),
+                destination,
+                product_mix: sorted_weights(&zone.product_mix, |p| p.as_str()),
+            });
+        }
+        for peak in &scenario.commute {
+            sources.push(Source {
+                name: peak.name.clone(),
+                rate: Rate::Bell {
+                    peak_hour: peak.peak_hour,
+                    width_hours: peak.width_hours,
+                    peak: peak.requests_per_hour,
+                    weekdays_only: peak.weekdays_only,
+                },
+                origin: evenly(&peak.from),
+                destination: evenly(&peak.to),
+                product_mix: sorted_weights(&peak.product_mix, |p| p.as_str()),
+            });
+        }
+        for spike in &scenario.spikes {
+            areas.push(Area::new(Extent::Circle {
+                center: spike.center,
+                radius_m: spike.radius_m,
+            }));
+            let venue = Place::Area(areas.len() - 1);
+            let (origin, destination) = match spike.direction {
+                SpikeDirection::FromVenue => (venue, evenly(&spike.zones)),
+                SpikeDirection::ToVenue => (evenly(&spike.zones), venue),
+            };
+            sources.push(Source {
+                name: spike.name.clone(),
+                rate: Rate::Window {
+                    start: spike.start.to_utc(),
+                    end: spike.end.to_utc(),
+                    rate: spike.requests_per_hour,
+                },
+                origin,
+                destination,
+                product_mix: sorted_weights(&spike.product_mix, |p| p.as_str()),
+            });
+        }
+
+        Self {
+            scenario,
+            areas,
+            sources,
+            rng,
+        }
+    }
+
+    pub fn source_name(&self, source: usize) -> &str {
+        &self.sources[source].name
+    }
+
+    /// Requests expected to start in each hex cell between `from` and `from + window`, what a
+    /// perfect forecast would say. Requests from anywhere on the map aren't in any cell.
+    pub fn expected_per_cell(&self, from: Duration, window: Duration) -> HashMap<HexCell, f64> {
+        let start = self.scenario.start_utc();
+        let steps = (window.as_secs_f64() / EXPECTATION_STEP.as_secs_f64()).ceil() as u32;
+        let step_hours = window.as_secs_f64() / 3600.0 / steps.max(1) as f64;
+        let mut expected = HashMap::new();
+        for source in &self.sources {
+            let requests: f64 = (0..steps)
+                .filter_map(|i| {
+                    let mid = from + window.mul_f64((i as f64 + 0.5) / steps as f64);
+                    let at = start + chrono::Duration::from_std(mid).ok()?;
+                    Some(source.rate.at(&self.scenario, at) * step_hours)
+                })
+                .sum();
+            if requests <= 0.0 {
+                continue;
+            }
+            let areas: Vec<(usize, f64)> = match &source.origin {
+                Place::Area(area) => vec![(*area, 1.0)],
+                Place::Zones(weights) => weights.clone(),
+                Place::Anywhere => continue,
+            };
+            let total: f64 = areas.iter().map(|(_, w)| w.max(0.0)).sum();
+            for (area, weight) in areas {
+                let cells = &self.areas[area].cells;
+                let share = requests * weight.max(0.0) / total / cells.len() as f64;
+                for cell in cells {
+                    *expected.entry(*cell).or_insert(0.0) += share;
+                }
+            }
+        }
+        expected
+    }
+
+    /// The next request after `after`, None if there is none before `end`.
+    pub fn next_request(&mut self, after: Duration, end: Duration, map: &CityMap) -> Option<GeneratedRequest> {
+        let start = self.scenario.start_utc();
+        let mut at = after;
+        while at < end {
+            let now = start + chrono::Duration::from_std(at).ok()?;
+            let window_end = (at + WINDOW.min(self.scenario.until_next_local_hour(now))).min(end);
+            let until = start + chrono::Duration::from_std(window_end).ok()?;
+            let bound: f64 = self
+                .sources
+                .iter()
+                .map(|s| s.rate.max(&self.scenario, now, until))
+                .sum();
+            if bound > 0.0 {
+                let candidate = at + exponential(&mut self.rng, Duration::from_secs_f64(3600.0 / bound));
+                if candidate < window_end {
+                    let when = start + chrono::Duration::from_std(candidate).ok()?;
+                    let rates: Vec<(usize, f64)> = self
+                        .sources
+                        .iter()
+                        .enumerate()
+                        .map(|(i, s)| (i, s.rate.at(&self.scenario, when)))
+                        .collect();
+                    let total: f64 = rates.iter().map(|(_, r)| r).sum();
+                    at = candidate;
+                    if self.rng.random::<f64>() * bound < total {
+                        let source = pick_weighted(&mut self.rng, &rates)?;
+                        return Some(self.request(candidate, source, map));
+                    }
+                    continue;
+                }
+            }
+            at = window_end;
+        }
+        None
+    }
+
+    // who asks for which ride
+    fn request(&mut self, at: Duration, source: usize, map: &CityMap) -> GeneratedRequest {
+        let Self {
+            scenario,
+            areas,
+            sources,
+            rng,
+        } = self;
+        let from = &sources[source];
+        let rider = rng.random_range(0..scenario.riders);
+        let origin = random_point(areas, &from.origin, map, rng);
+        let mut destination = random_point(areas, &from.destination, map, rng);
+        for _ in 0..MAX_DESTINATION_DRAWS {
+            if haversine_m(origin, destination) >= MIN_TRIP_M {
+                break;
+            }
+            destination = random_point(areas, &from.destination, map, rng);
+        }
+        let product = pick_weighted(rng, &from.product_mix).unwrap_or(RideProduct::Economy);
+        GeneratedRequest {
+            at,
+            source,
+            rider,
+            origin,
+            destination,
+            product,
+        }
+    }
+}
+
+fn random_point(areas: &[Area], place: &Place, map: &CityMap, rng: &mut ChaCha8Rng) -> LatLng {
+    let area = match place {
+        Place::Area(area) => Some(*area),
+        Place::Zones(weights) => pick_weighted(rng, weights),
+        Place::Anywhere => None,
+    };
+    match area {
+        Some(area) => areas[area].random_point(rng),
+        None => map.random_point(rng),
+    }
+}
@@ -0,0 +1,822 @@
+// Discrete event mode: the whole system in one process, on virtual time.
+//
+// The matcher, its surge engine and the driver service's ride lifecycle run for real and talk
+// to each other with the real events over the in-memory bus. Postgres and Redis aren't
+// involved, their tables and the live driver state are kept in memory behind the same traits.
+// The simulated drivers play the driver app: they report where they are, go on and offline,
+// read ride offers off their websocket and answer them the way the accept and reject
+// endpoints do. Nothing waits on the wall clock: the virtual clock jumps from one scheduled
+// event to the next, so a simulated day takes seconds, and since everything runs on one task
+// with one seeded generator per agent the same seed always gives the same run.
+//
+// With drivers following repositioning suggestions, the repositioning service is a stand-in
+// too: it plans with the demand generator's own expected requests, a perfect forecast, so the
+// effect measured is the most repositioning can do.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::Message as WsMessage;
+use chrono::{DateTime, Utc};
+use common::clock::{Clock, SharedClock, VirtualClock};
+use common::driver_state::{DriverStateStore, InMemoryDriverStateStore};
+use common::events_schema::{
+    DriverAssignedRideEvent, DriverAvailabilityChangedEvent, RideRequestedEvent,
+};
+use common::geo::hex::{HexCell, CELL_RESOLUTION};
+use common::geo::LatLng;
+use common::redis_namespaces::{
+    DRIVER_AVAILABILITY_FIELD, DRIVER_AVAILABILITY_REASON_FIELD, DRIVER_CELL_FIELD,
+    DRIVER_LAST_AVAILABILITY_UPDATE_FIELD, DRIVER_LAST_LOCATION_UPDATE_FIELD,
+};
+use common::repositioning::{plan_moves, RepositioningPolicy};
+use common::service_area::ServiceArea;
+use common::subjects::{DRIVER_ASSIGNED_SUBJECT, DRIVER_AVAILABILITY_SUBJECT, RIDE_REQUESTED_SUBJECT};
+use common::vehicle::DriverCapabilities;
+use common::ws_schema::{Envelope, RideOffer};
+use driver::events::handlers::EventHandler as _;
+use driver::events::publisher::EventPublisher;
+use driver::infra::repository::driver_eligibility_repository::InMemoryDriverEligibilityRepository;
+use driver::infra::repository::driver_status_repository::{
+    DriverStatusRepository, InMemoryDriverStatusRepository,
+};
+use driver::infra::repository::ride_trace_repository::InMemoryRideTraceRepository;
+use driver::infra::ws::hub::{ClientTx, WsHub};
+use driver::models::{AvailabilityReason, DriverStatus, RideStatus};
+use driver::service::eligibility::{CooldownPolicy, EligibilityService};
+use driver::service::eta_service::EtaService;
+use driver::service::location_update::DRIVER_STATE_TTL_SECS;
+use driver::service::ride_lifecycle::{RideLifeCycle, RideLifeCycleService};
+use driver::service::ride_trace::RideTraceService;
+use futures_util::{FutureExt, Stream, StreamExt};
+use matcher::events::handler::EventHandler as _;
+use matcher::events::producers::EventProducer;
+use matcher::matcher::service::MatcherService;
+use matcher::matcher::surge::{SurgeEngine, SurgePolicy};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use ubersimx_messaging::memory::InMemoryBus;
+use ubersimx_messaging::{Message, Messaging};
+use uuid::Uuid;
+
+use crate::agents::driver::{DriverBehavior, DriverPlan};
+use crate::agents::{exponential, random_uuid};
+use crate::api::vehicle_seats;
+use crate::demand::{DemandGenerator, GeneratedRequest};
+use crate::map::{CityMap, Path};
+use crate::report::{KpiCollector, Leg};
+use crate::scenario::Scenario;
+
+// how often the repositioning stand-in plans, like REPOSITIONING_INTERVAL_SECS in the driver service
+const REPOSITIONING_INTERVAL: Duration = Duration::from_secs(60);
+
+type Subscription = Pin<Box<dyn Stream<Item = anyhow::Result<Message>> + Send>>;
+
+/// One seeded generator stream per agent, see `agent_rng` in main.
+pub struct Streams {
+    pub drivers: Vec<ChaCha8Rng>,
+    pub demand: ChaCha8Rng,
+    /// ride, rider and driver ids
+    pub ids: ChaCha8Rng,
+}
+
+enum Event {
+    ShiftStart(usize),
+    ShiftEnd(usize),
+    // carries the driver's generation when it was scheduled, stale ones are ignored
+    Cruise(usize, u64),
+    BreakStart(usize),
+    BreakEnd(usize),
+    Answer(usize, Uuid),
+    Arrive(usize, Uuid),
+    Request(GeneratedRequest),
+    Reposition,
+    Surge,
+}
+
+struct Scheduled {
+    at: Duration,
+    // ties are handled in the order they were scheduled
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // reversed, BinaryHeap pops the largest and we want the earliest
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Offline,
+    Idle,
+    Offered(Uuid),
+    ToPickup(Uuid),
+    ToDropoff(Uuid),
+    OnBreak,
+}
+
+// the ride as the offer described it
+#[derive(Debug, Clone, Copy)]
+struct Trip {
+    pickup: LatLng,
+    dropoff: LatLng,
+}
+
+struct SimDriver {
+    id: Uuid,
+    capabilities: DriverCapabilities,
+    plan: DriverPlan,
+    status: Status,
+    trip: Option<Trip>,
+    // the driver's end of the websocket, ride offers arrive here
+    offers: mpsc::UnboundedReceiver<WsMessage>,
+    path: Path,
+    // when the path was last advanced
+    moved_at: Duration,
+    // bumped when a new cruise starts, so the end of the old one is ignored
+    generation: u64,
+    // cruising to a suggested cell rather than a random intersection
+    repositioning: bool,
+    // past the end of the shift, only the ride in progress is finished
+    shift_over: bool,
+    rng: ChaCha8Rng,
+}
+
+impl SimDriver {
+    // moves the driver along the path up to `at`
+    fn position(&mut self, at: Duration, kpis: &mut KpiCollector) -> LatLng {
+        let meters = self.path.advance(at.saturating_sub(self.moved_at).as_secs_f64());
+        self.moved_at = at;
+        let leg = match self.status {
+            Status::ToPickup(_) => Leg::ToPickup,
+            Status::ToDropoff(_) => Leg::WithRider,
+            Status::Idle if self.repositioning => Leg::Repositioning,
+            // anything else is parked or cruising
+            _ => Leg::Cruising,
+        };
+        kpis.driven(self.id, meters, leg);
+        self.path.position()
+    }
+}
+
+/// What was published during the run.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub events: BTreeMap<String, usize>,
+    /// hash over every published subject and payload in order, equal digests mean equal runs
+    pub digest: u64,
+    pub kpis: KpiCollector,
+}
+
+pub struct DiscreteSimulation {
+    scenario: Arc<Scenario>,
+    map: Arc<CityMap>,
+    behavior: DriverBehavior,
+    clock: Arc<VirtualClock>,
+    bus: Arc<InMemoryBus>,
+    // what Redis holds for the services
+    driver_state: Arc<dyn DriverStateStore>,
+    matcher: Arc<MatcherService>,
+    surge: Arc<SurgeEngine>,
+    surge_interval: Duration,
+    driver_status_repo: Arc<InMemoryDriverStatusRepository>,
+    eligibility: Arc<EligibilityService>,
+    ride_lifecycle: Arc<RideLifeCycleService>,
+    queue: BinaryHeap<Scheduled>,
+    seq: u64,
+    drivers: Vec<SimDriver>,
+    // lookups only, never iterated so the hash order can't leak into the run
+    driver_index: HashMap<Uuid, usize>,
+    demand: DemandGenerator,
+    riders: Vec<Uuid>,
+    ids: ChaCha8Rng,
+    kpis: KpiCollector,
+}
+
+impl DiscreteSimulation {
+    pub async fn new(
+        scenario: Arc<Scenario>,
+        map: Arc<CityMap>,
+        streams: Streams,
+    ) -> Result<Self, anyhow::Error> {
+        let Streams {
+            drivers: driver_rngs,
+            demand: demand_rng,
+            mut ids,
+        } = streams;
+        let vehicle_mix = crate::scenario::sorted_weights(&scenario.fleet.vehicle_mix, |c| c.as_str());
+
+        let clock = Arc::new(VirtualClock::new(scenario.start_utc()));
+        let shared_clock: SharedClock = clock.clone();
+        let bus = Arc::new(InMemoryBus::new());
+        let messaging: Arc<dyn Messaging> = bus.clone();
+        let driver_state: Arc<dyn DriverStateStore> = Arc::new(InMemoryDriverStateStore::new());
+        let eta_engine = map.eta_engine();
+
+        // the matcher, a scenario has no service area or airport queues
+        let producer = Arc::new(EventProducer::new(messaging.clone()));
+        let surge_policy = SurgePolicy::default();
+        let surge_interval = surge_policy.interval;
+        let surge = Arc::new(SurgeEngine::new(
+            producer.clone(),
+            driver_state.clone(),
+            surge_policy,
+            shared_clock.clone(),
+        ));
+        let matcher = Arc::new(MatcherService::new(
+            producer,
+            driver_state.clone(),
+            eta_engine.clone(),
+            surge.clone(),
+            Arc::new(ServiceArea::unrestricted()),
+            shared_clock.clone(),
+        ));
+
+        // the driver service's side of a ride
+        let driver_status_repo = Arc::new(InMemoryDriverStatusRepository::new(shared_clock.clone()));
+        let eligibility_repo = Arc::new(InMemoryDriverEligibilityRepository::new(shared_clock.clone()));
+        let eligibility = Arc::new(EligibilityService {
+            eligibility_repo: eligibility_repo.clone(),
+            driver_state: driver_state.clone(),
+            cooldown_policy: CooldownPolicy::default(),
+            clock: shared_clock.clone(),
+        });
+        let ws_hub = Arc::new(WsHub::new());
+        let ride_lifecycle = Arc::new(RideLifeCycleService {
+            driver_status_repo: driver_status_repo.clone(),
+            producer: Arc::new(EventPublisher::new(messaging)),
+            driver_state: driver_state.clone(),
+            eligibility_service: eligibility.clone(),
+            eta_service: Arc::new(EtaService {
+                engine: eta_engine,
+                driver_state: driver_state.clone(),
+                own_changes: Default::default(),
+                clock: shared_clock.clone(),
+            }),
+            ws_hub: ws_hub.clone(),
+            ride_trace_service: Arc::new(RideTraceService {
+                ride_trace_repo: Arc::new(InMemoryRideTraceRepository::new()),
+                driver_state: driver_state.clone(),
+                clock: shared_clock.clone(),
+            }),
+            clock: shared_clock.clone(),
+        });
+
+        let riders = (0..scenario.riders).map(|_| random_uuid(&mut ids)).collect();
+        let mut drivers = Vec::with_capacity(driver_rngs.len());
+        for mut rng in driver_rngs {
+            let plan = DriverPlan::draw(&scenario, &vehicle_mix, &mut rng)
+                .ok_or_else(|| anyhow::anyhow!("the fleet vehicle mix is empty"))?;
+            let id = random_uuid(&mut ids);
+            // registered like the app would be, offline until the shift starts
+            eligibility_repo.add_driver(id, None);
+            driver_status_repo
+                .create_status(&DriverStatus {
+                    driver_id: id,
+                    driver_available: false,
+                    ride_status: RideStatus::None,
+                    current_trip_id: None,
+                    status_updated_at: clock.now(),
+                })
+                .await?;
+            // connected for the whole run, the session id only tells reconnects apart
+            let (tx, offers) = mpsc::unbounded_channel();
+            ws_hub.register(id, ClientTx { session_id: id, tx }).await;
+            drivers.push(SimDriver {
+                id,
+                capabilities: DriverCapabilities {
+                    vehicle_class: plan.vehicle_class,
+                    seats: vehicle_seats(plan.vehicle_class),
+                    pet_friendly: false,
+                    wheelchair_accessible: false,
+                },
+                plan,
+                status: Status::Offline,
+                trip: None,
+                offers,
+                path: Path::parked(map.random_point(&mut rng)),
+                moved_at: Duration::ZERO,
+                generation: 0,
+                repositioning: false,
+                shift_over: false,
+                rng,
+            });
+        }
+        let demand = DemandGenerator::new(scenario.clone(), demand_rng);
+
+        Ok(Self {
+            behavior: DriverBehavior::from(&scenario.drivers),
+            clock,
+            bus,
+            driver_state,
+            matcher,
+            surge,
+            surge_interval,
+            driver_status_repo,
+            eligibility,
+            ride_lifecycle,
+            queue: BinaryHeap::new(),
+            seq: 0,
+            driver_index: drivers.iter().enumerate().map(|(i, d)| (d.id, i)).collect(),
+            drivers,
+            demand,
+            riders,
+            ids,
+            kpis: KpiCollector::new(),
+            scenario,
+            map,
+        })
+    }
+
+    /// Runs the scenario to its end.
+    pub async fn run(mut self) -> Result<Summary, anyhow::Error> {
+        let mut requests = self.bus.subscribe(RIDE_REQUESTED_SUBJECT.to_string()).await?;
+        let mut assignments = self.bus.subscribe(DRIVER_ASSIGNED_SUBJECT.to_string()).await?;
+        let mut everything = self.bus.subscribe(">".to_string()).await?;
+
+        let end = self.scenario.duration();
+        for index in 0..self.drivers.len() {
+            let plan = &self.drivers[index].plan;
+            let (start, stop) = (plan.shift_start, plan.shift_end);
+            self.schedule(start, Event::ShiftStart(index));
+            self.schedule(stop, Event::ShiftEnd(index));
+        }
+        if let Some(request) = self.demand.next_request(Duration::ZERO, end, &self.map) {
+            self.schedule(request.at, Event::Request(request));
+        }
+        if self.behavior.follow_repositioning > 0.0 {
+            self.schedule(REPOSITIONING_INTERVAL, Event::Reposition);
+        }
+        self.schedule(self.surge_interval, Event::Surge);
+
+        let mut summary = Summary::default();
+        let mut hasher = DefaultHasher::new();
+        while let Some(Scheduled { at, event, .. }) = self.queue.pop() {
+            if at > end {
+                break;
+            }
+            self.clock.advance_to(self.at(at));
+            self.handle(at, event).await?;
+
+            // deliver until nothing is left, the same order messages would arrive in
+            loop {
+                if let Some(message) = next_message(&mut requests) {
+                    // the matcher searches the positions the drivers reported
+                    self.report_positions(at).await?;
+                    let event: RideRequestedEvent = serde_json::from_slice(&message.data)?;
+                    self.matcher.handle(event).await;
+                } else if let Some(message) = next_message(&mut assignments) {
+                    let event: DriverAssignedRideEvent = serde_json::from_slice(&message.data)?;
+                    let driver_id = event.driver_id;
+                    self.ride_lifecycle.handle(event).await;
+                    self.read_offers(at, driver_id)?;
+                } else {
+                    break;
+                }
+            }
+            while let Some(message) = next_message(&mut everything) {
+                message.subject.hash(&mut hasher);
+                message.data.hash(&mut hasher);
+                self.kpis.record_event(self.clock.now(), &message.subject, &message.data);
+                *summary.events.entry(message.subject).or_default() += 1;
+            }
+        }
+        // drivers still driving at the end
+        let end = self.scenario.duration();
+        for driver in &mut self.drivers {
+            driver.position(end, &mut self.kpis);
+        }
+        summary.digest = hasher.finish();
+        summary.kpis = self.kpis;
+        Ok(summary)
+    }
+
+    fn at(&self, at: Duration) -> DateTime<Utc> {
+        self.scenario.start_utc() + chrono::Duration::from_std(at).unwrap_or_default()
+    }
+
+    fn schedule(&mut self, at: Duration, event: Event) {
+        self.seq += 1;
+        self.queue.push(Scheduled {
+            at,
+            seq: self.seq,
+            event,
+        });
+    }
+
+    async fn publish<T: Serialize>(&self, subject: &str, event: &T) -> Result<(), anyhow::Error> {
+        self.bus.publish(subject.to_string(), serde_json::to_vec(event)?).await
+    }
+
+    async fn handle(&mut self, at: Duration, event: Event) -> Result<(), anyhow::Error> {
+        match event {
+            Event::ShiftStart(d) => {
+                self.drivers[d].moved_at = at;
+                self.go_available(at, d).await?;
+                self.schedule_break(at, d);
+            }
+            Event::ShiftEnd(d) => {
+                // an offered or accepted ride is finished first, like the live drivers do
+                self.drivers[d].shift_over = true;
+                if matches!(self.drivers[d].status, Status::Idle | Status::OnBreak) {
+                    self.go_unavailable(at, d, Status::Offline).await?;
+                }
+            }
+            Event::Cruise(d, generation) => {
+                let driver = &self.drivers[d];
+                if driver.status == Status::Idle && driver.generation == generation {
+                    self.cruise(at, d);
+                }
+            }
+            Event::BreakStart(d) => match self.drivers[d].status {
+                Status::Idle => {
+                    self.go_unavailable(at, d, Status::OnBreak).await?;
+                    let length = exponential(&mut self.drivers[d].rng, self.behavior.mean_break_length);
+                    self.schedule(at + length, Event::BreakEnd(d));
+                }
+                Status::Offline => {}
+                // busy, the break has to wait
+                _ => self.schedule_break(at, d),
+            },
+            Event::BreakEnd(d) => {
+                if self.drivers[d].status == Status::OnBreak {
+                    self.go_available(at, d).await?;
+                    self.schedule_break(at, d);
+                }
+            }
+            Event::Answer(d, ride_id) => {
+                if self.drivers[d].status == Status::Offered(ride_id) {
+                    self.answer(at, d, ride_id).await?;
+                }
+            }
+            Event::Arrive(d, ride_id) => {
+                // the leg driven so far counts for the current status
+                self.drivers[d].position(at, &mut self.kpis);
+                let driver_id = self.drivers[d].id;
+                match (self.drivers[d].status, self.drivers[d].trip) {
+                    (Status::ToPickup(id), Some(trip)) if id == ride_id => {
+                        // the trace marks the pickup where the driver last reported
+                        self.report_position(at, d).await?;
+                        self.ride_lifecycle.start_ride(driver_id, ride_id).await?;
+                        self.drivers[d].status = Status::ToDropoff(ride_id);
+                        self.drive_to(at, d, trip.dropoff, Event::Arrive(d, ride_id));
+                    }
+                    (Status::ToDropoff(id), Some(_)) if id == ride_id => {
+                        self.report_position(at, d).await?;
+                        self.ride_lifecycle.complete_ride(driver_id, ride_id).await?;
+                        self.drivers[d].trip = None;
+                        self.back_from_ride(at, d).await?;
+                    }
+                    _ => {}
+                }
+            }
+            // the rider service's part, the request as it gets published once the quote is accepted
+            Event::Request(request) => {
+                let event = request.event(random_uuid(&mut self.ids), self.riders[request.rider], self.clock.now());
+                self.publish(RIDE_REQUESTED_SUBJECT, &event).await?;
+                if let Some(next) = self.demand.next_request(at, self.scenario.duration(), &self.map) {
+                    self.schedule(next.at, Event::Request(next));
+                }
+            }
+            Event::Reposition => {
+                self.reposition(at);
+                self.schedule(at + REPOSITIONING_INTERVAL, Event::Reposition);
+            }
+            Event::Surge => {
+                self.report_positions(at).await?;
+                self.surge.recompute().await?;
+                self.schedule(at + self.surge_interval, Event::Surge);
+            }
+        }
+        Ok(())
+    }
+
+    // what the availability endpoint does for a driver coming online
+    async fn go_available(&mut self, at: Duration, d: usize) -> Result<(), anyhow::Error> {
+        let driver_id = self.drivers[d].id;
+        let now = self.clock.now();
+        self.drivers[d].status = Status::Idle;
+        self.kpis.driver_online(driver_id, now);
+        self.driver_status_repo
+            .patch_status(driver_id, Some(true), None, None)
+            .await?;
+        self.report_position(at, d).await?;
+        let mut fields = vec![
+            (DRIVER_AVAILABILITY_FIELD, "1".to_string()),
+            (DRIVER_AVAILABILITY_REASON_FIELD, AvailabilityReason::Available.to_string()),
+            (DRIVER_LAST_AVAILABILITY_UPDATE_FIELD, now.timestamp().to_string()),
+        ];
+        fields.extend(self.drivers[d].capabilities.to_redis_fields());
+        self.driver_state
+            .update_state(driver_id, &fields, &[], DRIVER_STATE_TTL_SECS)
+            .await?;
+        // a cooldown from rejected offers keeps the matcher away from the driver
+        self.eligibility.refresh(driver_id).await?;
+        self.publish(
+            DRIVER_AVAILABILITY_SUBJECT,
+            &DriverAvailabilityChangedEvent {
+                driver_id,
+                driver_available: true,
+            },
+        )
+        .await?;
+        self.cruise(at, d);
+        Ok(())
+    }
+
+    async fn go_unavailable(&mut self, at: Duration, d: usize, status: Status) -> Result<(), anyhow::Error> {
+        let driver = &mut self.drivers[d];
+        let position = driver.position(at, &mut self.kpis);
+        driver.path = Path::parked(position);
+        driver.status = status;
+        let driver_id = driver.id;
+        let now = self.clock.now();
+        self.kpis.driver_offline(driver_id, now);
+        self.driver_status_repo
+            .patch_status(driver_id, Some(false), None, None)
+            .await?;
+        self.driver_state
+            .update_state(
+                driver_id,
+                &[
+                    (DRIVER_AVAILABILITY_FIELD, "0".to_string()),
+                    (DRIVER_AVAILABILITY_REASON_FIELD, AvailabilityReason::OfflineToggle.to_string()),
+                    (DRIVER_LAST_AVAILABILITY_UPDATE_FIELD, now.timestamp().to_string()),
+                ],
+                &[],
+                DRIVER_STATE_TTL_SECS,
+            )
+            .await?;
+        self.publish(
+            DRIVER_AVAILABILITY_SUBJECT,
+            &DriverAvailabilityChangedEvent {
+                driver_id,
+                driver_available: false,
+            },
+        )
+        .await
+    }
+
+    // idle again after a ride or an offer, unless the shift ended in the meantime
+    async fn back_from_ride(&mut self, at: Duration, d: usize) -> Result<(), anyhow::Error> {
+        self.drivers[d].status = Status::Idle;
+        if self.drivers[d].shift_over {
+            return self.go_unavailable(at, d, Status::Offline).await;
+        }
+        self.cruise(at, d);
+        Ok(())
+    }
+
+    fn schedule_break(&mut self, at: Duration, d: usize) {
+        let mean = self.behavior.mean_time_between_breaks;
+        if !mean.is_zero() {
+            let gap = exponential(&mut self.drivers[d].rng, mean);
+            self.schedule(at + gap, Event::BreakStart(d));
+        }
+    }
+
+    // idle drivers drive to a random intersection, and on to the next one once there
+    fn cruise(&mut self, at: Duration, d: usize) {
+        let destination = self.map.random_point(&mut self.drivers[d].rng);
+        let driver = &mut self.drivers[d];
+        driver.repositioning = false;
+        driver.generation += 1;
+        let generation = driver.generation;
+        self.drive_to(at, d, destination, Event::Cruise(d, generation));
+    }
+
+    // repositioning service stand-in, drivers already on the way to a suggested cell are left
+    // alone like the live service does within a suggestion's lifetime
+    fn reposition(&mut self, at: Duration) {
+        let policy = RepositioningPolicy::default();
+        let lookahead = policy.lookahead.to_std().unwrap_or_default();
+        let demand = self.demand.expected_per_cell(at, lookahead);
+        let mut idle = Vec::new();
+        for driver in self.drivers.iter_mut() {
+            if driver.status == Status::Idle && !driver.repositioning {
+                let cell = HexCell::of(driver.position(at, &mut self.kpis), CELL_RESOLUTION);
+                idle.push((driver.id, cell));
+            }
+        }
+        let probability = self.behavior.follow_repositioning.clamp(0.0, 1.0);
+        for mv in plan_moves(&policy, &idle, &demand) {
+            let d = self.driver_index[&mv.driver_id];
+            if !self.drivers[d].rng.random_bool(probability) {
+                continue;
+            }
+            let driver = &mut self.drivers[d];
+            driver.repositioning = true;
+            driver.generation += 1;
+            let generation = driver.generation;
+            self.kpis.repositioned(mv.driver_id);
+            self.drive_to(at, d, mv.to.center(), Event::Cruise(d, generation));
+        }
+    }
+
+    // routes the driver to `to` and schedules `arrival` for when they get there
+    fn drive_to(&mut self, at: Duration, d: usize, to: LatLng, arrival: Event) {
+        let now = self.at(at);
+        let position = self.drivers[d].position(at, &mut self.kpis);
+        let path = self.map.route(position, to, now);
+        // at least one location interval, so a driver already there doesn't spin in place
+        let travel = Duration::from_secs_f64(path.remaining_secs()).max(self.behavior.location_interval);
+        self.drivers[d].path = path;
+        self.schedule(at + travel, arrival);
+    }
+
+    // the driver app's location updates, a driver on the move reports where they are now
+    async fn report_position(&mut self, at: Duration, d: usize) -> Result<(), anyhow::Error> {
+        let driver = &mut self.drivers[d];
+        let position = driver.position(at, &mut self.kpis);
+        self.driver_state.set_position(driver.id, position).await?;
+        self.driver_state
+            .update_state(
+                driver.id,
+                &[
+                    (DRIVER_CELL_FIELD, HexCell::of(position, CELL_RESOLUTION).to_string()),
+                    (DRIVER_LAST_LOCATION_UPDATE_FIELD, self.clock.now().timestamp().to_string()),
+                ],
+                &[],
+                DRIVER_STATE_TTL_SECS,
+            )
+            .await
+    }
+
+    // every online driver, before the services look at positions
+    async fn report_positions(&mut self, at: Duration) -> Result<(), anyhow::Error> {
+        for d in 0..self.drivers.len() {
+            if self.drivers[d].status != Status::Offline {
+                self.report_position(at, d).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // the offers pushed to the driver's websocket, the driver stops and answers after a moment
+    fn read_offers(&mut self, at: Duration, driver_id: Uuid) -> Result<(), anyhow::Error> {
+        let Some(&d) = self.driver_index.get(&driver_id) else {
+            return Ok(());
+        };
+        while let Ok(message) = self.drivers[d].offers.try_recv() {
+            let WsMessage::Text(text) = message else {
+                continue;
+            };
+            let offer: Envelope<RideOffer> = serde_json::from_str(&text)?;
+            let driver = &mut self.drivers[d];
+            // the matcher only offers rides to available drivers, and the state says this one is
+            if driver.status != Status::Idle {
+                continue;
+            }
+            let position = driver.position(at, &mut self.kpis);
+            driver.path = Path::parked(position);
+            driver.status = Status::Offered(offer.data.ride_id);
+            driver.trip = Some(Trip {
+                pickup: LatLng::new(offer.data.pickup.lat, offer.data.pickup.lng),
+                dropoff: LatLng::new(offer.data.dropoff.lat, offer.data.dropoff.lng),
+            });
+            self.schedule(at + self.behavior.response_delay, Event::Answer(d, offer.data.ride_id));
+        }
+        Ok(())
+    }
+
+    // what the accept and reject endpoints do
+    async fn answer(&mut self, at: Duration, d: usize, ride_id: Uuid) -> Result<(), anyhow::Error> {
+        let probability = self.behavior.acceptance_probability.clamp(0.0, 1.0);
+        let accept = self.drivers[d].rng.random_bool(probability);
+        let driver_id = self.drivers[d].id;
+        let Some(trip) = self.drivers[d].trip else {
+            return Ok(());
+        };
+
+        if !accept {
+            // nobody else is asked, the matcher doesn't retry
+            self.ride_lifecycle
+                .handle_driver_reject_ride_assignment(driver_id, ride_id)
+                .await?;
+            self.drivers[d].trip = None;
+            return self.back_from_ride(at, d).await;
+        }
+
+        // the pickup ETA is computed from the reported position
+        self.report_position(at, d).await?;
+        self.ride_lifecycle
+            .handle_driver_accept_ride_assignment(driver_id, ride_id)
+            .await?;
+        self.drivers[d].status = Status::ToPickup(ride_id);
+        self.drive_to(at, d, trip.pickup, Event::Arrive(d, ride_id));
+        Ok(())
+    }
+}
+
+// a message already delivered to the subscription, None if there is none
+fn next_message(subscription: &mut Subscription) -> Option<Message> {
+    match subscription.next().now_or_never() {
+        Some(Some(Ok(message))) => Some(message),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::RunInfo;
+    use common::eta::traffic::TrafficProfile;
+    use common::eta::EtaEngine;
+    use rand::SeedableRng;
+
+    // small enough to run in a test, busy enough for rejects, breaks and repositioning to happen
+    const SCENARIO: &str = r#"
+name: determinism
+seed: 11
+start: "2026-10-19T08:00:00-07:00"
+duration_hours: 1
+riders: 20
+map:
+  bounds: { south: 37.76, west: -122.44, north: 37.80, east: -122.39 }
+fleet:
+  size: 6
+  vehicle_mix: { economy: 1.0 }
+drivers:
+  acceptance_probability: 0.7
+  shift_hours: { min: 1, max: 1 }
+  mean_time_between_breaks_mins: 20
+  follow_repositioning: 0.5
+demand:
+  - zone: downtown
+    bounds: { south: 37.77, west: -122.42, north: 37.80, east: -122.39 }
+    requests_per_hour: [30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30]
+"#;
+
+    // the streams main hands out, see agent_rng there
+    fn streams(scenario: &Scenario) -> Streams {
+        let rng = |stream: u64| {
+            let mut rng = ChaCha8Rng::seed_from_u64(scenario.seed);
+            rng.set_stream(stream);
+            rng
+        };
+        let fleet_size = scenario.fleet.size as u64;
+        Streams {
+            drivers: (0..fleet_size).map(rng).collect(),
+            demand: rng(fleet_size),
+            ids: rng(fleet_size + 1),
+        }
+    }
+
+    async fn run(scenario: &Scenario) -> (u64, serde_json::Value) {
+        let bounds = &scenario.map.bounds;
+        let road_graph = CityMap::grid_for(bounds, scenario.map.grid_spacing_m);
+        let map = CityMap::new(EtaEngine::new(road_graph, TrafficProfile::default()), bounds).unwrap();
+        let simulation = DiscreteSimulation::new(Arc::new(scenario.clone()), Arc::new(map), streams(scenario))
+            .await
+            .unwrap();
+        let summary = tokio::task::unconstrained(simulation.run()).await.unwrap();
+
+        let run = RunInfo {
+            scenario: scenario.name.clone(),
+            seed: scenario.seed,
+            mode: "discrete",
+            started_at: scenario.start_utc(),
+            simulated_hours: scenario.duration_hours,
+        };
+        let end = scenario.start_utc() + chrono::Duration::from_std(scenario.duration()).unwrap();
+        let report = summary.kpis.report(run, end);
+        (summary.digest, serde_json::to_value(report).unwrap())
+    }
+
+    #[tokio::test]
+    async fn same_seed_gives_the_same_run() {
+        let scenario: Scenario = serde_yaml::from_str(SCENARIO).unwrap();
+        scenario.validate().unwrap();
+
+        let (digest, report) = run(&scenario).await;
+        let (again_digest, again_report) = run(&scenario).await;
+
+        assert!(report["rides"]["requested"].as_u64().unwrap() > 0, "nothing happened: {}", report);
+        assert_eq!(digest, again_digest);
+        assert_eq!(report, again_report);
+    }
+}
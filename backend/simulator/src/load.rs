@@ -0,0 +1,52 @@
+// Load mode: the scenario's demand published straight onto NATS as RideRequestedEvents, as if
+// the rider service had taken the requests. No drivers and no rider accounts, for loading the
+// matcher (or anything else consuming ride requests) with realistic traffic.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use common::subjects::RIDE_REQUESTED_SUBJECT;
+use rand_chacha::ChaCha8Rng;
+use ubersimx_messaging::Messaging;
+use uuid::Uuid;
+
+use crate::agents::random_uuid;
+use crate::clock::SimClock;
+use crate::demand::DemandGenerator;
+use crate::map::CityMap;
+
+pub struct LoadSource {
+    pub demand: DemandGenerator,
+    pub riders: Vec<Uuid>,
+    /// ride ids
+    pub ids: ChaCha8Rng,
+    pub clock: SimClock,
+}
+
+impl LoadSource {
+    /// Publishes requests until `duration` of simulated time has passed, counting them per
+    /// source of demand into `published`.
+    pub async fn run(
+        &mut self,
+        messaging: &dyn Messaging,
+        map: &CityMap,
+        duration: Duration,
+        published: &mut BTreeMap<String, usize>,
+    ) -> Result<(), anyhow::Error> {
+        let mut at = self.clock.elapsed();
+
+        while let Some(request) = self.demand.next_request(at, duration, map) {
+            at = request.at;
+            self.clock.sleep(at.saturating_sub(self.clock.elapsed())).await;
+
+            let event = request.event(random_uuid(&mut self.ids), self.riders[request.rider], self.clock.now());
+            messaging
+                .publish(RIDE_REQUESTED_SUBJECT.to_string(), serde_json::to_vec(&event)?)
+                .await?;
+            *published
+                .entry(self.demand.source_name(request.source).to_string())
+                .or_default() += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+// Headless city simulation: drives the running services through their public APIs with
+// simulated drivers and riders, see README.md.
+
+mod agents;
+mod api;
+mod clock;
+mod demand;
+mod discrete;
+mod load;
+mod map;
+mod report;
+mod scenario;
+
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use chrono::Utc;
+use common::clock::wall_anchor_from_env;
+use common::eta::graph::RoadGraph;
+use common::eta::traffic::TrafficProfile;
+use common::eta::EtaEngine;
+use futures_util::StreamExt;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use ubersimx_messaging::messagingclient::MessagingClient;
+use ubersimx_messaging::Messaging;
+
+use agents::driver::{DriverAgent, DriverBehavior, DriverPlan};
+use agents::random_uuid;
+use agents::rider::{create_riders, DemandAgent};
+use api::{DriverApi, RiderApi};
+use clock::SimClock;
+use demand::DemandGenerator;
+use discrete::{DiscreteSimulation, Streams};
+use load::LoadSource;
+use map::CityMap;
+use report::{report_name, write_report, KpiCollector, RunInfo, SharedKpis};
+use scenario::{sorted_weights, Scenario};
+
+// every agent draws from its own stream of the seeded generator, so what an agent does
+// doesn't depend on the order tasks happen to run in
+fn agent_rng(seed: u64, stream: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
+
+fn run_info(scenario: &Scenario, mode: &'static str) -> RunInfo {
+    RunInfo {
+        scenario: scenario.name.clone(),
+        seed: scenario.seed,
+        mode,
+        started_at: scenario.start_utc(),
+        simulated_hours: scenario.duration_hours,
+    }
+}
+
+// the KPI report of the run as of `end`, into SIM_REPORT_DIR
+fn save_report(kpis: &KpiCollector, run: RunInfo, end: chrono::DateTime<chrono::Utc>) -> Result<(), anyhow::Error> {
+    let dir = env::var("SIM_REPORT_DIR").unwrap_or_else(|_| "reports".to_string());
+    let name = report_name(&run);
+    let report = kpis.report(run, end);
+    println!(
+        "{} rides requested, {:.1}% unmatched, median rider wait {:.0}s, utilization {:.1}%, {:.1} dead-head km",
+        report.rides.requested,
+        report.rides.unmatched_rate * 100.0,
+        report.rides.rider_wait.p50_secs,
+        report.drivers.utilization * 100.0,
+        report.drivers.dead_head_km
+    );
+    for path in write_report(std::path::Path::new(&dir), &name, &report, &kpis.ride_rows())? {
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    dotenvy::from_filename("settings.env").ok();
+
+    // simulator [--check | --discrete | --load] <scenario file>, or the file in SIM_SCENARIO
+    let args: Vec<String> = env::args().skip(1).collect();
+    let check_only = args.iter().any(|a| a == "--check");
+    let discrete = args.iter().any(|a| a == "--discrete");
+    let load_only = args.iter().any(|a| a == "--load");
+    let scenario_path = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => path.clone(),
+        None => env::var("SIM_SCENARIO")
+            .map_err(|e| anyhow!("SIM_SCENARIO must be set in .env or a scenario file given: {}", e))?,
+    };
+    let mut scenario = Scenario::load(&scenario_path)?;
+    // handy for sweeps over one scenario
+    if let Some(seed) = env::var("SIM_SEED").ok().and_then(|v| v.parse().ok()) {
+        scenario.seed = seed;
+    }
+    if let Some(speedup) = env::var("SIM_SPEEDUP").ok().and_then(|v| v.parse().ok()) {
+        scenario.speedup = speedup;
+        scenario.validate()?;
+    }
+
+    let road_graph = match &scenario.map.road_graph {
+        Some(path) => RoadGraph::load(path)?,
+        None => CityMap::grid_for(&scenario.map.bounds, scenario.map.grid_spacing_m),
+    };
+    let traffic_profile = match &scenario.map.traffic_profile {
+        Some(path) => TrafficProfile::load(path)?,
+        None => TrafficProfile::default(),
+    };
+    let map = Arc::new(
+        CityMap::new(EtaEngine::new(road_graph, traffic_profile), &scenario.map.bounds)
+            .ok_or_else(|| anyhow!("the road graph has no intersection inside the map bounds"))?,
+    );
+
+    if check_only {
+        println!("Scenario {:?} is valid", scenario.name);
+        return Ok(());
+    }
+
+    // driver streams first, then the demand, then the ids of the discrete and load modes
+    let fleet_size = scenario.fleet.size as u64;
+    let driver_streams = 0..fleet_size;
+    let demand_stream = fleet_size;
+    let ids_stream = fleet_size + 1;
+
+    if load_only {
+        let messaging_url = env::var("MESSAGING_URL")
+            .map_err(|e| anyhow!("MESSAGING_URL must be set in .env: {}", e))?;
+        let messaging_client = MessagingClient::connect(&messaging_url).await?;
+        let mut ids = agent_rng(scenario.seed, ids_stream);
+        let riders = (0..scenario.riders).map(|_| random_uuid(&mut ids)).collect();
+        println!(
+            "Publishing the demand of scenario {:?} for {:?} at {}x, seed {}",
+            scenario.name,
+            scenario.duration(),
+            scenario.speedup,
+            scenario.seed
+        );
+        let duration = scenario.duration();
+        let anchor = wall_anchor_from_env()?.unwrap_or_else(Utc::now);
+        let clock = SimClock::new(scenario.start_utc(), anchor, scenario.speedup);
+        clock.wait_for_start().await;
+        let demand_rng = agent_rng(scenario.seed, demand_stream);
+        let mut source = LoadSource {
+            demand: DemandGenerator::new(Arc::new(scenario), demand_rng),
+            riders,
+            ids,
+            clock: clock.clone(),
+        };
+        let mut published = BTreeMap::new();
+        tokio::select! {
+            result = source.run(&messaging_client, &map, duration, &mut published) => result?,
+            _ = tokio::signal::ctrl_c() => println!("Load interrupted"),
+        }
+        println!("Published {} ride requests in {:?} of simulated time", published.values().sum::<usize>(), clock.elapsed());
+        for (source, count) in &published {
+            println!("  {:<36} {}", source, count);
+        }
+        return Ok(());
+    }
+
+    if discrete {
+        let streams = Streams {
+            drivers: driver_streams.map(|s| agent_rng(scenario.seed, s)).collect(),
+            demand: agent_rng(scenario.seed, demand_stream),
+            ids: agent_rng(scenario.seed, ids_stream),
+        };
+        println!(
+            "Running scenario {:?} in process: {} drivers, {} demand zones, {} riders for {:?}, seed {}",
+            scenario.name,
+            scenario.fleet.size,
+            scenario.demand.len(),
+            scenario.riders,
+            scenario.duration(),
+            scenario.seed
+        );
+        let run = run_info(&scenario, "discrete");
+        let end = scenario.start_utc() + chrono::Duration::from_std(scenario.duration())?;
+        let started = std::time::Instant::now();
+        let simulation = DiscreteSimulation::new(Arc::new(scenario), map, streams).await?;
+        // the run never yields, without this tokio's cooperative budget would start reporting
+        // the bus subscriptions as empty after a while
+        let summary = tokio::task::unconstrained(simulation.run()).await?;
+        println!("Simulated in {:?}", started.elapsed());
+        for (subject, count) in &summary.events {
+            println!("  {:<36} {}", subject, count);
+        }
+        println!("Run digest {:016x}", summary.digest);
+        save_report(&summary.kpis, run, end)?;
+        return Ok(());
+    }
+
+    let driver_service_url = env::var("DRIVER_SERVICE_URL")
+        .map_err(|e| anyhow!("DRIVER_SERVICE_URL must be set in .env: {}", e))?;
+    let rider_service_url = env::var("RIDER_SERVICE_URL")
+        .map_err(|e| anyhow!("RIDER_SERVICE_URL must be set in .env: {}", e))?;
+    // ride timings for the report are read off the services' events
+    let messaging_url = env::var("MESSAGING_URL")
+        .map_err(|e| anyhow!("MESSAGING_URL must be set in .env: {}", e))?;
+    let messaging_client = MessagingClient::connect(&messaging_url).await?;
+
+    let http = reqwest::Client::new();
+    let driver_api = DriverApi::new(http.clone(), &driver_service_url);
+    let rider_api = RiderApi::new(http, &rider_service_url);
+
+    println!(
+        "Running scenario {:?}: {} drivers, {} demand zones, {} riders for {:?} at {}x, seed {}",
+        scenario.name,
+        scenario.fleet.size,
+        scenario.demand.len(),
+        scenario.riders,
+        scenario.duration(),
+        scenario.speedup,
+        scenario.seed
+    );
+    let riders = Arc::new(create_riders(&rider_api, scenario.riders).await?);
+
+    // the services have to be anchored at the same wall clock instant, without CLOCK_WALL_ANCHOR
+    // the clock starts once the accounts exist, signing them up isn't part of the run
+    let anchor = wall_anchor_from_env()?.unwrap_or_else(Utc::now);
+    let clock = SimClock::new(scenario.start_utc(), anchor, scenario.speedup);
+    println!(
+        "Services should run with CLOCK_START={} CLOCK_SPEEDUP={} CLOCK_WALL_ANCHOR={}",
+        scenario.start.to_rfc3339(),
+        scenario.speedup,
+        clock.anchor().to_rfc3339()
+    );
+    clock.wait_for_start().await;
+    let run = run_info(&scenario, "live");
+    let kpis: SharedKpis = Arc::new(Mutex::new(KpiCollector::new()));
+    let mut events = messaging_client.subscribe(">".to_string()).await?;
+    let recorder = {
+        let (kpis, clock) = (kpis.clone(), clock.clone());
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = events.next().await {
+                kpis.lock().unwrap().record_event(clock.now(), &message.subject, &message.data);
+            }
+        })
+    };
+    let duration = scenario.duration();
+    let scenario = Arc::new(scenario);
+    let vehicle_mix = sorted_weights(&scenario.fleet.vehicle_mix, |c| c.as_str());
+    let behavior = DriverBehavior::from(&scenario.drivers);
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, stream) in driver_streams.enumerate() {
+        let mut rng = agent_rng(scenario.seed, stream);
+        let plan = DriverPlan::draw(&scenario, &vehicle_mix, &mut rng)
+            .ok_or_else(|| anyhow!("the fleet vehicle mix is empty"))?;
+        let agent = DriverAgent {
+            index,
+            plan,
+            behavior: behavior.clone(),
+            api: driver_api.clone(),
+            clock: clock.clone(),
+            map: map.clone(),
+            rng,
+            kpis: kpis.clone(),
+        };
+        tasks.spawn(async move { (format!("driver {}", index), agent.run().await) });
+    }
+    if scenario.has_demand() {
+        let agent = DemandAgent {
+            demand: DemandGenerator::new(scenario.clone(), agent_rng(scenario.seed, demand_stream)),
+            riders: riders.clone(),
+            api: rider_api.clone(),
+            clock: clock.clone(),
+            map: map.clone(),
+        };
+        tasks.spawn(async move { ("riders".to_string(), agent.run(duration).await) });
+    }
+
+    tokio::select! {
+        _ = async {
+            while let Some(result) = tasks.join_next().await {
+                match result {
+                    Ok((agent, Err(e))) => eprintln!("Simulated {} stopped: {:?}", agent, e),
+                    Err(e) => eprintln!("Simulation task panicked: {:?}", e),
+                    Ok(_) => {}
+                }
+            }
+        } => println!("Simulation finished after {:?} of simulated time", clock.elapsed()),
+        _ = tokio::signal::ctrl_c() => println!("Simulation interrupted"),
+    }
+
+    // an interrupted run still gets a report, up to where it got
+    recorder.abort();
+    let kpis = kpis.lock().unwrap();
+    save_report(&kpis, run, clock.now())?;
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// The city the agents drive in: the road graph plus traffic, and paths a car follows
+// along it.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use common::eta::graph::{GridSpec, NodeId, RoadGraph};
+use common::eta::routing::shortest_path;
+use common::eta::{EtaEngine, MAX_SNAP_DISTANCE_M};
+use common::geo::{haversine_m, LatLng};
+use rand::Rng;
+
+use crate::scenario::Bounds;
+
+// getting from a point to the nearest node and from the last node to the destination,
+// and the whole way when there is no route
+const OFF_ROAD_SPEED_KMH: f64 = 15.0;
+
+fn kmh_to_mps(kmh: f64) -> f64 {
+    kmh / 3.6
+}
+
+pub struct CityMap {
+    // shared with the services a discrete run hosts, so everyone sees the same traffic
+    eta_engine: Arc<EtaEngine>,
+    // intersections inside the scenario bounds, where drivers cruise and rides go
+    nodes: Vec<NodeId>,
+}
+
+impl CityMap {
+    /// None if no intersection of the graph is inside the bounds.
+    pub fn new(eta_engine: EtaEngine, bounds: &Bounds) -> Option<Self> {
+        let graph = eta_engine.graph();
+        let nodes: Vec<NodeId> = (0..graph.node_count() as NodeId)
+            .filter(|node| bounds.contains(graph.node(*node)))
+            .collect();
+        if nodes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            eta_engine: Arc::new(eta_engine),
+            nodes,
+        })
+    }
+
+    /// Grid covering the bounds, for scenarios without a road graph.
+    pub fn grid_for(bounds: &Bounds, spacing_m: f64) -> RoadGraph {
+        let height_m = haversine_m(
+            LatLng::new(bounds.south, bounds.west),
+            LatLng::new(bounds.north, bounds.west),
+        );
+        let center = bounds.center();
+        let width_m = haversine_m(
+            LatLng::new(center.lat, bounds.west),
+            LatLng::new(center.lat, bounds.east),
+        );
+        RoadGraph::generate_grid(&GridSpec {
+            center,
+            rows: (height_m / spacing_m).floor() as u32 + 1,
+            cols: (width_m / spacing_m).floor() as u32 + 1,
+            spacing_m,
+            ..GridSpec::default()
+        })
+    }
+
+    /// A random intersection.
+    pub fn random_point(&self, rng: &mut impl Rng) -> LatLng {
+        let node = self.nodes[rng.random_range(0..self.nodes.len())];
+        self.eta_engine.graph().node(node)
+    }
+
+    pub fn eta_engine(&self) -> Arc<EtaEngine> {
+        self.eta_engine.clone()
+    }
+
+    /// Fastest way from `from` to `to` with the traffic at `at`, straight across when the
+    /// graph doesn't connect them.
+    pub fn route(&self, from: LatLng, to: LatLng, at: DateTime<Utc>) -> Path {
+        let graph = self.eta_engine.graph();
+        let traffic = self.eta_engine.traffic();
+        let off_road = kmh_to_mps(OFF_ROAD_SPEED_KMH);
+
+        let nodes = graph
+            .nearest_node(from, MAX_SNAP_DISTANCE_M)
+            .zip(graph.nearest_node(to, MAX_SNAP_DISTANCE_M))
+            .and_then(|((start, _), (goal, _))| {
+                let max_speed_mps = kmh_to_mps(graph.max_speed_kmh() * traffic.profile().max_multiplier());
+                shortest_path(graph, start, goal, max_speed_mps, |edge_id, edge| {
+                    edge.length_m / kmh_to_mps(edge.speed_kmh * traffic.speed_factor(edge_id, edge, at))
+                })
+            });
+        let Some(route) = nodes else {
+            return Path::new(vec![from, to], vec![off_road]);
+        };
+
+        let mut points = vec![from];
+        let mut speeds = vec![off_road];
+        for pair in route.nodes.windows(2) {
+            let speed = graph
+                .out_edges(pair[0])
+                .find(|edge_id| graph.edge(*edge_id).to == pair[1])
+                .map(|edge_id| {
+                    let edge = graph.edge(edge_id);
+                    kmh_to_mps(edge.speed_kmh * traffic.speed_factor(edge_id, edge, at))
+                })
+                .filter(|speed| *speed > 0.0)
+                .unwrap_or(off_road);
+            points.push(graph.node(pair[0]));
+            speeds.push(speed);
+        }
+        points.push(graph.node(*route.nodes.last().unwrap_or(&0)));
+        speeds.push(off_road);
+        points.push(to);
+        Path::new(points, speeds)
+    }
+}
+
+/// Polyline driven at a speed per segment, `advance` moves along it.
+#[derive(Debug, Clone)]
+pub struct Path {
+    points: Vec<LatLng>,
+    // speed on points[i] -> points[i + 1], m/s
+    speeds_mps: Vec<f64>,
+    segment: usize,
+    // meters into the current segment
+    offset_m: f64,
+}
+
+impl Path {
+    fn new(points: Vec<LatLng>, speeds_mps: Vec<f64>) -> Self {
+        Self {
+            points,
+            speeds_mps,
+            segment: 0,
+            offset_m: 0.0,
+        }
+    }
+
+    /// Standing still at `position`.
+    pub fn parked(position: LatLng) -> Self {
+        Self::new(vec![position], Vec::new())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.segment + 1 >= self.points.len()
+    }
+
+    pub fn position(&self) -> LatLng {
+        if self.is_done() {
+            return *self.points.last().expect("a path has at least one point");
+        }
+        let (a, b) = (self.points[self.segment], self.points[self.segment + 1]);
+        let length = haversine_m(a, b);
+        let t = if length > 0.0 { self.offset_m / length } else { 1.0 };
+        LatLng::new(a.lat + (b.lat - a.lat) * t, a.lng + (b.lng - a.lng) * t)
+    }
+
+    /// Seconds of driving left to the end.
+    pub fn remaining_secs(&self) -> f64 {
+        if self.is_done() {
+            return 0.0;
+        }
+        let mut secs = (haversine_m(self.points[self.segment], self.points[self.segment + 1])
+            - self.offset_m)
+            / self.speeds_mps[self.segment];
+        for i in self.segment + 1..self.points.len() - 1 {
+            secs += haversine_m(self.points[i], self.points[i + 1]) / self.speeds_mps[i];
+        }
+        secs
+    }
+
+    /// Drives on for `secs` seconds, returns the meters covered.
+    pub fn advance(&mut self, mut secs: f64) -> f64 {
+        let mut driven = 0.0;
+        while secs > 0.0 && !self.is_done() {
+            let length = haversine_m(self.points[self.segment], self.points[self.segment + 1]);
+            let speed = self.speeds_mps[self.segment];
+            let remaining = length - self.offset_m;
+            if remaining <= speed * secs {
+                secs -= remaining / speed;
+                driven += remaining;
+                self.segment += 1;
+                self.offset_m = 0.0;
+            } else {
+                self.offset_m += speed * secs;
+                driven += speed * secs;
+                secs = 0.0;
+            }
+        }
+        driven
+    }
+}
@@ -0,0 +1,465 @@
+// KPIs of a run, to compare dispatch policies on the same scenario and seed.
+//
+// Ride timings come from the events on the bus (request, assignment, accept or reject,
+// pickup, dropoff), timed when the simulator sees them so everything is on the simulated
+// clock. What only the drivers know, when they are online and how far they drive with and
+// without a rider, the agents report themselves. At the end it's written as a JSON summary,
+// the same summary as metric,value CSV and a CSV with one row per ride.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use common::events_schema::{
+    DriverAcceptedRideEvent, DriverAssignedRideEvent, DriverRejectedRideEvent,
+    NoDriversAvailableEvent, RideCompletedEvent, RidePickedUpEvent, RideRequestedEvent,
+};
+use common::subjects::{
+    DRIVER_ACCEPTED_RIDE_SUBJECT, DRIVER_ASSIGNED_SUBJECT, DRIVER_REJECTED_RIDE_SUBJECT,
+    NO_DRIVERS_AVAILABLE_SUBJECT, RIDE_COMPLETED_SUBJECT, RIDE_PICKED_UP_SUBJECT,
+    RIDE_REQUESTED_SUBJECT,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub type SharedKpis = Arc<Mutex<KpiCollector>>;
+
+/// What a driver is doing while driving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Leg {
+    Cruising,
+    /// heading where a repositioning suggestion said
+    Repositioning,
+    ToPickup,
+    WithRider,
+}
+
+#[derive(Debug, Default)]
+struct RideTimeline {
+    rider_id: Option<Uuid>,
+    driver_id: Option<Uuid>,
+    requested_at: Option<DateTime<Utc>>,
+    assigned_at: Option<DateTime<Utc>>,
+    accepted_at: Option<DateTime<Utc>>,
+    rejected_at: Option<DateTime<Utc>>,
+    unmatched_at: Option<DateTime<Utc>>,
+    picked_up_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl RideTimeline {
+    fn outcome(&self) -> &'static str {
+        if self.completed_at.is_some() {
+            "completed"
+        } else if self.accepted_at.is_some() {
+            "in_progress"
+        } else if self.rejected_at.is_some() {
+            "rejected"
+        } else if self.unmatched_at.is_some() {
+            "unmatched"
+        } else {
+            "pending"
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct DriverActivity {
+    online_since: Option<DateTime<Utc>>,
+    online_secs: f64,
+    cruising_m: f64,
+    repositioning_m: f64,
+    to_pickup_m: f64,
+    with_rider_m: f64,
+    repositions: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct KpiCollector {
+    rides: HashMap<Uuid, RideTimeline>,
+    // in the order rides were first seen, for the per ride CSV
+    ride_order: Vec<Uuid>,
+    // ordered, so the totals add up the same way every run
+    drivers: BTreeMap<Uuid, DriverActivity>,
+}
+
+impl KpiCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn ride(&mut self, ride_id: Uuid) -> &mut RideTimeline {
+        if !self.rides.contains_key(&ride_id) {
+            self.ride_order.push(ride_id);
+        }
+        self.rides.entry(ride_id).or_default()
+    }
+
+    /// Records a bus message seen at `at`, subjects that don't matter for the KPIs are ignored.
+    pub fn record_event(&mut self, at: DateTime<Utc>, subject: &str, data: &[u8]) {
+        match subject {
+            RIDE_REQUESTED_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<RideRequestedEvent>(data) {
+                    let ride = self.ride(event.ride_id);
+                    ride.rider_id = Some(event.rider_id);
+                    ride.requested_at = Some(at);
+                }
+            }
+            DRIVER_ASSIGNED_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<DriverAssignedRideEvent>(data) {
+                    let ride = self.ride(event.ride_id);
+                    ride.driver_id = Some(event.driver_id);
+                    ride.assigned_at = Some(at);
+                }
+            }
+            NO_DRIVERS_AVAILABLE_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<NoDriversAvailableEvent>(data) {
+                    self.ride(event.ride_id).unmatched_at = Some(at);
+                }
+            }
+            DRIVER_ACCEPTED_RIDE_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<DriverAcceptedRideEvent>(data) {
+                    let ride = self.ride(event.ride_id);
+                    ride.driver_id = Some(event.driver_id);
+                    ride.accepted_at = Some(at);
+                }
+            }
+            DRIVER_REJECTED_RIDE_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<DriverRejectedRideEvent>(data) {
+                    self.ride(event.ride_id).rejected_at = Some(at);
+                }
+            }
+            RIDE_PICKED_UP_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<RidePickedUpEvent>(data) {
+                    self.ride(event.ride_id).picked_up_at = Some(at);
+                }
+            }
+            RIDE_COMPLETED_SUBJECT => {
+                if let Ok(event) = serde_json::from_slice::<RideCompletedEvent>(data) {
+                    self.ride(event.ride_id).completed_at = Some(at);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn driver_online(&mut self, driver_id: Uuid, at: DateTime<Utc>) {
+        let driver = self.drivers.entry(driver_id).or_default();
+        driver.online_since.get_or_insert(at);
+    }
+
+    pub fn driver_offline(&mut self, driver_id: Uuid, at: DateTime<Utc>) {
+        let driver = self.drivers.entry(driver_id).or_default();
+        if let Some(since) = driver.online_since.take() {
+            driver.online_secs += secs_between(since, at);
+        }
+    }
+
+    pub fn driven(&mut self, driver_id: Uuid, meters: f64, leg: Leg) {
+        let driver = self.drivers.entry(driver_id).or_default();
+        match leg {
+            Leg::Cruising => driver.cruising_m += meters,
+            Leg::Repositioning => driver.repositioning_m += meters,
+            Leg::ToPickup => driver.to_pickup_m += meters,
+            Leg::WithRider => driver.with_rider_m += meters,
+        }
+    }
+
+    /// A repositioning suggestion the driver followed.
+    pub fn repositioned(&mut self, driver_id: Uuid) {
+        self.drivers.entry(driver_id).or_default().repositions += 1;
+    }
+
+    /// The KPIs as of `end`, drivers still online count as online until then.
+    pub fn report(&self, run: RunInfo, end: DateTime<Utc>) -> KpiReport {
+        let rides: Vec<&RideTimeline> = self.ride_order.iter().map(|id| &self.rides[id]).collect();
+        let count = |f: fn(&RideTimeline) -> bool| rides.iter().filter(|r| f(r)).count();
+        let requested = count(|r| r.requested_at.is_some());
+        let accepted = count(|r| r.accepted_at.is_some());
+        let stats = |from: fn(&RideTimeline) -> Option<DateTime<Utc>>,
+                     to: fn(&RideTimeline) -> Option<DateTime<Utc>>| {
+            DurationStats::of(
+                rides
+                    .iter()
+                    .filter_map(|r| Some(secs_between(from(r)?, to(r)?)))
+                    .collect(),
+            )
+        };
+
+        let ride_kpis = RideKpis {
+            requested,
+            assigned: count(|r| r.assigned_at.is_some()),
+            accepted,
+            rejected: count(|r| r.rejected_at.is_some()),
+            no_drivers_available: count(|r| r.unmatched_at.is_some()),
+            picked_up: count(|r| r.picked_up_at.is_some()),
+            completed: count(|r| r.completed_at.is_some()),
+            unmatched_rate: ratio((requested - accepted.min(requested)) as f64, requested as f64),
+            request_to_assigned: stats(|r| r.requested_at, |r| r.assigned_at),
+            assigned_to_accepted: stats(|r| r.assigned_at, |r| r.accepted_at),
+            accepted_to_pickup: stats(|r| r.accepted_at, |r| r.picked_up_at),
+            pickup_to_dropoff: stats(|r| r.picked_up_at, |r| r.completed_at),
+            rider_wait: stats(|r| r.requested_at, |r| r.picked_up_at),
+        };
+
+        // a driver is busy from accepting a ride until dropping the rider off
+        let mut busy_secs: HashMap<Uuid, f64> = HashMap::new();
+        let mut with_rider_secs: HashMap<Uuid, f64> = HashMap::new();
+        for ride in &rides {
+            let Some(driver_id) = ride.driver_id else {
+                continue;
+            };
+            let until = ride.completed_at.unwrap_or(end);
+            if let Some(accepted_at) = ride.accepted_at {
+                *busy_secs.entry(driver_id).or_default() += secs_between(accepted_at, until);
+            }
+            if let Some(picked_up_at) = ride.picked_up_at {
+                *with_rider_secs.entry(driver_id).or_default() += secs_between(picked_up_at, until);
+            }
+        }
+
+        let mut driver_kpis = DriverKpis {
+            drivers: self.drivers.len(),
+            rejections: ride_kpis.rejected,
+            rejection_rate: ratio(ride_kpis.rejected as f64, ride_kpis.assigned as f64),
+            ..DriverKpis::default()
+        };
+        let mut online_secs = 0.0;
+        let mut idle_secs = 0.0;
+        let mut busy_total = 0.0;
+        let mut with_rider_total = 0.0;
+        for (driver_id, driver) in &self.drivers {
+            let online = driver.online_secs
+                + driver.online_since.map_or(0.0, |since| secs_between(since, end));
+            let busy = busy_secs.get(driver_id).copied().unwrap_or(0.0);
+            online_secs += online;
+            idle_secs += (online - busy).max(0.0);
+            busy_total += busy;
+            with_rider_total += with_rider_secs.get(driver_id).copied().unwrap_or(0.0);
+            driver_kpis.cruising_km += driver.cruising_m / 1000.0;
+            driver_kpis.repositioning_km += driver.repositioning_m / 1000.0;
+            driver_kpis.repositions += driver.repositions;
+            driver_kpis.to_pickup_km += driver.to_pickup_m / 1000.0;
+            driver_kpis.with_rider_km += driver.with_rider_m / 1000.0;
+        }
+        driver_kpis.online_hours = online_secs / 3600.0;
+        driver_kpis.idle_hours = idle_secs / 3600.0;
+        driver_kpis.utilization = ratio(with_rider_total, online_secs);
+        driver_kpis.busy_share = ratio(busy_total, online_secs);
+        driver_kpis.dead_head_km =
+            driver_kpis.cruising_km + driver_kpis.repositioning_km + driver_kpis.to_pickup_km;
+        driver_kpis.dead_head_share = ratio(
+            driver_kpis.dead_head_km,
+            driver_kpis.dead_head_km + driver_kpis.with_rider_km,
+        );
+
+        KpiReport {
+            run,
+            rides: ride_kpis,
+            drivers: driver_kpis,
+        }
+    }
+
+    /// One row per ride, offsets in seconds since the request.
+    pub fn ride_rows(&self) -> Vec<RideRow> {
+        self.ride_order
+            .iter()
+            .map(|ride_id| {
+                let ride = &self.rides[ride_id];
+                let since_request = |at: Option<DateTime<Utc>>| {
+                    Some(secs_between(ride.requested_at?, at?))
+                };
+                RideRow {
+                    ride_id: *ride_id,
+                    rider_id: ride.rider_id,
+                    driver_id: ride.driver_id,
+                    requested_at: ride.requested_at,
+                    outcome: ride.outcome(),
+                    assigned_secs: since_request(ride.assigned_at),
+                    accepted_secs: since_request(ride.accepted_at),
+                    picked_up_secs: since_request(ride.picked_up_at),
+                    completed_secs: since_request(ride.completed_at),
+                }
+            })
+            .collect()
+    }
+}
+
+fn secs_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+fn ratio(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        part / whole
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunInfo {
+    pub scenario: String,
+    pub seed: u64,
+    /// "live" or "discrete"
+    pub mode: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub simulated_hours: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DurationStats {
+    pub count: usize,
+    pub mean_secs: f64,
+    pub p50_secs: f64,
+    pub p90_secs: f64,
+    pub max_secs: f64,
+}
+
+impl DurationStats {
+    fn of(mut secs: Vec<f64>) -> Self {
+        if secs.is_empty() {
+            return Self::default();
+        }
+        secs.sort_by(f64::total_cmp);
+        // nearest rank
+        let percentile = |p: f64| secs[((p * secs.len() as f64).ceil() as usize).clamp(1, secs.len()) - 1];
+        Self {
+            count: secs.len(),
+            mean_secs: secs.iter().sum::<f64>() / secs.len() as f64,
+            p50_secs: percentile(0.5),
+            p90_secs: percentile(0.9),
+            max_secs: secs[secs.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RideKpis {
+    pub requested: usize,
+    pub assigned: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub no_drivers_available: usize,
+    pub picked_up: usize,
+    pub completed: usize,
+    /// requests no driver accepted
+    pub unmatched_rate: f64,
+    pub request_to_assigned: DurationStats,
+    pub assigned_to_accepted: DurationStats,
+    pub accepted_to_pickup: DurationStats,
+    pub pickup_to_dropoff: DurationStats,
+    /// request to pickup, what the rider waits
+    pub rider_wait: DurationStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DriverKpis {
+    pub drivers: usize,
+    pub online_hours: f64,
+    /// online without a ride accepted
+    pub idle_hours: f64,
+    /// share of online time with a rider on board
+    pub utilization: f64,
+    /// share of online time between accepting a ride and the dropoff
+    pub busy_share: f64,
+    pub cruising_km: f64,
+    /// on the way to suggested cells
+    pub repositioning_km: f64,
+    pub to_pickup_km: f64,
+    pub with_rider_km: f64,
+    /// driven without a rider: cruising, repositioning and on the way to pickups
+    pub dead_head_km: f64,
+    pub dead_head_share: f64,
+    /// repositioning suggestions followed
+    pub repositions: usize,
+    pub rejections: usize,
+    /// rejections per assignment
+    pub rejection_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KpiReport {
+    pub run: RunInfo,
+    pub rides: RideKpis,
+    pub drivers: DriverKpis,
+}
+
+impl KpiReport {
+    // metric,value rows of the summary, nested fields joined with dots
+    fn metric_rows(&self) -> Result<Vec<(String, String)>, serde_json::Error> {
+        fn flatten(prefix: &str, value: &serde_json::Value, rows: &mut Vec<(String, String)>) {
+            match value {
+                serde_json::Value::Object(fields) => {
+                    for (key, value) in fields {
+                        let name = if prefix.is_empty() {
+                            key.clone()
+                        } else {
+                            format!("{}.{}", prefix, key)
+                        };
+                        flatten(&name, value, rows);
+                    }
+                }
+                serde_json::Value::String(s) => rows.push((prefix.to_string(), s.clone())),
+                other => rows.push((prefix.to_string(), other.to_string())),
+            }
+        }
+        let mut rows = Vec::new();
+        flatten("", &serde_json::to_value(self)?, &mut rows);
+        Ok(rows)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RideRow {
+    pub ride_id: Uuid,
+    pub rider_id: Option<Uuid>,
+    pub driver_id: Option<Uuid>,
+    pub requested_at: Option<DateTime<Utc>>,
+    pub outcome: &'static str,
+    pub assigned_secs: Option<f64>,
+    pub accepted_secs: Option<f64>,
+    pub picked_up_secs: Option<f64>,
+    pub completed_secs: Option<f64>,
+}
+
+/// Writes `<name>.json`, `<name>.csv` and `<name>-rides.csv` into `dir`, returns their paths.
+pub fn write_report(
+    dir: &Path,
+    name: &str,
+    report: &KpiReport,
+    rides: &[RideRow],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+    let json_path = dir.join(format!("{}.json", name));
+    let summary_path = dir.join(format!("{}.csv", name));
+    let rides_path = dir.join(format!("{}-rides.csv", name));
+
+    std::fs::write(&json_path, serde_json::to_string_pretty(report)?)?;
+
+    let mut summary = csv::Writer::from_path(&summary_path)?;
+    summary.write_record(["metric", "value"])?;
+    for (metric, value) in report.metric_rows()? {
+        summary.write_record([metric, value])?;
+    }
+    summary.flush()?;
+
+    let mut rows = csv::Writer::from_path(&rides_path)?;
+    for ride in rides {
+        rows.serialize(ride)?;
+    }
+    rows.flush()?;
+
+    Ok(vec![json_path, summary_path, rides_path])
+}
+
+/// File name for a run's report: the scenario name, seed and mode.
+pub fn report_name(run: &RunInfo) -> String {
+    let slug: String = run
+        .scenario
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    format!("{}-seed{}-{}", slug, run.seed, run.mode)
+}
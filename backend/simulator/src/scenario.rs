@@ -0,0 +1,624 @@
+// Scenario files: everything a simulation run depends on, so an experiment can be repeated
+// and compared against another dispatch strategy by rerunning the same file.
+//
+// TOML or YAML (by extension), see scenarios/ for a full example:
+//
+//   name = "downtown weekday"
+//   seed = 42
+//   start = "2026-10-19T06:00:00-07:00"   # local start, the offset gives the hours demand follows
+//   duration_hours = 4.0
+//   speedup = 10.0
+//   riders = 200                          # accounts requests are made from
+//
+//   [map]                                 # where drivers cruise and rides go
+//   bounds = { south = 37.70, west = -122.52, north = 37.83, east = -122.35 }
+//   road_graph = "graph.txt"              # optional, a grid over the bounds otherwise
+//   traffic_profile = "traffic.json"      # optional, the default profile otherwise
+//
+//   [fleet]
+//   size = 40
+//   vehicle_mix = { economy = 0.6, comfort = 0.2, xl = 0.15, lux = 0.05 }
+//
+//   [drivers]
+//   acceptance_probability = 0.85
+//   shift_hours = { min = 4.0, max = 8.0 }
+//   follow_repositioning = 0.5            # chance an idle driver heads where it is told to
+//
+//   [[demand]]                            # one per zone
+//   zone = "downtown"
+//   bounds = { south = 37.77, west = -122.42, north = 37.80, east = -122.39 }
+//   requests_per_hour = [2, 1, 1, ..., 6]   # 24 values, local hours 0-23
+//
+//   [od.sunset]                           # where rides from a zone go, weight per zone
+//   downtown = 3.0
+//   sunset = 1.0
+//
+//   [[commute]]                           # peaks on top of the zones' demand
+//   name = "morning"
+//   from = ["sunset"]
+//   to = ["downtown"]
+//   peak_hour = 8.25                      # local
+//   width_hours = 1.0
+//   requests_per_hour = 40                # at the peak
+//
+//   [[spikes]]                            # one-off demand around a venue
+//   name = "stadium"
+//   center = { lat = 37.7786, lng = -122.3893 }
+//   radius_m = 400
+//   start = "2026-10-19T21:30:00-07:00"
+//   end = "2026-10-19T22:30:00-07:00"
+//   direction = "from_venue"              # or "to_venue"
+//   requests_per_hour = 200
+//
+// `validate` checks the lot up front and reports every problem at once.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
+use common::geo::LatLng;
+use common::vehicle::{RideProduct, VehicleClass};
+use serde::Deserialize;
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Yaml(serde_yaml::Error),
+    UnknownFormat(String),
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "failed to read scenario: {}", e),
+            ScenarioError::Toml(e) => write!(f, "failed to parse scenario: {}", e),
+            ScenarioError::Yaml(e) => write!(f, "failed to parse scenario: {}", e),
+            ScenarioError::UnknownFormat(path) => {
+                write!(f, "scenario {} must be a .toml, .yaml or .yml file", path)
+            }
+            ScenarioError::Invalid(problems) => {
+                write!(f, "invalid scenario: {}", problems.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Bounds {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl Bounds {
+    pub fn contains(&self, point: LatLng) -> bool {
+        (self.south..=self.north).contains(&point.lat) && (self.west..=self.east).contains(&point.lng)
+    }
+
+    pub fn contains_bounds(&self, other: &Bounds) -> bool {
+        self.contains(LatLng::new(other.south, other.west))
+            && self.contains(LatLng::new(other.north, other.east))
+    }
+
+    pub fn center(&self) -> LatLng {
+        LatLng::new((self.south + self.north) / 2.0, (self.west + self.east) / 2.0)
+    }
+
+    fn problems(&self, what: &str, problems: &mut Vec<String>) {
+        if !(-90.0..=90.0).contains(&self.south) || !(-90.0..=90.0).contains(&self.north) {
+            problems.push(format!("{} latitudes must be within -90 and 90", what));
+        }
+        if !(-180.0..=180.0).contains(&self.west) || !(-180.0..=180.0).contains(&self.east) {
+            problems.push(format!("{} longitudes must be within -180 and 180", what));
+        }
+        if self.south >= self.north || self.west >= self.east {
+            problems.push(format!("{} must have south < north and west < east", what));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapConfig {
+    pub bounds: Bounds,
+    /// road graph file, a grid over the bounds is generated without one. Relative paths
+    /// are relative to the scenario file, like traffic_profile.
+    pub road_graph: Option<String>,
+    /// traffic profile JSON, the default profile without one
+    pub traffic_profile: Option<String>,
+    /// street spacing of the generated grid
+    #[serde(default = "default_grid_spacing_m")]
+    pub grid_spacing_m: f64,
+}
+
+fn default_grid_spacing_m() -> f64 {
+    200.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FleetConfig {
+    pub size: usize,
+    /// weight per vehicle class, normalized
+    pub vehicle_mix: HashMap<VehicleClass, f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct DriverConfig {
+    pub acceptance_probability: f64,
+    pub response_delay_secs: f64,
+    /// every driver works one shift of a length drawn from this range
+    pub shift_hours: Range,
+    /// shifts start spread over this many minutes from the start of the run
+    pub shift_start_window_mins: f64,
+    pub mean_time_between_breaks_mins: f64,
+    pub mean_break_length_mins: f64,
+    pub location_interval_secs: f64,
+    /// chance a repositioning suggestion is followed, 0 ignores them all
+    pub follow_repositioning: f64,
+}
+
+impl Default for DriverConfig {
+    fn default() -> Self {
+        Self {
+            acceptance_probability: 0.85,
+            response_delay_secs: 5.0,
+            shift_hours: Range { min: 4.0, max: 8.0 },
+            shift_start_window_mins: 30.0,
+            mean_time_between_breaks_mins: 90.0,
+            mean_break_length_mins: 15.0,
+            location_interval_secs: 5.0,
+            follow_repositioning: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneDemand {
+    pub zone: String,
+    /// ride origins are spread evenly over the zone
+    pub bounds: Bounds,
+    /// requests per hour starting in the zone, by local hour 0-23
+    pub requests_per_hour: Vec<f64>,
+    /// weight per product, economy only without one
+    #[serde(default)]
+    pub product_mix: HashMap<RideProduct, f64>,
+}
+
+impl ZoneDemand {
+    pub fn rate_per_hour(&self, local_hour: u32) -> f64 {
+        self.requests_per_hour
+            .get(local_hour as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+fn default_width_hours() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A commute peak: extra requests from some zones to others, bell shaped around a local hour.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommutePeak {
+    pub name: String,
+    /// zones the rides start in and go to, picked evenly
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+    /// local hour of the peak, fractions allowed
+    pub peak_hour: f64,
+    /// standard deviation of the bell
+    #[serde(default = "default_width_hours")]
+    pub width_hours: f64,
+    /// requests per hour at the peak
+    pub requests_per_hour: f64,
+    #[serde(default = "default_true")]
+    pub weekdays_only: bool,
+    #[serde(default)]
+    pub product_mix: HashMap<RideProduct, f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpikeDirection {
+    /// rides start at the venue, e.g. the end of a game or flights landing
+    FromVenue,
+    /// rides end at the venue
+    ToVenue,
+}
+
+/// Extra demand around a venue for a while.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DemandSpike {
+    pub name: String,
+    pub center: LatLng,
+    pub radius_m: f64,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub direction: SpikeDirection,
+    pub requests_per_hour: f64,
+    /// zones at the other end of the rides, anywhere on the map without any
+    #[serde(default)]
+    pub zones: Vec<String>,
+    #[serde(default)]
+    pub product_mix: HashMap<RideProduct, f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    /// simulated start, the offset is the city's local time
+    pub start: DateTime<FixedOffset>,
+    pub duration_hours: f64,
+    #[serde(default = "default_speedup")]
+    pub speedup: f64,
+    pub riders: usize,
+    pub map: MapConfig,
+    pub fleet: FleetConfig,
+    #[serde(default)]
+    pub drivers: DriverConfig,
+    pub demand: Vec<ZoneDemand>,
+    /// origin zone -> destination zone -> weight. Rides from a zone without a row go to a
+    /// random intersection of the map.
+    #[serde(default)]
+    pub od: HashMap<String, HashMap<String, f64>>,
+    #[serde(default)]
+    pub commute: Vec<CommutePeak>,
+    #[serde(default)]
+    pub spikes: Vec<DemandSpike>,
+}
+
+fn default_speedup() -> f64 {
+    1.0
+}
+
+impl Scenario {
+    /// Reads and validates a scenario file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(ScenarioError::Io)?;
+        let mut scenario: Scenario = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(ScenarioError::Toml)?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&text).map_err(ScenarioError::Yaml)?,
+            _ => return Err(ScenarioError::UnknownFormat(path.display().to_string())),
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for file in [&mut scenario.map.road_graph, &mut scenario.map.traffic_profile]
+            .into_iter()
+            .flatten()
+        {
+            *file = dir.join(&*file).display().to_string();
+        }
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        let mut problems = Vec::new();
+
+        if !positive(self.duration_hours) || !fits_duration(self.duration_hours * 3600.0) {
+            problems.push("duration_hours must be positive".to_string());
+        }
+        if !positive(self.speedup) {
+            problems.push("speedup must be positive".to_string());
+        }
+        if self.riders == 0 && self.has_demand() {
+            problems.push("riders must be at least 1 when there is demand".to_string());
+        }
+
+        self.map.bounds.problems("map bounds", &mut problems);
+        if !positive(self.map.grid_spacing_m) {
+            problems.push("map grid_spacing_m must be positive".to_string());
+        }
+
+        if self.fleet.vehicle_mix.values().any(|w| !non_negative(*w))
+            || !positive(self.fleet.vehicle_mix.values().sum::<f64>())
+        {
+            problems.push("fleet vehicle_mix weights must be non-negative and not all 0".to_string());
+        }
+
+        let drivers = &self.drivers;
+        if !(0.0..=1.0).contains(&drivers.acceptance_probability) {
+            problems.push("drivers acceptance_probability must be within 0 and 1".to_string());
+        }
+        let shift_hours = drivers.shift_hours;
+        if !positive(shift_hours.min)
+            || !positive(shift_hours.max)
+            || shift_hours.min > shift_hours.max
+            || !fits_duration(shift_hours.max * 3600.0)
+        {
+            problems.push("drivers shift_hours must have 0 < min <= max".to_string());
+        }
+        for (name, value, unit_secs) in [
+            ("response_delay_secs", drivers.response_delay_secs, 1.0),
+            ("shift_start_window_mins", drivers.shift_start_window_mins, 60.0),
+            ("mean_time_between_breaks_mins", drivers.mean_time_between_breaks_mins, 60.0),
+            ("mean_break_length_mins", drivers.mean_break_length_mins, 60.0),
+        ] {
+            if !non_negative(value) || !fits_duration(value * unit_secs) {
+                problems.push(format!("drivers {} must not be negative", name));
+            }
+        }
+        if !positive(drivers.location_interval_secs) || !fits_duration(drivers.location_interval_secs) {
+            problems.push("drivers location_interval_secs must be positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&drivers.follow_repositioning) {
+            problems.push("drivers follow_repositioning must be within 0 and 1".to_string());
+        }
+
+        let mut zones = std::collections::HashSet::new();
+        for zone in &self.demand {
+            let what = format!("demand zone {:?}", zone.zone);
+            if !zones.insert(&zone.zone) {
+                problems.push(format!("{} is defined twice", what));
+            }
+            zone.bounds.problems(&format!("{} bounds", what), &mut problems);
+            if !self.map.bounds.contains_bounds(&zone.bounds) {
+                problems.push(format!("{} must be inside the map bounds", what));
+            }
+            if zone.requests_per_hour.len() != 24 {
+                problems.push(format!(
+                    "{} needs 24 requests_per_hour values, one per hour, got {}",
+                    what,
+                    zone.requests_per_hour.len()
+                ));
+            }
+            if zone.requests_per_hour.iter().any(|r| !non_negative(*r)) {
+                problems.push(format!("{} requests_per_hour must not be negative", what));
+            }
+            mix_problems(&what, &zone.product_mix, &mut problems);
+        }
+
+        let unknown_zones = |what: &str, names: &[&String], problems: &mut Vec<String>| {
+            for name in names {
+                if !zones.contains(name) {
+                    problems.push(format!("{} refers to unknown demand zone {:?}", what, name));
+                }
+            }
+        };
+        for (from, row) in &self.od {
+            let what = format!("od row {:?}", from);
+            unknown_zones(&what, &[from], &mut problems);
+            unknown_zones(&what, &row.keys().collect::<Vec<_>>(), &mut problems);
+            if row.values().any(|w| !non_negative(*w)) || !positive(row.values().sum::<f64>()) {
+                problems.push(format!("{} weights must be non-negative and not all 0", what));
+            }
+        }
+
+        for peak in &self.commute {
+            let what = format!("commute peak {:?}", peak.name);
+            if peak.from.is_empty() || peak.to.is_empty() {
+                problems.push(format!("{} needs zones to go from and to", what));
+            }
+            unknown_zones(&what, &peak.from.iter().chain(&peak.to).collect::<Vec<_>>(), &mut problems);
+            if !(0.0..24.0).contains(&peak.peak_hour) {
+                problems.push(format!("{} peak_hour must be within 0 and 24", what));
+            }
+            if !positive(peak.width_hours) {
+                problems.push(format!("{} width_hours must be positive", what));
+            }
+            if !non_negative(peak.requests_per_hour) {
+                problems.push(format!("{} requests_per_hour must not be negative", what));
+            }
+            mix_problems(&what, &peak.product_mix, &mut problems);
+        }
+
+        for spike in &self.spikes {
+            let what = format!("spike {:?}", spike.name);
+            if !self.map.bounds.contains(spike.center) {
+                problems.push(format!("{} center must be inside the map bounds", what));
+            }
+            if !positive(spike.radius_m) {
+                problems.push(format!("{} radius_m must be positive", what));
+            }
+            if spike.end <= spike.start {
+                problems.push(format!("{} must end after it starts", what));
+            }
+            if !non_negative(spike.requests_per_hour) {
+                problems.push(format!("{} requests_per_hour must not be negative", what));
+            }
+            unknown_zones(&what, &spike.zones.iter().collect::<Vec<_>>(), &mut problems);
+            mix_problems(&what, &spike.product_mix, &mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ScenarioError::Invalid(problems))
+        }
+    }
+
+    /// Whether any requests can be generated at all.
+    pub fn has_demand(&self) -> bool {
+        !self.demand.is_empty() || !self.commute.is_empty() || !self.spikes.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_hours * 3600.0)
+    }
+
+    pub fn start_utc(&self) -> DateTime<Utc> {
+        self.start.with_timezone(&Utc)
+    }
+
+    /// Hour of the day in the scenario's local time.
+    pub fn local_hour(&self, at: DateTime<Utc>) -> u32 {
+        at.with_timezone(self.start.offset()).hour()
+    }
+
+    /// Local time of day in hours, 8.5 is half past eight.
+    pub fn local_time_of_day(&self, at: DateTime<Utc>) -> f64 {
+        let local = at.with_timezone(self.start.offset());
+        local.num_seconds_from_midnight() as f64 / 3600.0 + local.nanosecond() as f64 / 3.6e12
+    }
+
+    pub fn is_local_weekday(&self, at: DateTime<Utc>) -> bool {
+        at.with_timezone(self.start.offset()).weekday().num_days_from_monday() < 5
+    }
+
+    /// Time left until the next full local hour.
+    pub fn until_next_local_hour(&self, at: DateTime<Utc>) -> Duration {
+        let local = at.with_timezone(self.start.offset());
+        let into_hour = Duration::new(
+            local.minute() as u64 * 60 + local.second() as u64,
+            local.nanosecond() % 1_000_000_000,
+        );
+        Duration::from_secs(3600).saturating_sub(into_hour)
+    }
+}
+
+fn mix_problems(what: &str, mix: &HashMap<RideProduct, f64>, problems: &mut Vec<String>) {
+    if mix.values().any(|w| !non_negative(*w)) || (!mix.is_empty() && !positive(mix.values().sum::<f64>())) {
+        problems.push(format!("{} product_mix weights must be non-negative and not all 0", what));
+    }
+}
+
+// NaN and infinities fail them too
+fn positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn non_negative(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+// scenario times become std Durations, which panic on anything they can't hold
+fn fits_duration(secs: f64) -> bool {
+    Duration::try_from_secs_f64(secs).is_ok()
+}
+
+/// Weighted choices in a stable order, HashMap iteration order would make the picks depend
+/// on the process instead of the seed.
+pub fn sorted_weights<K: Copy, O: Ord>(
+    weights: &HashMap<K, f64>,
+    key: impl Fn(&K) -> O,
+) -> Vec<(K, f64)> {
+    let mut weights: Vec<(K, f64)> = weights.iter().map(|(k, w)| (*k, *w)).collect();
+    weights.sort_by_key(|(k, _)| key(k));
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"
+name: test
+start: "2026-10-19T08:00:00-07:00"
+duration_hours: 2
+riders: 10
+map:
+  bounds: { south: 37.76, west: -122.44, north: 37.80, east: -122.39 }
+fleet:
+  size: 4
+  vehicle_mix: { economy: 1.0 }
+drivers:
+  shift_hours: { min: 1, max: 2 }
+demand:
+  - zone: downtown
+    bounds: { south: 37.77, west: -122.42, north: 37.80, east: -122.39 }
+    requests_per_hour: [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+"#;
+
+    fn scenario() -> Scenario {
+        serde_yaml::from_str(SCENARIO).unwrap()
+    }
+
+    fn problems(scenario: &Scenario) -> Vec<String> {
+        match scenario.validate() {
+            Ok(()) => Vec::new(),
+            Err(ScenarioError::Invalid(problems)) => problems,
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    // the test scenario with `from` replaced by `to` has a problem mentioning `problem`
+    fn rejects(from: &str, to: &str, problem: &str) {
+        assert!(SCENARIO.contains(from), "{:?} is not in the test scenario", from);
+        let scenario: Scenario = serde_yaml::from_str(&SCENARIO.replace(from, to)).unwrap();
+        let problems = problems(&scenario);
+        assert!(
+            problems.iter().any(|p| p.contains(problem)),
+            "{:?} not rejected for {}: {:?}",
+            to,
+            problem,
+            problems
+        );
+    }
+
+    #[test]
+    fn accepts_a_valid_scenario() {
+        assert_eq!(problems(&scenario()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn bundled_scenarios_are_valid() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        for name in ["downtown_weekday.toml", "quiet_night.yaml"] {
+            if let Err(e) = Scenario::load(dir.join(name)) {
+                panic!("{}: {:?}", name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_infinite_and_nan_durations() {
+        rejects("duration_hours: 2", "duration_hours: .inf", "duration_hours");
+        rejects("duration_hours: 2", "duration_hours: .nan", "duration_hours");
+        // finite but more than a Duration holds
+        rejects("duration_hours: 2", "duration_hours: 1e300", "duration_hours");
+        rejects("{ min: 1, max: 2 }", "{ min: 2, max: .nan }", "shift_hours");
+        rejects("{ min: 1, max: 2 }", "{ min: 1, max: .inf }", "shift_hours");
+
+        let mut scenario = scenario();
+        scenario.drivers.response_delay_secs = f64::INFINITY;
+        scenario.drivers.mean_break_length_mins = f64::NAN;
+        let problems = problems(&scenario);
+        for problem in ["response_delay_secs", "mean_break_length_mins"] {
+            assert!(problems.iter().any(|p| p.contains(problem)), "{} not in {:?}", problem, problems);
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_rates_and_weights() {
+        rejects("economy: 1.0", "economy: .inf", "vehicle_mix");
+        rejects("[1, 1, 1,", "[.nan, 1, 1,", "requests_per_hour");
+        rejects("riders: 10", "riders: 10\nspeedup: .inf", "speedup");
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let mut scenario = scenario();
+        scenario.drivers.acceptance_probability = f64::NAN;
+        scenario.drivers.follow_repositioning = 1.5;
+        scenario.drivers.location_interval_secs = 0.0;
+        scenario.map.bounds.north = f64::INFINITY;
+        let problems = problems(&scenario);
+        for problem in ["acceptance_probability", "follow_repositioning", "location_interval_secs", "map bounds"] {
+            assert!(problems.iter().any(|p| p.contains(problem)), "{} not in {:?}", problem, problems);
+        }
+    }
+}